@@ -0,0 +1,401 @@
+//! `vilya`: queries and administers a Vilya database from the shell.
+//!
+//! Opens the same data directory as the desktop app and `vilya-daemon`
+//! (`--data-dir`, else `VILYA_DATA_DIR`, else the desktop app's). Commands
+//! that write (`collect`, `replay`, `import`, `undo-import`, `label`) are safe
+//! to run beside a daemon on the same database.
+use anyhow::{bail, Context, Result};
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+use std::io::Write;
+use std::path::PathBuf;
+use tracing_subscriber::EnvFilter;
+use vilya_core::Core;
+use vilya_core::db::Db;
+use vilya_core::export::{self, ExportFormat, ExportQuery};
+use vilya_core::{exposure, geocode, quake};
+use vilya_core::history;
+use vilya_core::ingest::import::{self, ColumnMap, ImportFormat, ImportSpec, ImportTarget};
+use vilya_core::ipc::{detail, query, UiAlert, UiEvent};
+use vilya_core::runtime::{default_data_dir, DB_FILE};
+use vilya_core::stream::LogNotifier;
+use vilya_core::{ai, ingest, rules};
+
+#[cfg(test)] mod tests;
+
+#[derive(Parser)]
+#[command(name = "vilya", version, about = "Query and administer a Vilya database")]
+struct Cli {
+  /// Data directory holding vilya.sqlite, settings.json and rules.yaml.
+  #[arg(long, global = true)]
+  data_dir: Option<PathBuf>,
+  /// Database file, if not the one in the data directory.
+  #[arg(long, global = true)]
+  db: Option<PathBuf>,
+  #[command(subcommand)]
+  cmd: Cmd,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+  /// Events whose title or place contains QUERY, newest first.
+  Search {
+    query: Option<String>,
+    /// Unix seconds, RFC 3339, YYYY-MM-DD, or an age such as 6h or 7d.
+    #[arg(long)]
+    since: Option<String>,
+    #[arg(long)]
+    until: Option<String>,
+    /// The events known then, as they read then.
+    #[arg(long)]
+    as_of: Option<String>,
+    #[arg(long, default_value_t = 50)]
+    limit: usize,
+    /// One JSON object per line.
+    #[arg(long)]
+    json: bool,
+  },
+  /// Alerts in force now, or at --as-of, by bbox or at a point.
+  #[command(group(ArgGroup::new("area").required(true).args(["bbox", "point"])))]
+  Alerts {
+    /// minx,miny,maxx,maxy in degrees.
+    #[arg(long, value_parser = parse_floats::<4>)]
+    bbox: Option<[f64; 4]>,
+    /// lat,lon; only alerts whose area contains the point.
+    #[arg(long, value_parser = parse_floats::<2>)]
+    point: Option<[f64; 2]>,
+    #[arg(long)]
+    as_of: Option<String>,
+    #[arg(long)]
+    json: bool,
+  },
+  /// What changed between two times, oldest first, one JSON object per line.
+  Playback {
+    #[arg(long)]
+    from: String,
+    /// Default now.
+    #[arg(long)]
+    to: Option<String>,
+    /// History seconds per second, e.g. 3600 for an hour a second; 0 prints at once.
+    #[arg(long, default_value_t = 0.0)]
+    speed: f64,
+  },
+  /// Fetch one source once and store what it returns.
+  Collect {
+    /// gdacs, usgs, eonet, nws or cap, or drop to scan the drop folder.
+    source: String,
+  },
+  /// Feed a tape recorded with `tape.record` back through the collectors.
+  Replay {
+    tape: PathBuf,
+    /// Times as fast as recorded; 0 replays without pauses.
+    #[arg(long, default_value_t = 1.0)]
+    speed: f64,
+  },
+  /// Store feed responses saved to files as if SOURCE had just returned
+  /// them, or import GeoJSON, KML, CSV or CAP files as local source NAME.
+  #[command(group(ArgGroup::new("origin").required(true).args(["source", "name"])))]
+  Import {
+    /// gdacs, usgs, eonet, emsc, nws or cap.
+    #[arg(long)]
+    source: Option<String>,
+    /// Tag the items `import:NAME`; each file is one batch that `undo-import` removes.
+    #[arg(long)]
+    name: Option<String>,
+    /// File format; from the extension if absent.
+    #[arg(long, value_enum, requires = "name")]
+    format: Option<InFormat>,
+    /// Store rows as alerts rather than events.
+    #[arg(long, requires = "name")]
+    alerts: bool,
+    /// Column or property holding a field, as FIELD=COLUMN (e.g. title=Incident); repeatable.
+    #[arg(long = "column", value_parser = parse_column, requires = "name")]
+    columns: Vec<(String, String)>,
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+  },
+  /// Imported batches, newest first.
+  Imports,
+  /// Remove everything an import batch stored.
+  UndoImport {
+    batch: String,
+  },
+  /// Write events or alerts as GeoJSON, KML, CSV with WKT, or GeoPackage.
+  Export {
+    what: ExportKind,
+    #[arg(long, value_enum, default_value = "geojson")]
+    format: Format,
+    /// Events whose title contains this.
+    #[arg(long)]
+    q: Option<String>,
+    /// Events first seen since.
+    #[arg(long)]
+    since: Option<String>,
+    #[arg(long)]
+    until: Option<String>,
+    /// minx,miny,maxx,maxy in degrees.
+    #[arg(long, value_parser = parse_floats::<4>)]
+    bbox: Option<[f64; 4]>,
+    /// Alerts in force at this time; now if absent.
+    #[arg(long)]
+    at: Option<String>,
+    /// Output file; stdout if absent, except for gpkg, which is written to
+    /// `events.gpkg` or `alerts.gpkg`.
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+  },
+  /// Load GeoNames files into the gazetteer, place stored events and alerts
+  /// and re-rate earthquakes by shaking at its places.
+  Gazetteer {
+    /// countryInfo.txt, cities*.txt and optionally admin1CodesASCII.txt,
+    /// admin2Codes.txt and country outlines; defaults to gazetteer.dir.
+    dir: Option<PathBuf>,
+  },
+  /// Country, divisions and nearest place of a lat,lon, as JSON.
+  Place {
+    #[arg(value_parser = parse_floats::<2>)]
+    point: [f64; 2],
+  },
+  /// Load population layers and reassess the exposure of stored items.
+  Population {
+    /// ESRI ASCII grids (.asc) or CSV points with lat, lon and population.
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+  },
+  /// Load facility layers (hospitals, airports, ...) and reassess exposure.
+  Facilities {
+    /// Kind of every facility loaded; else a kind, type, amenity or aeroway
+    /// property or column, else the file name.
+    #[arg(long)]
+    kind: Option<String>,
+    /// GeoJSON, or CSV with lat and lon.
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+  },
+  /// Create missing tables and apply schema upgrades.
+  Migrate,
+  /// Parse and check a rules file without storing it.
+  CheckRules {
+    /// Defaults to rules.yaml in the data directory.
+    file: Option<PathBuf>,
+  },
+  /// Label events that have no AI label (or embedding), most severe first.
+  Label {
+    /// Stop after this many events.
+    #[arg(long)]
+    limit: Option<usize>,
+  },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportKind { Events, Alerts }
+
+#[derive(Clone, Copy, ValueEnum)]
+enum InFormat { Geojson, Kml, Csv, Cap }
+
+impl From<InFormat> for ImportFormat {
+  fn from(f: InFormat) -> Self {
+    match f { InFormat::Geojson => ImportFormat::Geojson, InFormat::Kml => ImportFormat::Kml, InFormat::Csv => ImportFormat::Csv, InFormat::Cap => ImportFormat::Cap }
+  }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format { Geojson, Kml, Csv, Gpkg }
+
+impl From<Format> for ExportFormat {
+  fn from(f: Format) -> Self {
+    match f { Format::Geojson => ExportFormat::Geojson, Format::Kml => ExportFormat::Kml, Format::Csv => ExportFormat::Csv, Format::Gpkg => ExportFormat::Gpkg }
+  }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+  tracing_subscriber::fmt().with_writer(std::io::stderr)
+    .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"))).init();
+  let cli = Cli::parse();
+  let dir = match cli.data_dir { Some(d) => d, None => default_data_dir()? };
+  let db_path = cli.db.unwrap_or_else(|| dir.join(DB_FILE));
+  let now = chrono::Utc::now().timestamp();
+
+  match cli.cmd {
+    Cmd::Migrate => {
+      let db = Db::open(db_path.clone())?;
+      let version: i64 = db.conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+      println!("{}: schema upgrade {version}", db_path.display());
+    }
+    Cmd::CheckRules { file } => {
+      let path = file.unwrap_or_else(|| dir.join("rules.yaml"));
+      let txt = std::fs::read_to_string(&path).with_context(|| format!("read {}", path.display()))?;
+      let parsed = rules::parse(&txt)?;
+      for (r, _) in &parsed { println!("{}\t{}\t{}", r.id, r.target, r.name); }
+      println!("{}: {} rules ok", path.display(), parsed.len());
+    }
+    cmd => {
+      let core = Core::open_db(db_path, dir, Box::new(LogNotifier))?;
+      run(&core, cmd, now).await?;
+    }
+  }
+  Ok(())
+}
+
+async fn run(core: &Core, cmd: Cmd, now: i64) -> Result<()> {
+  match cmd {
+    Cmd::Search { query, since, until, as_of, limit, json } => {
+      let (since, until, as_of) = (parse_time_opt(since, now)?, parse_time_opt(until, now)?, parse_time_opt(as_of, now)?);
+      print_events(&query::search_events(&core.db, query.as_deref(), since, until, as_of, limit)?, json)?;
+    }
+    Cmd::Alerts { bbox, point, as_of, json } => {
+      let as_of = parse_time_opt(as_of, now)?;
+      let alerts = match (bbox, point) {
+        (_, Some([lat, lon])) => detail::covering_alerts(&core.db, geo::Point::new(lon, lat), now, as_of)?,
+        (Some(bbox), None) => query::query_alerts(&core.db, bbox, now, as_of)?,
+        (None, None) => unreachable!("clap requires one of --bbox and --point"),
+      };
+      print_alerts(&alerts, json)?;
+    }
+    Cmd::Playback { from, to, speed } => {
+      let (from, to) = (parse_time(&from, now)?, parse_time_opt(to, now)?.unwrap_or(now));
+      let changes = history::changes(&core.db, from, to, &Default::default())?;
+      let mut out = std::io::stdout().lock();
+      history::play(&changes, speed, |c| { writeln!(out, "{}", serde_json::to_string(c)?)?; out.flush()?; Ok(()) }).await?;
+    }
+    Cmd::Collect { source } => {
+      let n = ingest::run_once(core, &source).await?;
+      println!("{source}: {n} items");
+    }
+    Cmd::Replay { tape, speed } => {
+      let r = ingest::tape::replay(core, &tape, speed).await.with_context(|| format!("replay {}", tape.display()))?;
+      println!("{}: {} runs, {} items, {} failed", tape.display(), r.runs, r.items, r.failed);
+    }
+    Cmd::Import { source: Some(source), files, .. } => {
+      for f in files {
+        let body = std::fs::read_to_string(&f).with_context(|| format!("read {}", f.display()))?;
+        let n = ingest::ingest(core, &source, &body).with_context(|| format!("import {}", f.display()))?;
+        println!("{}: {n} items", f.display());
+      }
+    }
+    Cmd::Import { name: Some(name), format, alerts, columns, files, .. } => {
+      let mut map = ColumnMap::default();
+      for (field, column) in columns { map.set(&field, column)?; }
+      let target = if alerts { ImportTarget::Alert } else { ImportTarget::Event };
+      for f in files {
+        let format = match format {
+          Some(format) => format.into(),
+          None => ImportFormat::from_path(&f).with_context(|| format!("{}: unknown extension; pass --format", f.display()))?,
+        };
+        let spec = ImportSpec { name: name.clone(), format, target, columns: map.clone() };
+        let b = import::import_file(core, &spec, &f).with_context(|| format!("import {}", f.display()))?;
+        println!("{}: batch {}, {} events, {} alerts, {} skipped", f.display(), b.id, b.events, b.alerts, b.skipped);
+      }
+    }
+    Cmd::Import { .. } => unreachable!("clap requires one of --source and --name"),
+    Cmd::Imports => {
+      for b in import::batches(&core.db)? {
+        let state = b.undone_at.map_or_else(String::new, |t| format!("undone {}", when(t)));
+        println!("{}\t{}\t{}\t{}\t{} events\t{} alerts\t{}", when(b.created_at), b.id, b.source, b.file, b.events, b.alerts, state);
+      }
+    }
+    Cmd::UndoImport { batch } => {
+      let b = import::undo(core, &batch)?;
+      println!("{}: removed {} events and {} alerts from {}", b.id, b.events, b.alerts, b.source);
+    }
+    Cmd::Export { what, format, q, since, until, bbox, at, out } => {
+      let query = match what {
+        ExportKind::Events => ExportQuery::Events { q, since: parse_time_opt(since, now)?, until: parse_time_opt(until, now)?, bbox },
+        ExportKind::Alerts => ExportQuery::Alerts { bbox, at: parse_time_opt(at, now)? },
+      };
+      let out = out.or_else(|| matches!(format, Format::Gpkg).then(|| PathBuf::from(query.file_name(format.into()))));
+      match out {
+        Some(p) => {
+          let n = export::to_file(&core.db, &query, format.into(), &p, now)?;
+          eprintln!("{}: {n} rows", p.display());
+        }
+        None => {
+          let mut w = std::io::stdout().lock();
+          export::write(&export::records(&core.db, &query, now)?, format.into(), &mut w)?;
+          w.flush()?;
+        }
+      }
+    }
+    Cmd::Gazetteer { dir } => {
+      let dir = dir.or_else(|| core.settings.gazetteer.dir.clone()).unwrap_or_else(|| core.data_dir.join("gazetteer"));
+      let r = geocode::load(&core.db.conn, &dir)?;
+      let n = geocode::annotate_all(&core.db.conn, &core.settings.gazetteer)?;
+      let q = quake::estimate_all(&core.db.conn)?;
+      println!("{}: {} places, {} divisions, {} countries, {} outlines; placed {n} items, re-rated {q} earthquakes", dir.display(), r.places, r.admins, r.countries, r.shapes);
+    }
+    Cmd::Place { point: [lat, lon] } => {
+      let place = geocode::lookup(&core.db.conn, lat, lon, core.settings.gazetteer.max_place_km)?.context("no gazetteer loaded; run `vilya gazetteer`")?;
+      println!("{}", serde_json::to_string_pretty(&place)?);
+    }
+    Cmd::Population { files } => {
+      for f in files {
+        let r = exposure::load_population(&core.db.conn, &f)?;
+        println!("{}: {} populated points", r.layer, r.rows);
+      }
+      println!("assessed {} items", exposure::assess_all(&core.db.conn, &core.settings.exposure)?);
+    }
+    Cmd::Facilities { kind, files } => {
+      for f in files {
+        let r = exposure::load_facilities(&core.db.conn, &f, kind.as_deref())?;
+        println!("{}: {} facilities", r.layer, r.rows);
+      }
+      println!("assessed {} items", exposure::assess_all(&core.db.conn, &core.settings.exposure)?);
+    }
+    Cmd::Label { limit } => {
+      let s = ai::label_backlog(core, limit).await?;
+      println!("labelled {}, failed {}, {} left", s.processed, s.failed, s.depth);
+    }
+    Cmd::Migrate | Cmd::CheckRules { .. } => unreachable!("handled without opening the pipeline"),
+  }
+  Ok(())
+}
+
+fn print_events(events: &[UiEvent], json: bool) -> Result<()> {
+  if json { write_lines(&mut std::io::stdout().lock(), events)?; return Ok(()); }
+  for e in events {
+    println!("{}\t{}\t{}\t{:.2}\t{:.3},{:.3}\t{}", when(e.ts), e.id, e.class, e.severity, e.lat, e.lon, e.title);
+  }
+  Ok(())
+}
+
+fn print_alerts(alerts: &[UiAlert], json: bool) -> Result<()> {
+  if json { write_lines(&mut std::io::stdout().lock(), alerts)?; return Ok(()); }
+  for a in alerts {
+    println!("{}\t{}\t{}\t{}\t{}", when(a.expires), a.id, a.severity, a.event, a.headline);
+  }
+  Ok(())
+}
+
+fn write_lines<T: serde::Serialize>(w: &mut impl Write, rows: &[T]) -> Result<usize> {
+  for r in rows { writeln!(w, "{}", serde_json::to_string(r)?)?; }
+  Ok(rows.len())
+}
+
+fn when(ts: i64) -> String {
+  chrono::DateTime::from_timestamp(ts, 0).map_or_else(|| ts.to_string(), |t| t.format("%Y-%m-%d %H:%M").to_string())
+}
+
+fn parse_time_opt(s: Option<String>, now: i64) -> Result<Option<i64>> {
+  s.map(|s| parse_time(&s, now)).transpose()
+}
+
+/// Unix seconds, RFC 3339, `YYYY-MM-DD` (UTC midnight), or an age before
+/// `now` in `m`, `h` or `d`.
+fn parse_time(s: &str, now: i64) -> Result<i64> {
+  if let Ok(ts) = s.parse::<i64>() { return Ok(ts); }
+  if let Ok(t) = chrono::DateTime::parse_from_rfc3339(s) { return Ok(t.timestamp()); }
+  if let Ok(d) = chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d") { return Ok(d.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp()); }
+  let unit = match s.chars().last() { Some('m') => 60, Some('h') => 3600, Some('d') => 86400, _ => bail!("bad time `{s}`") };
+  let n: i64 = s[..s.len() - 1].parse().with_context(|| format!("bad time `{s}`"))?;
+  Ok(now - n * unit)
+}
+
+fn parse_column(s: &str) -> Result<(String, String), String> {
+  let (field, column) = s.split_once('=').ok_or("expected FIELD=COLUMN")?;
+  Ok((field.trim().to_string(), column.trim().to_string()))
+}
+
+fn parse_floats<const N: usize>(s: &str) -> Result<[f64; N], String> {
+  let v: Vec<f64> = s.split(',').map(|x| x.trim().parse::<f64>()).collect::<Result<_, _>>().map_err(|e| e.to_string())?;
+  v.try_into().map_err(|_| format!("expected {N} comma-separated numbers"))
+}
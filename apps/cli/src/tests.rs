@@ -0,0 +1,23 @@
+use super::parse_time;
+
+const NOW: i64 = 1_700_000_000;
+
+#[test]
+fn times_parse_as_seconds_dates_and_ages(){
+  assert_eq!(parse_time("1600000000", NOW).unwrap(), 1_600_000_000);
+  assert_eq!(parse_time("2024-03-01T12:00:00Z", NOW).unwrap(), 1_709_294_400);
+  assert_eq!(parse_time("2024-03-01T13:00:00+01:00", NOW).unwrap(), 1_709_294_400);
+  assert_eq!(parse_time("2024-03-01", NOW).unwrap(), 1_709_251_200);
+  assert_eq!(parse_time("30m", NOW).unwrap(), NOW - 1800);
+  assert_eq!(parse_time("6h", NOW).unwrap(), NOW - 6 * 3600);
+  assert_eq!(parse_time("7d", NOW).unwrap(), NOW - 7 * 86400);
+}
+
+#[test]
+fn malformed_times_are_rejected(){
+  for bad in ["", "h", "5x", "1.5h", "-d", "2024-13-01", "2024-03-01T12:00:00", "yesterday"] {
+    assert!(parse_time(bad, NOW).is_err(), "{bad}");
+  }
+  assert_eq!(super::parse_time_opt(None, NOW).unwrap(), None);
+  assert!(super::parse_time_opt(Some("soon".into()), NOW).is_err());
+}
@@ -0,0 +1,35 @@
+//! Runs the collectors, AI worker and rules without the desktop UI.
+//!
+//! Uses the same data directory layout as the desktop app (`vilya.sqlite`,
+//! `settings.json`, `rules.yaml`): by default the desktop app's own
+//! directory, otherwise `--data-dir` or `VILYA_DATA_DIR`. Point the desktop
+//! app at the daemon's database with `desktop.mode = "attach"`.
+use anyhow::{bail, Context, Result};
+use std::path::PathBuf;
+use tracing_subscriber::EnvFilter;
+use vilya_core::Core;
+use vilya_core::runtime::default_data_dir;
+use vilya_core::stream::LogNotifier;
+
+fn data_dir() -> Result<PathBuf> {
+  let mut args = std::env::args().skip(1);
+  match args.next().as_deref() {
+    Some("--data-dir") => return args.next().map(PathBuf::from).context("--data-dir needs a path"),
+    Some("-h" | "--help") => { println!("usage: vilya-daemon [--data-dir <dir>]"); std::process::exit(0); }
+    Some(a) => bail!("unknown argument `{a}`"),
+    None => {}
+  }
+  default_data_dir()
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+  tracing_subscriber::fmt().with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"))).init();
+  let dir = data_dir()?;
+  tracing::info!("vilya-daemon: data dir {}", dir.display());
+  let core = Core::open(dir, Box::new(LogNotifier))?;
+  core.start()?;
+  tokio::signal::ctrl_c().await?;
+  tracing::info!("vilya-daemon: shutting down");
+  Ok(())
+}
@@ -0,0 +1,307 @@
+use std::sync::Arc;
+use vilya_core::Core;
+use vilya_core::ai::{self, labels::{self, LabelComparison, LabelRecord, RelabelFilter}, queue::QueueStats, review::{self, Verdict}, summary::{self, Summary, SummaryScope}, embed::{self, SimilarEvent}, translate::{self, FieldTranslation}};
+use vilya_core::analytics::{self, AnalyticsQuery, HeatCell, TimeSeries};
+use vilya_core::merge::{self, DuplicateCandidate};
+use vilya_core::entities::{self, EntityGraph, EntityRow};
+use vilya_core::export::{self, ExportFormat, ExportQuery};
+use vilya_core::exposure::{self, LayerReport};
+use vilya_core::history;
+use vilya_core::ingest::import::{self, ImportBatch, ImportSpec};
+use vilya_core::ipc::{detail, query, Aoi, EventDetail, IpcError, IpcResult, RelabelReport, ShakingContour, UiAlert, UiEvent};
+use vilya_core::stream::StreamFilter;
+use crate::stream::{Playbacks, Subscriptions, PLAYBACK, PLAYBACK_DONE};
+use tauri::{AppHandle, Emitter, Manager, State, Window};
+use rusqlite::params;
+
+#[tauri::command]
+pub fn search_events(core: State<Arc<Core>>, q: Option<String>, since: Option<i64>, until: Option<i64>, as_of: Option<i64>) -> IpcResult<Vec<UiEvent>> {
+  Ok(query::search_events(&core.db, q.as_deref(), since, until, as_of, 1000)?)
+}
+
+/// Full context for one event. `radius_km` (default 100) and `hours`
+/// (default 48, either side of first seen) bound the nearby events.
+#[tauri::command]
+pub fn get_event(core: State<Arc<Core>>, id: String, radius_km: Option<f64>, hours: Option<i64>) -> IpcResult<EventDetail> {
+  let radius_km = radius_km.unwrap_or(detail::DEFAULT_RADIUS_KM);
+  let window_secs = hours.map_or(detail::DEFAULT_WINDOW_SECS, |h| h * 3600);
+  if radius_km.is_nan() || radius_km < 0.0 || window_secs < 0 { return Err(IpcError::invalid("radius_km and hours must be non-negative")); }
+  Ok(detail::event_detail(&core.db, &id, radius_km, window_secs, chrono::Utc::now().timestamp())?)
+}
+
+#[tauri::command]
+pub fn query_alerts(core: State<Arc<Core>>, minx: f64, miny: f64, maxx: f64, maxy: f64, now_after: i64, as_of: Option<i64>) -> IpcResult<Vec<UiAlert>> {
+  Ok(query::query_alerts(&core.db, [minx, miny, maxx, maxy], now_after, as_of)?)
+}
+
+/// Intensity discs of earthquakes first seen in the last week in view.
+#[tauri::command]
+pub fn query_shaking(core: State<Arc<Core>>, minx: f64, miny: f64, maxx: f64, maxy: f64) -> IpcResult<Vec<ShakingContour>> {
+  let since = chrono::Utc::now().timestamp() - query::SHAKING_WINDOW_SECS;
+  Ok(query::query_shaking(&core.db, [minx, miny, maxx, maxy], since)?)
+}
+
+#[tauri::command]
+pub fn analytics_daily(core: State<Arc<Core>>) -> IpcResult<Vec<(String,i64)>> {
+  let mut stmt = core.db.conn.prepare(
+    "SELECT strftime('%Y-%m-%d', datetime(first_seen,'unixepoch')) AS d, COUNT(1)
+     FROM event WHERE first_seen >= strftime('%s','now','-30 day')
+     GROUP BY d ORDER BY d"
+  )?;
+  let rows = stmt.query_map([], |r| Ok((r.get::<_,Option<String>>(0)?.unwrap_or_default(), r.get::<_,i64>(1)?)))?;
+  Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+#[tauri::command]
+pub fn analytics_by_class(core: State<Arc<Core>>) -> IpcResult<Vec<(String,i64)>> {
+  let mut stmt = core.db.conn.prepare(
+    "SELECT class, COUNT(1)
+     FROM event WHERE first_seen >= strftime('%s','now','-7 day')
+     GROUP BY class ORDER BY COUNT(1) DESC"
+  )?;
+  let rows = stmt.query_map([], |r| Ok((r.get::<_,Option<String>>(0)?.unwrap_or_default(), r.get::<_,i64>(1)?)))?;
+  Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Bucketed event counts with anomaly flags; see `analytics::series`.
+#[tauri::command]
+pub fn analytics_series(core: State<Arc<Core>>, query: AnalyticsQuery) -> IpcResult<TimeSeries> {
+  analytics::series(&core.db, &query, chrono::Utc::now().timestamp()).map_err(IpcError::from)
+}
+
+/// Geohash cell counts over the query range; `precision` defaults to 4
+/// characters (about 40 km cells).
+#[tauri::command]
+pub fn analytics_heatmap(core: State<Arc<Core>>, query: AnalyticsQuery, precision: Option<usize>) -> IpcResult<Vec<HeatCell>> {
+  analytics::heatmap(&core.db, &query, precision.unwrap_or(4), chrono::Utc::now().timestamp()).map_err(IpcError::from)
+}
+
+#[tauri::command]
+pub fn ai_queue_stats(core: State<Arc<Core>>) -> QueueStats {
+  core.queue.stats()
+}
+
+#[tauri::command]
+pub fn ai_label_history(core: State<Arc<Core>>, event_id: String) -> IpcResult<Vec<LabelRecord>> {
+  labels::history(&core.db, &event_id).map_err(IpcError::from)
+}
+
+/// Re-labels the filtered events with `model` (default: the configured one)
+/// and returns old and new labels side by side. `apply` makes the new
+/// labels current; otherwise they are only recorded in the batch.
+#[tauri::command]
+pub async fn ai_relabel(app: AppHandle, filter: RelabelFilter, model: Option<String>, apply: Option<bool>) -> IpcResult<RelabelReport> {
+  let mut cfg = app.state::<Arc<Core>>().settings.ai.clone();
+  if let Some(m) = model { cfg.model = m; }
+  let provider = ai::provider::from_settings(&cfg)?;
+  let db = &app.state::<Arc<Core>>().inner().db;
+  let batch_id = labels::relabel(db, provider.as_ref(), &filter, cfg.max_attempts, apply.unwrap_or(false)).await?;
+  let results = labels::compare(db, &batch_id)?;
+  Ok(RelabelReport { batch_id, results })
+}
+
+#[tauri::command]
+pub fn ai_label_compare(core: State<Arc<Core>>, batch_id: String) -> IpcResult<Vec<LabelComparison>> {
+  labels::compare(&core.db, &batch_id).map_err(IpcError::from)
+}
+
+#[tauri::command]
+pub fn accept_label(core: State<Arc<Core>>, event_id: String, note: Option<String>) -> IpcResult<()> {
+  review::review(&core.db, &event_id, Verdict::Accepted, None, None, note).map_err(IpcError::from)
+}
+
+#[tauri::command]
+pub fn correct_label(core: State<Arc<Core>>, event_id: String, class: Option<String>, severity: Option<f32>, note: Option<String>) -> IpcResult<()> {
+  review::review(&core.db, &event_id, Verdict::Corrected, class, severity, note).map_err(IpcError::from)
+}
+
+#[tauri::command]
+pub fn reject_label(core: State<Arc<Core>>, event_id: String, note: Option<String>) -> IpcResult<()> {
+  review::review(&core.db, &event_id, Verdict::Rejected, None, None, note).map_err(IpcError::from)
+}
+
+/// Writes (text, model label, human label) pairs for reviewed events as JSONL.
+#[tauri::command]
+pub fn export_label_feedback(core: State<Arc<Core>>, path: String) -> IpcResult<usize> {
+  review::export_feedback(&core.db, std::path::Path::new(&path)).map_err(IpcError::from)
+}
+
+/// Writes the rows of an event search or alert query to `path` as
+/// GeoJSON, KML, CSV or GeoPackage. Returns the row count.
+#[tauri::command]
+pub fn export_data(core: State<Arc<Core>>, query: ExportQuery, format: ExportFormat, path: String) -> IpcResult<usize> {
+  export::to_file(&core.db, &query, format, std::path::Path::new(&path), chrono::Utc::now().timestamp()).map_err(IpcError::from)
+}
+
+/// Imports a GeoJSON, KML, CSV or CAP file at `path` as one batch tagged
+/// `import:<name>`.
+#[tauri::command]
+pub fn import_file(core: State<Arc<Core>>, spec: ImportSpec, path: String) -> IpcResult<ImportBatch> {
+  import::import_file(&core, &spec, std::path::Path::new(&path)).map_err(IpcError::from)
+}
+
+#[tauri::command]
+pub fn list_imports(core: State<Arc<Core>>) -> IpcResult<Vec<ImportBatch>> {
+  Ok(import::batches(&core.db)?)
+}
+
+/// Removes everything import batch `id` stored.
+#[tauri::command]
+pub fn undo_import(core: State<Arc<Core>>, id: String) -> IpcResult<ImportBatch> {
+  import::undo(&core, &id).map_err(IpcError::from)
+}
+
+/// Loads a population layer (ASCII grid or CSV points) and reassesses the
+/// exposure of everything stored.
+#[tauri::command]
+pub fn load_population(core: State<Arc<Core>>, path: String) -> IpcResult<LayerReport> {
+  let r = exposure::load_population(&core.db.conn, std::path::Path::new(&path))?;
+  exposure::assess_all(&core.db.conn, &core.settings.exposure)?;
+  Ok(r)
+}
+
+/// Loads a facility layer (GeoJSON or CSV); `kind` overrides the kinds in
+/// the file.
+#[tauri::command]
+pub fn load_facilities(core: State<Arc<Core>>, path: String, kind: Option<String>) -> IpcResult<LayerReport> {
+  let r = exposure::load_facilities(&core.db.conn, std::path::Path::new(&path), kind.as_deref())?;
+  exposure::assess_all(&core.db.conn, &core.settings.exposure)?;
+  Ok(r)
+}
+
+/// Cited briefing for the events and alerts in `scope`; served from cache
+/// until the underlying items change, or regenerated with `force`.
+#[tauri::command]
+pub async fn ai_summarize(app: AppHandle, scope: SummaryScope, force: Option<bool>) -> IpcResult<Summary> {
+  let provider = ai::provider::from_settings(&app.state::<Arc<Core>>().settings.ai)?;
+  let db = &app.state::<Arc<Core>>().inner().db;
+  summary::summarize(db, provider.as_ref(), &scope, force.unwrap_or(false)).await.map_err(IpcError::from)
+}
+
+#[tauri::command]
+pub fn save_aoi(core: State<Arc<Core>>, aoi: Aoi) -> IpcResult<()> {
+  let (minx, miny, maxx, maxy) = aoi.bbox;
+  core.db.conn.execute(
+    "INSERT INTO aoi(id,name,minx,miny,maxx,maxy,created_at) VALUES (?1,?2,?3,?4,?5,?6,strftime('%s','now'))
+     ON CONFLICT(id) DO UPDATE SET name=excluded.name,minx=excluded.minx,miny=excluded.miny,maxx=excluded.maxx,maxy=excluded.maxy",
+    params![aoi.id, aoi.name, minx, miny, maxx, maxy])?;
+  Ok(())
+}
+
+#[tauri::command]
+pub fn list_aois(core: State<Arc<Core>>) -> IpcResult<Vec<Aoi>> {
+  let mut stmt = core.db.conn.prepare("SELECT id,name,minx,miny,maxx,maxy FROM aoi ORDER BY name")?;
+  let rows = stmt.query_map([], |r| Ok(Aoi{ id: r.get(0)?, name: r.get(1)?, bbox: (r.get(2)?, r.get(3)?, r.get(4)?, r.get(5)?) }))?;
+  rows.collect::<rusqlite::Result<_>>().map_err(IpcError::from)
+}
+
+#[tauri::command]
+pub fn delete_aoi(core: State<Arc<Core>>, id: String) -> IpcResult<()> {
+  core.db.conn.execute("DELETE FROM aoi WHERE id=?1", [id])?;
+  Ok(())
+}
+
+/// The model the worker stores vectors under, which is the provider's: the
+/// mock provider embeds with its own model whatever the settings name.
+fn embedding_model(app: &AppHandle) -> IpcResult<String> {
+  let provider = ai::provider::from_settings(&app.state::<Arc<Core>>().settings.ai)?;
+  provider.embedding_model().map(str::to_string).ok_or_else(|| IpcError::invalid("embeddings are disabled in settings"))
+}
+
+#[tauri::command]
+pub fn similar_events(app: AppHandle, id: String, limit: Option<usize>) -> IpcResult<Vec<SimilarEvent>> {
+  let model = embedding_model(&app)?;
+  embed::similar_events(&app.state::<Arc<Core>>().inner().db, &model, &id, limit.unwrap_or(20)).map_err(IpcError::from)
+}
+
+#[tauri::command]
+pub async fn semantic_search(app: AppHandle, text: String, limit: Option<usize>) -> IpcResult<Vec<SimilarEvent>> {
+  let provider = ai::provider::from_settings(&app.state::<Arc<Core>>().settings.ai)?;
+  embed::semantic_search(&app.state::<Arc<Core>>().inner().db, provider.as_ref(), &text, limit.unwrap_or(50)).await.map_err(IpcError::from)
+}
+
+/// Likely duplicate reports among events first seen in the last `hours`.
+#[tauri::command]
+pub fn duplicate_candidates(app: AppHandle, min_score: Option<f32>, max_km: Option<f64>, hours: Option<i64>) -> IpcResult<Vec<DuplicateCandidate>> {
+  let model = embedding_model(&app)?;
+  let since = chrono::Utc::now().timestamp() - hours.unwrap_or(72) * 3600;
+  merge::semantic_duplicates(&app.state::<Arc<Core>>().inner().db, &model, min_score.unwrap_or(0.85), max_km.unwrap_or(100.0), 6 * 3600, since)
+    .map_err(IpcError::from)
+}
+
+#[tauri::command]
+pub fn list_entities(core: State<Arc<Core>>, q: Option<String>, kind: Option<String>, limit: Option<i64>) -> IpcResult<Vec<EntityRow>> {
+  entities::list(&core.db, q.as_deref(), kind.as_deref(), limit.unwrap_or(200)).map_err(IpcError::from)
+}
+
+/// Events linked to the entity `name` (aliases included), e.g. everything
+/// mentioning "Port of Rotterdam" in the last week.
+#[tauri::command]
+pub fn events_mentioning(core: State<Arc<Core>>, name: String, kind: Option<String>, since: Option<i64>, until: Option<i64>) -> IpcResult<Vec<UiEvent>> {
+  let kinds: Vec<&str> = match &kind { Some(k) => vec![k.as_str()], None => ai::output::EntityKind::ALL.iter().copied().chain(["other"]).collect() };
+  let mut ids = Vec::new();
+  for k in kinds {
+    if let Some(id) = entities::resolve(&core.db.conn, k, &name)? { ids.push(id); }
+  }
+  let ids = serde_json::to_string(&ids)?;
+  let mut stmt = core.db.conn.prepare(
+    "SELECT DISTINCT e.id,e.title,e.class,e.lat,e.lon,e.severity,e.first_seen FROM event e JOIN event_entity x ON x.event_id=e.id
+     WHERE x.entity_id IN (SELECT value FROM json_each(?1)) AND (?2 IS NULL OR e.last_seen >= ?2) AND (?3 IS NULL OR e.first_seen <= ?3)
+     ORDER BY e.first_seen DESC LIMIT 1000")?;
+  let rows = stmt.query_map(params![ids, since, until], UiEvent::from_row)?;
+  rows.collect::<rusqlite::Result<_>>().map_err(IpcError::from)
+}
+
+#[tauri::command]
+pub fn entity_graph(core: State<Arc<Core>>, since: Option<i64>, min_weight: Option<i64>, limit: Option<i64>) -> IpcResult<EntityGraph> {
+  let since = since.unwrap_or_else(|| chrono::Utc::now().timestamp() - 7 * 86400);
+  entities::graph(&core.db, since, min_weight.unwrap_or(1), limit.unwrap_or(100)).map_err(IpcError::from)
+}
+
+#[tauri::command]
+pub fn merge_entities(core: State<Arc<Core>>, keep: i64, merge: i64) -> IpcResult<()> {
+  entities::merge(&core.db, keep, merge).map_err(IpcError::from)
+}
+
+/// Stored translations of an alert or event (`kind` is "alert" or "event")
+/// into `lang`, defaulting to the configured target language.
+#[tauri::command]
+pub fn get_translations(app: AppHandle, kind: String, id: String, lang: Option<String>) -> IpcResult<Vec<FieldTranslation>> {
+  if kind != "alert" && kind != "event" { return Err(IpcError::invalid(format!("kind must be `alert` or `event`, not `{kind}`"))); }
+  let lang = lang.unwrap_or_else(|| app.state::<Arc<Core>>().settings.translation.target_language.clone());
+  translate::for_item(&app.state::<Arc<Core>>().inner().db, &kind, &id, &lang).map_err(IpcError::from)
+}
+
+/// Starts or replaces this window's live stream of events and alerts.
+#[tauri::command]
+pub fn subscribe_stream(window: Window, subs: State<Subscriptions>, filter: StreamFilter) {
+  subs.set(window.label(), filter);
+}
+
+#[tauri::command]
+pub fn unsubscribe_stream(window: Window, subs: State<Subscriptions>) {
+  subs.remove(window.label());
+}
+
+/// Plays the changes between `from` and `to` to this window as `playback`
+/// events, `speed` history seconds per second, then `playback_done`.
+/// Replaces the window's running playback; returns the change count.
+#[tauri::command]
+pub fn start_playback(window: Window, core: State<Arc<Core>>, playbacks: State<Playbacks>, from: i64, to: i64, speed: f64, filter: Option<StreamFilter>) -> IpcResult<usize> {
+  if speed.is_nan() || speed < 0.0 { return Err(IpcError::invalid("speed must be 0 or more")); }
+  let changes = history::changes(&core.db, from, to, &filter.unwrap_or_default()).map_err(IpcError::from)?;
+  let n = changes.len();
+  let label = window.label().to_string();
+  let task = tauri::async_runtime::spawn(async move {
+    let res = history::play(&changes, speed, |c| Ok(window.emit_to(window.label(), PLAYBACK, c)?)).await;
+    if let Err(e) = res { tracing::warn!("playback: {e:#}"); }
+    let _ = window.emit_to(window.label(), PLAYBACK_DONE, n);
+  });
+  playbacks.start(&label, task);
+  Ok(n)
+}
+
+#[tauri::command]
+pub fn stop_playback(window: Window, playbacks: State<Playbacks>) {
+  playbacks.stop(window.label());
+}
@@ -1,10 +1,13 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
-mod db; mod ingest; mod merge; mod normalize; mod ai; mod telemetry; mod ipc; mod rules;
+mod ipc; mod stream;
 
 use anyhow::Result;
 use std::path::PathBuf;
+use std::time::Duration;
 use tauri::Manager;
 use tracing_subscriber::EnvFilter;
+use vilya_core::Core;
+use vilya_core::settings::{self, DesktopMode};
 
 fn data_dir(app: &tauri::App) -> PathBuf {
   app.path().app_data_dir().expect("data dir")
@@ -20,20 +23,42 @@ async fn main() -> Result<()> {
   tauri::Builder::default()
     .plugin(tauri_plugin_log::Builder::default().build())
     .setup(|app| {
-      let db_path = data_dir(app).join("vilya.sqlite");
-      let db = db::Db::open(db_path).expect("db");
-      app.manage(db);
-      {
-        let dbr: &db::Db = app.state::<db::Db>().inner();
-        let _ = rules::load_and_compile(app, dbr);
-      }
-      ai::spawn(app.handle());
-      ingest::spawn_collectors(app.handle());
+      let dir = data_dir(app);
+      // before the pipeline starts: the notifier reads it
+      app.manage(stream::Subscriptions::default());
+      app.manage(stream::Playbacks::default());
+      let notifier = Box::new(stream::WindowNotifier(app.handle().clone()));
+      let desktop = settings::load(&dir).map(|s| s.desktop).unwrap_or_default();
+      let core = match desktop.mode {
+        DesktopMode::Embedded => {
+          let core = Core::open(dir, notifier)?;
+          core.start()?;
+          core
+        }
+        // a vilya-daemon owns the pipeline; follow what it writes
+        DesktopMode::Attach => {
+          let db = desktop.database.ok_or_else(|| anyhow::anyhow!("attach mode needs `desktop.database` in settings.json"))?;
+          tracing::info!("attaching to {}", db.display());
+          let core = Core::open_db(db, dir, notifier)?;
+          vilya_core::stream::follow(core.clone(), Duration::from_secs(5));
+          if let Err(e) = vilya_core::api::spawn(core.clone()) { tracing::warn!("api: {e}"); }
+          core
+        }
+      };
+      app.manage(core);
       Ok(())
     })
     .invoke_handler(tauri::generate_handler![
-      ping, ipc::search_events, ipc::get_event, ipc::query_alerts,
-      ipc::analytics_daily, ipc::analytics_by_class
+      ping, ipc::search_events, ipc::get_event, ipc::query_alerts, ipc::query_shaking,
+      ipc::analytics_daily, ipc::analytics_by_class, ipc::analytics_series, ipc::analytics_heatmap, ipc::ai_queue_stats,
+      ipc::ai_label_history, ipc::ai_relabel, ipc::ai_label_compare,
+      ipc::accept_label, ipc::correct_label, ipc::reject_label, ipc::export_label_feedback, ipc::export_data,
+      ipc::import_file, ipc::list_imports, ipc::undo_import, ipc::load_population, ipc::load_facilities,
+      ipc::ai_summarize, ipc::save_aoi, ipc::list_aois, ipc::delete_aoi,
+      ipc::similar_events, ipc::semantic_search, ipc::duplicate_candidates,
+      ipc::list_entities, ipc::events_mentioning, ipc::entity_graph, ipc::merge_entities,
+      ipc::get_translations, ipc::subscribe_stream, ipc::unsubscribe_stream,
+      ipc::start_playback, ipc::stop_playback
     ])
     .run(tauri::generate_context!())
     .expect("error running app");
@@ -1 +0,0 @@
-// normalization helpers
\ No newline at end of file
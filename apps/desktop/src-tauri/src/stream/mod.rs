@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use anyhow::Result;
+use tauri::{AppHandle, Emitter, Manager};
+use vilya_core::Core;
+use vilya_core::normalize::cap_severity;
+use vilya_core::stream::{ui_alert, ui_event, AlertCancelled, Notice, Notifier, StreamFilter};
+
+pub const EVENT_CREATED: &str = "event_created";
+pub const EVENT_UPDATED: &str = "event_updated";
+pub const ALERT_CREATED: &str = "alert_created";
+pub const ALERT_CANCELLED: &str = "alert_cancelled";
+pub const RULE_MATCHED: &str = "rule_matched";
+pub const SOURCE_STATUS: &str = "source_status";
+pub const AI_LABEL: &str = "ai_label";
+pub const AI_LABEL_FAILED: &str = "ai_label_failed";
+pub const PLAYBACK: &str = "playback";
+pub const PLAYBACK_DONE: &str = "playback_done";
+
+/// Stream filters keyed by window label.
+#[derive(Default)]
+pub struct Subscriptions(Mutex<HashMap<String, StreamFilter>>);
+
+impl Subscriptions {
+  pub fn set(&self, window: &str, filter: StreamFilter) { self.0.lock().unwrap().insert(window.into(), filter); }
+  pub fn remove(&self, window: &str) { self.0.lock().unwrap().remove(window); }
+  fn snapshot(&self) -> Vec<(String, StreamFilter)> { self.0.lock().unwrap().iter().map(|(k, v)| (k.clone(), v.clone())).collect() }
+}
+
+/// Running playbacks keyed by window label; a window plays one at a time.
+#[derive(Default)]
+pub struct Playbacks(Mutex<HashMap<String, tauri::async_runtime::JoinHandle<()>>>);
+
+impl Playbacks {
+  pub fn start(&self, window: &str, task: tauri::async_runtime::JoinHandle<()>) {
+    if let Some(old) = self.0.lock().unwrap().insert(window.into(), task) { old.abort(); }
+  }
+  pub fn stop(&self, window: &str) {
+    if let Some(task) = self.0.lock().unwrap().remove(window) { task.abort(); }
+  }
+}
+
+/// Pushes pipeline notices to the app's windows.
+pub struct WindowNotifier(pub AppHandle);
+
+impl Notifier for WindowNotifier {
+  fn notify(&self, core: &Core, notice: Notice) -> Result<()> {
+    let app = &self.0;
+    match notice {
+      Notice::Events { created, ids } => events(app, core, created, ids),
+      Notice::AlertsCreated(ids) => alerts_created(app, core, ids),
+      Notice::AlertsCancelled { source, ids } => alerts_cancelled(app, source, ids),
+      Notice::RulesMatched(matches) => { for m in matches { app.emit(RULE_MATCHED, m)?; } Ok(()) }
+      Notice::SourceStatus(status) => Ok(app.emit(SOURCE_STATUS, status)?),
+      Notice::AiLabel { id, labels, model } => Ok(app.emit(AI_LABEL, serde_json::json!({"id": id, "labels": labels, "model": model}))?),
+      Notice::AiLabelFailed { id, error } => Ok(app.emit(AI_LABEL_FAILED, serde_json::json!({"id": id, "error": error}))?),
+    }
+  }
+}
+
+/// Pushes `event_created` or `event_updated` for each id to the windows
+/// whose filter accepts it.
+fn events(app: &AppHandle, core: &Core, created: bool, ids: &[String]) -> Result<()> {
+  let subs = app.state::<Subscriptions>().snapshot();
+  if subs.is_empty() { return Ok(()); }
+  let name = if created { EVENT_CREATED } else { EVENT_UPDATED };
+  for id in ids {
+    let Some(e) = ui_event(&core.db, id)? else { continue };
+    for (window, f) in &subs {
+      if f.accepts(&e.class, e.severity as f64, (e.lon, e.lat, e.lon, e.lat)) { app.emit_to(window.as_str(), name, &e)?; }
+    }
+  }
+  Ok(())
+}
+
+fn alerts_created(app: &AppHandle, core: &Core, ids: &[String]) -> Result<()> {
+  let subs = app.state::<Subscriptions>().snapshot();
+  if subs.is_empty() { return Ok(()); }
+  for id in ids {
+    let Some(a) = ui_alert(&core.db, id)? else { continue };
+    for (window, f) in &subs {
+      if f.accepts("alert", cap_severity(&a.severity), a.bbox) { app.emit_to(window.as_str(), ALERT_CREATED, &a)?; }
+    }
+  }
+  Ok(())
+}
+
+/// Cancellations go to every subscribed window so stale polygons are
+/// removed even if the filter changed since they were pushed.
+fn alerts_cancelled(app: &AppHandle, source: &str, ids: &[String]) -> Result<()> {
+  let subs = app.state::<Subscriptions>().snapshot();
+  for id in ids {
+    for (window, _) in &subs {
+      app.emit_to(window.as_str(), ALERT_CANCELLED, AlertCancelled { id: id.clone(), source: source.into() })?;
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,275 @@
+// Generated by `cargo test export_ts_bindings` from the Rust IPC types. Do not edit.
+
+export type JsonValue = number | string | boolean | Array<JsonValue> | { [key in string]?: JsonValue } | null;
+
+export type IpcError = { "code": "not_found", "message": string } | { "code": "invalid_argument", "message": string } | { "code": "unauthorized", "message": string } | { "code": "db_busy", "message": string } | { "code": "internal", "message": string };
+
+export type UiEvent = { id: string, title: string, class: string, lat: number, lon: number, severity: number, ts: number, };
+
+export type UiAlert = { id: string, headline: string, event: string, severity: string, urgency: string, certainty: string, onset: number, expires: number, bbox: [number, number, number, number], 
+/**
+ * GeoJSON geometry or FeatureCollection of the alert area.
+ */
+geojson: JsonValue | null, };
+
+export type EventDetail = { id: string, title: string | null, summary: string | null, class: string | null, severity: number | null, confidence: number | null, lat: number | null, lon: number | null, geojson: JsonValue | null, first_seen: number, last_seen: number, 
+/**
+ * Country, divisions and nearest place, once the gazetteer has placed it.
+ */
+place: Place | null, 
+/**
+ * People and facilities within its severity radius, once assessed.
+ */
+exposure: Exposure | null, 
+/**
+ * Magnitude, depth and estimated intensities of an earthquake.
+ */
+shaking: Shaking | null, 
+/**
+ * The first report of the same incident, when this one was merged into it.
+ */
+duplicate_of: string | null, 
+/**
+ * Raw feed items behind the event.
+ */
+sources: Array<SourcePayload>, 
+/**
+ * The AI label currently in effect, with its run provenance.
+ */
+label: LabelRecord | null, review: Review | null, 
+/**
+ * Active alerts whose area covers the event location.
+ */
+alerts: Array<UiAlert>, 
+/**
+ * Other events within the requested radius and time window, nearest first.
+ */
+nearby: Array<NearbyEvent>, rules: Array<MatchedRule>, };
+
+export type SourcePayload = { source: string, fetched_at: number | null, payload: JsonValue, };
+
+export type NearbyEvent = { event: UiEvent, km: number, secs: number, };
+
+export type Aoi = { id: string, name: string, bbox: [number, number, number, number], };
+
+export type StreamFilter = { 
+/**
+ * [minx, miny, maxx, maxy]
+ */
+bbox: [number, number, number, number] | null, 
+/**
+ * Event classes; alerts count as class `alert`.
+ */
+classes: Array<string> | null, min_severity: number | null, };
+
+export type AlertCancelled = { id: string, source: string, };
+
+export type SourceStatus = { source: string, ok: boolean, items: number, error: string | null, at: number, };
+
+export type RuleMatch = { rule_id: string, rule_name: string, target: string, item_id: string, };
+
+export type MatchedRule = { rule_id: string, rule_name: string, matched_at: number, };
+
+export type QueueStats = { depth: number, enqueued: number, processed: number, failed: number, 
+/**
+ * Jobs finished (ok or failed) during the last 60 seconds.
+ */
+per_minute: number, };
+
+export type AiOutput = { class: string, confidence: number, severity: number, entities: Array<Entity>, };
+
+export type Entity = { name: string, kind: EntityKind, };
+
+export type EntityKind = "location" | "organisation" | "person" | "facility" | "infrastructure" | "other";
+
+export type LabelRecord = { run_id: number, provider: string, model: string, prompt_hash: string, labels: AiOutput | null, error: string | null, latency_ms: number, batch_id: string | null, created_at: number, };
+
+export type LabelComparison = { event_id: string, title: string, before: LabelRecord | null, after: LabelRecord, };
+
+export type RelabelFilter = { ids: Array<string> | null, q: string | null, class: string | null, since: number | null, until: number | null, 
+/**
+ * Only events whose current label came from another model or prompt version.
+ */
+stale_only: boolean, limit: number | null, };
+
+export type RelabelReport = { batch_id: string, results: Array<LabelComparison>, };
+
+export type SummaryScope = { bbox: [number, number, number, number] | null, aoi: string | null, hours: number | null, since: number | null, until: number | null, };
+
+export type Summary = { id: number, scope: SummaryScope, text: string, 
+/**
+ * Input ids the text actually cites.
+ */
+citations: Array<string>, events: Array<string>, alerts: Array<string>, model: string, created_at: number, cached: boolean, };
+
+export type SimilarEvent = { id: string, title: string, class: string, lat: number, lon: number, first_seen: number, 
+/**
+ * Cosine similarity in [-1,1].
+ */
+score: number, };
+
+export type DuplicateCandidate = { a: string, b: string, score: number, km: number, secs: number, };
+
+export type EntityRow = { id: number, kind: string, name: string, events: number, aliases: Array<string>, };
+
+export type EntityGraph = { nodes: Array<EntityRow>, edges: Array<[number, number, number]>, };
+
+export type FieldTranslation = { field: string, source_lang: string | null, target_lang: string, original: string, translated: string, };
+
+export type Review = { run_id: number | null, verdict: Verdict, class: string | null, severity: number | null, note: string | null, reviewed_at: number, };
+
+export type Verdict = "accepted" | "corrected" | "rejected";
+
+export type AnalyticsQuery = { 
+/**
+ * Defaults to 30 days before `until`.
+ */
+since: number | null, 
+/**
+ * Defaults to now.
+ */
+until: number | null, bucket: Bucket, group_by: GroupBy | null, 
+/**
+ * [minx, miny, maxx, maxy]
+ */
+bbox: [number, number, number, number] | null, 
+/**
+ * Restricts to an area of interest's bbox.
+ */
+aoi: string | null, classes: Array<string> | null, baseline: Baseline, };
+
+export type Baseline = { periods: number, ratio: number, min_count: number, };
+
+export type Bucket = "hour" | "day" | "week";
+
+export type GroupBy = "class" | "source" | "severity_band" | "country" | "region" | "aoi";
+
+export type SeriesPoint = { 
+/**
+ * Bucket start.
+ */
+ts: number, 
+/**
+ * Group key; empty when the query is not grouped.
+ */
+group: string, count: number, baseline: number, anomaly: boolean, };
+
+export type TimeSeries = { since: number, until: number, bucket_secs: number, 
+/**
+ * Buckets without events are omitted.
+ */
+points: Array<SeriesPoint>, };
+
+export type HeatCell = { geohash: string, 
+/**
+ * Cell centre.
+ */
+lat: number, lon: number, count: number, max_severity: number, baseline: number, anomaly: boolean, };
+
+export type ExportQuery = { "kind": "events", q: string | null, since: number | null, until: number | null, bbox: [number, number, number, number] | null, } | { "kind": "alerts", bbox: [number, number, number, number] | null, at: number | null, };
+
+export type ExportFormat = "geojson" | "kml" | "csv" | "gpkg";
+
+export type ImportSpec = { 
+/**
+ * Items are tagged `import:<name>`. Letters, digits, `-`, `_` and `.`.
+ */
+name: string, format: ImportFormat, 
+/**
+ * Ignored for CAP, which is always an alert.
+ */
+target: ImportTarget, columns: ColumnMap, };
+
+export type ImportFormat = "geojson" | "kml" | "csv" | "cap";
+
+export type ImportTarget = "event" | "alert";
+
+export type ColumnMap = { 
+/**
+ * Stable row id, so re-importing a corrected file updates its items.
+ * Rows without one are keyed by their content.
+ */
+id: string | null, title: string | null, summary: string | null, class: string | null, 
+/**
+ * 0–1, or a CAP severity word.
+ */
+severity: string | null, lat: string | null, lon: string | null, 
+/**
+ * When it happened: RFC 3339, `YYYY-MM-DD[ HH:MM[:SS]]`, or Unix
+ * seconds or milliseconds.
+ */
+time: string | null, 
+/**
+ * Alert expiry, in the same forms as `time`.
+ */
+expires: string | null, };
+
+export type ImportBatch = { id: string, 
+/**
+ * `import:<name>`.
+ */
+source: string, format: ImportFormat, file: string, created_at: number, events: number, alerts: number, 
+/**
+ * Rows without a location or, for CAP, documents that carry no alert.
+ */
+skipped: number, undone_at: number | null, };
+
+export type Change = { at: number, kind: ChangeKind, id: string, event: UiEvent | null, alert: UiAlert | null, };
+
+export type ChangeKind = "event_created" | "event_updated" | "alert_issued" | "alert_updated" | "alert_expired" | "alert_cancelled";
+
+export type Place = { 
+/**
+ * ISO 3166-1 alpha-3.
+ */
+country: string | null, country_name: string | null, admin1: string | null, admin2: string | null, 
+/**
+ * Nearest populated place within `gazetteer.max_place_km`.
+ */
+place: string | null, place_km: number | null, };
+
+export type Exposure = { 
+/**
+ * Radius around the event; `None` for an alert, assessed over its area.
+ */
+radius_km: number | null, population: number, 
+/**
+ * Facilities per kind.
+ */
+facility_counts: { [key in string]?: number }, 
+/**
+ * Nearest first, at most `MAX_FACILITIES`.
+ */
+facilities: Array<ExposedFacility>, computed_at: number, };
+
+export type ExposedFacility = { kind: string, name: string | null, lat: number, lon: number, 
+/**
+ * From the event; `None` inside an alert's area.
+ */
+km: number | null, };
+
+export type LayerReport = { layer: string, rows: number, };
+
+export type Quake = { mag: number, 
+/**
+ * As the feed spells it: `mw`, `ml`, `mb`, ...
+ */
+mag_type: string | null, depth_km: number | null, 
+/**
+ * Uncertainties, when the feed reports them.
+ */
+mag_error: number | null, depth_error_km: number | null, horizontal_error_km: number | null, };
+
+export type Shaking = { quake: Quake, 
+/**
+ * Estimated MMI at the most shaken gazetteer place, which it names.
+ */
+max_mmi: number | null, centre: string | null, centre_km: number | null, 
+/**
+ * Largest disc first.
+ */
+contours: Array<Contour>, computed_at: number, };
+
+export type Contour = { mmi: number, radius_km: number, };
+
+export type ShakingContour = { event_id: string, mmi: number, radius_km: number, geojson: JsonValue, };
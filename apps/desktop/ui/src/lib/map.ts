@@ -1,6 +1,9 @@
 import maplibregl from "maplibre-gl";
-import { events, alerts } from "./store";
+import { events, alerts, streamFilter } from "./store";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import { get } from "svelte/store";
+import type { AlertCancelled, ShakingContour, StreamFilter, UiAlert, UiEvent } from "./bindings";
 
 const map = new maplibregl.Map({
   container: "mapContainer",
@@ -12,7 +15,7 @@ const map = new maplibregl.Map({
 events.subscribe((evs) => {
   const fc = {
     type: "FeatureCollection",
-    features: evs.map((e:any)=>({
+    features: evs.map((e)=>({
       type:"Feature",
       properties:{ id:e.id, title:e.title, class:e.class, severity:e.severity },
       geometry:{ type:"Point", coordinates:[e.lon,e.lat] }
@@ -31,17 +34,17 @@ async function refreshAlerts() {
   const b = map.getBounds();
   const bbox: [number,number,number,number] = [b.getWest(), b.getSouth(), b.getEast(), b.getNorth()];
   const now = Math.floor(Date.now()/1000);
-  const list = await invoke("query_alerts", { minx: bbox[0], miny: bbox[1], maxx: bbox[2], maxy: bbox[3], nowAfter: now }) as any[];
+  const list = await invoke<UiAlert[]>("query_alerts", { minx: bbox[0], miny: bbox[1], maxx: bbox[2], maxy: bbox[3], nowAfter: now });
   alerts.set(list);
+  drawAlerts(list);
+}
 
+function drawAlerts(list: UiAlert[]) {
   const fc = {
     type: "FeatureCollection",
     features: list.map(a=>{
-      if (a.geojson && a.geojson !== "null") {
-        try {
-          const g = JSON.parse(a.geojson);
-          return { type:"Feature", properties:{ id:a.id, severity:a.severity, headline:a.headline }, geometry:g };
-        } catch {}
+      if (a.geojson) {
+        return { type:"Feature", properties:{ id:a.id, severity:a.severity, headline:a.headline }, geometry:a.geojson };
       }
       const [minx,miny,maxx,maxy] = a.bbox;
       return {
@@ -71,7 +74,56 @@ async function refreshAlerts() {
   }
 }
 
+// estimated felt area of recent earthquakes, in the USGS ShakeMap palette
+async function refreshShaking() {
+  const b = map.getBounds();
+  const list = await invoke<ShakingContour[]>("query_shaking", { minx: b.getWest(), miny: b.getSouth(), maxx: b.getEast(), maxy: b.getNorth() });
+  const fc = {
+    type: "FeatureCollection",
+    features: list.map(c=>({ type:"Feature", properties:{ id:c.event_id, mmi:c.mmi }, geometry:c.geojson }))
+  };
+  if (!map.getSource("shaking")) {
+    map.addSource("shaking", { type:"geojson", data: fc });
+    map.addLayer({ id:"shaking-fill", type:"fill", source:"shaking",
+      paint:{
+        "fill-color": ["step", ["get","mmi"], "#bfccff", 3, "#a0e6ff", 4, "#80ffff", 5, "#7aff93", 6, "#ffff00",
+          7, "#ffc800", 8, "#ff9100", 9, "#ff0000", 10, "#c80000"],
+        "fill-opacity": 0.15
+      }
+    }, map.getLayer("alerts-fill") ? "alerts-fill" : undefined);
+  } else {
+    (map.getSource("shaking") as any).setData(fc);
+  }
+}
+
+// live updates for the visible area instead of re-querying
+async function subscribe() {
+  const b = map.getBounds();
+  const filter: StreamFilter = { ...get(streamFilter), bbox: [b.getWest(), b.getSouth(), b.getEast(), b.getNorth()] };
+  await invoke("subscribe_stream", { filter });
+}
+
+function upsert<T extends { id: string }>(list: T[], item: T) {
+  const i = list.findIndex(x => x.id === item.id);
+  return i < 0 ? [item, ...list] : list.map((x, j) => j === i ? item : x);
+}
+
+function onEvent(e: UiEvent) {
+  events.update(l => upsert(l, e));
+  if (e.class === "eq") refreshShaking();
+}
+
+listen<UiEvent>("event_created", ({ payload }) => onEvent(payload));
+listen<UiEvent>("event_updated", ({ payload }) => onEvent(payload));
+listen<UiAlert>("alert_created", ({ payload }) => { alerts.update(l => upsert(l, payload)); drawAlerts(get(alerts)); });
+listen<AlertCancelled>("alert_cancelled", ({ payload }) => { alerts.update(l => l.filter(a => a.id !== payload.id)); drawAlerts(get(alerts)); });
+streamFilter.subscribe(() => { if (map.loaded()) subscribe(); });
+
 map.on("moveend", refreshAlerts);
 map.on("load", refreshAlerts);
+map.on("moveend", refreshShaking);
+map.on("load", refreshShaking);
+map.on("moveend", subscribe);
+map.on("load", subscribe);
 
 export default map;
@@ -1,5 +1,7 @@
 import { writable } from 'svelte/store';
-export const events = writable<any[]>([]);
+import type { StreamFilter, UiAlert, UiEvent } from './bindings';
+export const events = writable<UiEvent[]>([]);
 export const selected = writable<string|null>(null);
-export const alerts = writable<any[]>([]);
+export const alerts = writable<UiAlert[]>([]);
 export const viewport = writable<[number, number, number, number] | null>(null);
+export const streamFilter = writable<StreamFilter>({ bbox: null, classes: null, min_severity: null });
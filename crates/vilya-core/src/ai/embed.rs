@@ -0,0 +1,93 @@
+use anyhow::{Result, anyhow};
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use crate::db::Db;
+use crate::error::Error;
+use super::provider::AiProvider;
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct SimilarEvent {
+  pub id: String,
+  pub title: String,
+  pub class: String,
+  pub lat: f64,
+  pub lon: f64,
+  pub first_seen: i64,
+  /// Cosine similarity in [-1,1].
+  pub score: f32,
+}
+
+/// Vectors are stored L2-normalised as little-endian f32, so cosine
+/// similarity is a plain dot product.
+pub fn to_blob(v: &[f32]) -> Vec<u8> {
+  let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+  let norm = if norm > 0.0 { norm } else { 1.0 };
+  v.iter().flat_map(|x| (x / norm).to_le_bytes()).collect()
+}
+
+pub fn from_blob(b: &[u8]) -> Vec<f32> {
+  b.chunks_exact(4).map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect()
+}
+
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+  a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn event_text(title: &str, summary: &str) -> String {
+  if summary.is_empty() || summary == title { title.to_string() } else { format!("{title}\n{summary}") }
+}
+
+/// Computes and stores the embedding for an event unless one exists for the
+/// same model and text. Returns whether a new vector was written.
+pub async fn embed_event(db: &Db, provider: &dyn AiProvider, event_id: &str) -> Result<bool> {
+  let model = provider.embedding_model().ok_or_else(|| anyhow!("embeddings disabled"))?;
+  let (title, summary): (String, String) = db.conn.query_row(
+    "SELECT COALESCE(title,''), COALESCE(summary,'') FROM event WHERE id=?1", [event_id], |r| Ok((r.get(0)?, r.get(1)?)))?;
+  let text = event_text(&title, &summary);
+  let hash = format!("{:x}", Sha256::digest(text.as_bytes()));
+  let current: Option<i64> = db.conn.query_row(
+    "SELECT 1 FROM event_embedding WHERE event_id=?1 AND model=?2 AND text_hash=?3", params![event_id, model, hash], |r| r.get(0)).optional()?;
+  if current.is_some() { return Ok(false); }
+
+  let v = provider.embed(&text).await?;
+  if v.is_empty() { return Err(anyhow!("empty embedding")); }
+  db.conn.execute(
+    "INSERT OR REPLACE INTO event_embedding(event_id,model,text_hash,dim,vector,created_at) VALUES (?1,?2,?3,?4,?5,strftime('%s','now'))",
+    params![event_id, model, hash, v.len() as i64, to_blob(&v)])?;
+  Ok(true)
+}
+
+/// Brute-force nearest neighbours among events embedded with `model`.
+pub fn nearest(db: &Db, model: &str, query: &[f32], limit: usize, exclude: Option<&str>) -> Result<Vec<SimilarEvent>> {
+  let q = from_blob(&to_blob(query));
+  let mut stmt = db.conn.prepare(
+    "SELECT e.id, COALESCE(e.title,''), COALESCE(e.class,''), COALESCE(e.lat,0), COALESCE(e.lon,0), e.first_seen, m.vector
+     FROM event_embedding m JOIN event e ON e.id=m.event_id
+     WHERE m.model=?1 AND m.dim=?2 AND (?3 IS NULL OR e.id<>?3)")?;
+  let rows = stmt.query_map(params![model, q.len() as i64, exclude], |r| {
+    let v: Vec<u8> = r.get(6)?;
+    Ok(SimilarEvent {
+      id: r.get(0)?, title: r.get(1)?, class: r.get(2)?, lat: r.get(3)?, lon: r.get(4)?, first_seen: r.get(5)?,
+      score: dot(&q, &from_blob(&v)),
+    })
+  })?;
+  let mut out = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+  out.sort_by(|a, b| b.score.total_cmp(&a.score));
+  out.truncate(limit);
+  Ok(out)
+}
+
+pub fn similar_events(db: &Db, model: &str, event_id: &str, limit: usize) -> Result<Vec<SimilarEvent>> {
+  let v: Vec<u8> = db.conn.query_row(
+    "SELECT vector FROM event_embedding WHERE event_id=?1 AND model=?2", params![event_id, model], |r| r.get(0)).optional()?
+    .ok_or_else(|| Error::not_found(format!("{model} embedding for event {event_id}")))?;
+  nearest(db, model, &from_blob(&v), limit, Some(event_id))
+}
+
+pub async fn semantic_search(db: &Db, provider: &dyn AiProvider, text: &str, limit: usize) -> Result<Vec<SimilarEvent>> {
+  let model = provider.embedding_model().ok_or_else(|| anyhow!("embeddings disabled"))?;
+  let q = provider.embed(text).await?;
+  nearest(db, model, &q, limit, None)
+}
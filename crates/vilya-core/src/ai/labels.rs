@@ -0,0 +1,205 @@
+use anyhow::Result;
+use rusqlite::{params, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use crate::db::Db;
+use super::{build_prompt, output::{self, AiOutput}, prompt_hash, provider::AiProvider};
+
+/// Outcome of labelling one event, successful or not.
+pub struct LabelRun {
+  pub provider: &'static str,
+  pub model: String,
+  pub output: Option<AiOutput>,
+  pub raw_response: Option<String>,
+  pub error: Option<String>,
+  /// Requests made, the last of them successful when there is an output.
+  pub attempts: u32,
+  pub latency_ms: i64,
+}
+
+/// A stored row of `ai_label_run` as shown to the UI.
+#[derive(Debug, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct LabelRecord {
+  pub run_id: i64,
+  pub provider: String,
+  pub model: String,
+  pub prompt_hash: String,
+  pub labels: Option<AiOutput>,
+  pub error: Option<String>,
+  pub latency_ms: i64,
+  pub batch_id: Option<String>,
+  pub created_at: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct LabelComparison {
+  pub event_id: String,
+  pub title: String,
+  pub before: Option<LabelRecord>,
+  pub after: LabelRecord,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(default)]
+pub struct RelabelFilter {
+  pub ids: Option<Vec<String>>,
+  pub q: Option<String>,
+  pub class: Option<String>,
+  pub since: Option<i64>,
+  pub until: Option<i64>,
+  /// Only events whose current label came from another model or prompt version.
+  pub stale_only: bool,
+  pub limit: Option<i64>,
+}
+
+/// Asks the provider for a label, retrying with the validation error as
+/// feedback up to `attempts` times.
+pub async fn classify(provider: &dyn AiProvider, title: &str, summary: &str, attempts: u32) -> LabelRun {
+  let schema = output::json_schema();
+  let mut tries = 0;
+  let started = Instant::now();
+  let mut feedback: Option<String> = None;
+  let mut raw: Option<String> = None;
+  let mut parsed: Option<AiOutput> = None;
+  while tries < attempts.max(1) {
+    tries += 1;
+    let prompt = build_prompt(title, summary, feedback.as_deref());
+    match provider.complete(&prompt, Some(&schema)).await {
+      Ok(r) => {
+        match output::parse(&r) {
+          Ok(out) => { parsed = Some(out); feedback = None; }
+          Err(e) => feedback = Some(e.to_string()),
+        }
+        raw = Some(r);
+        if parsed.is_some() { break; }
+      }
+      Err(e) => feedback = Some(e.to_string()),
+    }
+  }
+  LabelRun {
+    provider: provider.name(),
+    model: provider.model().to_string(),
+    output: parsed,
+    raw_response: raw,
+    error: feedback,
+    attempts: tries,
+    latency_ms: started.elapsed().as_millis() as i64,
+  }
+}
+
+/// Records a run. With `make_current` the run also becomes the event's label
+/// (or its failure record) and may raise the event severity, unless an
+/// analyst has already reviewed the event.
+pub fn store(db: &Db, event_id: &str, run: &LabelRun, batch_id: Option<&str>, make_current: bool) -> Result<i64> {
+  let labels_json = run.output.as_ref().map(serde_json::to_string).transpose()?;
+  let severity = run.output.as_ref().map(|o| o.severity);
+  let tx = db.conn.unchecked_transaction()?;
+  tx.execute(
+    "INSERT INTO ai_label_run(event_id,provider,model,prompt_hash,labels_json,severity,raw_response,error,latency_ms,batch_id,created_at)
+     VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,strftime('%s','now'))",
+    params![event_id, run.provider, run.model, prompt_hash(), labels_json, severity, run.raw_response, run.error, run.latency_ms, batch_id])?;
+  let run_id = tx.last_insert_rowid();
+  if make_current {
+    match &run.output {
+      Some(out) => {
+        tx.execute("INSERT OR REPLACE INTO ai_labels(event_id,labels_json,severity,run_id) VALUES (?1,?2,?3,?4)",
+          params![event_id, labels_json, out.severity, run_id])?;
+        tx.execute("DELETE FROM ai_label_failure WHERE event_id=?1", params![event_id])?;
+        crate::entities::link(&tx, event_id, &out.entities)?;
+        tx.execute("UPDATE event SET severity = MAX(severity, ?2) WHERE id=?1 AND NOT EXISTS (SELECT 1 FROM ai_label_review WHERE event_id=?1)",
+          params![event_id, out.severity])?;
+      }
+      None => {
+        tx.execute(
+          "INSERT OR REPLACE INTO ai_label_failure(event_id,attempts,error,raw_response,failed_at) VALUES (?1,?2,?3,?4,strftime('%s','now'))",
+          params![event_id, run.attempts, run.error.as_deref().unwrap_or(""), run.raw_response])?;
+      }
+    }
+  }
+  tx.commit()?;
+  Ok(run_id)
+}
+
+const RECORD_COLS: &str = "id,provider,model,prompt_hash,labels_json,error,latency_ms,batch_id,created_at";
+
+fn record(r: &Row) -> rusqlite::Result<LabelRecord> {
+  let labels: Option<String> = r.get(4)?;
+  Ok(LabelRecord {
+    run_id: r.get(0)?, provider: r.get(1)?, model: r.get(2)?, prompt_hash: r.get(3)?,
+    labels: labels.and_then(|s| serde_json::from_str(&s).ok()),
+    error: r.get(5)?, latency_ms: r.get(6)?, batch_id: r.get(7)?, created_at: r.get(8)?,
+  })
+}
+
+fn record_by_id(db: &Db, run_id: i64) -> Result<LabelRecord> {
+  Ok(db.conn.query_row(&format!("SELECT {RECORD_COLS} FROM ai_label_run WHERE id=?1"), [run_id], record)?)
+}
+
+/// The run currently in effect for an event, if it has a label.
+pub fn current(db: &Db, event_id: &str) -> Result<Option<LabelRecord>> {
+  Ok(db.conn.query_row(
+    &format!("SELECT {RECORD_COLS} FROM ai_label_run WHERE id=(SELECT run_id FROM ai_labels WHERE event_id=?1)"), [event_id], record)
+    .optional()?)
+}
+
+/// All runs for an event, newest first.
+pub fn history(db: &Db, event_id: &str) -> Result<Vec<LabelRecord>> {
+  let mut stmt = db.conn.prepare(&format!("SELECT {RECORD_COLS} FROM ai_label_run WHERE event_id=?1 ORDER BY id DESC"))?;
+  let rows = stmt.query_map([event_id], record)?;
+  Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Pairs each run of a relabel batch with the latest successful run for the
+/// same event from outside the batch.
+pub fn compare(db: &Db, batch_id: &str) -> Result<Vec<LabelComparison>> {
+  let mut stmt = db.conn.prepare(
+    "SELECT a.event_id, COALESCE(e.title,''), a.id,
+       (SELECT b.id FROM ai_label_run b
+         WHERE b.event_id=a.event_id AND b.id<a.id AND b.error IS NULL AND b.batch_id IS NOT a.batch_id
+         ORDER BY b.id DESC LIMIT 1)
+     FROM ai_label_run a JOIN event e ON e.id=a.event_id
+     WHERE a.batch_id=?1 ORDER BY a.id")?;
+  let rows = stmt.query_map([batch_id], |r| Ok((r.get::<_,String>(0)?, r.get::<_,String>(1)?, r.get::<_,i64>(2)?, r.get::<_,Option<i64>>(3)?)))?;
+  let mut out = Vec::new();
+  for row in rows {
+    let (event_id, title, after, before) = row?;
+    out.push(LabelComparison {
+      event_id, title,
+      before: before.map(|id| record_by_id(db, id)).transpose()?,
+      after: record_by_id(db, after)?,
+    });
+  }
+  Ok(out)
+}
+
+/// Labels the events selected by `filter` with `provider` as one batch and
+/// returns the batch id. Unless `apply` is set the current labels are kept,
+/// so the batch can be reviewed with [`compare`] first.
+pub async fn relabel(db: &Db, provider: &dyn AiProvider, filter: &RelabelFilter, attempts: u32, apply: bool) -> Result<String> {
+  let ids = filter.ids.as_ref().map(serde_json::to_string).transpose()?;
+  let targets: Vec<(String, String, String)> = {
+    let mut stmt = db.conn.prepare(
+      "SELECT e.id, COALESCE(e.title,''), COALESCE(e.summary,'') FROM event e
+       LEFT JOIN ai_labels l ON l.event_id=e.id LEFT JOIN ai_label_run r ON r.id=l.run_id
+       WHERE (?1 IS NULL OR e.id IN (SELECT value FROM json_each(?1)))
+         AND (?2 IS NULL OR e.title LIKE '%'||?2||'%')
+         AND (?3 IS NULL OR e.class=?3)
+         AND (?4 IS NULL OR e.first_seen>=?4) AND (?5 IS NULL OR e.first_seen<=?5)
+         AND (?6=0 OR r.id IS NULL OR r.model<>?7 OR r.prompt_hash<>?8)
+       ORDER BY e.severity DESC LIMIT ?9")?;
+    let rows = stmt.query_map(
+      params![ids, filter.q, filter.class, filter.since, filter.until, filter.stale_only, provider.model(), prompt_hash(), filter.limit.unwrap_or(200)],
+      |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?;
+    rows.collect::<rusqlite::Result<_>>()?
+  };
+
+  let batch_id = uuid::Uuid::new_v4().to_string();
+  for (id, title, summary) in targets {
+    let run = classify(provider, &title, &summary, attempts).await;
+    store(db, &id, &run, Some(&batch_id), apply)?;
+  }
+  Ok(batch_id)
+}
@@ -0,0 +1,66 @@
+use anyhow::Result;
+use futures::future::BoxFuture;
+use super::provider::AiProvider;
+
+/// Deterministic provider for tests and offline demos. Without a canned
+/// response it classifies by keyword so the output depends only on the prompt.
+#[derive(Default)]
+pub struct MockProvider {
+  response: Option<String>,
+}
+
+impl MockProvider {
+  pub fn with_response(response: impl Into<String>) -> Self {
+    Self { response: Some(response.into()) }
+  }
+}
+
+const MOCK_DIM: usize = 64;
+
+const KEYWORDS: &[(&str, &[&str])] = &[
+  ("eq", &["earthquake", "quake", "seismic", "magnitude"]),
+  ("volcano", &["volcano", "eruption"]),
+  ("wildfire", &["wildfire", "fire", "bushfire"]),
+  ("flood", &["flood", "inundation"]),
+  ("storm", &["storm", "cyclone", "hurricane", "typhoon", "tornado", "wind"]),
+  ("conflict", &["attack", "shelling", "airstrike", "clashes"]),
+  ("protest", &["protest", "demonstration", "riot"]),
+  ("aviation", &["aircraft", "flight", "airport"]),
+  ("alert", &["warning", "watch", "advisory"]),
+];
+
+fn classify(prompt: &str) -> &'static str {
+  let text = prompt.rsplit("Text:").next().unwrap_or(prompt).to_lowercase();
+  KEYWORDS.iter()
+    .find(|(_, words)| words.iter().any(|w| text.contains(w)))
+    .map(|(class, _)| *class)
+    .unwrap_or("other")
+}
+
+impl AiProvider for MockProvider {
+  fn name(&self) -> &'static str { "mock" }
+  fn model(&self) -> &str { "mock" }
+
+  fn complete<'a>(&'a self, prompt: &'a str, _schema: Option<&'a serde_json::Value>) -> BoxFuture<'a, Result<String>> {
+    Box::pin(async move {
+      if let Some(r) = &self.response { return Ok(r.clone()); }
+      let class = classify(prompt);
+      let severity = if class == "other" { 0.1 } else { 0.5 };
+      Ok(serde_json::json!({ "class": class, "confidence": 0.5, "severity": severity, "entities": [] }).to_string())
+    })
+  }
+
+  fn embedding_model(&self) -> Option<&str> { Some("mock") }
+
+  /// Hashed bag of words: texts sharing words get similar vectors.
+  fn embed<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f32>>> {
+    Box::pin(async move {
+      let mut v = vec![0f32; MOCK_DIM];
+      for w in text.to_lowercase().split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+        let h = w.bytes().fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+        v[(h % MOCK_DIM as u64) as usize] += 1.0;
+      }
+      Ok(v)
+    })
+  }
+}
@@ -0,0 +1,148 @@
+use anyhow::Result;
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+use crate::runtime::Core;
+use crate::stream::Notice;
+use once_cell::sync::Lazy;
+use rusqlite::{params, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+pub mod provider; pub mod ollama; pub mod openai; pub mod mock; pub mod queue; pub mod output; pub mod labels; pub mod review; pub mod summary; pub mod embed; pub mod translate;
+
+use provider::AiProvider;
+
+pub fn spawn(core: Arc<Core>) -> Result<()> {
+  let provider = provider::from_settings(&core.settings.ai)?;
+  let n = backfill(&core, provider.embedding_model())?;
+  if n > 0 { tracing::info!("ai: backfilled {n} unlabeled or unembedded events"); }
+  let n = crate::entities::backfill(&core.db)?;
+  if n > 0 { tracing::info!("ai: linked entities for {n} labelled events"); }
+  translate::spawn(core.clone())?;
+
+  tokio::spawn(async move {
+    loop {
+      let job = core.queue.pop().await;
+      let res = process_event(&core, provider.as_ref(), &job.event_id, job.relabel).await;
+      if let Err(e) = &res {
+        tracing::warn!("ai process {}: {}", job.event_id, e);
+      }
+      core.queue.record(res.is_ok());
+      sleep(Duration::from_millis(200)).await;
+    }
+  });
+  Ok(())
+}
+
+/// Queues every event that has no label, or no embedding from
+/// `embedding_model`, most severe first.
+fn backfill(core: &Core, embedding_model: Option<&str>) -> Result<usize> {
+  let mut stmt = core.db.conn.prepare(
+    "SELECT e.id, COALESCE(e.severity,0) FROM event e
+     LEFT JOIN ai_labels l ON l.event_id=e.id
+     LEFT JOIN event_embedding m ON m.event_id=e.id AND m.model=?1
+     WHERE l.event_id IS NULL OR (?1 IS NOT NULL AND m.event_id IS NULL)")?;
+  let rows = stmt.query_map([embedding_model], |r| Ok((r.get::<_,String>(0)?, r.get::<_,f64>(1)?)))?;
+  let mut n = 0;
+  for row in rows {
+    let (id, sev) = row?;
+    core.queue.push(&id, sev as f32, false);
+    n += 1;
+  }
+  Ok(n)
+}
+
+/// Labels (and embeds) the events `backfill` would queue, in the calling
+/// task rather than the worker, stopping after `limit` if given. For hosts
+/// that do not `spawn` the worker, such as the CLI.
+pub async fn label_backlog(core: &Core, limit: Option<usize>) -> Result<queue::QueueStats> {
+  let provider = provider::from_settings(&core.settings.ai)?;
+  backfill(core, provider.embedding_model())?;
+  let mut n = 0;
+  while limit.is_none_or(|l| n < l) {
+    let Some(job) = core.queue.try_pop() else { break };
+    let res = process_event(core, provider.as_ref(), &job.event_id, job.relabel).await;
+    if let Err(e) = &res { tracing::warn!("ai process {}: {}", job.event_id, e); }
+    core.queue.record(res.is_ok());
+    n += 1;
+  }
+  Ok(core.queue.stats())
+}
+
+async fn process_event(core: &Core, provider: &dyn AiProvider, event_id: &str, relabel: bool) -> Result<()> {
+  let db = &core.db;
+  if let Some(model) = provider.embedding_model() {
+    // a new vector may make the event a duplicate of another source's report
+    let merged = embed::embed_event(db, provider, event_id).await
+      .and_then(|new| if new { crate::merge::merge_semantic_duplicates(db, model, event_id) } else { Ok(0) });
+    if let Err(e) = merged {
+      tracing::warn!("ai embed {}: {}", event_id, e);
+    }
+  }
+  if !relabel {
+    let already: Option<i64> = db.conn.query_row("SELECT 1 FROM ai_labels WHERE event_id=?1", params![event_id], |r| r.get(0)).optional()?;
+    if already.is_some() { return Ok(()); }
+  }
+
+  let (title, summary) : (String,String) = db.conn.query_row(
+    "SELECT COALESCE(title,''), COALESCE(summary,'') FROM event WHERE id=?1",
+    params![event_id], |r| Ok((r.get(0)?, r.get(1)?)))?;
+
+  let attempts = core.settings.ai.max_attempts;
+  let run = labels::classify(provider, &title, &summary, attempts).await;
+  labels::store(db, event_id, &run, None, true)?;
+  match run.output {
+    Some(out) => {
+      core.notify(Notice::AiLabel { id: event_id, labels: &out, model: &run.model });
+      // storing the label can raise the event severity, which rules and filters see
+      crate::ingest::publish_events(core, &[], &[event_id.to_string()]);
+    }
+    None => {
+      let error = run.error.unwrap_or_default();
+      core.notify(Notice::AiLabelFailed { id: event_id, error: &error });
+      anyhow::bail!("label failed after {} attempts: {error}", run.attempts);
+    }
+  }
+  Ok(())
+}
+
+/// The classification prompt. Its hash is stored with every label run, so
+/// any edit here marks existing labels as stale.
+const PROMPT_TEMPLATE: &str = r#"You are a crisis-event classifier.
+Given the text below, output strict JSON with keys: class, confidence, severity, entities.
+- class ∈ [{classes}]
+- confidence ∈ [0,1]
+- severity ∈ [0,1]
+- entities is an array of {"name", "kind"} objects for the places, organisations,
+  people, facilities and infrastructure named in the text; kind ∈ [{kinds}].
+{retry}
+Text:
+{title}
+{summary}
+JSON:"#;
+
+/// Short fingerprint of a prompt template, stored with what it produced.
+pub fn template_hash(template: &str) -> String {
+  format!("{:x}", Sha256::digest(template.as_bytes()))[..16].to_string()
+}
+
+pub fn prompt_hash() -> &'static str {
+  static HASH: Lazy<String> = Lazy::new(|| template_hash(PROMPT_TEMPLATE));
+  &HASH
+}
+
+fn build_prompt(title: &str, summary: &str, feedback: Option<&str>) -> String {
+  let retry = feedback
+    .map(|f| format!("\nYour previous answer was rejected: {f}. Reply with a single JSON object and nothing else.\n"))
+    .unwrap_or_default();
+  PROMPT_TEMPLATE
+    .replace("{classes}", &output::CLASSES.join(", "))
+    .replace("{kinds}", &output::EntityKind::ALL.join(", "))
+    .replace("{retry}", &retry)
+    .replace("{title}", title)
+    .replace("{summary}", summary)
+}
+
+/// Queues an event for labelling; `relabel` forces a new label when one exists.
+pub fn enqueue(core: &Core, event_id: &str, severity: f32, relabel: bool) {
+  core.queue.push(event_id, severity, relabel);
+}
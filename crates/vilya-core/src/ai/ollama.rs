@@ -0,0 +1,65 @@
+use anyhow::{Result, anyhow};
+use futures::future::BoxFuture;
+use crate::settings::AiSettings;
+use super::provider::{AiProvider, http_client};
+
+pub struct OllamaProvider {
+  client: reqwest::Client,
+  endpoint: String,
+  model: String,
+  embedding_model: Option<String>,
+  temperature: f32,
+}
+
+impl OllamaProvider {
+  pub fn new(s: &AiSettings) -> Result<Self> {
+    Ok(Self {
+      client: http_client(s)?,
+      endpoint: s.endpoint.trim_end_matches('/').to_string(),
+      model: s.model.clone(),
+      embedding_model: s.embedding_model.clone(),
+      temperature: s.temperature,
+    })
+  }
+}
+
+impl AiProvider for OllamaProvider {
+  fn name(&self) -> &'static str { "ollama" }
+  fn model(&self) -> &str { &self.model }
+
+  fn complete<'a>(&'a self, prompt: &'a str, schema: Option<&'a serde_json::Value>) -> BoxFuture<'a, Result<String>> {
+    Box::pin(async move {
+      let mut body = serde_json::json!({
+        "model": self.model,
+        "prompt": prompt,
+        "stream": false,
+        "options": { "temperature": self.temperature }
+      });
+      if let Some(schema) = schema { body["format"] = schema.clone(); }
+      let resp = self.client
+        .post(format!("{}/api/generate", self.endpoint))
+        .json(&body).send().await?
+        .error_for_status()?
+        .json::<serde_json::Value>().await?;
+      resp.get("response").and_then(|v| v.as_str()).map(|s| s.to_string())
+        .ok_or_else(|| anyhow!("ollama: response missing `response` field"))
+    })
+  }
+
+  fn embedding_model(&self) -> Option<&str> { self.embedding_model.as_deref() }
+
+  fn embed<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f32>>> {
+    Box::pin(async move {
+      let model = self.embedding_model.as_deref().ok_or_else(|| anyhow!("ollama: no embedding model configured"))?;
+      let resp = self.client
+        .post(format!("{}/api/embed", self.endpoint))
+        .json(&serde_json::json!({ "model": model, "input": text }))
+        .send().await?
+        .error_for_status()?
+        .json::<serde_json::Value>().await?;
+      let v = resp.pointer("/embeddings/0").and_then(|v| v.as_array())
+        .ok_or_else(|| anyhow!("ollama: response missing `embeddings`"))?;
+      Ok(v.iter().filter_map(|x| x.as_f64()).map(|x| x as f32).collect())
+    })
+  }
+}
@@ -0,0 +1,74 @@
+use anyhow::{Result, anyhow};
+use futures::future::BoxFuture;
+use crate::settings::AiSettings;
+use super::provider::{AiProvider, http_client};
+
+/// Any server speaking the OpenAI `/v1/chat/completions` dialect
+/// (llama.cpp server, vLLM, LM Studio, ...). `endpoint` is the `/v1` base.
+pub struct OpenAiProvider {
+  client: reqwest::Client,
+  endpoint: String,
+  model: String,
+  embedding_model: Option<String>,
+  api_key: Option<String>,
+  temperature: f32,
+}
+
+impl OpenAiProvider {
+  pub fn new(s: &AiSettings) -> Result<Self> {
+    Ok(Self {
+      client: http_client(s)?,
+      endpoint: s.endpoint.trim_end_matches('/').to_string(),
+      model: s.model.clone(),
+      embedding_model: s.embedding_model.clone(),
+      api_key: s.api_key.clone(),
+      temperature: s.temperature,
+    })
+  }
+}
+
+impl AiProvider for OpenAiProvider {
+  fn name(&self) -> &'static str { "openai" }
+  fn model(&self) -> &str { &self.model }
+
+  fn complete<'a>(&'a self, prompt: &'a str, schema: Option<&'a serde_json::Value>) -> BoxFuture<'a, Result<String>> {
+    Box::pin(async move {
+      let mut body = serde_json::json!({
+        "model": self.model,
+        "messages": [{ "role": "user", "content": prompt }],
+        "temperature": self.temperature,
+        "stream": false
+      });
+      if let Some(schema) = schema {
+        body["response_format"] = serde_json::json!({
+          "type": "json_schema",
+          "json_schema": { "name": "output", "strict": true, "schema": schema }
+        });
+      }
+      let mut req = self.client.post(format!("{}/chat/completions", self.endpoint)).json(&body);
+      if let Some(key) = &self.api_key { req = req.bearer_auth(key); }
+      let resp = req.send().await?
+        .error_for_status()?
+        .json::<serde_json::Value>().await?;
+      resp.pointer("/choices/0/message/content").and_then(|v| v.as_str()).map(|s| s.to_string())
+        .ok_or_else(|| anyhow!("openai: response missing choices[0].message.content"))
+    })
+  }
+
+  fn embedding_model(&self) -> Option<&str> { self.embedding_model.as_deref() }
+
+  fn embed<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f32>>> {
+    Box::pin(async move {
+      let model = self.embedding_model.as_deref().ok_or_else(|| anyhow!("openai: no embedding model configured"))?;
+      let mut req = self.client.post(format!("{}/embeddings", self.endpoint))
+        .json(&serde_json::json!({ "model": model, "input": text }));
+      if let Some(key) = &self.api_key { req = req.bearer_auth(key); }
+      let resp = req.send().await?
+        .error_for_status()?
+        .json::<serde_json::Value>().await?;
+      let v = resp.pointer("/data/0/embedding").and_then(|v| v.as_array())
+        .ok_or_else(|| anyhow!("openai: response missing data[0].embedding"))?;
+      Ok(v.iter().filter_map(|x| x.as_f64()).map(|x| x as f32).collect())
+    })
+  }
+}
@@ -0,0 +1,147 @@
+use anyhow::{Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+
+pub const CLASSES: &[&str] = &["eq", "volcano", "wildfire", "flood", "storm", "conflict", "protest", "alert", "aviation", "other"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct AiOutput {
+  pub class: String,
+  pub confidence: f32,
+  pub severity: f32,
+  #[serde(default)]
+  pub entities: Vec<Entity>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all="snake_case")]
+pub enum EntityKind { Location, Organisation, Person, Facility, Infrastructure, Other }
+
+impl EntityKind {
+  pub const ALL: &'static [&'static str] = &["location", "organisation", "person", "facility", "infrastructure"];
+
+  pub fn as_str(self) -> &'static str {
+    match self {
+      EntityKind::Location => "location", EntityKind::Organisation => "organisation", EntityKind::Person => "person",
+      EntityKind::Facility => "facility", EntityKind::Infrastructure => "infrastructure", EntityKind::Other => "other",
+    }
+  }
+
+  pub fn parse(s: &str) -> Self {
+    match s.trim().to_ascii_lowercase().as_str() {
+      "location" | "place" | "gpe" | "loc" => EntityKind::Location,
+      "organisation" | "organization" | "org" => EntityKind::Organisation,
+      "person" | "per" => EntityKind::Person,
+      "facility" | "fac" => EntityKind::Facility,
+      "infrastructure" => EntityKind::Infrastructure,
+      _ => EntityKind::Other,
+    }
+  }
+}
+
+/// A named entity. Labels written before entities were typed hold bare
+/// strings, which deserialise with kind `other`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(from = "EntityRepr")]
+pub struct Entity { pub name: String, pub kind: EntityKind }
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EntityRepr { Typed { name: String, #[serde(default)] kind: String }, Bare(String) }
+
+impl From<EntityRepr> for Entity {
+  fn from(r: EntityRepr) -> Self {
+    match r {
+      EntityRepr::Typed { name, kind } => Entity { name, kind: EntityKind::parse(&kind) },
+      EntityRepr::Bare(name) => Entity { name, kind: EntityKind::Other },
+    }
+  }
+}
+
+/// JSON schema handed to providers that support constrained decoding.
+pub fn json_schema() -> serde_json::Value {
+  serde_json::json!({
+    "type": "object",
+    "properties": {
+      "class": { "type": "string", "enum": CLASSES },
+      "confidence": { "type": "number", "minimum": 0, "maximum": 1 },
+      "severity": { "type": "number", "minimum": 0, "maximum": 1 },
+      "entities": {
+        "type": "array",
+        "items": {
+          "type": "object",
+          "properties": {
+            "name": { "type": "string" },
+            "kind": { "type": "string", "enum": EntityKind::ALL }
+          },
+          "required": ["name", "kind"],
+          "additionalProperties": false
+        }
+      }
+    },
+    "required": ["class", "confidence", "severity", "entities"],
+    "additionalProperties": false
+  })
+}
+
+/// Parses and validates a model response. Models that ignore the schema
+/// often wrap the object in prose or markdown fences, so every balanced
+/// `{...}` span is tried in order and the first one that validates wins.
+pub fn parse(raw: &str) -> Result<AiOutput> {
+  let mut last_err = anyhow!("no JSON object in response");
+  for span in json_objects(raw) {
+    match serde_json::from_str::<AiOutput>(span).map_err(Into::into).and_then(validate) {
+      Ok(out) => return Ok(out),
+      Err(e) => last_err = e,
+    }
+  }
+  Err(last_err)
+}
+
+pub fn validate(mut out: AiOutput) -> Result<AiOutput> {
+  out.class = out.class.trim().to_ascii_lowercase();
+  if out.class == "earthquake" { out.class = "eq".into(); }
+  if !CLASSES.contains(&out.class.as_str()) { bail!("class `{}` is not one of {:?}", out.class, CLASSES); }
+  if !(0.0..=1.0).contains(&out.confidence) { bail!("confidence {} outside [0,1]", out.confidence); }
+  if !(0.0..=1.0).contains(&out.severity) { bail!("severity {} outside [0,1]", out.severity); }
+  out.entities = out.entities.into_iter()
+    .map(|e| Entity { name: e.name.split_whitespace().collect::<Vec<_>>().join(" "), kind: e.kind })
+    .filter(|e| !e.name.is_empty())
+    .fold(Vec::new(), |mut acc: Vec<Entity>, e| {
+      if !acc.iter().any(|a| a.kind == e.kind && a.name.eq_ignore_ascii_case(&e.name)) { acc.push(e); }
+      acc
+    });
+  Ok(out)
+}
+
+/// Yields each top-level balanced `{...}` span, ignoring braces inside strings.
+pub fn json_objects(raw: &str) -> impl Iterator<Item = &str> {
+  let bytes = raw.as_bytes();
+  let mut pos = 0;
+  std::iter::from_fn(move || {
+    while pos < bytes.len() {
+      let start = pos + bytes[pos..].iter().position(|&b| b == b'{')?;
+      let (mut depth, mut in_str, mut esc) = (0usize, false, false);
+      for (i, &b) in bytes.iter().enumerate().skip(start) {
+        if in_str {
+          if esc { esc = false; } else if b == b'\\' { esc = true; } else if b == b'"' { in_str = false; }
+          continue;
+        }
+        match b {
+          b'"' => in_str = true,
+          b'{' => depth += 1,
+          b'}' => {
+            depth -= 1;
+            if depth == 0 { pos = i + 1; return Some(&raw[start..=i]); }
+          }
+          _ => {}
+        }
+      }
+      // unbalanced: retry from the next brace
+      pos = start + 1;
+    }
+    None
+  })
+}
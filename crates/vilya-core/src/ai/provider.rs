@@ -0,0 +1,33 @@
+use anyhow::Result;
+use futures::future::BoxFuture;
+use std::time::Duration;
+use crate::settings::{AiProviderKind, AiSettings};
+use super::{mock::MockProvider, ollama::OllamaProvider, openai::OpenAiProvider};
+
+/// A text-generation and embedding backend. Implementations must be cheap to share
+/// between the worker and IPC commands.
+pub trait AiProvider: Send + Sync {
+  fn name(&self) -> &'static str;
+  fn model(&self) -> &str;
+  /// `schema`, when given, is a JSON schema the reply must satisfy; providers
+  /// that support constrained decoding pass it through, others ignore it.
+  fn complete<'a>(&'a self, prompt: &'a str, schema: Option<&'a serde_json::Value>) -> BoxFuture<'a, Result<String>>;
+  /// Embedding model id, or `None` when embeddings are disabled.
+  fn embedding_model(&self) -> Option<&str>;
+  fn embed<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f32>>>;
+}
+
+pub fn from_settings(s: &AiSettings) -> Result<Box<dyn AiProvider>> {
+  Ok(match s.provider {
+    AiProviderKind::Ollama => Box::new(OllamaProvider::new(s)?),
+    AiProviderKind::Openai => Box::new(OpenAiProvider::new(s)?),
+    AiProviderKind::Mock => Box::new(MockProvider::default()),
+  })
+}
+
+pub(super) fn http_client(s: &AiSettings) -> Result<reqwest::Client> {
+  Ok(reqwest::Client::builder()
+    .connect_timeout(Duration::from_secs(s.connect_timeout_secs))
+    .timeout(Duration::from_secs(s.request_timeout_secs))
+    .build()?)
+}
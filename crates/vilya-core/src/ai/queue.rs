@@ -0,0 +1,112 @@
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Pending classification work. Higher `priority` (event severity) is served
+/// first; ties are served in arrival order.
+#[derive(Debug, Clone)]
+pub struct Job {
+  pub event_id: String,
+  pub priority: f32,
+  /// Label even if a label already exists (the event text changed).
+  pub relabel: bool,
+  seq: u64,
+}
+
+impl PartialEq for Job { fn eq(&self, o: &Self) -> bool { self.cmp(o) == Ordering::Equal } }
+impl Eq for Job {}
+impl PartialOrd for Job { fn partial_cmp(&self, o: &Self) -> Option<Ordering> { Some(self.cmp(o)) } }
+impl Ord for Job {
+  fn cmp(&self, o: &Self) -> Ordering {
+    self.priority.total_cmp(&o.priority).then_with(|| o.seq.cmp(&self.seq))
+  }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct QueueStats {
+  pub depth: usize,
+  pub enqueued: u64,
+  pub processed: u64,
+  pub failed: u64,
+  /// Jobs finished (ok or failed) during the last 60 seconds.
+  pub per_minute: usize,
+}
+
+#[derive(Default)]
+struct Inner {
+  heap: BinaryHeap<Job>,
+  // id -> seq of the live heap entry; stale entries are skipped on pop
+  live: HashMap<String, (u64, f32, bool)>,
+  seq: u64,
+  stats: QueueStats,
+  recent: VecDeque<Instant>,
+}
+
+impl Inner {
+  fn prune(&mut self) {
+    let Some(cutoff) = Instant::now().checked_sub(Duration::from_secs(60)) else { return };
+    while self.recent.front().is_some_and(|t| *t < cutoff) { self.recent.pop_front(); }
+  }
+}
+
+/// Unbounded, de-duplicating priority queue feeding the AI worker. Pushing an
+/// id that is already queued keeps one entry with the higher priority, so the
+/// queue never grows beyond the number of distinct events and never drops ids.
+#[derive(Default)]
+pub struct AiQueue {
+  inner: Mutex<Inner>,
+  notify: Notify,
+}
+
+impl AiQueue {
+  pub fn push(&self, event_id: &str, priority: f32, relabel: bool) {
+    let priority = if priority.is_finite() { priority } else { 0.0 };
+    let mut g = self.inner.lock().unwrap();
+    let (priority, relabel) = match g.live.get(event_id) {
+      Some(&(_, p, r)) if p >= priority && (r || !relabel) => return,
+      Some(&(_, p, r)) => (p.max(priority), r || relabel),
+      None => { g.stats.enqueued += 1; (priority, relabel) }
+    };
+    g.seq += 1;
+    let seq = g.seq;
+    g.live.insert(event_id.to_string(), (seq, priority, relabel));
+    g.heap.push(Job { event_id: event_id.to_string(), priority, relabel, seq });
+    drop(g);
+    self.notify.notify_one();
+  }
+
+  pub fn try_pop(&self) -> Option<Job> {
+    let mut g = self.inner.lock().unwrap();
+    while let Some(job) = g.heap.pop() {
+      if g.live.get(&job.event_id).map(|l| l.0) == Some(job.seq) {
+        g.live.remove(&job.event_id);
+        return Some(job);
+      }
+    }
+    None
+  }
+
+  pub async fn pop(&self) -> Job {
+    loop {
+      if let Some(job) = self.try_pop() { return job; }
+      self.notify.notified().await;
+    }
+  }
+
+  pub fn record(&self, ok: bool) {
+    let mut g = self.inner.lock().unwrap();
+    if ok { g.stats.processed += 1; } else { g.stats.failed += 1; }
+    g.recent.push_back(Instant::now());
+    g.prune();
+  }
+
+  pub fn stats(&self) -> QueueStats {
+    let mut g = self.inner.lock().unwrap();
+    g.prune();
+    QueueStats { depth: g.live.len(), per_minute: g.recent.len(), ..g.stats.clone() }
+  }
+}
@@ -0,0 +1,129 @@
+use anyhow::Result;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use crate::db::Db;
+use crate::error::Error;
+use super::output::{AiOutput, CLASSES};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all="snake_case")]
+pub enum Verdict { Accepted, Corrected, Rejected }
+
+impl Verdict {
+  fn as_str(self) -> &'static str {
+    match self { Verdict::Accepted => "accepted", Verdict::Corrected => "corrected", Verdict::Rejected => "rejected" }
+  }
+
+  fn parse(s: &str) -> Option<Self> {
+    match s { "accepted" => Some(Verdict::Accepted), "corrected" => Some(Verdict::Corrected), "rejected" => Some(Verdict::Rejected), _ => None }
+  }
+}
+
+/// The analyst verdict stored for an event.
+#[derive(Debug, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct Review {
+  pub run_id: Option<i64>,
+  pub verdict: Verdict,
+  pub class: Option<String>,
+  pub severity: Option<f32>,
+  pub note: Option<String>,
+  pub reviewed_at: i64,
+}
+
+/// One line of the feedback export.
+#[derive(Debug, Serialize)]
+pub struct FeedbackRecord {
+  pub event_id: String,
+  pub text: String,
+  pub model: Option<String>,
+  pub prompt_hash: Option<String>,
+  pub model_label: Option<AiOutput>,
+  pub verdict: Verdict,
+  pub human_class: Option<String>,
+  pub human_severity: Option<f32>,
+  pub note: Option<String>,
+  pub reviewed_at: i64,
+}
+
+/// Stores an analyst verdict on the event's current AI label. Accepting copies
+/// the model's class and severity, correcting takes the analyst's values;
+/// either way those values then take precedence over collectors and the model
+/// in the `event` row. Rejecting records the disagreement only.
+pub fn review(db: &Db, event_id: &str, verdict: Verdict, class: Option<String>, severity: Option<f32>, note: Option<String>) -> Result<()> {
+  let exists: Option<i64> = db.conn.query_row("SELECT 1 FROM event WHERE id=?1", [event_id], |r| r.get(0)).optional()?;
+  if exists.is_none() { return Err(Error::not_found(format!("event {event_id}")).into()); }
+  let current: Option<(Option<i64>, String)> = db.conn.query_row(
+    "SELECT run_id, labels_json FROM ai_labels WHERE event_id=?1", [event_id], |r| Ok((r.get(0)?, r.get(1)?))).optional()?;
+  let run_id = current.as_ref().and_then(|c| c.0);
+  let model: Option<AiOutput> = current.and_then(|c| serde_json::from_str(&c.1).ok());
+
+  let (class, severity) = match verdict {
+    Verdict::Accepted => {
+      let m = model.ok_or_else(|| Error::invalid(format!("event {event_id} has no AI label to accept")))?;
+      (Some(m.class), Some(m.severity))
+    }
+    Verdict::Corrected => {
+      if class.is_none() && severity.is_none() { return Err(Error::invalid("a correction needs a class or a severity").into()); }
+      if let Some(c) = &class { if !CLASSES.contains(&c.as_str()) { return Err(Error::invalid(format!("class `{c}` is not one of {CLASSES:?}")).into()); } }
+      if let Some(s) = severity { if !(0.0..=1.0).contains(&s) { return Err(Error::invalid(format!("severity {s} outside [0,1]")).into()); } }
+      (class, severity)
+    }
+    Verdict::Rejected => (None, None),
+  };
+
+  let tx = db.conn.unchecked_transaction()?;
+  tx.execute(
+    "INSERT OR REPLACE INTO ai_label_review(event_id,run_id,verdict,class,severity,note,reviewed_at)
+     VALUES (?1,?2,?3,?4,?5,?6,strftime('%s','now'))",
+    params![event_id, run_id, verdict.as_str(), class, severity, note])?;
+  // fires event_human_label, which applies the reviewed values
+  tx.execute("UPDATE event SET class=class, severity=severity WHERE id=?1", [event_id])?;
+  tx.commit()?;
+  Ok(())
+}
+
+fn review_row(r: &rusqlite::Row) -> rusqlite::Result<Option<Review>> {
+  let Some(verdict) = Verdict::parse(&r.get::<_,String>(1)?) else { return Ok(None) };
+  Ok(Some(Review { run_id: r.get(0)?, verdict, class: r.get(2)?, severity: r.get(3)?, note: r.get(4)?, reviewed_at: r.get(5)? }))
+}
+
+pub fn get(db: &Db, event_id: &str) -> Result<Option<Review>> {
+  let review = db.conn.query_row(
+    "SELECT run_id, verdict, class, severity, note, reviewed_at FROM ai_label_review WHERE event_id=?1", [event_id], review_row).optional()?;
+  Ok(review.flatten())
+}
+
+/// Writes every reviewed event as one JSON object per line and returns the
+/// number of lines written.
+pub fn export_feedback(db: &Db, path: &Path) -> Result<usize> {
+  let mut stmt = db.conn.prepare(
+    "SELECT v.event_id, TRIM(COALESCE(e.title,'')||char(10)||COALESCE(e.summary,'')), r.model, r.prompt_hash, r.labels_json,
+            v.verdict, v.class, v.severity, v.note, v.reviewed_at
+     FROM ai_label_review v JOIN event e ON e.id=v.event_id
+     LEFT JOIN ai_label_run r ON r.id=v.run_id
+     ORDER BY v.reviewed_at")?;
+  let rows = stmt.query_map([], |r| {
+    let labels: Option<String> = r.get(4)?;
+    let verdict: String = r.get(5)?;
+    Ok(FeedbackRecord {
+      event_id: r.get(0)?, text: r.get(1)?, model: r.get(2)?, prompt_hash: r.get(3)?,
+      model_label: labels.and_then(|s| serde_json::from_str(&s).ok()),
+      verdict: Verdict::parse(&verdict).unwrap_or(Verdict::Rejected),
+      human_class: r.get(6)?, human_severity: r.get(7)?, note: r.get(8)?, reviewed_at: r.get(9)?,
+    })
+  })?;
+
+  let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+  let mut n = 0;
+  for row in rows {
+    serde_json::to_writer(&mut out, &row?)?;
+    out.write_all(b"\n")?;
+    n += 1;
+  }
+  out.flush()?;
+  Ok(n)
+}
@@ -0,0 +1,181 @@
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::time::Instant;
+use crate::db::Db;
+use crate::error::Error;
+use super::provider::AiProvider;
+
+/// What a briefing covers. `aoi` names a saved area of interest and wins over
+/// `bbox`; `hours` is a window ending now, otherwise `since`/`until` apply.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(default)]
+pub struct SummaryScope {
+  pub bbox: Option<[f64; 4]>,
+  pub aoi: Option<String>,
+  pub hours: Option<i64>,
+  pub since: Option<i64>,
+  pub until: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct Summary {
+  pub id: i64,
+  pub scope: SummaryScope,
+  pub text: String,
+  /// Input ids the text actually cites.
+  pub citations: Vec<String>,
+  pub events: Vec<String>,
+  pub alerts: Vec<String>,
+  pub model: String,
+  pub created_at: i64,
+  pub cached: bool,
+}
+
+const MAX_EVENTS: i64 = 40;
+const MAX_ALERTS: i64 = 20;
+
+const PROMPT_TEMPLATE: &str = r#"You are a crisis analyst writing a short situation briefing.
+Write one paragraph of at most 150 words summarising the items below: the most severe
+developments first, then notable trends. Use only these items. After every statement,
+cite the supporting item ids in square brackets, e.g. [us7000abcd] or [id1, id2].
+Do not use markdown or headings.
+
+Items:
+{items}
+
+Briefing:"#;
+
+struct Inputs {
+  events: Vec<(String, i64, String)>,
+  alerts: Vec<(String, i64, String)>,
+}
+
+fn window(scope: &SummaryScope, now: i64) -> (i64, i64) {
+  match scope.hours {
+    Some(h) => (now - h * 3600, now),
+    None => (scope.since.unwrap_or(now - 86400), scope.until.unwrap_or(now)),
+  }
+}
+
+fn bbox(db: &Db, scope: &SummaryScope) -> Result<[f64; 4]> {
+  if let Some(aoi) = &scope.aoi {
+    return db.conn.query_row("SELECT minx,miny,maxx,maxy FROM aoi WHERE id=?1", [aoi],
+      |r| Ok([r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?]))
+      .optional()?.ok_or_else(|| Error::not_found(format!("area of interest {aoi}")).into());
+  }
+  Ok(scope.bbox.unwrap_or([-180.0, -90.0, 180.0, 90.0]))
+}
+
+fn fmt_ts(ts: i64) -> String {
+  chrono::DateTime::from_timestamp(ts, 0).map(|d| d.format("%Y-%m-%d %H:%M UTC").to_string()).unwrap_or_default()
+}
+
+fn gather(db: &Db, scope: &SummaryScope, now: i64) -> Result<Inputs> {
+  let [minx, miny, maxx, maxy] = bbox(db, scope)?;
+  let (since, until) = window(scope, now);
+
+  let mut stmt = db.conn.prepare(
+    "SELECT id, last_seen, COALESCE(class,''), COALESCE(severity,0), first_seen, COALESCE(title,'') FROM event
+     WHERE lon BETWEEN ?1 AND ?3 AND lat BETWEEN ?2 AND ?4 AND last_seen >= ?5 AND first_seen <= ?6
+     ORDER BY severity DESC, last_seen DESC LIMIT ?7")?;
+  let events = stmt.query_map(params![minx, miny, maxx, maxy, since, until, MAX_EVENTS], |r| {
+    let (id, last_seen): (String, i64) = (r.get(0)?, r.get(1)?);
+    let line = format!("- [{}] event, {}, severity {:.2}, first seen {}: {}",
+      id, r.get::<_,String>(2)?, r.get::<_,f64>(3)?, fmt_ts(r.get(4)?), r.get::<_,String>(5)?);
+    Ok((id, last_seen, line))
+  })?.collect::<rusqlite::Result<Vec<_>>>()?;
+
+  let mut stmt = db.conn.prepare(
+    "SELECT a.id, a.last_seen, COALESCE(a.severity,''), COALESCE(a.urgency,''), a.onset, a.expires, COALESCE(a.headline,a.event,''), COALESCE(a.area_desc,'')
+     FROM alert_rtree r JOIN alert a ON a.rowid = r.rowid
+     WHERE r.minx <= ?3 AND r.maxx >= ?1 AND r.miny <= ?4 AND r.maxy >= ?2
+       AND a.expires >= ?5 AND a.onset <= ?6
+     ORDER BY CASE a.severity WHEN 'Extreme' THEN 0 WHEN 'Severe' THEN 1 WHEN 'Moderate' THEN 2 ELSE 3 END, a.onset DESC LIMIT ?7")?;
+  let alerts = stmt.query_map(params![minx, miny, maxx, maxy, since, until, MAX_ALERTS], |r| {
+    let (id, last_seen): (String, i64) = (r.get(0)?, r.get(1)?);
+    let line = format!("- [{}] alert, {}/{}, {} to {}: {} ({})",
+      id, r.get::<_,String>(2)?, r.get::<_,String>(3)?, fmt_ts(r.get(4)?), fmt_ts(r.get(5)?), r.get::<_,String>(6)?, r.get::<_,String>(7)?);
+    Ok((id, last_seen, line))
+  })?.collect::<rusqlite::Result<Vec<_>>>()?;
+
+  Ok(Inputs { events, alerts })
+}
+
+/// Fingerprint of the inputs and generator; a change means the cached
+/// briefing is out of date.
+fn inputs_hash(inputs: &Inputs, provider: &dyn AiProvider) -> String {
+  let mut h = Sha256::new();
+  for (id, seen, _) in inputs.events.iter().chain(&inputs.alerts) {
+    h.update(id.as_bytes()); h.update(seen.to_le_bytes());
+  }
+  h.update(provider.model().as_bytes());
+  h.update(prompt_hash().as_bytes());
+  format!("{:x}", h.finalize())
+}
+
+fn prompt_hash() -> &'static str {
+  static HASH: Lazy<String> = Lazy::new(|| super::template_hash(PROMPT_TEMPLATE));
+  &HASH
+}
+
+/// Keeps `[id]` citations that name an input and drops the rest, so the
+/// briefing never points at items the model invented.
+fn check_citations(text: &str, known: &HashSet<&str>) -> (String, Vec<String>) {
+  static CITE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[([^\[\]]+)\]").unwrap());
+  let mut cited: Vec<String> = Vec::new();
+  let cleaned = CITE.replace_all(text, |c: &regex::Captures| {
+    let ids: Vec<&str> = c[1].split(',').map(str::trim).filter(|id| known.contains(id)).collect();
+    for id in &ids { if !cited.iter().any(|c| c == id) { cited.push(id.to_string()); } }
+    if ids.is_empty() { String::new() } else { format!("[{}]", ids.join(", ")) }
+  });
+  (cleaned.replace(" .", ".").trim().to_string(), cited)
+}
+
+/// Returns the briefing for `scope`, from cache unless the inputs changed
+/// since it was written or `force` is set.
+pub async fn summarize(db: &Db, provider: &dyn AiProvider, scope: &SummaryScope, force: bool) -> Result<Summary> {
+  let now = chrono::Utc::now().timestamp();
+  let inputs = gather(db, scope, now)?;
+  let scope_key = serde_json::to_string(scope)?;
+  let hash = inputs_hash(&inputs, provider);
+  let events: Vec<String> = inputs.events.iter().map(|e| e.0.clone()).collect();
+  let alerts: Vec<String> = inputs.alerts.iter().map(|a| a.0.clone()).collect();
+
+  if !force {
+    let cached = db.conn.query_row(
+      "SELECT id, text, citations_json, model, created_at FROM ai_summary WHERE scope_key=?1 AND inputs_hash=?2 ORDER BY id DESC LIMIT 1",
+      params![scope_key, hash],
+      |r| Ok((r.get::<_,i64>(0)?, r.get::<_,String>(1)?, r.get::<_,String>(2)?, r.get::<_,String>(3)?, r.get::<_,i64>(4)?))).optional()?;
+    if let Some((id, text, citations, model, created_at)) = cached {
+      return Ok(Summary { id, scope: scope.clone(), text, citations: serde_json::from_str(&citations)?, events, alerts, model, created_at, cached: true });
+    }
+  }
+
+  if events.is_empty() && alerts.is_empty() {
+    return Ok(Summary { id: 0, scope: scope.clone(), text: "No events or alerts in scope.".into(), citations: vec![],
+      events, alerts, model: provider.model().to_string(), created_at: now, cached: false });
+  }
+
+  let items: Vec<&str> = inputs.events.iter().chain(&inputs.alerts).map(|i| i.2.as_str()).collect();
+  let prompt = PROMPT_TEMPLATE.replace("{items}", &items.join("\n"));
+  let started = Instant::now();
+  let raw = provider.complete(&prompt, None).await?;
+  let latency_ms = started.elapsed().as_millis() as i64;
+
+  let known: HashSet<&str> = events.iter().chain(&alerts).map(String::as_str).collect();
+  let (text, citations) = check_citations(&raw, &known);
+  let inputs_json = serde_json::json!({ "events": events, "alerts": alerts }).to_string();
+  db.conn.execute(
+    "INSERT INTO ai_summary(scope_key,inputs_hash,inputs_json,provider,model,prompt_hash,text,citations_json,latency_ms,created_at)
+     VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10)",
+    params![scope_key, hash, inputs_json, provider.name(), provider.model(), prompt_hash(), text, serde_json::to_string(&citations)?, latency_ms, now])?;
+  Ok(Summary { id: db.conn.last_insert_rowid(), scope: scope.clone(), text, citations, events, alerts,
+    model: provider.model().to_string(), created_at: now, cached: false })
+}
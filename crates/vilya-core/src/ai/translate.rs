@@ -0,0 +1,215 @@
+use anyhow::{Result, anyhow};
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+use crate::db::Db;
+use crate::runtime::Core;
+use super::{output, provider::{self, AiProvider}};
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct FieldTranslation {
+  pub field: String,
+  pub source_lang: Option<String>,
+  pub target_lang: String,
+  pub original: String,
+  pub translated: String,
+}
+
+#[derive(Deserialize)]
+struct ModelReply { source_language: String, translation: String }
+
+const PROMPT_TEMPLATE: &str = r#"Detect the language of the text below and translate it into {target}.
+Keep names, numbers, units and times unchanged. If the text is already in {target}, return it unchanged.
+Reply with JSON: {"source_language": "<BCP 47 tag>", "translation": "<text>"}.
+
+Text:
+{text}
+
+JSON:"#;
+
+fn schema() -> serde_json::Value {
+  serde_json::json!({
+    "type": "object",
+    "properties": { "source_language": { "type": "string" }, "translation": { "type": "string" } },
+    "required": ["source_language", "translation"],
+    "additionalProperties": false
+  })
+}
+
+/// `en-US` matches `en`.
+pub fn same_language(tag: &str, target: &str) -> bool {
+  let primary = |t: &str| t.split(['-', '_']).next().unwrap_or("").to_ascii_lowercase();
+  !tag.is_empty() && primary(tag) == primary(target)
+}
+
+/// Function words common enough to tell languages apart in a sentence.
+const FUNCTION_WORDS: &[(&str, &[&str])] = &[
+  ("en", &["the", "and", "of", "in", "is", "for", "with", "are", "from", "near", "until", "has"]),
+  ("de", &["der", "die", "das", "und", "mit", "für", "von", "ist", "bis", "auf", "im", "vor"]),
+  ("fr", &["le", "la", "les", "et", "des", "du", "pour", "est", "avec", "au", "sur", "jusqu"]),
+  ("es", &["el", "los", "las", "y", "del", "para", "con", "por", "está", "hasta", "en", "una"]),
+  ("it", &["il", "gli", "e", "della", "per", "con", "di", "è", "fino", "nel", "sul", "una"]),
+  ("pt", &["o", "os", "as", "e", "do", "da", "para", "com", "em", "até", "uma", "no"]),
+  ("nl", &["de", "het", "en", "van", "een", "voor", "met", "is", "tot", "op", "bij", "naar"]),
+];
+
+/// Guesses the language of text that declares none from its function
+/// words; `None` when it has too few to tell, as short titles often do.
+pub fn guess_language(text: &str) -> Option<&'static str> {
+  let words: Vec<String> = text.split(|c: char| !c.is_alphabetic()).filter(|w| !w.is_empty()).map(str::to_lowercase).collect();
+  let mut hits: Vec<(usize, &str)> = FUNCTION_WORDS.iter()
+    .map(|(lang, fw)| (words.iter().filter(|w| fw.contains(&w.as_str())).count(), *lang))
+    .collect();
+  hits.sort_by_key(|h| std::cmp::Reverse(h.0));
+  match hits[..] {
+    [(best, lang), (next, _), ..] if best >= 2 && best > next => Some(lang),
+    _ => None,
+  }
+}
+
+/// Translates `text` into `target`, answering from `translation_cache` when
+/// the same text was translated before. `source_hint` is the declared
+/// language, if any; text already in `target` is returned as is.
+pub async fn translate(db: &Db, provider: &dyn AiProvider, text: &str, source_hint: Option<&str>, target: &str) -> Result<(Option<String>, String)> {
+  if text.trim().is_empty() { return Ok((source_hint.map(str::to_string), text.to_string())); }
+  if let Some(src) = source_hint.filter(|s| same_language(s, target)) { return Ok((Some(src.to_string()), text.to_string())); }
+
+  let hash = format!("{:x}", Sha256::digest(format!("{target}\0{text}").as_bytes()));
+  let cached: Option<(Option<String>, String)> = db.conn.query_row(
+    "SELECT source_lang, translated FROM translation_cache WHERE hash=?1", [&hash], |r| Ok((r.get(0)?, r.get(1)?))).optional()?;
+  if let Some(hit) = cached { return Ok(hit); }
+
+  let prompt = PROMPT_TEMPLATE.replace("{target}", target).replace("{text}", text);
+  let raw = provider.complete(&prompt, Some(&schema())).await?;
+  let reply: ModelReply = output::json_objects(&raw)
+    .find_map(|span| serde_json::from_str(span).ok())
+    .ok_or_else(|| anyhow!("translation reply is not the expected JSON"))?;
+  let source = Some(reply.source_language).filter(|s| !s.is_empty()).or(source_hint.map(str::to_string));
+  db.conn.execute(
+    "INSERT OR REPLACE INTO translation_cache(hash,source_lang,target_lang,translated,model,created_at) VALUES (?1,?2,?3,?4,?5,strftime('%s','now'))",
+    params![hash, source, target, reply.translation, provider.model()])?;
+  Ok((source, reply.translation))
+}
+
+fn store(db: &Db, (kind, id): (&str, &str), field: &str, target: &str, source: Option<&str>, original: &str, translated: &str) -> Result<()> {
+  db.conn.execute(
+    "INSERT OR REPLACE INTO item_translation(item_kind,item_id,field,target_lang,source_lang,original,translated,updated_at)
+     VALUES (?1,?2,?3,?4,?5,?6,?7,strftime('%s','now'))",
+    params![kind, id, field, target, source, original, translated])?;
+  Ok(())
+}
+
+/// (id, declared language, (field, text) pairs)
+type PendingItem = (String, Option<String>, Vec<(&'static str, String)>);
+
+/// Items whose first field has no translation into `target` matching the
+/// current text, newest first. Items that failed wait out their backoff
+/// unless their text changed since.
+fn pending(db: &Db, kind: &str, target: &str, limit: i64) -> Result<Vec<PendingItem>> {
+  let (sql, fields): (&str, &[&'static str]) = match kind {
+    "alert" => (
+      "SELECT a.id, a.language, COALESCE(a.headline,''), COALESCE(a.description,''), COALESCE(a.instruction,'') FROM alert a
+       WHERE NOT EXISTS (SELECT 1 FROM item_translation t WHERE t.item_kind='alert' AND t.item_id=a.id AND t.field='headline'
+                           AND t.target_lang=?1 AND t.original=COALESCE(a.headline,''))
+         AND NOT EXISTS (SELECT 1 FROM translation_failure f WHERE f.item_kind='alert' AND f.item_id=a.id AND f.target_lang=?1
+                           AND f.original=COALESCE(a.headline,'') AND f.retry_at > strftime('%s','now'))
+       ORDER BY a.last_seen DESC LIMIT ?2",
+      &["headline", "description", "instruction"]),
+    _ => (
+      "SELECT e.id, NULL, COALESCE(e.title,''), COALESCE(e.summary,'') FROM event e
+       WHERE NOT EXISTS (SELECT 1 FROM item_translation t WHERE t.item_kind='event' AND t.item_id=e.id AND t.field='title'
+                           AND t.target_lang=?1 AND t.original=COALESCE(e.title,''))
+         AND NOT EXISTS (SELECT 1 FROM translation_failure f WHERE f.item_kind='event' AND f.item_id=e.id AND f.target_lang=?1
+                           AND f.original=COALESCE(e.title,'') AND f.retry_at > strftime('%s','now'))
+       ORDER BY e.last_seen DESC LIMIT ?2",
+      &["title", "summary"]),
+  };
+  let mut stmt = db.conn.prepare(sql)?;
+  let rows = stmt.query_map(params![target, limit], |r| {
+    let mut values = Vec::new();
+    for (i, f) in fields.iter().enumerate() { values.push((*f, r.get::<_,String>(i + 2)?)); }
+    Ok((r.get(0)?, r.get(1)?, values))
+  })?;
+  Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+const RETRY_SECS: i64 = 60;
+const MAX_RETRY_SECS: i64 = 24 * 3600;
+
+/// Puts a failed item off for a doubling delay, up to a day.
+fn record_failure(db: &Db, (kind, id): (&str, &str), target: &str, original: &str, error: &str) -> Result<()> {
+  let attempts: i64 = db.conn.query_row(
+    "SELECT attempts FROM translation_failure WHERE item_kind=?1 AND item_id=?2 AND target_lang=?3 AND original=?4",
+    params![kind, id, target, original], |r| r.get(0)).optional()?.unwrap_or(0) + 1;
+  let delay = RETRY_SECS.saturating_mul(1 << (attempts - 1).min(20)).min(MAX_RETRY_SECS);
+  db.conn.execute(
+    "INSERT OR REPLACE INTO translation_failure(item_kind,item_id,target_lang,original,attempts,retry_at,error)
+     VALUES (?1,?2,?3,?4,?5,strftime('%s','now')+?6,?7)",
+    params![kind, id, target, original, attempts, delay, error])?;
+  Ok(())
+}
+
+async fn translate_item(db: &Db, provider: &dyn AiProvider, item: (&str, &str), lang: Option<&str>, fields: &[(&str, String)], target: &str) -> Result<()> {
+  // without a declared language, text that reads as `target` is kept as is
+  let text = fields.iter().map(|(_, t)| t.as_str()).collect::<Vec<_>>().join(" ");
+  let lang = lang.or_else(|| guess_language(&text));
+  // the first field (headline/title) is written last: it marks the item done
+  for (i, (field, text)) in fields.iter().enumerate().rev() {
+    if i > 0 && text.is_empty() { continue; }
+    let (source, translated) = translate(db, provider, text, lang, target).await?;
+    store(db, item, field, target, source.as_deref(), text, &translated)?;
+  }
+  Ok(())
+}
+
+/// Translates one batch of pending alerts and events; returns how many items
+/// were translated. Failed items are retried with a growing backoff.
+pub async fn translate_pending(db: &Db, provider: &dyn AiProvider, target: &str, limit: i64) -> Result<usize> {
+  let mut n = 0;
+  for kind in ["alert", "event"] {
+    for (id, lang, fields) in pending(db, kind, target, limit)? {
+      match translate_item(db, provider, (kind, &id), lang.as_deref(), &fields, target).await {
+        Ok(()) => {
+          db.conn.execute("DELETE FROM translation_failure WHERE item_kind=?1 AND item_id=?2 AND target_lang=?3", params![kind, id, target])?;
+          n += 1;
+        }
+        Err(e) => {
+          tracing::warn!("translate {kind} {id}: {e}");
+          record_failure(db, (kind, &id), target, &fields[0].1, &e.to_string())?;
+        }
+      }
+    }
+  }
+  Ok(n)
+}
+
+pub fn for_item(db: &Db, kind: &str, id: &str, target: &str) -> Result<Vec<FieldTranslation>> {
+  let mut stmt = db.conn.prepare(
+    "SELECT field, source_lang, target_lang, original, translated FROM item_translation
+     WHERE item_kind=?1 AND item_id=?2 AND target_lang=?3 ORDER BY field")?;
+  let rows = stmt.query_map(params![kind, id, target], |r| Ok(FieldTranslation {
+    field: r.get(0)?, source_lang: r.get(1)?, target_lang: r.get(2)?, original: r.get(3)?, translated: r.get(4)?,
+  }))?;
+  Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Background sweep that keeps alert and event translations current.
+pub fn spawn(core: Arc<Core>) -> Result<()> {
+  let cfg = &core.settings;
+  if !cfg.translation.enabled { return Ok(()); }
+  let provider = provider::from_settings(&cfg.ai)?;
+  tokio::spawn(async move {
+    let target = &core.settings.translation.target_language;
+    loop {
+      match translate_pending(&core.db, provider.as_ref(), target, 20).await {
+        Ok(0) => sleep(Duration::from_secs(30)).await,
+        Ok(_) => {}
+        Err(e) => { tracing::warn!("translate: {e}"); sleep(Duration::from_secs(60)).await; }
+      }
+    }
+  });
+  Ok(())
+}
@@ -0,0 +1,245 @@
+use std::collections::{BTreeMap, HashMap};
+use anyhow::Result;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use crate::db::Db;
+use crate::error::Error;
+use crate::rules::source_of_rank;
+
+/// Longest series a single query may produce.
+pub const MAX_BUCKETS: i64 = 5000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all="snake_case")]
+pub enum Bucket { Hour, #[default] Day, Week }
+
+impl Bucket {
+  pub fn secs(self) -> i64 {
+    match self { Bucket::Hour => 3600, Bucket::Day => 86400, Bucket::Week => 7 * 86400 }
+  }
+
+  /// Start of the UTC bucket holding `ts`. Weeks start on Monday; the epoch
+  /// was a Thursday.
+  pub fn start(self, ts: i64) -> i64 {
+    let off = if self == Bucket::Week { 4 * 86400 } else { 0 };
+    (ts - off).div_euclid(self.secs()) * self.secs() + off
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all="snake_case")]
+pub enum GroupBy {
+  Class, Source, SeverityBand, Country,
+  /// First-level division from the gazetteer, as `<name>, <ISO alpha-3>`.
+  Region,
+  Aoi,
+}
+
+/// When a count is flagged: at least `min_count` events and `ratio` times
+/// the mean of the `periods` preceding buckets (for heatmaps, preceding
+/// ranges of the same length).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(default)]
+pub struct Baseline { pub periods: u32, pub ratio: f64, pub min_count: i64 }
+
+impl Default for Baseline {
+  fn default() -> Self { Baseline { periods: 7, ratio: 3.0, min_count: 3 } }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(default)]
+pub struct AnalyticsQuery {
+  /// Defaults to 30 days before `until`.
+  pub since: Option<i64>,
+  /// Defaults to now.
+  pub until: Option<i64>,
+  pub bucket: Bucket,
+  pub group_by: Option<GroupBy>,
+  /// [minx, miny, maxx, maxy]
+  pub bbox: Option<[f64; 4]>,
+  /// Restricts to an area of interest's bbox.
+  pub aoi: Option<String>,
+  pub classes: Option<Vec<String>>,
+  pub baseline: Baseline,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct SeriesPoint {
+  /// Bucket start.
+  pub ts: i64,
+  /// Group key; empty when the query is not grouped.
+  pub group: String,
+  pub count: i64,
+  pub baseline: f64,
+  pub anomaly: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct TimeSeries {
+  pub since: i64,
+  pub until: i64,
+  pub bucket_secs: i64,
+  /// Buckets without events are omitted.
+  pub points: Vec<SeriesPoint>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct HeatCell {
+  pub geohash: String,
+  /// Cell centre.
+  pub lat: f64,
+  pub lon: f64,
+  pub count: i64,
+  pub max_severity: f64,
+  pub baseline: f64,
+  pub anomaly: bool,
+}
+
+/// Band names follow the CAP severities that `cap_severity` maps from.
+pub fn severity_band(s: f64) -> &'static str {
+  if s >= 0.75 { "extreme" } else if s >= 0.5 { "severe" } else if s >= 0.25 { "moderate" } else { "minor" }
+}
+
+struct Row { ts: i64, class: String, source: &'static str, severity: f64, country: Option<String>, region: Option<String>, lat: Option<f64>, lon: Option<f64> }
+
+struct Aoi { id: String, bbox: [f64; 4] }
+
+fn contains([minx, miny, maxx, maxy]: [f64; 4], r: &Row) -> bool {
+  matches!((r.lat, r.lon), (Some(lat), Some(lon)) if lon >= minx && lon <= maxx && lat >= miny && lat <= maxy)
+}
+
+impl AnalyticsQuery {
+  fn range(&self, now: i64) -> Result<(i64, i64)> {
+    let until = self.until.unwrap_or(now);
+    let since = self.since.unwrap_or(until - 30 * 86400);
+    if since >= until { return Err(Error::invalid("since must be before until").into()); }
+    if (until - since) / self.bucket.secs() > MAX_BUCKETS {
+      return Err(Error::invalid(format!("more than {MAX_BUCKETS} buckets; use a larger bucket")).into());
+    }
+    Ok((since, until))
+  }
+
+  /// `None` when unrestricted, so events without a location still count.
+  fn area(&self, db: &Db) -> Result<Option<[f64; 4]>> {
+    if let Some(aoi) = &self.aoi {
+      return db.conn.query_row("SELECT minx,miny,maxx,maxy FROM aoi WHERE id=?1", [aoi],
+        |r| Ok(Some([r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?])))
+        .optional()?.ok_or_else(|| Error::not_found(format!("area of interest {aoi}")).into());
+    }
+    Ok(self.bbox)
+  }
+
+  /// Events first seen in `[from, until)` inside the query area and classes.
+  fn rows(&self, db: &Db, from: i64, until: i64) -> Result<Vec<Row>> {
+    let area = self.area(db)?;
+    let [minx, miny, maxx, maxy] = area.map_or([None; 4], |b| b.map(Some));
+    let mut stmt = db.conn.prepare(
+      "SELECT e.first_seen, COALESCE(e.class,''), COALESCE(e.source_rank,0), COALESCE(e.severity,0), e.country, p.admin1 || ', ' || p.country, e.lat, e.lon
+       FROM event e LEFT JOIN item_place p ON p.item_kind='event' AND p.item_id=e.id
+       WHERE e.first_seen >= ?1 AND e.first_seen < ?2 AND (?3 IS NULL OR (e.lon BETWEEN ?3 AND ?5 AND e.lat BETWEEN ?4 AND ?6))")?;
+    let rows = stmt.query_map(params![from, until, minx, miny, maxx, maxy], |r| Ok(Row {
+      ts: r.get(0)?, class: r.get(1)?, source: source_of_rank(r.get(2)?), severity: r.get(3)?,
+      country: r.get(4)?, region: r.get(5)?, lat: r.get(6)?, lon: r.get(7)?,
+    }))?;
+    let mut out = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+    if let Some(classes) = &self.classes {
+      out.retain(|r| classes.iter().any(|c| c.eq_ignore_ascii_case(&r.class)));
+    }
+    Ok(out)
+  }
+}
+
+/// The group keys a row counts towards. Only area-of-interest grouping can
+/// give more than one (overlapping areas) or none (outside every area).
+fn groups(by: Option<GroupBy>, r: &Row, aois: &[Aoi]) -> Vec<String> {
+  match by {
+    None => vec![String::new()],
+    Some(GroupBy::Class) => vec![r.class.clone()],
+    Some(GroupBy::Source) => vec![r.source.to_string()],
+    Some(GroupBy::SeverityBand) => vec![severity_band(r.severity).to_string()],
+    Some(GroupBy::Country) => vec![r.country.clone().unwrap_or_else(|| "unknown".into())],
+    Some(GroupBy::Region) => vec![r.region.clone().unwrap_or_else(|| "unknown".into())],
+    Some(GroupBy::Aoi) => aois.iter().filter(|a| contains(a.bbox, r)).map(|a| a.id.clone()).collect(),
+  }
+}
+
+fn is_anomaly(b: &Baseline, count: i64, baseline: f64) -> bool {
+  count >= b.min_count && count as f64 >= b.ratio * baseline
+}
+
+/// Event counts per bucket and group over the query range, each compared
+/// with the mean of the preceding `baseline.periods` buckets. `since` is
+/// rounded down to a bucket start.
+pub fn series(db: &Db, q: &AnalyticsQuery, now: i64) -> Result<TimeSeries> {
+  let (since, until) = q.range(now)?;
+  let secs = q.bucket.secs();
+  let first = q.bucket.start(since);
+  let lookback = first - secs * q.baseline.periods as i64;
+  let aois = if q.group_by == Some(GroupBy::Aoi) { list_aois(db)? } else { Vec::new() };
+
+  let mut counts: HashMap<String, BTreeMap<i64, i64>> = HashMap::new();
+  for r in q.rows(db, lookback, until)? {
+    for g in groups(q.group_by, &r, &aois) {
+      *counts.entry(g).or_default().entry(q.bucket.start(r.ts)).or_default() += 1;
+    }
+  }
+
+  let periods = q.baseline.periods as i64;
+  let mut points = Vec::new();
+  for (group, buckets) in counts {
+    for (&ts, &count) in buckets.range(first..) {
+      let baseline = if periods == 0 { 0.0 } else {
+        buckets.range(ts - secs * periods..ts).map(|(_, c)| *c).sum::<i64>() as f64 / periods as f64
+      };
+      points.push(SeriesPoint { ts, group: group.clone(), count, baseline, anomaly: periods > 0 && is_anomaly(&q.baseline, count, baseline) });
+    }
+  }
+  points.sort_by(|a, b| a.ts.cmp(&b.ts).then_with(|| a.group.cmp(&b.group)));
+  Ok(TimeSeries { since: first, until, bucket_secs: secs, points })
+}
+
+/// Event counts per geohash cell of `precision` characters over the query
+/// range, each compared with the mean over the preceding
+/// `baseline.periods` ranges of equal length. `bucket` and `group_by` are
+/// ignored.
+pub fn heatmap(db: &Db, q: &AnalyticsQuery, precision: usize, now: i64) -> Result<Vec<HeatCell>> {
+  if !(1..=8).contains(&precision) { return Err(Error::invalid("geohash precision must be 1 to 8").into()); }
+  let (since, until) = q.range(now)?;
+  let periods = q.baseline.periods as i64;
+  let lookback = since - (until - since) * periods;
+
+  // (count in range, count in baseline, max severity in range)
+  let mut cells: HashMap<String, (i64, i64, f64)> = HashMap::new();
+  for r in q.rows(db, lookback, until)? {
+    let (Some(lat), Some(lon)) = (r.lat, r.lon) else { continue };
+    let Ok(hash) = geohash::encode(geohash::Coord { x: lon, y: lat }, precision) else { continue };
+    let c = cells.entry(hash).or_insert((0, 0, 0.0));
+    if r.ts >= since { c.0 += 1; c.2 = c.2.max(r.severity); } else { c.1 += 1; }
+  }
+
+  let mut out = Vec::new();
+  for (hash, (count, before, max_severity)) in cells {
+    if count == 0 { continue; }
+    let Ok((centre, _, _)) = geohash::decode(&hash) else { continue };
+    let baseline = if periods == 0 { 0.0 } else { before as f64 / periods as f64 };
+    out.push(HeatCell {
+      lat: centre.y, lon: centre.x, count, max_severity, baseline,
+      anomaly: periods > 0 && is_anomaly(&q.baseline, count, baseline), geohash: hash,
+    });
+  }
+  out.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.geohash.cmp(&b.geohash)));
+  Ok(out)
+}
+
+fn list_aois(db: &Db) -> Result<Vec<Aoi>> {
+  let mut stmt = db.conn.prepare("SELECT id,minx,miny,maxx,maxy FROM aoi")?;
+  let rows = stmt.query_map([], |r| Ok(Aoi { id: r.get(0)?, bbox: [r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?] }))?;
+  Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
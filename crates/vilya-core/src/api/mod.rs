@@ -0,0 +1,218 @@
+//! Optional HTTP/JSON API on 127.0.0.1 for dashboards and scripts. Mirrors
+//! the desktop commands for search, event detail, alerts and analytics,
+//! plus a Server-Sent Events stream of what windows are pushed.
+//!
+//! ```text
+//! GET  /api/events?q=&since=&until=&as_of=&limit=
+//! GET  /api/events/{id}?radius_km=&hours=
+//! GET  /api/alerts?minx=&miny=&maxx=&maxy=&now_after=&as_of=
+//! GET  /api/shaking?minx=&miny=&maxx=&maxy=&since=
+//! POST /api/analytics/series              body: AnalyticsQuery
+//! POST /api/analytics/heatmap?precision=  body: AnalyticsQuery
+//! GET  /api/stream?bbox=&classes=&min_severity=
+//! GET  /api/playback?from=&to=&speed=&bbox=&classes=&min_severity=
+//! ```
+//!
+//! `ogc` serves the same events and alerts as OGC API – Features under `/ogc`.
+use std::convert::Infallible;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::Stream;
+use serde::Deserialize;
+use tokio::sync::broadcast::error::RecvError;
+use crate::analytics::{self, AnalyticsQuery, HeatCell, TimeSeries};
+use crate::history;
+use crate::ipc::{detail, query, EventDetail, IpcError, ShakingContour, UiAlert, UiEvent};
+use crate::runtime::Core;
+use crate::stream::StreamFilter;
+
+pub mod ogc;
+
+/// Starts the server if `api.enabled`. Refuses to without a token.
+pub fn spawn(core: Arc<Core>) -> Result<()> {
+  let s = &core.settings.api;
+  if !s.enabled { return Ok(()); }
+  let token = s.token.clone().filter(|t| !t.is_empty()).context("`api.enabled` needs `api.token` in settings.json")?;
+  let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, s.port));
+  let app = router(core.clone(), token);
+  tokio::spawn(async move {
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+      Ok(l) => l,
+      Err(e) => { tracing::warn!("api: bind {addr}: {e}"); return; }
+    };
+    tracing::info!("api: listening on http://{addr}");
+    if let Err(e) = axum::serve(listener, app).await { tracing::warn!("api: {e}"); }
+  });
+  Ok(())
+}
+
+pub fn router(core: Arc<Core>, token: String) -> Router {
+  Router::new()
+    .route("/api/events", get(search_events))
+    .route("/api/events/{id}", get(get_event))
+    .route("/api/alerts", get(query_alerts))
+    .route("/api/shaking", get(query_shaking))
+    .route("/api/analytics/series", post(analytics_series))
+    .route("/api/analytics/heatmap", post(analytics_heatmap))
+    .route("/api/stream", get(stream))
+    .route("/api/playback", get(playback))
+    .merge(ogc::routes())
+    .layer(middleware::from_fn_with_state(Arc::new(token), auth))
+    .with_state(core)
+}
+
+/// `IpcError` as the response body, with a matching status.
+struct ApiError(IpcError);
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+impl<E: Into<IpcError>> From<E> for ApiError {
+  fn from(e: E) -> Self { ApiError(e.into()) }
+}
+
+impl IntoResponse for ApiError {
+  fn into_response(self) -> Response {
+    let status = match self.0 {
+      IpcError::NotFound(_) => StatusCode::NOT_FOUND,
+      IpcError::InvalidArgument(_) => StatusCode::BAD_REQUEST,
+      IpcError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+      IpcError::DbBusy(_) => StatusCode::SERVICE_UNAVAILABLE,
+      IpcError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(self.0)).into_response()
+  }
+}
+
+/// Accepts `Authorization: Bearer <token>`, or `?token=` (URL-encoded) for
+/// clients such as `EventSource` that cannot set headers.
+async fn auth(State(token): State<Arc<String>>, req: Request, next: Next) -> Response {
+  let bearer = req.headers().get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()).and_then(|v| v.strip_prefix("Bearer "));
+  let param = req.uri().query()
+    .and_then(|q| url::form_urlencoded::parse(q.as_bytes()).find(|(k, _)| k == "token"))
+    .map(|(_, v)| v.into_owned());
+  if bearer.map(str::to_string).or(param).is_some_and(|t| same(t.as_bytes(), token.as_bytes())) {
+    next.run(req).await
+  } else {
+    ApiError(IpcError::Unauthorized("missing or wrong token".into())).into_response()
+  }
+}
+
+/// Compares without returning early, so timing does not reveal a prefix.
+fn same(a: &[u8], b: &[u8]) -> bool {
+  a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[derive(Deserialize)]
+struct SearchParams { q: Option<String>, since: Option<i64>, until: Option<i64>, as_of: Option<i64>, limit: Option<usize> }
+
+async fn search_events(State(core): State<Arc<Core>>, Query(p): Query<SearchParams>) -> ApiResult<Vec<UiEvent>> {
+  Ok(Json(query::search_events(&core.db, p.q.as_deref(), p.since, p.until, p.as_of, p.limit.unwrap_or(1000).min(1000))?))
+}
+
+#[derive(Deserialize)]
+struct DetailParams { radius_km: Option<f64>, hours: Option<i64> }
+
+async fn get_event(State(core): State<Arc<Core>>, Path(id): Path<String>, Query(p): Query<DetailParams>) -> ApiResult<EventDetail> {
+  let radius_km = p.radius_km.unwrap_or(detail::DEFAULT_RADIUS_KM);
+  let window_secs = p.hours.map_or(detail::DEFAULT_WINDOW_SECS, |h| h * 3600);
+  if radius_km.is_nan() || radius_km < 0.0 || window_secs < 0 { return Err(ApiError(IpcError::invalid("radius_km and hours must be non-negative"))); }
+  Ok(Json(detail::event_detail(&core.db, &id, radius_km, window_secs, chrono::Utc::now().timestamp())?))
+}
+
+#[derive(Deserialize)]
+struct AlertParams { minx: Option<f64>, miny: Option<f64>, maxx: Option<f64>, maxy: Option<f64>, now_after: Option<i64>, as_of: Option<i64> }
+
+async fn query_alerts(State(core): State<Arc<Core>>, Query(p): Query<AlertParams>) -> ApiResult<Vec<UiAlert>> {
+  let bbox = [p.minx.unwrap_or(-180.0), p.miny.unwrap_or(-90.0), p.maxx.unwrap_or(180.0), p.maxy.unwrap_or(90.0)];
+  Ok(Json(query::query_alerts(&core.db, bbox, p.now_after.unwrap_or_else(|| chrono::Utc::now().timestamp()), p.as_of)?))
+}
+
+#[derive(Deserialize)]
+struct ShakingParams { minx: Option<f64>, miny: Option<f64>, maxx: Option<f64>, maxy: Option<f64>, since: Option<i64> }
+
+async fn query_shaking(State(core): State<Arc<Core>>, Query(p): Query<ShakingParams>) -> ApiResult<Vec<ShakingContour>> {
+  let bbox = [p.minx.unwrap_or(-180.0), p.miny.unwrap_or(-90.0), p.maxx.unwrap_or(180.0), p.maxy.unwrap_or(90.0)];
+  let since = p.since.unwrap_or_else(|| chrono::Utc::now().timestamp() - query::SHAKING_WINDOW_SECS);
+  Ok(Json(query::query_shaking(&core.db, bbox, since)?))
+}
+
+async fn analytics_series(State(core): State<Arc<Core>>, Json(q): Json<AnalyticsQuery>) -> ApiResult<TimeSeries> {
+  Ok(Json(analytics::series(&core.db, &q, chrono::Utc::now().timestamp())?))
+}
+
+#[derive(Deserialize)]
+struct HeatParams { precision: Option<usize> }
+
+async fn analytics_heatmap(State(core): State<Arc<Core>>, Query(p): Query<HeatParams>, Json(q): Json<AnalyticsQuery>) -> ApiResult<Vec<HeatCell>> {
+  Ok(Json(analytics::heatmap(&core.db, &q, p.precision.unwrap_or(4), chrono::Utc::now().timestamp())?))
+}
+
+/// `StreamFilter` as query parameters: `bbox=minx,miny,maxx,maxy` and
+/// comma-separated `classes`.
+#[derive(Deserialize)]
+struct StreamParams { bbox: Option<String>, classes: Option<String>, min_severity: Option<f64> }
+
+impl StreamParams {
+  fn filter(self) -> Result<StreamFilter, IpcError> {
+    let bbox = self.bbox.map(|b| {
+      let v = b.split(',').map(|x| x.trim().parse::<f64>()).collect::<Result<Vec<_>, _>>().map_err(|e| IpcError::invalid(format!("bbox: {e}")))?;
+      <[f64; 4]>::try_from(v).map_err(|_| IpcError::invalid("bbox needs four numbers"))
+    }).transpose()?;
+    let classes = self.classes.map(|c| c.split(',').map(str::to_string).collect());
+    Ok(StreamFilter { bbox, classes, min_severity: self.min_severity })
+  }
+}
+
+/// Each item is an SSE event named like the desktop window events, with
+/// the item as JSON data. A slow client that falls behind gets a `lagged`
+/// event with the number of items it missed and should re-query.
+async fn stream(State(core): State<Arc<Core>>, Query(p): Query<StreamParams>) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+  let filter = p.filter()?;
+  let rx = core.live.subscribe();
+  let events = futures::stream::unfold((rx, filter), |(mut rx, filter)| async move {
+    loop {
+      let event = match rx.recv().await {
+        Ok(item) if item.accepted_by(&filter) => {
+          let data = serde_json::to_value(&item).map(|mut v| v["data"].take()).unwrap_or_default();
+          Event::default().event(item.name()).data(data.to_string())
+        }
+        Ok(_) => continue,
+        Err(RecvError::Lagged(n)) => Event::default().event("lagged").data(n.to_string()),
+        Err(RecvError::Closed) => return None,
+      };
+      return Some((Ok(event), (rx, filter)));
+    }
+  });
+  Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Deserialize)]
+struct PlaybackParams { from: i64, to: i64, speed: Option<f64>, bbox: Option<String>, classes: Option<String>, min_severity: Option<f64> }
+
+/// The changes between `from` and `to` as SSE events named by their kind,
+/// paced `speed` history seconds per second (default 0, no pauses), then
+/// a `done` event.
+async fn playback(State(core): State<Arc<Core>>, Query(p): Query<PlaybackParams>) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+  let filter = StreamParams { bbox: p.bbox, classes: p.classes, min_severity: p.min_severity }.filter()?;
+  let changes = history::changes(&core.db, p.from, p.to, &filter)?;
+  let speed = p.speed.unwrap_or(0.0);
+  history::check_speed(&changes, speed)?;
+  let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+  tokio::spawn(async move {
+    let sent = history::play(&changes, speed, |c| {
+      let name = serde_json::to_value(c.kind)?.as_str().unwrap_or_default().to_string();
+      tx.send(Event::default().event(name).data(serde_json::to_string(c)?)).map_err(|_| anyhow::anyhow!("client gone"))
+    }).await;
+    if sent.is_ok() { let _ = tx.send(Event::default().event("done").data(changes.len().to_string())); }
+  });
+  let events = futures::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|e| (Ok(e), rx)) });
+  Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
@@ -0,0 +1,285 @@
+//! OGC API – Features (Part 1: Core, GeoJSON) over the `events` and
+//! `alerts` collections, so GIS clients such as QGIS can load them as live
+//! layers. Served under `/ogc` behind the same token as the rest of the API.
+//!
+//! `bbox` filters event points and alert bboxes (through `alert_rtree`);
+//! `datetime` filters events by first seen and alerts by overlap of onset
+//! and expiry. Without `datetime`, alerts are those in force now. Alert
+//! geometry is `polygon_geojson`; CAP alerts store several polygons, which
+//! become one `GeometryCollection`.
+use std::sync::Arc;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use rusqlite::types::Value as Sql;
+use rusqlite::params_from_iter;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use crate::export::area_geometry;
+use crate::ipc::dto::parse_geojson;
+use crate::ipc::IpcError;
+use crate::rules::source_of_rank;
+use crate::runtime::Core;
+use super::ApiError;
+
+pub const DEFAULT_LIMIT: usize = 100;
+pub const MAX_LIMIT: usize = 10_000;
+
+const CONFORMANCE: &[&str] = &[
+  "http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/core",
+  "http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/geojson",
+];
+const CRS84: &str = "http://www.opengis.net/def/crs/OGC/1.3/CRS84";
+
+pub fn routes() -> Router<Arc<Core>> {
+  Router::new()
+    .route("/ogc", get(landing))
+    .route("/ogc/conformance", get(conformance))
+    .route("/ogc/collections", get(collections))
+    .route("/ogc/collections/{collection}", get(collection))
+    .route("/ogc/collections/{collection}/items", get(items))
+    .route("/ogc/collections/{collection}/items/{id}", get(item))
+}
+
+#[derive(Clone, Copy)]
+enum Collection { Events, Alerts }
+
+impl Collection {
+  fn parse(name: &str) -> Result<Self, ApiError> {
+    match name {
+      "events" => Ok(Collection::Events),
+      "alerts" => Ok(Collection::Alerts),
+      _ => Err(ApiError(IpcError::not_found(format!("collection {name}")))),
+    }
+  }
+
+  fn describe(self, links: &Links) -> Value {
+    let (id, title, description) = match self {
+      Collection::Events => ("events", "Events", "Merged crisis events as points, by time first seen."),
+      Collection::Alerts => ("alerts", "Alerts", "Warning areas from CAP and NWS feeds, by onset to expiry."),
+    };
+    json!({
+      "id": id, "title": title, "description": description, "itemType": "feature", "crs": [CRS84],
+      "extent": { "spatial": { "bbox": [[-180.0, -90.0, 180.0, 90.0]], "crs": CRS84 }, "temporal": { "interval": [[null, null]] } },
+      "links": [
+        { "href": links.href(&format!("/ogc/collections/{id}"), Vec::new()), "rel": "self", "type": "application/json" },
+        { "href": links.href(&format!("/ogc/collections/{id}/items"), Vec::new()), "rel": "items", "type": "application/geo+json" },
+      ],
+    })
+  }
+}
+
+#[derive(Deserialize)]
+struct TokenParam { token: Option<String> }
+
+/// Absolute links need the address the client used, and the `?token=` it
+/// authenticated with, if any, for the client to follow them.
+struct Links { base: String, token: Option<String> }
+
+impl Links {
+  fn new(headers: &HeaderMap, token: Option<String>) -> Self {
+    let host = headers.get(header::HOST).and_then(|h| h.to_str().ok()).unwrap_or("127.0.0.1");
+    Links { base: format!("http://{host}"), token }
+  }
+
+  fn href(&self, path: &str, mut query: Vec<String>) -> String {
+    if let Some(t) = &self.token { query.push(format!("token={}", encode(t))); }
+    if query.is_empty() { format!("{}{path}", self.base) } else { format!("{}{path}?{}", self.base, query.join("&")) }
+  }
+}
+
+fn encode(s: &str) -> String {
+  url::form_urlencoded::byte_serialize(s.as_bytes()).collect()
+}
+
+async fn landing(headers: HeaderMap, Query(t): Query<TokenParam>) -> Json<Value> {
+  let links = Links::new(&headers, t.token);
+  Json(json!({
+    "title": "Vilya",
+    "description": "Crisis events and alerts as OGC API – Features.",
+    "links": [
+      { "href": links.href("/ogc", Vec::new()), "rel": "self", "type": "application/json" },
+      { "href": links.href("/ogc/conformance", Vec::new()), "rel": "conformance", "type": "application/json" },
+      { "href": links.href("/ogc/collections", Vec::new()), "rel": "data", "type": "application/json" },
+    ],
+  }))
+}
+
+async fn conformance() -> Json<Value> {
+  Json(json!({ "conformsTo": CONFORMANCE }))
+}
+
+async fn collections(headers: HeaderMap, Query(t): Query<TokenParam>) -> Json<Value> {
+  let links = Links::new(&headers, t.token);
+  Json(json!({
+    "links": [{ "href": links.href("/ogc/collections", Vec::new()), "rel": "self", "type": "application/json" }],
+    "collections": [Collection::Events.describe(&links), Collection::Alerts.describe(&links)],
+  }))
+}
+
+async fn collection(headers: HeaderMap, Path(name): Path<String>, Query(t): Query<TokenParam>) -> Result<Json<Value>, ApiError> {
+  Ok(Json(Collection::parse(&name)?.describe(&Links::new(&headers, t.token))))
+}
+
+#[derive(Deserialize)]
+struct ItemParams { bbox: Option<String>, datetime: Option<String>, limit: Option<usize>, offset: Option<usize>, token: Option<String> }
+
+fn geo_json(v: Value) -> Response {
+  ([(header::CONTENT_TYPE, "application/geo+json")], Json(v)).into_response()
+}
+
+async fn items(State(core): State<Arc<Core>>, headers: HeaderMap, Path(name): Path<String>, Query(p): Query<ItemParams>) -> Result<Response, ApiError> {
+  let c = Collection::parse(&name)?;
+  let bbox = p.bbox.as_deref().map(parse_bbox).transpose()?;
+  let (since, until) = match p.datetime.as_deref() {
+    Some(d) => parse_interval(d)?,
+    None => match c {
+      Collection::Events => (None, None),
+      Collection::Alerts => { let now = chrono::Utc::now().timestamp(); (Some(now), Some(now)) }
+    },
+  };
+  let limit = p.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+  let offset = p.offset.unwrap_or(0);
+
+  let (mut sql, mut args) = match c {
+    Collection::Events => events_where(bbox, since, until),
+    Collection::Alerts => alerts_where(bbox, since, until),
+  };
+  let matched: i64 = core.db.conn.query_row(&format!("SELECT COUNT(1) {sql}"), params_from_iter(&args), |r| r.get(0))?;
+  sql.push_str(match c { Collection::Events => " ORDER BY e.first_seen DESC", Collection::Alerts => " ORDER BY a.onset DESC" });
+  sql.push_str(" LIMIT ? OFFSET ?");
+  args.extend([Sql::Integer(limit as i64), Sql::Integer(offset as i64)]);
+  let features = select_features(&core, c, &sql, &args)?;
+
+  let links = Links::new(&headers, p.token.clone());
+  let page = |offset: usize| {
+    let mut q = vec![format!("limit={limit}"), format!("offset={offset}")];
+    if let Some(b) = &p.bbox { q.push(format!("bbox={b}")); }
+    if let Some(d) = &p.datetime { q.push(format!("datetime={}", encode(d))); }
+    links.href(&format!("/ogc/collections/{name}/items"), q)
+  };
+  let mut links = vec![json!({ "href": page(offset), "rel": "self", "type": "application/geo+json" })];
+  if (offset + features.len()) < matched as usize {
+    links.push(json!({ "href": page(offset + limit), "rel": "next", "type": "application/geo+json" }));
+  }
+  Ok(geo_json(json!({
+    "type": "FeatureCollection",
+    "timeStamp": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+    "numberMatched": matched,
+    "numberReturned": features.len(),
+    "features": features,
+    "links": links,
+  })))
+}
+
+async fn item(State(core): State<Arc<Core>>, Path((name, id)): Path<(String, String)>) -> Result<Response, ApiError> {
+  let c = Collection::parse(&name)?;
+  let sql = match c { Collection::Events => "FROM event e WHERE e.id = ?", Collection::Alerts => "FROM alert a WHERE a.id = ?" };
+  let feature = select_features(&core, c, sql, &[Sql::Text(id.clone())])?.pop()
+    .ok_or_else(|| IpcError::not_found(format!("{name} {id}")))?;
+  Ok(geo_json(feature))
+}
+
+fn events_where(bbox: Option<[f64; 4]>, since: Option<i64>, until: Option<i64>) -> (String, Vec<Sql>) {
+  // reports merged into an earlier one stay hidden, as in the app
+  let mut sql = "FROM event e WHERE e.lat IS NOT NULL AND e.lon IS NOT NULL
+    AND NOT EXISTS (SELECT 1 FROM event_duplicate d WHERE d.event_id=e.id)".to_string();
+  let mut args = Vec::new();
+  if let Some([minx, miny, maxx, maxy]) = bbox {
+    // minx > maxx crosses the antimeridian
+    sql.push_str(if minx <= maxx { " AND e.lon BETWEEN ? AND ?" } else { " AND (e.lon >= ? OR e.lon <= ?)" });
+    sql.push_str(" AND e.lat BETWEEN ? AND ?");
+    args.extend([minx, maxx, miny, maxy].map(Sql::Real));
+  }
+  if let Some(t) = since { sql.push_str(" AND e.first_seen >= ?"); args.push(Sql::Integer(t)); }
+  if let Some(t) = until { sql.push_str(" AND e.first_seen <= ?"); args.push(Sql::Integer(t)); }
+  (sql, args)
+}
+
+fn alerts_where(bbox: Option<[f64; 4]>, since: Option<i64>, until: Option<i64>) -> (String, Vec<Sql>) {
+  let mut sql = "FROM alert a".to_string();
+  let mut args = Vec::new();
+  if let Some([minx, miny, maxx, maxy]) = bbox {
+    sql.push_str(" JOIN alert_rtree r ON r.rowid = a.rowid AND r.miny <= ? AND r.maxy >= ?");
+    sql.push_str(if minx <= maxx { " AND r.minx <= ? AND r.maxx >= ?" } else { " AND (r.minx <= ? OR r.maxx >= ?)" });
+    args.extend([maxy, miny, maxx, minx].map(Sql::Real));
+  }
+  sql.push_str(" WHERE a.cancelled_at IS NULL");
+  if let Some(t) = since { sql.push_str(" AND a.expires >= ?"); args.push(Sql::Integer(t)); }
+  if let Some(t) = until { sql.push_str(" AND a.onset <= ?"); args.push(Sql::Integer(t)); }
+  (sql, args)
+}
+
+fn select_features(core: &Core, c: Collection, from: &str, args: &[Sql]) -> Result<Vec<Value>, ApiError> {
+  let columns = match c {
+    Collection::Events => "e.id,e.title,e.summary,e.class,e.severity,e.confidence,e.first_seen,e.last_seen,e.country,COALESCE(e.source_rank,0),e.lon,e.lat",
+    Collection::Alerts => "a.id,a.source,a.headline,a.event,a.severity,a.urgency,a.certainty,a.onset,a.expires,a.area_desc,a.language,a.polygon_geojson",
+  };
+  let mut stmt = core.db.conn.prepare(&format!("SELECT {columns} {from}"))?;
+  let rows = stmt.query_map(params_from_iter(args), |r| Ok(match c {
+    Collection::Events => event_feature(r)?,
+    Collection::Alerts => alert_feature(r)?,
+  }))?;
+  Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+fn rfc3339(ts: Option<i64>) -> Option<String> {
+  ts.and_then(|t| chrono::DateTime::from_timestamp(t, 0)).map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+}
+
+fn event_feature(r: &rusqlite::Row) -> rusqlite::Result<Value> {
+  let point = match (r.get::<_, Option<f64>>(10)?, r.get::<_, Option<f64>>(11)?) {
+    (Some(lon), Some(lat)) => json!({ "type": "Point", "coordinates": [lon, lat] }),
+    _ => Value::Null,
+  };
+  Ok(json!({
+    "type": "Feature", "id": r.get::<_, String>(0)?,
+    "geometry": point,
+    "properties": {
+      "title": r.get::<_, Option<String>>(1)?, "summary": r.get::<_, Option<String>>(2)?, "class": r.get::<_, Option<String>>(3)?,
+      "severity": r.get::<_, Option<f64>>(4)?, "confidence": r.get::<_, Option<f64>>(5)?,
+      "first_seen": rfc3339(r.get(6)?), "last_seen": rfc3339(r.get(7)?),
+      "country": r.get::<_, Option<String>>(8)?, "source": source_of_rank(r.get(9)?),
+    },
+  }))
+}
+
+fn alert_feature(r: &rusqlite::Row) -> rusqlite::Result<Value> {
+  Ok(json!({
+    "type": "Feature", "id": r.get::<_, String>(0)?,
+    "geometry": parse_geojson(r.get(11)?).and_then(area_geometry),
+    "properties": {
+      "source": r.get::<_, String>(1)?, "headline": r.get::<_, Option<String>>(2)?, "event": r.get::<_, Option<String>>(3)?,
+      "severity": r.get::<_, Option<String>>(4)?, "urgency": r.get::<_, Option<String>>(5)?, "certainty": r.get::<_, Option<String>>(6)?,
+      "onset": rfc3339(r.get(7)?), "expires": rfc3339(r.get(8)?),
+      "area_desc": r.get::<_, Option<String>>(9)?, "language": r.get::<_, Option<String>>(10)?,
+    },
+  }))
+}
+
+/// `minx,miny,maxx,maxy`, or the six-number form with heights, which are
+/// ignored.
+fn parse_bbox(s: &str) -> Result<[f64; 4], ApiError> {
+  let v = s.split(',').map(|x| x.trim().parse::<f64>()).collect::<Result<Vec<_>, _>>()
+    .map_err(|e| IpcError::invalid(format!("bbox: {e}")))?;
+  match v[..] {
+    [minx, miny, maxx, maxy] | [minx, miny, _, maxx, maxy, _] if miny <= maxy => Ok([minx, miny, maxx, maxy]),
+    _ => Err(ApiError(IpcError::invalid("bbox needs minx,miny,maxx,maxy with miny <= maxy"))),
+  }
+}
+
+/// An RFC 3339 instant, or `start/end` where either side may be `..` or
+/// empty for open.
+fn parse_interval(s: &str) -> Result<(Option<i64>, Option<i64>), ApiError> {
+  let instant = |t: &str| -> Result<Option<i64>, ApiError> {
+    if t.is_empty() || t == ".." { return Ok(None); }
+    chrono::DateTime::parse_from_rfc3339(t).map(|d| Some(d.timestamp()))
+      .map_err(|e| ApiError(IpcError::invalid(format!("datetime `{t}`: {e}"))))
+  };
+  match s.split_once('/') {
+    Some((a, b)) => Ok((instant(a)?, instant(b)?)),
+    None => { let t = instant(s)?.ok_or_else(|| IpcError::invalid("datetime needs an instant or an interval"))?; Ok((Some(t), Some(t))) }
+  }
+}
@@ -0,0 +1,453 @@
+PRAGMA journal_mode=WAL;
+PRAGMA foreign_keys=ON;
+
+CREATE TABLE IF NOT EXISTS source_item (
+  id TEXT PRIMARY KEY,
+  source TEXT NOT NULL,
+  fetched_at INTEGER NOT NULL,
+  seen_at INTEGER NOT NULL,
+  payload_json TEXT NOT NULL,
+  title TEXT,
+  body TEXT,
+  lat REAL,
+  lon REAL,
+  occurred_at INTEGER,
+  hash TEXT NOT NULL
+);
+
+-- one imported file; its rows are the source_item rows with this batch_id
+CREATE TABLE IF NOT EXISTS import_batch (
+  id TEXT PRIMARY KEY,
+  source TEXT NOT NULL, -- import:<name>
+  format TEXT NOT NULL,
+  file TEXT NOT NULL,
+  created_at INTEGER NOT NULL,
+  events INTEGER NOT NULL DEFAULT 0,
+  alerts INTEGER NOT NULL DEFAULT 0,
+  skipped INTEGER NOT NULL DEFAULT 0,
+  undone_at INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS event (
+  id TEXT PRIMARY KEY,
+  first_seen INTEGER NOT NULL,
+  last_seen INTEGER NOT NULL,
+  title TEXT,
+  summary TEXT,
+  class TEXT,
+  severity REAL,
+  confidence REAL,
+  lat REAL, lon REAL,
+  bbox TEXT,
+  geojson TEXT,
+  source_rank INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS ai_labels (
+  event_id TEXT REFERENCES event(id) ON DELETE CASCADE,
+  labels_json TEXT,
+  severity REAL,
+  PRIMARY KEY(event_id)
+);
+
+-- every labelling attempt, kept for provenance and model comparisons;
+-- ai_labels.run_id points at the run currently in effect
+CREATE TABLE IF NOT EXISTS ai_label_run (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  event_id TEXT NOT NULL REFERENCES event(id) ON DELETE CASCADE,
+  provider TEXT NOT NULL,
+  model TEXT NOT NULL,
+  prompt_hash TEXT NOT NULL,
+  labels_json TEXT,
+  severity REAL,
+  raw_response TEXT,
+  error TEXT,
+  latency_ms INTEGER NOT NULL,
+  batch_id TEXT,
+  created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_ai_label_run_event ON ai_label_run(event_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_ai_label_run_batch ON ai_label_run(batch_id);
+
+-- analyst verdict on the current AI label; class/severity, when set,
+-- override the event fields (see event_human_label)
+CREATE TABLE IF NOT EXISTS ai_label_review (
+  event_id TEXT PRIMARY KEY REFERENCES event(id) ON DELETE CASCADE,
+  run_id INTEGER REFERENCES ai_label_run(id),
+  verdict TEXT NOT NULL CHECK (verdict IN ('accepted','corrected','rejected')),
+  class TEXT,
+  severity REAL,
+  note TEXT,
+  reviewed_at INTEGER NOT NULL
+);
+
+CREATE TRIGGER IF NOT EXISTS event_human_label AFTER UPDATE OF class, severity ON event
+WHEN EXISTS (SELECT 1 FROM ai_label_review v WHERE v.event_id=NEW.id AND (v.class IS NOT NULL OR v.severity IS NOT NULL))
+BEGIN
+  UPDATE event SET
+    class = COALESCE((SELECT class FROM ai_label_review WHERE event_id=NEW.id), NEW.class),
+    severity = COALESCE((SELECT severity FROM ai_label_review WHERE event_id=NEW.id), NEW.severity)
+  WHERE id=NEW.id;
+END;
+
+-- one vector per event and model (keyed so by an upgrade); `vector` is an
+-- L2-normalised little-endian f32 array
+CREATE TABLE IF NOT EXISTS event_embedding (
+  event_id TEXT PRIMARY KEY REFERENCES event(id) ON DELETE CASCADE,
+  model TEXT NOT NULL,
+  text_hash TEXT NOT NULL,
+  dim INTEGER NOT NULL,
+  vector BLOB NOT NULL,
+  created_at INTEGER NOT NULL
+);
+
+-- a later report of an incident from another source, merged into the first
+-- report by merge::merge_semantic_duplicates
+CREATE TABLE IF NOT EXISTS event_duplicate (
+  event_id TEXT PRIMARY KEY REFERENCES event(id) ON DELETE CASCADE,
+  duplicate_of TEXT NOT NULL REFERENCES event(id) ON DELETE CASCADE,
+  model TEXT NOT NULL,
+  score REAL NOT NULL,
+  km REAL NOT NULL,
+  merged_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_event_duplicate_of ON event_duplicate(duplicate_of);
+
+-- entities extracted by the AI labeller; `norm` is entities::normalize(name)
+CREATE TABLE IF NOT EXISTS entity (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  kind TEXT NOT NULL,
+  name TEXT NOT NULL,
+  norm TEXT NOT NULL,
+  UNIQUE(kind, norm)
+);
+
+-- alternative normalised names that resolve to an entity (filled by merges)
+CREATE TABLE IF NOT EXISTS entity_alias (
+  kind TEXT NOT NULL,
+  norm TEXT NOT NULL,
+  entity_id INTEGER NOT NULL REFERENCES entity(id) ON DELETE CASCADE,
+  PRIMARY KEY(kind, norm)
+);
+
+CREATE TABLE IF NOT EXISTS event_entity (
+  event_id TEXT NOT NULL REFERENCES event(id) ON DELETE CASCADE,
+  entity_id INTEGER NOT NULL REFERENCES entity(id) ON DELETE CASCADE,
+  PRIMARY KEY(event_id, entity_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_event_entity_entity ON event_entity(entity_id);
+
+CREATE TABLE IF NOT EXISTS ai_label_failure (
+  event_id TEXT PRIMARY KEY REFERENCES event(id) ON DELETE CASCADE,
+  attempts INTEGER NOT NULL,
+  error TEXT NOT NULL,
+  raw_response TEXT,
+  failed_at INTEGER NOT NULL
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS event_fts USING fts5(title, summary, content='event', content_rowid='rowid');
+
+CREATE TABLE IF NOT EXISTS alert (
+  id TEXT PRIMARY KEY,
+  source TEXT NOT NULL,
+  headline TEXT,
+  event TEXT,
+  severity TEXT,
+  urgency TEXT,
+  certainty TEXT,
+  onset INTEGER,
+  sent INTEGER,
+  expires INTEGER,
+  area_desc TEXT,
+  polygon_geojson TEXT,
+  bbox_minx REAL, bbox_miny REAL, bbox_maxx REAL, bbox_maxy REAL,
+  raw_json TEXT NOT NULL,
+  last_seen INTEGER NOT NULL
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS alert_rtree USING rtree(
+  rowid, minx, maxx, miny, maxy
+);
+
+-- what events and alerts said over time; the version in effect at T is the
+-- latest with valid_from <= T. Kept by the triggers below on every write path.
+CREATE TABLE IF NOT EXISTS event_version (
+  event_id TEXT NOT NULL REFERENCES event(id) ON DELETE CASCADE,
+  valid_from INTEGER NOT NULL,
+  title TEXT, summary TEXT, class TEXT, severity REAL, lat REAL, lon REAL
+);
+CREATE INDEX IF NOT EXISTS idx_event_version ON event_version(event_id, valid_from);
+CREATE INDEX IF NOT EXISTS idx_event_version_from ON event_version(valid_from);
+
+-- no foreign key: collectors INSERT OR REPLACE alerts, which would cascade
+CREATE TABLE IF NOT EXISTS alert_version (
+  alert_id TEXT NOT NULL,
+  valid_from INTEGER NOT NULL,
+  headline TEXT, event TEXT, severity TEXT, urgency TEXT, certainty TEXT,
+  onset INTEGER, expires INTEGER, polygon_geojson TEXT,
+  bbox_minx REAL, bbox_miny REAL, bbox_maxx REAL, bbox_maxy REAL
+);
+CREATE INDEX IF NOT EXISTS idx_alert_version ON alert_version(alert_id, valid_from);
+CREATE INDEX IF NOT EXISTS idx_alert_version_from ON alert_version(valid_from);
+CREATE INDEX IF NOT EXISTS idx_alert_version_expires ON alert_version(expires);
+
+CREATE TRIGGER IF NOT EXISTS event_history_insert AFTER INSERT ON event
+BEGIN
+  INSERT INTO event_version VALUES (NEW.id, NEW.first_seen, NEW.title, NEW.summary, NEW.class, NEW.severity, NEW.lat, NEW.lon);
+END;
+
+-- event_history_update is created by an upgrade in db/mod.rs
+
+-- collectors rewrite every active alert on each poll; only changes are kept
+CREATE TRIGGER IF NOT EXISTS alert_history_insert AFTER INSERT ON alert
+WHEN NOT EXISTS (SELECT 1 FROM (SELECT * FROM alert_version WHERE alert_id=NEW.id ORDER BY valid_from DESC, rowid DESC LIMIT 1) v
+  WHERE v.headline IS NEW.headline AND v.event IS NEW.event AND v.severity IS NEW.severity AND v.urgency IS NEW.urgency
+    AND v.certainty IS NEW.certainty AND v.onset IS NEW.onset AND v.expires IS NEW.expires AND v.polygon_geojson IS NEW.polygon_geojson)
+BEGIN
+  INSERT INTO alert_version VALUES (NEW.id, NEW.last_seen, NEW.headline, NEW.event, NEW.severity, NEW.urgency, NEW.certainty,
+    NEW.onset, NEW.expires, NEW.polygon_geojson, NEW.bbox_minx, NEW.bbox_miny, NEW.bbox_maxx, NEW.bbox_maxy);
+END;
+
+CREATE TRIGGER IF NOT EXISTS alert_history_update AFTER UPDATE OF headline, event, severity, urgency, certainty, onset, expires, polygon_geojson ON alert
+WHEN NOT EXISTS (SELECT 1 FROM (SELECT * FROM alert_version WHERE alert_id=NEW.id ORDER BY valid_from DESC, rowid DESC LIMIT 1) v
+  WHERE v.headline IS NEW.headline AND v.event IS NEW.event AND v.severity IS NEW.severity AND v.urgency IS NEW.urgency
+    AND v.certainty IS NEW.certainty AND v.onset IS NEW.onset AND v.expires IS NEW.expires AND v.polygon_geojson IS NEW.polygon_geojson)
+BEGIN
+  INSERT INTO alert_version VALUES (NEW.id, NEW.last_seen, NEW.headline, NEW.event, NEW.severity, NEW.urgency, NEW.certainty,
+    NEW.onset, NEW.expires, NEW.polygon_geojson, NEW.bbox_minx, NEW.bbox_miny, NEW.bbox_maxx, NEW.bbox_maxy);
+END;
+
+CREATE INDEX IF NOT EXISTS idx_ai_labels_event ON ai_labels(event_id);
+
+CREATE TABLE IF NOT EXISTS aoi (
+  id TEXT PRIMARY KEY,
+  name TEXT NOT NULL,
+  minx REAL NOT NULL, miny REAL NOT NULL, maxx REAL NOT NULL, maxy REAL NOT NULL,
+  created_at INTEGER NOT NULL
+);
+
+-- generated briefings; a request is answered from here when scope and
+-- inputs_hash match
+CREATE TABLE IF NOT EXISTS ai_summary (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  scope_key TEXT NOT NULL, -- canonical SummaryScope JSON
+  inputs_hash TEXT NOT NULL,
+  inputs_json TEXT NOT NULL,
+  provider TEXT NOT NULL,
+  model TEXT NOT NULL,
+  prompt_hash TEXT NOT NULL,
+  text TEXT NOT NULL,
+  citations_json TEXT NOT NULL,
+  latency_ms INTEGER NOT NULL,
+  created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_ai_summary_scope ON ai_summary(scope_key, id);
+
+-- model translations keyed by sha256(target_lang, text), shared by all items
+CREATE TABLE IF NOT EXISTS translation_cache (
+  hash TEXT PRIMARY KEY,
+  source_lang TEXT,
+  target_lang TEXT NOT NULL,
+  translated TEXT NOT NULL,
+  model TEXT,
+  created_at INTEGER NOT NULL
+);
+
+-- per-field original and translated text of alerts and events
+CREATE TABLE IF NOT EXISTS item_translation (
+  item_kind TEXT NOT NULL CHECK (item_kind IN ('alert','event')),
+  item_id TEXT NOT NULL,
+  field TEXT NOT NULL,
+  target_lang TEXT NOT NULL,
+  source_lang TEXT,
+  original TEXT NOT NULL,
+  translated TEXT NOT NULL,
+  updated_at INTEGER NOT NULL,
+  PRIMARY KEY(item_kind, item_id, field, target_lang)
+);
+
+-- items whose translation failed; retried after retry_at, or at once when
+-- the text changes
+CREATE TABLE IF NOT EXISTS translation_failure (
+  item_kind TEXT NOT NULL CHECK (item_kind IN ('alert','event')),
+  item_id TEXT NOT NULL,
+  target_lang TEXT NOT NULL,
+  original TEXT NOT NULL,
+  attempts INTEGER NOT NULL,
+  retry_at INTEGER NOT NULL,
+  error TEXT,
+  PRIMARY KEY(item_kind, item_id, target_lang)
+);
+
+CREATE TABLE IF NOT EXISTS rule (
+  id TEXT PRIMARY KEY,
+  name TEXT NOT NULL,
+  enabled INTEGER NOT NULL DEFAULT 1,
+  target TEXT NOT NULL DEFAULT 'event' CHECK(target IN ('event','alert')),
+  spec_json TEXT NOT NULL,
+  updated_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS rule_match (
+  rule_id TEXT NOT NULL REFERENCES rule(id) ON DELETE CASCADE,
+  target TEXT NOT NULL CHECK(target IN ('event','alert')),
+  item_id TEXT NOT NULL,
+  matched_at INTEGER NOT NULL,
+  PRIMARY KEY(rule_id, target, item_id)
+);
+CREATE INDEX IF NOT EXISTS rule_match_item ON rule_match(target, item_id);
+
+-- offline gazetteer, loaded from GeoNames dumps by `geocode::load`
+CREATE TABLE IF NOT EXISTS gazetteer_place (
+  id INTEGER PRIMARY KEY, -- geonameid
+  name TEXT NOT NULL,
+  country_code TEXT, -- ISO 3166-1 alpha-2
+  admin1_code TEXT,
+  admin2_code TEXT,
+  population INTEGER,
+  lat REAL NOT NULL, lon REAL NOT NULL
+);
+CREATE VIRTUAL TABLE IF NOT EXISTS gazetteer_place_rtree USING rtree(
+  rowid, minx, maxx, miny, maxy
+);
+
+-- `CC.A1` for first-level and `CC.A1.A2` for second-level divisions
+CREATE TABLE IF NOT EXISTS gazetteer_admin (
+  code TEXT PRIMARY KEY,
+  name TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS gazetteer_country (
+  iso2 TEXT PRIMARY KEY,
+  iso3 TEXT NOT NULL,
+  name TEXT NOT NULL
+);
+
+-- country outlines; a country may have several
+CREATE TABLE IF NOT EXISTS gazetteer_shape (
+  id INTEGER PRIMARY KEY,
+  iso2 TEXT NOT NULL,
+  geojson TEXT NOT NULL
+);
+CREATE VIRTUAL TABLE IF NOT EXISTS gazetteer_shape_rtree USING rtree(
+  id, minx, maxx, miny, maxy
+);
+
+-- one row naming the load the gazetteer tables hold; parsed outlines are
+-- cached under it
+CREATE TABLE IF NOT EXISTS gazetteer_load (
+  id TEXT NOT NULL,
+  loaded_at INTEGER NOT NULL
+);
+
+-- where an alert or event is, from the gazetteer. Kept apart from the
+-- item because collectors replace alert rows on every poll.
+CREATE TABLE IF NOT EXISTS item_place (
+  item_kind TEXT NOT NULL CHECK (item_kind IN ('alert','event')),
+  item_id TEXT NOT NULL,
+  lat REAL NOT NULL, lon REAL NOT NULL, -- the point looked up
+  country TEXT, -- ISO 3166-1 alpha-3
+  country_name TEXT,
+  admin1 TEXT,
+  admin2 TEXT,
+  place TEXT,
+  place_km REAL,
+  PRIMARY KEY(item_kind, item_id)
+);
+
+CREATE TRIGGER IF NOT EXISTS event_place_delete AFTER DELETE ON event
+BEGIN
+  DELETE FROM item_place WHERE item_kind='event' AND item_id=OLD.id;
+END;
+
+-- without recursive_triggers, INSERT OR REPLACE does not fire this, so a
+-- replaced alert keeps its place
+CREATE TRIGGER IF NOT EXISTS alert_place_delete AFTER DELETE ON alert
+BEGIN
+  DELETE FROM item_place WHERE item_kind='alert' AND item_id=OLD.id;
+END;
+
+-- exposure layers, each loaded from one file (`layer` is its name) and
+-- replaced when it is loaded again: people at raster cell centres or
+-- dataset points, and facilities such as hospitals and airports
+CREATE TABLE IF NOT EXISTS population_point (
+  id INTEGER PRIMARY KEY,
+  layer TEXT NOT NULL,
+  lat REAL NOT NULL, lon REAL NOT NULL,
+  population REAL NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_population_point_layer ON population_point(layer);
+CREATE VIRTUAL TABLE IF NOT EXISTS population_rtree USING rtree(
+  id, minx, maxx, miny, maxy
+);
+
+CREATE TABLE IF NOT EXISTS facility (
+  id INTEGER PRIMARY KEY,
+  layer TEXT NOT NULL,
+  kind TEXT NOT NULL,
+  name TEXT,
+  lat REAL NOT NULL, lon REAL NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_facility_layer ON facility(layer);
+CREATE VIRTUAL TABLE IF NOT EXISTS facility_rtree USING rtree(
+  id, minx, maxx, miny, maxy
+);
+
+-- what lies within an event's severity radius or an alert's area
+CREATE TABLE IF NOT EXISTS item_exposure (
+  item_kind TEXT NOT NULL CHECK (item_kind IN ('alert','event')),
+  item_id TEXT NOT NULL,
+  lat REAL NOT NULL, lon REAL NOT NULL, -- the point assessed around
+  radius_km REAL, -- NULL for an alert's area
+  population REAL NOT NULL,
+  facility_counts_json TEXT NOT NULL,
+  facilities_json TEXT NOT NULL,
+  computed_at INTEGER NOT NULL,
+  PRIMARY KEY(item_kind, item_id)
+);
+
+CREATE TRIGGER IF NOT EXISTS event_exposure_delete AFTER DELETE ON event
+BEGIN
+  DELETE FROM item_exposure WHERE item_kind='event' AND item_id=OLD.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS alert_exposure_delete AFTER DELETE ON alert
+BEGIN
+  DELETE FROM item_exposure WHERE item_kind='alert' AND item_id=OLD.id;
+END;
+
+-- earthquake source parameters, and the shaking `quake::estimate` derives
+-- from them; intensities are Modified Mercalli (MMI)
+CREATE TABLE IF NOT EXISTS quake (
+  event_id TEXT PRIMARY KEY REFERENCES event(id) ON DELETE CASCADE,
+  mag REAL NOT NULL,
+  mag_type TEXT,
+  depth_km REAL,
+  mag_error REAL, depth_error_km REAL, horizontal_error_km REAL,
+  max_mmi REAL, -- at the most shaken gazetteer place, NULL without one
+  centre TEXT, -- that place
+  centre_km REAL,
+  computed_at INTEGER NOT NULL
+);
+
+-- one disc per whole intensity reached, out to where shaking falls below it
+CREATE TABLE IF NOT EXISTS quake_contour (
+  id INTEGER PRIMARY KEY,
+  event_id TEXT NOT NULL REFERENCES event(id) ON DELETE CASCADE,
+  mmi INTEGER NOT NULL,
+  radius_km REAL NOT NULL,
+  polygon_geojson TEXT NOT NULL,
+  UNIQUE(event_id, mmi)
+);
+CREATE VIRTUAL TABLE IF NOT EXISTS quake_contour_rtree USING rtree(
+  id, minx, maxx, miny, maxy
+);
+
+CREATE TRIGGER IF NOT EXISTS quake_contour_delete AFTER DELETE ON quake_contour
+BEGIN
+  DELETE FROM quake_contour_rtree WHERE id=OLD.id;
+END;
@@ -0,0 +1,119 @@
+use anyhow::Result;
+use rusqlite::{Connection, OpenFlags};
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::time::Duration;
+use thread_local::ThreadLocal;
+
+pub struct Db {
+  pub conn: Conn
+}
+
+/// One connection per thread onto the same database, so a `Db` can be shared
+/// by the UI, the collectors and the AI worker. Derefs to the calling
+/// thread's connection, opened on first use.
+pub struct Conn {
+  uri: String,
+  conns: ThreadLocal<Connection>,
+}
+
+impl Conn {
+  /// The calling thread's connection, opening it if this is the thread's
+  /// first use. Background work starts with this, so a database that can no
+  /// longer be opened fails the run instead of panicking its thread.
+  pub fn get(&self) -> rusqlite::Result<&Connection> {
+    self.conns.get_or_try(|| connect(&self.uri))
+  }
+}
+
+impl Deref for Conn {
+  type Target = Connection;
+  /// Panics if the thread's connection cannot be opened; see `get`.
+  fn deref(&self) -> &Connection {
+    self.get().unwrap_or_else(|e| panic!("open {}: {e}", self.uri))
+  }
+}
+
+fn connect(uri: &str) -> rusqlite::Result<Connection> {
+  let conn = Connection::open_with_flags(uri, OpenFlags::default() | OpenFlags::SQLITE_OPEN_URI)?;
+  // the desktop app and the daemon may share the file
+  conn.busy_timeout(Duration::from_secs(5))?;
+  conn.pragma_update(None, "foreign_keys", true)?;
+  Ok(conn)
+}
+
+/// Column additions to tables created by `migrations.sql`, which can only
+/// create missing tables. `PRAGMA user_version` counts the applied entries,
+/// so append only.
+const UPGRADES: &[&str] = &[
+  "ALTER TABLE ai_labels ADD COLUMN run_id INTEGER REFERENCES ai_label_run(id)",
+  "ALTER TABLE alert ADD COLUMN language TEXT;
+   ALTER TABLE alert ADD COLUMN description TEXT;
+   ALTER TABLE alert ADD COLUMN instruction TEXT;",
+  "ALTER TABLE alert ADD COLUMN cancelled_at INTEGER",
+  "ALTER TABLE event ADD COLUMN country TEXT;
+   CREATE INDEX IF NOT EXISTS idx_event_first_seen ON event(first_seen);",
+  "ALTER TABLE source_item ADD COLUMN batch_id TEXT REFERENCES import_batch(id);
+   CREATE INDEX IF NOT EXISTS idx_source_item_batch ON source_item(batch_id);",
+  // history starts with what is stored; when an alert was first received
+  // was not kept, so it dates from when it was sent
+  "INSERT INTO event_version SELECT id, first_seen, title, summary, class, severity, lat, lon FROM event
+     WHERE id NOT IN (SELECT event_id FROM event_version);
+   INSERT INTO alert_version SELECT id, MIN(COALESCE(sent, last_seen), last_seen), headline, event, severity, urgency, certainty,
+     onset, expires, polygon_geojson, bbox_minx, bbox_miny, bbox_maxx, bbox_maxy FROM alert
+     WHERE id NOT IN (SELECT alert_id FROM alert_version);",
+  // vectors from different models are not comparable, so each model keeps its own
+  "CREATE TABLE event_embedding_by_model (
+     event_id TEXT NOT NULL REFERENCES event(id) ON DELETE CASCADE,
+     model TEXT NOT NULL,
+     text_hash TEXT NOT NULL,
+     dim INTEGER NOT NULL,
+     vector BLOB NOT NULL,
+     created_at INTEGER NOT NULL,
+     PRIMARY KEY(event_id, model)
+   );
+   INSERT INTO event_embedding_by_model SELECT event_id, model, text_hash, dim, vector, created_at FROM event_embedding;
+   DROP TABLE event_embedding;
+   ALTER TABLE event_embedding_by_model RENAME TO event_embedding;",
+  // event versions are dated by the report that wrote them, and only reports
+  // move last_seen: re-rating, AI labels and reviews rewrite an event in
+  // place and stay out of its history. Never dated before the version it follows.
+  "DROP TRIGGER IF EXISTS event_history_update;
+   CREATE TRIGGER event_history_update AFTER UPDATE OF last_seen, title, summary, class, severity, lat, lon ON event
+   WHEN NEW.last_seen IS NOT OLD.last_seen
+     AND (OLD.title IS NOT NEW.title OR OLD.summary IS NOT NEW.summary OR OLD.class IS NOT NEW.class
+       OR OLD.severity IS NOT NEW.severity OR OLD.lat IS NOT NEW.lat OR OLD.lon IS NOT NEW.lon)
+   BEGIN
+     INSERT INTO event_version VALUES (NEW.id,
+       MAX(NEW.first_seen, NEW.last_seen, COALESCE((SELECT MAX(valid_from) FROM event_version WHERE event_id=NEW.id), 0)),
+       NEW.title, NEW.summary, NEW.class, NEW.severity, NEW.lat, NEW.lon);
+   END;",
+  // an alert's area can change around the same centre
+  "ALTER TABLE item_exposure ADD COLUMN area_hash TEXT",
+];
+
+impl Db {
+  /// `:memory:` opens a private in-memory database that all threads share.
+  pub fn open(path: PathBuf) -> Result<Self> {
+    let uri = if path.as_os_str() == ":memory:" {
+      format!("file:vilya-{}?mode=memory&cache=shared", uuid::Uuid::new_v4())
+    } else {
+      path.to_string_lossy().into_owned()
+    };
+    let conn = Conn { uri, conns: ThreadLocal::new() };
+    conn.get()?.execute_batch(include_str!("migrations.sql"))?;
+    upgrade(&conn)?;
+    Ok(Self { conn })
+  }
+}
+
+fn upgrade(conn: &Connection) -> Result<()> {
+  let applied: usize = conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+  for (i, sql) in UPGRADES.iter().enumerate().skip(applied) {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute_batch(sql)?;
+    tx.pragma_update(None, "user_version", i + 1)?;
+    tx.commit()?;
+  }
+  Ok(())
+}
@@ -0,0 +1,140 @@
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use crate::ai::output::Entity;
+use crate::db::Db;
+use crate::error::Error;
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct EntityRow { pub id: i64, pub kind: String, pub name: String, pub events: i64, pub aliases: Vec<String> }
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct EntityGraph { pub nodes: Vec<EntityRow>, pub edges: Vec<(i64, i64, i64)> }
+
+/// Lookup key for a name: lowercase, punctuation dropped, whitespace
+/// collapsed and a leading "the" removed, so "The Port of Rotterdam" and
+/// "port of rotterdam." resolve to the same entity.
+pub fn normalize(name: &str) -> String {
+  let cleaned: String = name.chars()
+    .map(|c| if c.is_alphanumeric() { c.to_lowercase().next().unwrap_or(c) } else { ' ' })
+    .collect();
+  let words: Vec<&str> = cleaned.split_whitespace().collect();
+  let words = match words.first() { Some(&"the") if words.len() > 1 => &words[1..], _ => &words[..] };
+  words.join(" ")
+}
+
+/// Finds the entity a name refers to, following aliases.
+pub fn resolve(conn: &Connection, kind: &str, name: &str) -> Result<Option<i64>> {
+  let norm = normalize(name);
+  Ok(conn.query_row(
+    "SELECT entity_id FROM entity_alias WHERE kind=?1 AND norm=?2
+     UNION ALL SELECT id FROM entity WHERE kind=?1 AND norm=?2 LIMIT 1",
+    params![kind, norm], |r| r.get(0)).optional()?)
+}
+
+fn resolve_or_insert(conn: &Connection, e: &Entity) -> Result<i64> {
+  if let Some(id) = resolve(conn, e.kind.as_str(), &e.name)? { return Ok(id); }
+  conn.execute("INSERT INTO entity(kind,name,norm) VALUES (?1,?2,?3)", params![e.kind.as_str(), e.name, normalize(&e.name)])?;
+  Ok(conn.last_insert_rowid())
+}
+
+/// Replaces the entity links of an event with `entities`.
+pub fn link(conn: &Connection, event_id: &str, entities: &[Entity]) -> Result<()> {
+  conn.execute("DELETE FROM event_entity WHERE event_id=?1", [event_id])?;
+  for e in entities {
+    if normalize(&e.name).is_empty() { continue; }
+    let id = resolve_or_insert(conn, e)?;
+    conn.execute("INSERT OR IGNORE INTO event_entity(event_id,entity_id) VALUES (?1,?2)", params![event_id, id])?;
+  }
+  Ok(())
+}
+
+/// Links entities for labelled events that have none yet, e.g. labels
+/// written before entity extraction existed.
+pub fn backfill(db: &Db) -> Result<usize> {
+  let rows: Vec<(String, String)> = {
+    let mut stmt = db.conn.prepare(
+      "SELECT l.event_id, l.labels_json FROM ai_labels l
+       WHERE l.labels_json IS NOT NULL AND NOT EXISTS (SELECT 1 FROM event_entity x WHERE x.event_id=l.event_id)")?;
+    let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+    rows.collect::<rusqlite::Result<_>>()?
+  };
+  let tx = db.conn.unchecked_transaction()?;
+  let mut n = 0;
+  for (event_id, json) in rows {
+    let Ok(out) = serde_json::from_str::<crate::ai::output::AiOutput>(&json) else { continue };
+    if out.entities.is_empty() { continue; }
+    link(&tx, &event_id, &out.entities)?;
+    n += 1;
+  }
+  tx.commit()?;
+  Ok(n)
+}
+
+/// Folds `merge` into `keep`: its event links and aliases move over and its
+/// name becomes an alias of `keep`.
+pub fn merge(db: &Db, keep: i64, merge: i64) -> Result<()> {
+  if keep == merge { return Err(Error::invalid("cannot merge an entity into itself").into()); }
+  let tx = db.conn.unchecked_transaction()?;
+  let (kind, norm): (String, String) = tx.query_row("SELECT kind, norm FROM entity WHERE id=?1", [merge], |r| Ok((r.get(0)?, r.get(1)?)))
+    .optional()?.ok_or_else(|| Error::not_found(format!("entity {merge}")))?;
+  let keep_kind: String = tx.query_row("SELECT kind FROM entity WHERE id=?1", [keep], |r| r.get(0))
+    .optional()?.ok_or_else(|| Error::not_found(format!("entity {keep}")))?;
+  tx.execute("INSERT OR IGNORE INTO event_entity(event_id,entity_id) SELECT event_id, ?1 FROM event_entity WHERE entity_id=?2", params![keep, merge])?;
+  tx.execute("UPDATE entity_alias SET entity_id=?1 WHERE entity_id=?2", params![keep, merge])?;
+  tx.execute("DELETE FROM entity WHERE id=?1", [merge])?;
+  tx.execute("INSERT OR REPLACE INTO entity_alias(kind,norm,entity_id) VALUES (?1,?2,?3)", params![kind, norm, keep])?;
+  if kind != keep_kind {
+    // the merged name may be extracted under either kind later
+    tx.execute("INSERT OR IGNORE INTO entity_alias(kind,norm,entity_id) VALUES (?1,?2,?3)", params![keep_kind, norm, keep])?;
+  }
+  tx.commit()?;
+  Ok(())
+}
+
+fn aliases(db: &Db, id: i64) -> Result<Vec<String>> {
+  let mut stmt = db.conn.prepare("SELECT DISTINCT norm FROM entity_alias WHERE entity_id=?1 ORDER BY norm")?;
+  let rows = stmt.query_map([id], |r| r.get(0))?;
+  Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Entities ordered by number of linked events, optionally filtered by a
+/// name substring and kind.
+pub fn list(db: &Db, q: Option<&str>, kind: Option<&str>, limit: i64) -> Result<Vec<EntityRow>> {
+  let mut stmt = db.conn.prepare(
+    "SELECT n.id, n.kind, n.name, COUNT(x.event_id) FROM entity n LEFT JOIN event_entity x ON x.entity_id=n.id
+     WHERE (?1 IS NULL OR n.norm LIKE '%'||?1||'%') AND (?2 IS NULL OR n.kind=?2)
+     GROUP BY n.id ORDER BY COUNT(x.event_id) DESC, n.name LIMIT ?3")?;
+  let rows = stmt.query_map(params![q.map(normalize), kind, limit], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?)))?;
+  let mut out = Vec::new();
+  for row in rows {
+    let (id, kind, name, events) = row?;
+    out.push(EntityRow { id, kind, name, events, aliases: aliases(db, id)? });
+  }
+  Ok(out)
+}
+
+/// Co-occurrence graph over events last seen since `since`: nodes are the
+/// `limit` most mentioned entities, edges count events naming both ends.
+pub fn graph(db: &Db, since: i64, min_weight: i64, limit: i64) -> Result<EntityGraph> {
+  let mut stmt = db.conn.prepare(
+    "SELECT n.id, n.kind, n.name, COUNT(*) FROM event_entity x
+     JOIN entity n ON n.id=x.entity_id JOIN event e ON e.id=x.event_id
+     WHERE e.last_seen >= ?1 GROUP BY n.id ORDER BY COUNT(*) DESC LIMIT ?2")?;
+  let nodes = stmt.query_map(params![since, limit], |r| Ok(EntityRow { id: r.get(0)?, kind: r.get(1)?, name: r.get(2)?, events: r.get(3)?, aliases: vec![] }))?
+    .collect::<rusqlite::Result<Vec<_>>>()?;
+  let ids = serde_json::to_string(&nodes.iter().map(|n| n.id).collect::<Vec<_>>())?;
+
+  let mut stmt = db.conn.prepare(
+    "SELECT a.entity_id, b.entity_id, COUNT(*) FROM event_entity a
+     JOIN event_entity b ON b.event_id=a.event_id AND b.entity_id>a.entity_id
+     JOIN event e ON e.id=a.event_id
+     WHERE e.last_seen >= ?1
+       AND a.entity_id IN (SELECT value FROM json_each(?2)) AND b.entity_id IN (SELECT value FROM json_each(?2))
+     GROUP BY a.entity_id, b.entity_id HAVING COUNT(*) >= ?3 ORDER BY COUNT(*) DESC")?;
+  let edges = stmt.query_map(params![since, ids, min_weight], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?
+    .collect::<rusqlite::Result<Vec<_>>>()?;
+  Ok(EntityGraph { nodes, edges })
+}
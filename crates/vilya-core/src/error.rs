@@ -0,0 +1,16 @@
+//! Errors domain code raises for a missing record or a bad argument. They
+//! travel inside `anyhow` like any other failure; `ipc` and the HTTP API
+//! find them in the chain to answer `not_found` or `invalid_argument`.
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum Error {
+  #[error("not found: {0}")]
+  NotFound(String),
+  #[error("invalid argument: {0}")]
+  InvalidArgument(String),
+}
+
+impl Error {
+  pub fn not_found(what: impl std::fmt::Display) -> Self { Error::NotFound(what.to_string()) }
+  pub fn invalid(why: impl std::fmt::Display) -> Self { Error::InvalidArgument(why.to_string()) }
+}
@@ -0,0 +1,64 @@
+//! WKT and WKB for GeoJSON geometries, 2D only: heights (USGS depth) are
+//! dropped.
+use geojson::{Position, Value};
+
+pub fn wkt(g: &Value) -> String {
+  let pos = |p: &Position| format!("{} {}", p[0], p[1]);
+  let line = |ps: &[Position]| format!("({})", ps.iter().map(pos).collect::<Vec<_>>().join(", "));
+  let poly = |rings: &[Vec<Position>]| format!("({})", rings.iter().map(|r| line(r)).collect::<Vec<_>>().join(", "));
+  match g {
+    Value::Point(p) => format!("POINT ({})", pos(p)),
+    Value::MultiPoint(ps) => format!("MULTIPOINT ({})", ps.iter().map(|p| format!("({})", pos(p))).collect::<Vec<_>>().join(", ")),
+    Value::LineString(ps) => format!("LINESTRING {}", line(ps)),
+    Value::MultiLineString(ls) => format!("MULTILINESTRING ({})", ls.iter().map(|l| line(l)).collect::<Vec<_>>().join(", ")),
+    Value::Polygon(rings) => format!("POLYGON {}", poly(rings)),
+    Value::MultiPolygon(ps) => format!("MULTIPOLYGON ({})", ps.iter().map(|p| poly(p)).collect::<Vec<_>>().join(", ")),
+    Value::GeometryCollection(gs) => format!("GEOMETRYCOLLECTION ({})", gs.iter().map(|g| wkt(&g.value)).collect::<Vec<_>>().join(", ")),
+  }
+}
+
+/// Little-endian ISO WKB.
+pub fn wkb(g: &Value, out: &mut Vec<u8>) {
+  let head = |out: &mut Vec<u8>, kind: u32| { out.push(1); out.extend(kind.to_le_bytes()); };
+  let count = |out: &mut Vec<u8>, n: usize| out.extend((n as u32).to_le_bytes());
+  let pos = |out: &mut Vec<u8>, p: &Position| { out.extend(p[0].to_le_bytes()); out.extend(p[1].to_le_bytes()); };
+  let line = |out: &mut Vec<u8>, ps: &[Position]| { count(out, ps.len()); for p in ps { pos(out, p); } };
+  let rings = |out: &mut Vec<u8>, rs: &[Vec<Position>]| { count(out, rs.len()); for r in rs { line(out, r); } };
+  match g {
+    Value::Point(p) => { head(out, 1); pos(out, p); }
+    Value::LineString(ps) => { head(out, 2); line(out, ps); }
+    Value::Polygon(rs) => { head(out, 3); rings(out, rs); }
+    Value::MultiPoint(ps) => { head(out, 4); count(out, ps.len()); for p in ps { head(out, 1); pos(out, p); } }
+    Value::MultiLineString(ls) => { head(out, 5); count(out, ls.len()); for l in ls { head(out, 2); line(out, l); } }
+    Value::MultiPolygon(ps) => { head(out, 6); count(out, ps.len()); for p in ps { head(out, 3); rings(out, p); } }
+    Value::GeometryCollection(gs) => { head(out, 7); count(out, gs.len()); for g in gs { wkb(&g.value, out); } }
+  }
+}
+
+fn visit(g: &Value, f: &mut impl FnMut(&Position)) {
+  match g {
+    Value::Point(p) => f(p),
+    Value::MultiPoint(ps) | Value::LineString(ps) => ps.iter().for_each(f),
+    Value::MultiLineString(ls) | Value::Polygon(ls) => ls.iter().flatten().for_each(f),
+    Value::MultiPolygon(ps) => ps.iter().flatten().flatten().for_each(f),
+    Value::GeometryCollection(gs) => for g in gs { visit(&g.value, f) },
+  }
+}
+
+/// [minx, miny, maxx, maxy] over every position, if there are any.
+pub fn envelope(g: &Value) -> Option<[f64; 4]> {
+  let mut env: Option<[f64; 4]> = None;
+  visit(g, &mut |p| {
+    let [x0, y0, x1, y1] = env.get_or_insert([p[0], p[1], p[0], p[1]]);
+    *x0 = x0.min(p[0]); *y0 = y0.min(p[1]); *x1 = x1.max(p[0]); *y1 = y1.max(p[1]);
+  });
+  env
+}
+
+/// The envelope of `g` when it has positions and each is a longitude and
+/// latitude in range.
+pub fn lon_lat_envelope(g: &Value) -> Option<[f64; 4]> {
+  let mut valid = true;
+  visit(g, &mut |p| valid &= p.len() >= 2 && (-180.0..=180.0).contains(&p[0]) && (-90.0..=90.0).contains(&p[1]));
+  if valid { envelope(g) } else { None }
+}
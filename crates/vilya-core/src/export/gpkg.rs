@@ -0,0 +1,72 @@
+//! A minimal OGC GeoPackage 1.2: one feature table in WGS 84.
+use std::path::Path;
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use super::{geom, rfc3339, Record};
+
+const SCHEMA: &str = r#"
+PRAGMA application_id = 1196444487;
+PRAGMA user_version = 10200;
+CREATE TABLE gpkg_spatial_ref_sys (
+  srs_name TEXT NOT NULL, srs_id INTEGER PRIMARY KEY, organization TEXT NOT NULL,
+  organization_coordsys_id INTEGER NOT NULL, definition TEXT NOT NULL, description TEXT);
+INSERT INTO gpkg_spatial_ref_sys VALUES
+  ('Undefined cartesian SRS', -1, 'NONE', -1, 'undefined', NULL),
+  ('Undefined geographic SRS', 0, 'NONE', 0, 'undefined', NULL),
+  ('WGS 84 geodetic', 4326, 'EPSG', 4326,
+   'GEOGCS["WGS 84",DATUM["WGS_1984",SPHEROID["WGS 84",6378137,298.257223563,AUTHORITY["EPSG","7030"]],AUTHORITY["EPSG","6326"]],PRIMEM["Greenwich",0,AUTHORITY["EPSG","8901"]],UNIT["degree",0.0174532925199433,AUTHORITY["EPSG","9122"]],AUTHORITY["EPSG","4326"]]',
+   'longitude/latitude coordinates in decimal degrees on the WGS 84 spheroid');
+CREATE TABLE gpkg_contents (
+  table_name TEXT NOT NULL PRIMARY KEY, data_type TEXT NOT NULL, identifier TEXT UNIQUE, description TEXT DEFAULT '',
+  last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  min_x DOUBLE, min_y DOUBLE, max_x DOUBLE, max_y DOUBLE,
+  srs_id INTEGER REFERENCES gpkg_spatial_ref_sys(srs_id));
+CREATE TABLE gpkg_geometry_columns (
+  table_name TEXT NOT NULL REFERENCES gpkg_contents(table_name), column_name TEXT NOT NULL,
+  geometry_type_name TEXT NOT NULL, srs_id INTEGER NOT NULL REFERENCES gpkg_spatial_ref_sys(srs_id),
+  z TINYINT NOT NULL, m TINYINT NOT NULL, PRIMARY KEY (table_name, column_name));
+"#;
+
+/// Writes `records` to a new GeoPackage at `path` as feature table `table`.
+/// An existing file is replaced.
+pub fn write(path: &Path, table: &str, records: &[Record]) -> Result<()> {
+  if path.exists() { std::fs::remove_file(path).with_context(|| format!("replace {}", path.display()))?; }
+  let mut conn = Connection::open(path)?;
+  conn.execute_batch(SCHEMA)?;
+  let tx = conn.transaction()?;
+  tx.execute_batch(&format!(
+    "CREATE TABLE \"{table}\" (fid INTEGER PRIMARY KEY AUTOINCREMENT, geom GEOMETRY, id TEXT, kind TEXT, title TEXT, class TEXT,
+       severity REAL, severity_label TEXT, source TEXT, start DATETIME, \"end\" DATETIME)"))?;
+  let mut extent: Option<[f64; 4]> = None;
+  {
+    let mut stmt = tx.prepare(&format!(
+      "INSERT INTO \"{table}\" (geom,id,kind,title,class,severity,severity_label,source,start,\"end\") VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10)"))?;
+    for r in records {
+      let env = r.geometry.as_ref().and_then(geom::envelope);
+      if let Some([x0, y0, x1, y1]) = env {
+        let e = extent.get_or_insert([x0, y0, x1, y1]);
+        *e = [e[0].min(x0), e[1].min(y0), e[2].max(x1), e[3].max(y1)];
+      }
+      let blob = r.geometry.as_ref().map(|g| geometry_blob(g, env));
+      stmt.execute(params![blob, r.id, r.kind, r.title, r.class, r.severity, r.severity_label, r.source,
+        rfc3339(r.start), r.end.map(rfc3339)])?;
+    }
+  }
+  let [minx, miny, maxx, maxy] = extent.map(|e| e.map(Some)).unwrap_or_default();
+  tx.execute("INSERT INTO gpkg_contents (table_name,data_type,identifier,min_x,min_y,max_x,max_y,srs_id) VALUES (?1,'features',?1,?2,?3,?4,?5,4326)",
+    params![table, minx, miny, maxx, maxy])?;
+  tx.execute("INSERT INTO gpkg_geometry_columns VALUES (?1,'geom','GEOMETRY',4326,0,0)", [table])?;
+  tx.commit()?;
+  Ok(())
+}
+
+/// GeoPackage binary: `GP` header with SRS id and XY envelope, then WKB.
+fn geometry_blob(g: &geojson::Value, env: Option<[f64; 4]>) -> Vec<u8> {
+  let mut out = vec![b'G', b'P', 0];
+  // little endian; envelope [minx, maxx, miny, maxy] if known, else flagged empty
+  out.push(match env { Some(_) => 0b0000_0011, None => 0b0001_0001 });
+  out.extend(4326i32.to_le_bytes());
+  if let Some([x0, y0, x1, y1]) = env { for v in [x0, x1, y0, y1] { out.extend(v.to_le_bytes()); } }
+  geom::wkb(g, &mut out);
+  out
+}
@@ -0,0 +1,65 @@
+//! KML 2.2 with one style per severity band, so partners see the same
+//! colour scale as the map.
+use std::io::Write;
+use anyhow::Result;
+use geojson::{Position, Value};
+use quick_xml::escape::escape;
+use super::{rfc3339, Record};
+
+/// Band, line colour, fill colour; KML colours are `aabbggrr`.
+const STYLES: &[(&str, &str, &str)] = &[
+  ("minor", "ff00ffff", "4000ffff"),
+  ("moderate", "ff0099ff", "4d0099ff"),
+  ("severe", "ff0000ff", "590000ff"),
+  ("extreme", "ff800080", "66800080"),
+];
+
+pub fn write(records: &[Record], w: &mut dyn Write) -> Result<()> {
+  writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+  writeln!(w, r#"<kml xmlns="http://www.opengis.net/kml/2.2"><Document><name>Vilya export</name>"#)?;
+  for (band, line, fill) in STYLES {
+    writeln!(w, "<Style id=\"{band}\"><IconStyle><color>{line}</color></IconStyle><LineStyle><color>{line}</color><width>2</width></LineStyle><PolyStyle><color>{fill}</color></PolyStyle></Style>")?;
+  }
+  for r in records {
+    write!(w, "<Placemark id=\"{}\"><name>{}</name><styleUrl>#{}</styleUrl>", escape(&r.id), escape(&r.title), r.band())?;
+    write!(w, "<TimeSpan><begin>{}</begin>", rfc3339(r.start))?;
+    if let Some(end) = r.end { write!(w, "<end>{}</end>", rfc3339(end))?; }
+    write!(w, "</TimeSpan><ExtendedData>")?;
+    for (k, v) in r.attributes() {
+      write!(w, "<Data name=\"{k}\"><value>{}</value></Data>", escape(&v))?;
+    }
+    write!(w, "</ExtendedData>")?;
+    if let Some(g) = &r.geometry { write!(w, "{}", geometry(g))?; }
+    writeln!(w, "</Placemark>")?;
+  }
+  writeln!(w, "</Document></kml>")?;
+  Ok(())
+}
+
+fn coords(ps: &[Position]) -> String {
+  ps.iter().map(|p| format!("{},{}", p[0], p[1])).collect::<Vec<_>>().join(" ")
+}
+
+fn polygon(rings: &[Vec<Position>]) -> String {
+  let mut s = String::from("<Polygon>");
+  for (i, ring) in rings.iter().enumerate() {
+    let side = if i == 0 { "outerBoundaryIs" } else { "innerBoundaryIs" };
+    s += &format!("<{side}><LinearRing><coordinates>{}</coordinates></LinearRing></{side}>", coords(ring));
+  }
+  s + "</Polygon>"
+}
+
+fn geometry(g: &Value) -> String {
+  let point = |p: &Position| format!("<Point><coordinates>{},{}</coordinates></Point>", p[0], p[1]);
+  let line = |ps: &[Position]| format!("<LineString><coordinates>{}</coordinates></LineString>", coords(ps));
+  let multi = |parts: Vec<String>| format!("<MultiGeometry>{}</MultiGeometry>", parts.concat());
+  match g {
+    Value::Point(p) => point(p),
+    Value::LineString(ps) => line(ps),
+    Value::Polygon(rings) => polygon(rings),
+    Value::MultiPoint(ps) => multi(ps.iter().map(point).collect()),
+    Value::MultiLineString(ls) => multi(ls.iter().map(|l| line(l)).collect()),
+    Value::MultiPolygon(ps) => multi(ps.iter().map(|p| polygon(p)).collect()),
+    Value::GeometryCollection(gs) => multi(gs.iter().map(|g| geometry(&g.value)).collect()),
+  }
+}
@@ -0,0 +1,216 @@
+//! Writes the rows of an event search or alert query as GeoJSON, KML, CSV
+//! with WKT geometry, or GeoPackage, for sharing outside the app.
+use std::io::Write;
+use std::path::Path;
+use anyhow::{bail, Context, Result};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::analytics::severity_band;
+use crate::db::Db;
+use crate::ipc::query;
+use crate::normalize::cap_severity;
+use crate::rules::source_of_rank;
+
+pub mod geom; pub mod gpkg; pub mod kml;
+
+/// What to export: the same filters as `search_events` and `query_alerts`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExportQuery {
+  /// Events `search_events` finds for `q`, `since` and `until`, located in
+  /// `bbox` ([minx, miny, maxx, maxy]).
+  Events {
+    #[serde(default)] q: Option<String>,
+    #[serde(default)] since: Option<i64>,
+    #[serde(default)] until: Option<i64>,
+    #[serde(default)] bbox: Option<[f64; 4]>,
+  },
+  /// Alerts in force at `at` (default now) whose bbox meets `bbox`.
+  Alerts {
+    #[serde(default)] bbox: Option<[f64; 4]>,
+    #[serde(default)] at: Option<i64>,
+  },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat { Geojson, Kml, Csv, Gpkg }
+
+impl ExportFormat {
+  pub fn extension(self) -> &'static str {
+    match self { ExportFormat::Geojson => "geojson", ExportFormat::Kml => "kml", ExportFormat::Csv => "csv", ExportFormat::Gpkg => "gpkg" }
+  }
+}
+
+impl ExportQuery {
+  /// `events` or `alerts`: the GeoPackage table and default file stem.
+  pub fn table(&self) -> &'static str {
+    match self { ExportQuery::Events { .. } => "events", ExportQuery::Alerts { .. } => "alerts" }
+  }
+
+  /// The name to save under when none is given, e.g. `alerts.kml`.
+  pub fn file_name(&self, format: ExportFormat) -> String {
+    format!("{}.{}", self.table(), format.extension())
+  }
+}
+
+/// One exported event or alert.
+#[derive(Debug, Clone)]
+pub struct Record {
+  pub id: String,
+  /// `event` or `alert`.
+  pub kind: &'static str,
+  pub title: String,
+  /// The event class, or the alert's CAP event type.
+  pub class: String,
+  /// 0–1. Alerts map their CAP severity through `cap_severity`.
+  pub severity: f64,
+  /// `minor` to `extreme` for events; the CAP severity word for alerts.
+  pub severity_label: String,
+  pub source: String,
+  /// First seen for events, onset for alerts.
+  pub start: i64,
+  /// Last seen for events, expiry for alerts.
+  pub end: Option<i64>,
+  pub geometry: Option<geojson::Value>,
+}
+
+impl Record {
+  /// `minor`, `moderate`, `severe` or `extreme`, which picks the KML style.
+  /// CAP words name their own band: `cap_severity` puts `Severe` at 0.75,
+  /// which `severity_band` would call extreme.
+  pub fn band(&self) -> &'static str {
+    match self.severity_label.as_str() {
+      "Extreme" => "extreme", "Severe" => "severe", "Moderate" => "moderate", "Minor" => "minor",
+      _ => severity_band(self.severity),
+    }
+  }
+
+  /// Flat attributes shared by every format, times in RFC 3339.
+  pub fn attributes(&self) -> [(&'static str, String); 8] {
+    [
+      ("kind", self.kind.into()), ("title", self.title.clone()), ("class", self.class.clone()),
+      ("severity", format!("{:.3}", self.severity)), ("severity_label", self.severity_label.clone()),
+      ("source", self.source.clone()), ("start", rfc3339(self.start)), ("end", self.end.map(rfc3339).unwrap_or_default()),
+    ]
+  }
+}
+
+pub fn rfc3339(ts: i64) -> String {
+  chrono::DateTime::from_timestamp(ts, 0).map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)).unwrap_or_default()
+}
+
+/// A feature geometry from a stored alert area: a geometry as is, a
+/// feature's geometry, or a collection's geometries as one
+/// `GeometryCollection` (CAP alerts store one feature per polygon).
+pub fn area_geometry(v: Value) -> Option<Value> {
+  match v.get("type").and_then(Value::as_str)? {
+    "Feature" => v.get("geometry").cloned().filter(|g| !g.is_null()),
+    "FeatureCollection" => {
+      let geoms: Vec<Value> = v.get("features")?.as_array()?.iter()
+        .filter_map(|f| f.get("geometry").cloned().filter(|g| !g.is_null())).collect();
+      match geoms.len() {
+        0 => None,
+        1 => geoms.into_iter().next(),
+        _ => Some(json!({ "type": "GeometryCollection", "geometries": geoms })),
+      }
+    }
+    _ => Some(v),
+  }
+}
+
+pub fn records(db: &Db, q: &ExportQuery, now: i64) -> Result<Vec<Record>> {
+  match q {
+    ExportQuery::Events { q, since, until, bbox } => {
+      // search_events picks and orders the events; the rest of each row is read here
+      let found = query::search_events(db, q.as_deref(), *since, *until, None, i64::MAX as usize)?;
+      let mut stmt = db.conn.prepare("SELECT severity, COALESCE(source_rank,0), last_seen, lon, lat FROM event WHERE id=?1")?;
+      let mut out = Vec::new();
+      for e in found {
+        let (severity, rank, last_seen, lon, lat): (Option<f64>, i64, i64, Option<f64>, Option<f64>) =
+          stmt.query_row([&e.id], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?)))?;
+        let point = lon.zip(lat);
+        if let Some([minx, miny, maxx, maxy]) = bbox {
+          if !point.is_some_and(|(lon, lat)| (minx..=maxx).contains(&&lon) && (miny..=maxy).contains(&&lat)) { continue; }
+        }
+        let severity = severity.unwrap_or(0.0);
+        out.push(Record {
+          id: e.id, kind: "event", title: e.title, class: e.class,
+          severity, severity_label: severity_band(severity).into(), source: source_of_rank(rank).into(),
+          start: e.ts, end: Some(last_seen), geometry: point.map(|(lon, lat)| geojson::Value::Point(vec![lon, lat])),
+        });
+      }
+      Ok(out)
+    }
+    ExportQuery::Alerts { bbox, at } => {
+      let [minx, miny, maxx, maxy] = bbox.unwrap_or([-180.0, -90.0, 180.0, 90.0]);
+      let mut stmt = db.conn.prepare(
+        "SELECT a.id,a.headline,a.event,a.severity,a.source,COALESCE(a.onset,a.sent,a.last_seen),a.expires,a.polygon_geojson
+         FROM alert_rtree r JOIN alert a ON a.rowid = r.rowid
+         WHERE r.minx <= ?3 AND r.maxx >= ?1 AND r.miny <= ?4 AND r.maxy >= ?2
+           AND a.expires >= ?5 AND (a.cancelled_at IS NULL OR a.cancelled_at > ?5)
+         ORDER BY a.onset DESC")?;
+      let rows = stmt.query_map(params![minx, miny, maxx, maxy, at.unwrap_or(now)], |r| {
+        let label = r.get::<_, Option<String>>(3)?.unwrap_or_default();
+        let area = crate::ipc::dto::parse_geojson(r.get(7)?).and_then(area_geometry);
+        Ok(Record {
+          id: r.get(0)?, kind: "alert", title: r.get::<_, Option<String>>(1)?.unwrap_or_default(), class: r.get::<_, Option<String>>(2)?.unwrap_or_default(),
+          severity: cap_severity(&label), severity_label: label, source: r.get(4)?,
+          start: r.get(5)?, end: r.get(6)?, geometry: area.and_then(|g| geojson::Geometry::from_json_value(g).ok()).map(|g| g.value),
+        })
+      })?;
+      Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+  }
+}
+
+/// Writes a text format. GeoPackage is a database and needs `to_file`.
+pub fn write(records: &[Record], format: ExportFormat, w: &mut dyn Write) -> Result<()> {
+  match format {
+    ExportFormat::Geojson => write_geojson(records, w),
+    ExportFormat::Kml => kml::write(records, w),
+    ExportFormat::Csv => write_csv(records, w),
+    ExportFormat::Gpkg => bail!("GeoPackage export needs an output file"),
+  }
+}
+
+/// Runs `q` and writes the rows to `path`, replacing it. Returns the row count.
+pub fn to_file(db: &Db, q: &ExportQuery, format: ExportFormat, path: &Path, now: i64) -> Result<usize> {
+  let rows = records(db, q, now)?;
+  if format == ExportFormat::Gpkg {
+    gpkg::write(path, q.table(), &rows)?;
+  } else {
+    let f = std::fs::File::create(path).with_context(|| format!("create {}", path.display()))?;
+    let mut w = std::io::BufWriter::new(f);
+    write(&rows, format, &mut w)?;
+    w.flush()?;
+  }
+  Ok(rows.len())
+}
+
+fn write_geojson(records: &[Record], w: &mut dyn Write) -> Result<()> {
+  let features: Vec<Value> = records.iter().map(|r| {
+    let props: serde_json::Map<String, Value> = r.attributes().into_iter()
+      .map(|(k, v)| (k.to_string(), if k == "severity" { json!(r.severity) } else { json!(v) })).collect();
+    json!({ "type": "Feature", "id": r.id, "geometry": r.geometry.as_ref().map(|g| geojson::Geometry::new(g.clone())), "properties": props })
+  }).collect();
+  serde_json::to_writer(&mut *w, &json!({ "type": "FeatureCollection", "features": features }))?;
+  Ok(())
+}
+
+fn write_csv(records: &[Record], w: &mut dyn Write) -> Result<()> {
+  let mut out = csv::Writer::from_writer(w);
+  let header = ["id", "kind", "title", "class", "severity", "severity_label", "source", "start", "end", "wkt"];
+  out.write_record(header)?;
+  for r in records {
+    let mut row = vec![r.id.clone()];
+    row.extend(r.attributes().into_iter().map(|(_, v)| v));
+    row.push(r.geometry.as_ref().map(geom::wkt).unwrap_or_default());
+    out.write_record(&row)?;
+  }
+  out.flush()?;
+  Ok(())
+}
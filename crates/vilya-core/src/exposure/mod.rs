@@ -0,0 +1,368 @@
+//! Who and what is near an event or inside an alert. Population comes from
+//! gridded rasters (GHS-POP, WorldPop) in ESRI ASCII grid form or from
+//! point datasets; facilities from a GeoJSON or CSV layer the user
+//! supplies. `assess` sums them within a severity-dependent radius around
+//! each event and inside each alert's area, into `item_exposure`, where
+//! `get_event` and rules read them.
+use std::collections::BTreeMap;
+use std::path::Path;
+use anyhow::{bail, Context, Result};
+use geo::{BoundingRect, Centroid, HaversineDistance, Intersects, Point};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::info;
+use crate::ingest::import::{column, csv_reader};
+use crate::error::Error;
+use crate::quake;
+use crate::runtime::Core;
+use crate::settings::ExposureSettings;
+
+/// Most facilities listed per item; `facility_counts` counts them all.
+pub const MAX_FACILITIES: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct ExposedFacility {
+  pub kind: String,
+  pub name: Option<String>,
+  pub lat: f64,
+  pub lon: f64,
+  /// From the event; `None` inside an alert's area.
+  pub km: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct Exposure {
+  /// Radius around the event; `None` for an alert, assessed over its area.
+  pub radius_km: Option<f64>,
+  pub population: f64,
+  /// Facilities per kind.
+  pub facility_counts: BTreeMap<String, i64>,
+  /// Nearest first, at most `MAX_FACILITIES`.
+  pub facilities: Vec<ExposedFacility>,
+  pub computed_at: i64,
+}
+
+/// What loading one layer file stored.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct LayerReport { pub layer: String, pub rows: usize }
+
+fn layer_name(path: &Path) -> Result<String> {
+  Ok(path.file_name().context("no file name")?.to_string_lossy().into_owned())
+}
+
+/// Loads a population layer: an ESRI ASCII grid (`.asc`; convert GeoTIFF
+/// tiles with `gdal_translate -of AAIGrid`) whose cells are counted at
+/// their centres, or a CSV of points with lat/lon (or x/y) and population
+/// (or pop, value, z) columns. Replaces a layer loaded from a file of the
+/// same name.
+pub fn load_population(conn: &Connection, path: &Path) -> Result<LayerReport> {
+  let layer = layer_name(path)?;
+  let text = std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+  let is_grid = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("asc"));
+  let points = if is_grid { grid_points(&text) } else { csv_points(&text) }.with_context(|| format!("{}", path.display()))?;
+  let tx = conn.unchecked_transaction()?;
+  tx.execute("DELETE FROM population_rtree WHERE id IN (SELECT id FROM population_point WHERE layer=?1)", [&layer])?;
+  tx.execute("DELETE FROM population_point WHERE layer=?1", [&layer])?;
+  for (lat, lon, population) in &points {
+    tx.prepare_cached("INSERT INTO population_point(layer,lat,lon,population) VALUES (?1,?2,?3,?4)")?.execute(params![layer, lat, lon, population])?;
+    tx.prepare_cached("INSERT INTO population_rtree(id,minx,maxx,miny,maxy) VALUES (?1,?2,?2,?3,?3)")?.execute(params![tx.last_insert_rowid(), lon, lat])?;
+  }
+  tx.commit()?;
+  Ok(LayerReport { layer, rows: points.len() })
+}
+
+/// Populated cell centres of an ESRI ASCII grid, north row first.
+fn grid_points(text: &str) -> Result<Vec<(f64, f64, f64)>> {
+  let mut header = BTreeMap::new();
+  let mut lines = text.lines().peekable();
+  while let Some(line) = lines.peek() {
+    let mut parts = line.split_whitespace();
+    let (Some(key), Some(value)) = (parts.next(), parts.next()) else { lines.next(); continue };
+    if !key.starts_with(|c: char| c.is_ascii_alphabetic()) { break; }
+    header.insert(key.to_ascii_lowercase(), value.parse::<f64>().with_context(|| format!("header `{key}`"))?);
+    lines.next();
+  }
+  let get = |k: &str| header.get(k).copied().with_context(|| format!("no `{k}` in the grid header"));
+  let (ncols, nrows, cell) = (get("ncols")? as usize, get("nrows")? as usize, get("cellsize")?);
+  // corners name the outer edge of the lower-left cell, centres its middle
+  let x0 = match header.get("xllcenter") { Some(x) => *x, None => get("xllcorner")? + cell / 2.0 };
+  let y0 = match header.get("yllcenter") { Some(y) => *y, None => get("yllcorner")? + cell / 2.0 };
+  let nodata = header.get("nodata_value").copied();
+  let mut out = Vec::new();
+  let values = lines.flat_map(str::split_whitespace);
+  for (i, v) in values.enumerate() {
+    if i >= ncols * nrows { bail!("more than {nrows} x {ncols} values"); }
+    let v: f64 = v.parse().with_context(|| format!("value {}", i + 1))?;
+    if Some(v) == nodata || v <= 0.0 || v.is_nan() { continue; }
+    let (row, col) = (i / ncols, i % ncols);
+    out.push((y0 + (nrows - 1 - row) as f64 * cell, x0 + col as f64 * cell, v));
+  }
+  Ok(out)
+}
+
+fn lat_lon_columns(headers: &csv::StringRecord) -> Result<(usize, usize)> {
+  let lat = column(headers, &["lat", "latitude", "y"]);
+  let lon = column(headers, &["lon", "lng", "longitude", "x"]);
+  let (Some(lat), Some(lon)) = (lat, lon) else {
+    return Err(Error::invalid(format!("no lat/lon (or y/x) columns among {}", headers.iter().collect::<Vec<_>>().join(", "))).into());
+  };
+  Ok((lat, lon))
+}
+
+fn csv_points(text: &str) -> Result<Vec<(f64, f64, f64)>> {
+  let mut reader = csv_reader(text);
+  let headers = reader.headers()?.clone();
+  let (lat, lon) = lat_lon_columns(&headers)?;
+  let Some(pop) = column(&headers, &["population", "pop", "value", "z"]) else {
+    return Err(Error::invalid("no population (or pop, value, z) column").into());
+  };
+  let mut out = Vec::new();
+  for (i, rec) in reader.records().enumerate() {
+    let rec = rec?;
+    let num = |c: usize| rec.get(c).unwrap_or_default().parse::<f64>().with_context(|| format!("row {}: `{}`", i + 2, &headers[c]));
+    let p = num(pop)?;
+    if p > 0.0 { out.push((num(lat)?, num(lon)?, p)); }
+  }
+  Ok(out)
+}
+
+/// Loads a facility layer: GeoJSON features (any geometry counts at its
+/// centroid) or CSV rows with lat/lon columns. The kind is `kind`, else
+/// the `kind`, `type`, `amenity` or `aeroway` property, else the file
+/// name. Replaces a layer loaded from a file of the same name.
+pub fn load_facilities(conn: &Connection, path: &Path, kind: Option<&str>) -> Result<LayerReport> {
+  let layer = layer_name(path)?;
+  let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| layer.clone());
+  let text = std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+  const KINDS: [&str; 4] = ["kind", "type", "amenity", "aeroway"];
+  let mut rows: Vec<(String, Option<String>, f64, f64)> = Vec::new();
+  if text.trim_start().starts_with('{') {
+    let fc: geojson::FeatureCollection = text.parse().with_context(|| format!("parse {}", path.display()))?;
+    for f in fc.features {
+      let Some(g) = f.geometry.as_ref().and_then(|g| geo::Geometry::<f64>::try_from(g.clone()).ok()) else { continue };
+      let Some(c) = g.centroid() else { continue };
+      let prop = |k: &str| f.property(k).and_then(Value::as_str).filter(|s| !s.is_empty()).map(str::to_string);
+      let k = kind.map(str::to_string).or_else(|| KINDS.into_iter().find_map(prop)).unwrap_or_else(|| stem.clone());
+      rows.push((k, prop("name"), c.y(), c.x()));
+    }
+  } else {
+    let mut reader = csv_reader(&text);
+    let headers = reader.headers()?.clone();
+    let (lat, lon) = lat_lon_columns(&headers)?;
+    let (name, kind_col) = (column(&headers, &["name"]), column(&headers, &KINDS));
+    for (i, rec) in reader.records().enumerate() {
+      let rec = rec?;
+      let num = |c: usize| rec.get(c).unwrap_or_default().parse::<f64>().with_context(|| format!("{}: row {}: `{}`", path.display(), i + 2, &headers[c]));
+      let text = |c: Option<usize>| c.and_then(|c| rec.get(c)).filter(|s| !s.is_empty()).map(str::to_string);
+      let k = kind.map(str::to_string).or_else(|| text(kind_col)).unwrap_or_else(|| stem.clone());
+      rows.push((k, text(name), num(lat)?, num(lon)?));
+    }
+  }
+  let tx = conn.unchecked_transaction()?;
+  tx.execute("DELETE FROM facility_rtree WHERE id IN (SELECT id FROM facility WHERE layer=?1)", [&layer])?;
+  tx.execute("DELETE FROM facility WHERE layer=?1", [&layer])?;
+  for (kind, name, lat, lon) in &rows {
+    tx.prepare_cached("INSERT INTO facility(layer,kind,name,lat,lon) VALUES (?1,?2,?3,?4,?5)")?.execute(params![layer, kind, name, lat, lon])?;
+    tx.prepare_cached("INSERT INTO facility_rtree(id,minx,maxx,miny,maxy) VALUES (?1,?2,?2,?3,?3)")?.execute(params![tx.last_insert_rowid(), lon, lat])?;
+  }
+  tx.commit()?;
+  Ok(LayerReport { layer, rows: rows.len() })
+}
+
+pub fn loaded(conn: &Connection) -> Result<bool> {
+  Ok(conn.query_row("SELECT EXISTS(SELECT 1 FROM population_point) OR EXISTS(SELECT 1 FROM facility)", [], |r| r.get(0))?)
+}
+
+/// The radius `radii` gives an event of `severity`.
+pub fn radius_km(cfg: &ExposureSettings, severity: f64) -> Option<f64> {
+  cfg.radii.iter().find(|r| severity >= r.min_severity).map(|r| r.km)
+}
+
+/// Where an item is assessed: within `km` of `p`, or inside `area`, whose
+/// outline and bbox hash to `hash`.
+enum Region { Circle { p: Point, km: f64 }, Area { centre: Point, area: geo::Geometry<f64>, hash: String } }
+
+impl Region {
+  fn bbox(&self) -> [f64; 4] {
+    match self {
+      Region::Circle { p, km } => {
+        let half_lat = km / 111.0;
+        let half_lon = (half_lat / p.y().to_radians().cos().max(0.01)).min(180.0);
+        [p.x() - half_lon, p.y() - half_lat, p.x() + half_lon, p.y() + half_lat]
+      }
+      Region::Area { area, .. } => area.bounding_rect().map_or([0.0; 4], |b| [b.min().x, b.min().y, b.max().x, b.max().y]),
+    }
+  }
+
+  /// Whether `q` is inside, and its distance in km from an event.
+  fn test(&self, q: Point) -> Option<Option<f64>> {
+    match self {
+      Region::Circle { p, km } => {
+        let d = p.haversine_distance(&q) / 1000.0;
+        (d <= *km).then_some(Some(d))
+      }
+      Region::Area { area, .. } => area.intersects(&q).then_some(None),
+    }
+  }
+}
+
+fn measure(conn: &Connection, region: &Region, now: i64) -> Result<Exposure> {
+  // a circle's box runs past ±180 near the antimeridian; try it a turn either way
+  let [[minx, miny, maxx, maxy], [wminx, _, wmaxx, _], [eminx, _, emaxx, _]] = quake::wrapped(region.bbox());
+  let within = |id: &str, rtree: &str| format!("{id} IN (SELECT id FROM {rtree} WHERE minx <= ?3 AND maxx >= ?1 AND miny <= ?4 AND maxy >= ?2
+    UNION SELECT id FROM {rtree} WHERE minx <= ?6 AND maxx >= ?5 AND miny <= ?4 AND maxy >= ?2
+    UNION SELECT id FROM {rtree} WHERE minx <= ?8 AND maxx >= ?7 AND miny <= ?4 AND maxy >= ?2)");
+  let mut population = 0.0;
+  let mut stmt = conn.prepare_cached(&format!(
+    "SELECT p.lat, p.lon, p.population FROM population_point p WHERE {}", within("p.id", "population_rtree")))?;
+  let rows = stmt.query_map(params![minx, miny, maxx, maxy, wminx, wmaxx, eminx, emaxx], |r| Ok((r.get::<_, f64>(0)?, r.get::<_, f64>(1)?, r.get::<_, f64>(2)?)))?;
+  for row in rows {
+    let (lat, lon, n) = row?;
+    if region.test(Point::new(lon, lat)).is_some() { population += n; }
+  }
+  let mut stmt = conn.prepare_cached(&format!(
+    "SELECT f.kind, f.name, f.lat, f.lon FROM facility f WHERE {}", within("f.id", "facility_rtree")))?;
+  let rows = stmt.query_map(params![minx, miny, maxx, maxy, wminx, wmaxx, eminx, emaxx], |r| Ok(ExposedFacility { kind: r.get(0)?, name: r.get(1)?, lat: r.get(2)?, lon: r.get(3)?, km: None }))?;
+  let mut facilities = Vec::new();
+  for row in rows {
+    let mut f = row?;
+    let Some(km) = region.test(Point::new(f.lon, f.lat)) else { continue };
+    f.km = km.map(|d| (d * 10.0).round() / 10.0);
+    facilities.push(f);
+  }
+  let mut facility_counts = BTreeMap::new();
+  for f in &facilities { *facility_counts.entry(f.kind.clone()).or_insert(0) += 1; }
+  match region {
+    Region::Circle { .. } => facilities.sort_by(|a, b| a.km.unwrap_or(0.0).total_cmp(&b.km.unwrap_or(0.0))),
+    Region::Area { centre, .. } => facilities.sort_by(|a, b| {
+      let d = |f: &ExposedFacility| centre.haversine_distance(&Point::new(f.lon, f.lat));
+      d(a).total_cmp(&d(b))
+    }),
+  }
+  facilities.truncate(MAX_FACILITIES);
+  let radius_km = match region { Region::Circle { km, .. } => Some(*km), Region::Area { .. } => None };
+  Ok(Exposure { radius_km, population: population.round(), facility_counts, facilities, computed_at: now })
+}
+
+/// An item's assessment point and region: an event's location and
+/// severity radius, an alert's area (its bbox without a polygon).
+fn region_of(conn: &Connection, kind: &str, id: &str, cfg: &ExposureSettings) -> Result<Option<Region>> {
+  if kind == "alert" {
+    let row: Option<(Option<String>, f64, f64, f64, f64)> = conn.query_row(
+      "SELECT polygon_geojson, bbox_minx, bbox_miny, bbox_maxx, bbox_maxy FROM alert WHERE id=?1 AND bbox_minx IS NOT NULL", [id],
+      |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?))).optional()?;
+    let Some((polygon, minx, miny, maxx, maxy)) = row else { return Ok(None) };
+    let hash = format!("{:x}", Sha256::digest(format!("{minx},{miny},{maxx},{maxy} {}", polygon.as_deref().unwrap_or_default())));
+    let area = polygon.and_then(|p| p.parse::<geojson::GeoJson>().ok())
+      .and_then(|g| geo::GeometryCollection::<f64>::try_from(&g).ok())
+      .filter(|c| !c.0.is_empty())
+      .map(geo::Geometry::GeometryCollection)
+      .unwrap_or_else(|| geo::Rect::new((minx, miny), (maxx, maxy)).into());
+    return Ok(Some(Region::Area { centre: Point::new((minx + maxx) / 2.0, (miny + maxy) / 2.0), area, hash }));
+  }
+  let row: Option<(f64, f64, f64)> = conn.query_row(
+    "SELECT lat, lon, COALESCE(severity, 0) FROM event WHERE id=?1 AND lat IS NOT NULL AND lon IS NOT NULL", [id],
+    |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?))).optional()?;
+  Ok(row.and_then(|(lat, lon, severity)| Some(Region::Circle { p: Point::new(lon, lat), km: radius_km(cfg, severity)? })))
+}
+
+/// Assesses the `kind` ("event" or "alert") items `ids` that are new, or
+/// whose location, radius or area changed since. Returns the number assessed.
+pub fn assess(conn: &Connection, kind: &str, ids: &[String], cfg: &ExposureSettings) -> Result<usize> {
+  if ids.is_empty() || !loaded(conn)? { return Ok(0); }
+  let now = chrono::Utc::now().timestamp();
+  let mut n = 0;
+  for id in ids {
+    let Some(region) = region_of(conn, kind, id, cfg)? else { continue };
+    let (p, radius, hash) = match &region {
+      Region::Circle { p, km } => (*p, Some(*km), None),
+      Region::Area { centre, hash, .. } => (*centre, None, Some(hash.clone())),
+    };
+    let prev: Option<(f64, f64, Option<f64>, Option<String>)> = conn.query_row(
+      "SELECT lat, lon, radius_km, area_hash FROM item_exposure WHERE item_kind=?1 AND item_id=?2", params![kind, id],
+      |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?))).optional()?;
+    if prev == Some((p.y(), p.x(), radius, hash.clone())) { continue; }
+    let e = measure(conn, &region, now)?;
+    conn.execute("INSERT OR REPLACE INTO item_exposure(item_kind,item_id,lat,lon,radius_km,population,facility_counts_json,facilities_json,computed_at,area_hash)
+      VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10)",
+      params![kind, id, p.y(), p.x(), e.radius_km, e.population, serde_json::to_string(&e.facility_counts)?, serde_json::to_string(&e.facilities)?, now, hash])?;
+    n += 1;
+  }
+  Ok(n)
+}
+
+/// Reassesses every event and alert, after layers change.
+pub fn assess_all(conn: &Connection, cfg: &ExposureSettings) -> Result<usize> {
+  let tx = conn.unchecked_transaction()?;
+  tx.execute("DELETE FROM item_exposure", [])?;
+  let n = assess_each(&tx, cfg)?;
+  tx.commit()?;
+  Ok(n)
+}
+
+/// Assesses the events and alerts that are new or changed since they last
+/// were, such as those stored while the process was down.
+pub fn assess_stored(conn: &Connection, cfg: &ExposureSettings) -> Result<usize> {
+  let tx = conn.unchecked_transaction()?;
+  let n = assess_each(&tx, cfg)?;
+  tx.commit()?;
+  Ok(n)
+}
+
+fn assess_each(conn: &Connection, cfg: &ExposureSettings) -> Result<usize> {
+  let ids = |sql: &str| -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map([], |r| r.get(0))?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+  };
+  let events = ids("SELECT id FROM event WHERE lat IS NOT NULL AND lon IS NOT NULL")?;
+  let alerts = ids("SELECT id FROM alert WHERE bbox_minx IS NOT NULL")?;
+  Ok(assess(conn, "event", &events, cfg)? + assess(conn, "alert", &alerts, cfg)?)
+}
+
+/// The stored assessment of an item.
+pub fn exposure_of(conn: &Connection, kind: &str, id: &str) -> Result<Option<Exposure>> {
+  let row: Option<(Option<f64>, f64, String, String, i64)> = conn.query_row(
+    "SELECT radius_km, population, facility_counts_json, facilities_json, computed_at FROM item_exposure WHERE item_kind=?1 AND item_id=?2",
+    params![kind, id], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?))).optional()?;
+  let Some((radius_km, population, counts, facilities, computed_at)) = row else { return Ok(None) };
+  Ok(Some(Exposure { radius_km, population, facility_counts: serde_json::from_str(&counts)?, facilities: serde_json::from_str(&facilities)?, computed_at }))
+}
+
+/// On start: loads the configured layers if the database has none and
+/// assesses everything stored, or with layers already loaded assesses what
+/// arrived or changed since.
+pub fn init(core: &Core) -> Result<()> {
+  let conn = core.db.conn.get()?;
+  let cfg = &core.settings.exposure;
+  if loaded(conn)? {
+    let n = assess_stored(conn, cfg)?;
+    if n > 0 { info!("exposure: assessed {n} items"); }
+    return Ok(());
+  }
+  let dir = cfg.dir.clone().unwrap_or_else(|| core.data_dir.join("exposure"));
+  let files = |sub: &str| -> Vec<std::path::PathBuf> {
+    let mut v: Vec<_> = std::fs::read_dir(dir.join(sub)).into_iter().flatten().filter_map(|e| e.ok()).map(|e| e.path())
+      .filter(|p| p.is_file() && !p.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.'))).collect();
+    v.sort();
+    v
+  };
+  let (population, facilities) = (files("population"), files("facilities"));
+  if population.is_empty() && facilities.is_empty() { return Ok(()); }
+  for f in population {
+    let r = load_population(conn, &f)?;
+    info!("exposure: {} population points from {}", r.rows, f.display());
+  }
+  for f in facilities {
+    let r = load_facilities(conn, &f, None)?;
+    info!("exposure: {} facilities from {}", r.rows, f.display());
+  }
+  let n = assess_all(conn, cfg)?;
+  info!("exposure: assessed {n} items");
+  Ok(())
+}
@@ -0,0 +1,365 @@
+//! Offline reverse geocoding. `load` reads GeoNames dumps into the
+//! gazetteer tables; `annotate` records, for each event and alert, the
+//! country containing it, its first- and second-level divisions and the
+//! nearest populated place in `item_place`, where search, rules and
+//! analytics read them.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use anyhow::{bail, Context, Result};
+use once_cell::sync::Lazy;
+use geo::{BoundingRect, HaversineDistance, Intersects, Point};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::Value;
+use tracing::info;
+use crate::quake;
+use crate::runtime::Core;
+use crate::settings::GazetteerSettings;
+
+/// Where the gazetteer puts a point.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct Place {
+  /// ISO 3166-1 alpha-3.
+  pub country: Option<String>,
+  pub country_name: Option<String>,
+  pub admin1: Option<String>,
+  pub admin2: Option<String>,
+  /// Nearest populated place within `gazetteer.max_place_km`.
+  pub place: Option<String>,
+  pub place_km: Option<f64>,
+}
+
+impl Place {
+  pub(crate) fn from_row(r: &rusqlite::Row, at: usize) -> rusqlite::Result<Place> {
+    Ok(Place {
+      country: r.get(at)?, country_name: r.get(at + 1)?, admin1: r.get(at + 2)?, admin2: r.get(at + 3)?,
+      place: r.get(at + 4)?, place_km: r.get(at + 5)?,
+    })
+  }
+}
+
+pub(crate) const PLACE_COLUMNS: &str = "p.country,p.country_name,p.admin1,p.admin2,p.place,p.place_km";
+
+/// What `load` stored.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LoadReport { pub places: usize, pub admins: usize, pub countries: usize, pub shapes: usize }
+
+/// The tables `load` replaces.
+const TABLES: [&str; 6] = ["gazetteer_place", "gazetteer_place_rtree", "gazetteer_admin", "gazetteer_country", "gazetteer_shape", "gazetteer_shape_rtree"];
+
+/// Rows `load` writes per transaction, so collectors and the UI are not
+/// locked out while a large dump loads.
+const LOAD_BATCH: usize = 20_000;
+
+/// Inserts that commit every `LOAD_BATCH` rows.
+struct Batched<'c> { conn: &'c Connection, tx: Option<rusqlite::Transaction<'c>>, rows: usize }
+
+impl<'c> Batched<'c> {
+  fn new(conn: &'c Connection) -> Result<Self> {
+    Ok(Self { conn, tx: Some(conn.unchecked_transaction()?), rows: 0 })
+  }
+
+  fn execute(&mut self, sql: &str, p: impl rusqlite::Params) -> Result<()> {
+    let tx = self.tx.as_ref().context("load transaction")?;
+    tx.prepare_cached(sql)?.execute(p)?;
+    self.rows += 1;
+    if self.rows.is_multiple_of(LOAD_BATCH) {
+      self.tx.take().context("load transaction")?.commit()?;
+      self.tx = Some(self.conn.unchecked_transaction()?);
+    }
+    Ok(())
+  }
+
+  fn commit(mut self) -> Result<()> {
+    if let Some(tx) = self.tx.take() { tx.commit()?; }
+    Ok(())
+  }
+}
+
+/// Replaces the gazetteer with the GeoNames files in `dir`:
+/// `countryInfo.txt` and one or more `cities*.txt` (e.g. `cities15000.txt`)
+/// are required; `admin1CodesASCII.txt`, `admin2Codes.txt` and country
+/// outlines are read if present. Outlines are GeoNames'
+/// `shapes_simplified_low.json` (tab-separated `geoNameId`, geometry) or a
+/// `*.geojson` FeatureCollection with ISO codes, as Natural Earth's.
+/// Clears every item's place, for `annotate_all` to redo.
+///
+/// The files load into `load_*` copies of the tables in batches; the
+/// copies then replace the tables at once, so lookups meanwhile, or after
+/// a failed load, use the previous gazetteer.
+pub fn load(conn: &Connection, dir: &Path) -> Result<LoadReport> {
+  let mut files: Vec<PathBuf> = std::fs::read_dir(dir).with_context(|| format!("read {}", dir.display()))?
+    .filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_file()).collect();
+  files.sort();
+  let named = |f: &dyn Fn(&str) -> bool| files.iter().filter(|p| p.file_name().is_some_and(|n| f(&n.to_string_lossy()))).cloned().collect::<Vec<_>>();
+  let cities = named(&|n| n.starts_with("cities") && n.ends_with(".txt"));
+  let shapes = named(&|n| n.starts_with("shapes") || n.ends_with(".geojson"));
+  if cities.is_empty() { bail!("no cities*.txt in {}; download one from https://download.geonames.org/export/dump/", dir.display()); }
+  let country_info = dir.join("countryInfo.txt");
+
+  let tx = conn.unchecked_transaction()?;
+  for t in TABLES {
+    let ddl: String = tx.query_row("SELECT sql FROM sqlite_master WHERE type='table' AND name=?1", [t], |r| r.get(0))?;
+    tx.execute_batch(&format!("DROP TABLE IF EXISTS load_{t}; {};", ddl.replacen(t, &format!("load_{t}"), 1)))?;
+  }
+  tx.commit()?;
+
+  let mut db = Batched::new(conn)?;
+  let mut report = LoadReport::default();
+  // geonameid -> ISO alpha-2, for GeoNames outlines
+  let mut by_geoname = HashMap::new();
+  let mut iso3_to_2 = HashMap::new();
+  for cols in rows(&country_info)? {
+    let [iso2, iso3, name] = [0, 1, 4].map(|i| cols.get(i).map_or("", String::as_str));
+    if iso2.is_empty() || iso3.is_empty() { continue; }
+    db.execute("INSERT OR REPLACE INTO load_gazetteer_country(iso2,iso3,name) VALUES (?1,?2,?3)", params![iso2, iso3, name])?;
+    if let Some(id) = cols.get(16).filter(|s| !s.is_empty()) { by_geoname.insert(id.clone(), iso2.to_string()); }
+    iso3_to_2.insert(iso3.to_string(), iso2.to_string());
+    report.countries += 1;
+  }
+  for file in &cities {
+    for (i, cols) in rows(file)?.into_iter().enumerate() {
+      if cols.len() < 15 { bail!("{}:{}: expected 19 tab-separated columns", file.display(), i + 1); }
+      let num = |c: usize| cols[c].parse::<f64>().with_context(|| format!("{}:{}: column {}", file.display(), i + 1, c + 1));
+      let (id, lat, lon) = (num(0)? as i64, num(4)?, num(5)?);
+      db.execute("INSERT OR REPLACE INTO load_gazetteer_place(id,name,country_code,admin1_code,admin2_code,population,lat,lon) VALUES (?1,?2,?3,?4,?5,?6,?7,?8)",
+        params![id, cols[1], cols[8], cols[10], cols[11], cols[14].parse::<i64>().ok(), lat, lon])?;
+      db.execute("INSERT OR REPLACE INTO load_gazetteer_place_rtree(rowid,minx,maxx,miny,maxy) VALUES (?1,?2,?2,?3,?3)", params![id, lon, lat])?;
+      report.places += 1;
+    }
+  }
+  for name in ["admin1CodesASCII.txt", "admin2Codes.txt"] {
+    let path = dir.join(name);
+    if !path.exists() { continue; }
+    for cols in rows(&path)? {
+      let (Some(code), Some(name)) = (cols.first(), cols.get(1)) else { continue };
+      db.execute("INSERT OR REPLACE INTO load_gazetteer_admin(code,name) VALUES (?1,?2)", params![code, name])?;
+      report.admins += 1;
+    }
+  }
+  for file in &shapes {
+    for (iso2, geometry) in read_shapes(file, &by_geoname, &iso3_to_2)? {
+      let g: geo::Geometry<f64> = geometry.clone().try_into().with_context(|| format!("{}: outline of {iso2}", file.display()))?;
+      let Some(b) = g.bounding_rect() else { continue };
+      report.shapes += 1;
+      db.execute("INSERT INTO load_gazetteer_shape(id,iso2,geojson) VALUES (?1,?2,?3)", params![report.shapes, iso2, geometry.to_string()])?;
+      db.execute("INSERT INTO load_gazetteer_shape_rtree(id,minx,maxx,miny,maxy) VALUES (?1,?2,?3,?4,?5)",
+        params![report.shapes, b.min().x, b.max().x, b.min().y, b.max().y])?;
+    }
+  }
+  db.commit()?;
+
+  let tx = conn.unchecked_transaction()?;
+  for t in TABLES { tx.execute_batch(&format!("DROP TABLE {t}; ALTER TABLE load_{t} RENAME TO {t};"))?; }
+  tx.execute_batch("DELETE FROM item_place; DELETE FROM gazetteer_load;")?;
+  tx.execute("INSERT INTO gazetteer_load(id,loaded_at) VALUES (?1,strftime('%s','now'))", [uuid::Uuid::new_v4().to_string()])?;
+  tx.commit()?;
+  Ok(report)
+}
+
+/// Tab-separated lines of a GeoNames file, without `#` comments.
+fn rows(path: &Path) -> Result<Vec<Vec<String>>> {
+  let text = std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+  Ok(text.lines().filter(|l| !l.is_empty() && !l.starts_with('#')).map(|l| l.split('\t').map(str::to_string).collect()).collect())
+}
+
+/// (ISO alpha-2, geometry) of each outline in `path`.
+fn read_shapes(path: &Path, by_geoname: &HashMap<String, String>, iso3_to_2: &HashMap<String, String>) -> Result<Vec<(String, geojson::Geometry)>> {
+  let text = std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+  let mut out = Vec::new();
+  if text.trim_start().starts_with('{') {
+    let fc: geojson::FeatureCollection = text.parse().with_context(|| format!("parse {}", path.display()))?;
+    for f in fc.features {
+      let prop = |k: &str| f.property(k).and_then(Value::as_str).filter(|s| *s != "-99").map(str::to_string);
+      let iso2 = ["ISO_A2", "iso_a2", "ISO2"].into_iter().find_map(prop)
+        .or_else(|| ["ISO_A3", "iso_a3", "ADM0_A3"].into_iter().find_map(prop).and_then(|c| iso3_to_2.get(&c).cloned()));
+      if let (Some(iso2), Some(g)) = (iso2, f.geometry) { out.push((iso2, g)); }
+    }
+    return Ok(out);
+  }
+  for (i, line) in text.lines().enumerate().skip(1) {
+    let Some((id, geometry)) = line.split_once('\t') else { continue };
+    let Some(iso2) = by_geoname.get(id) else { continue };
+    let g: geojson::Geometry = geometry.parse().with_context(|| format!("{}:{}", path.display(), i + 1))?;
+    out.push((iso2.clone(), g));
+  }
+  Ok(out)
+}
+
+pub fn loaded(conn: &Connection) -> Result<bool> {
+  Ok(conn.query_row("SELECT EXISTS(SELECT 1 FROM gazetteer_place)", [], |r| r.get(0))?)
+}
+
+struct Nearest { name: String, country_code: String, admin1: String, admin2: String, km: f64 }
+
+/// The populated place nearest to `p` within `max_km`, searching ever
+/// larger boxes, wrapped across ±180, until the nearest found lies inside
+/// the box.
+fn nearest(conn: &Connection, p: Point, max_km: f64) -> Result<Option<Nearest>> {
+  let mut stmt = conn.prepare(
+    "SELECT g.name, COALESCE(g.country_code,''), COALESCE(g.admin1_code,''), COALESCE(g.admin2_code,''), g.lat, g.lon
+     FROM gazetteer_place_rtree r JOIN gazetteer_place g ON g.id = r.rowid
+     WHERE r.minx <= ?3 AND r.maxx >= ?1 AND r.miny <= ?4 AND r.maxy >= ?2")?;
+  let mut half_deg = 0.25_f64;
+  loop {
+    // a degree of latitude is about 111 km; widen longitude towards the poles
+    let half_lon = (half_deg / p.y().to_radians().cos().max(0.01)).min(180.0);
+    let mut found = Vec::new();
+    for bbox in quake::wrapped([p.x() - half_lon, p.y() - half_deg, p.x() + half_lon, p.y() + half_deg]) {
+      let rows = stmt.query_map(params![bbox[0], bbox[1], bbox[2], bbox[3]], |r| {
+        let km = p.haversine_distance(&Point::new(r.get::<_, f64>(5)?, r.get::<_, f64>(4)?)) / 1000.0;
+        Ok(Nearest { name: r.get(0)?, country_code: r.get(1)?, admin1: r.get(2)?, admin2: r.get(3)?, km })
+      })?;
+      found.extend(rows.collect::<rusqlite::Result<Vec<_>>>()?);
+    }
+    let best = found.into_iter().min_by(|a, b| a.km.total_cmp(&b.km));
+    let reach = half_deg * 111.0;
+    match best {
+      Some(b) if b.km <= reach => return Ok((b.km <= max_km).then_some(b)),
+      _ if reach >= max_km || half_deg >= 90.0 => return Ok(best.filter(|b| b.km <= max_km)),
+      _ => half_deg *= 2.0,
+    }
+  }
+}
+
+/// Parsed outlines by shape id, and the load named in `gazetteer_load` they
+/// belong to.
+type Outlines = (String, HashMap<i64, Arc<geo::Geometry<f64>>>);
+static OUTLINES: Lazy<Mutex<Outlines>> = Lazy::new(Default::default);
+
+/// The outline `id`, parsed once per load. Gazetteers loaded before loads
+/// were named are parsed on every lookup.
+fn outline(conn: &Connection, id: i64) -> Result<Option<Arc<geo::Geometry<f64>>>> {
+  let load: Option<String> = conn.query_row("SELECT id FROM gazetteer_load", [], |r| r.get(0)).optional()?;
+  if let Some(load) = &load {
+    let mut cache = OUTLINES.lock().unwrap_or_else(|e| e.into_inner());
+    if cache.0 != *load { *cache = (load.clone(), HashMap::new()); }
+    if let Some(g) = cache.1.get(&id) { return Ok(Some(g.clone())); }
+  }
+  let gj: String = conn.query_row("SELECT geojson FROM gazetteer_shape WHERE id=?1", [id], |r| r.get(0))?;
+  let Ok(g) = gj.parse::<geojson::Geometry>().map_err(anyhow::Error::from).and_then(|g| Ok(geo::Geometry::<f64>::try_from(g)?)) else { return Ok(None) };
+  let g = Arc::new(g);
+  if let Some(load) = load {
+    let mut cache = OUTLINES.lock().unwrap_or_else(|e| e.into_inner());
+    if cache.0 == load { cache.1.insert(id, g.clone()); }
+  }
+  Ok(Some(g))
+}
+
+/// ISO alpha-2 of the country whose outline contains `p`.
+fn containing_country(conn: &Connection, p: Point) -> Result<Option<String>> {
+  let mut stmt = conn.prepare(
+    "SELECT s.id, s.iso2 FROM gazetteer_shape_rtree r JOIN gazetteer_shape s ON s.id = r.id
+     WHERE r.minx <= ?1 AND r.maxx >= ?1 AND r.miny <= ?2 AND r.maxy >= ?2")?;
+  let rows = stmt.query_map(params![p.x(), p.y()], |r| Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?)))?;
+  for row in rows {
+    let (id, iso2) = row?;
+    if outline(conn, id)?.is_some_and(|g| g.intersects(&p)) { return Ok(Some(iso2)); }
+  }
+  Ok(None)
+}
+
+/// Places `(lat, lon)`. The country is the outline containing it, or,
+/// without outlines, that of the nearest place; divisions are the nearest
+/// place's when it lies in the same country. `None` without a gazetteer.
+pub fn lookup(conn: &Connection, lat: f64, lon: f64, max_km: f64) -> Result<Option<Place>> {
+  if !loaded(conn)? { return Ok(None); }
+  let p = Point::new(lon, lat);
+  let near = nearest(conn, p, max_km)?;
+  let has_shapes: bool = conn.query_row("SELECT EXISTS(SELECT 1 FROM gazetteer_shape)", [], |r| r.get(0))?;
+  let iso2 = if has_shapes { containing_country(conn, p)? } else { near.as_ref().map(|n| n.country_code.clone()) };
+  let country: Option<(String, String)> = match &iso2 {
+    Some(c) => conn.query_row("SELECT iso3, name FROM gazetteer_country WHERE iso2=?1", [c], |r| Ok((r.get(0)?, r.get(1)?))).optional()?,
+    None => None,
+  };
+  let admin = |code: String| -> Result<Option<String>> {
+    Ok(conn.query_row("SELECT name FROM gazetteer_admin WHERE code=?1", [code], |r| r.get(0)).optional()?)
+  };
+  let (mut admin1, mut admin2) = (None, None);
+  if let Some(n) = near.as_ref().filter(|n| iso2.as_deref() == Some(n.country_code.as_str())) {
+    admin1 = admin(format!("{}.{}", n.country_code, n.admin1))?;
+    if !n.admin2.is_empty() { admin2 = admin(format!("{}.{}.{}", n.country_code, n.admin1, n.admin2))?; }
+  }
+  let (country, country_name) = country.unzip();
+  Ok(Some(Place {
+    country, country_name, admin1, admin2,
+    place_km: near.as_ref().map(|n| (n.km * 10.0).round() / 10.0),
+    place: near.map(|n| n.name),
+  }))
+}
+
+/// The point an item is placed by: an event's location, an alert's bbox
+/// centre.
+fn point_of(conn: &Connection, kind: &str, id: &str) -> Result<Option<(f64, f64)>> {
+  let sql = if kind == "alert" {
+    "SELECT (bbox_miny + bbox_maxy) / 2, (bbox_minx + bbox_maxx) / 2 FROM alert WHERE id=?1 AND bbox_minx IS NOT NULL"
+  } else {
+    "SELECT lat, lon FROM event WHERE id=?1 AND lat IS NOT NULL AND lon IS NOT NULL"
+  };
+  Ok(conn.query_row(sql, [id], |r| Ok((r.get(0)?, r.get(1)?))).optional()?)
+}
+
+/// Places the `kind` ("event" or "alert") items `ids` that are new or have
+/// moved since placed. An event without a country takes the one found.
+/// Returns the number placed.
+pub fn annotate(conn: &Connection, kind: &str, ids: &[String], cfg: &GazetteerSettings) -> Result<usize> {
+  if ids.is_empty() || !loaded(conn)? { return Ok(0); }
+  let mut n = 0;
+  for id in ids {
+    let Some((lat, lon)) = point_of(conn, kind, id)? else { continue };
+    let prev: Option<(f64, f64, Option<String>)> = conn.query_row(
+      "SELECT lat, lon, country FROM item_place WHERE item_kind=?1 AND item_id=?2", params![kind, id],
+      |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?))).optional()?;
+    if prev.as_ref().is_some_and(|(a, b, _)| (*a, *b) == (lat, lon)) { continue; }
+    let Some(p) = lookup(conn, lat, lon, cfg.max_place_km)? else { continue };
+    conn.execute("INSERT OR REPLACE INTO item_place(item_kind,item_id,lat,lon,country,country_name,admin1,admin2,place,place_km)
+      VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10)",
+      params![kind, id, lat, lon, p.country, p.country_name, p.admin1, p.admin2, p.place, p.place_km])?;
+    if kind == "event" && p.country.is_some() {
+      // keep a feed's own country, e.g. GDACS's list for multi-country events
+      conn.execute("UPDATE event SET country=?2 WHERE id=?1 AND (country IS NULL OR country IS ?3)",
+        params![id, p.country, prev.and_then(|p| p.2)])?;
+    }
+    n += 1;
+  }
+  Ok(n)
+}
+
+/// Places every event and alert not yet placed or moved since.
+pub fn annotate_all(conn: &Connection, cfg: &GazetteerSettings) -> Result<usize> {
+  let ids = |sql: &str| -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map([], |r| r.get(0))?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+  };
+  let events = ids("SELECT e.id FROM event e LEFT JOIN item_place p ON p.item_kind='event' AND p.item_id=e.id
+    WHERE e.lat IS NOT NULL AND e.lon IS NOT NULL AND (p.item_id IS NULL OR p.lat IS NOT e.lat OR p.lon IS NOT e.lon)")?;
+  let alerts = ids("SELECT a.id FROM alert a LEFT JOIN item_place p ON p.item_kind='alert' AND p.item_id=a.id
+    WHERE a.bbox_minx IS NOT NULL AND p.item_id IS NULL")?;
+  Ok(annotate(conn, "event", &events, cfg)? + annotate(conn, "alert", &alerts, cfg)?)
+}
+
+/// The stored place of an item.
+pub fn place_of(conn: &Connection, kind: &str, id: &str) -> Result<Option<Place>> {
+  Ok(conn.query_row(&format!("SELECT {PLACE_COLUMNS} FROM item_place p WHERE p.item_kind=?1 AND p.item_id=?2"),
+    params![kind, id], |r| Place::from_row(r, 0)).optional()?)
+}
+
+/// On start: loads the configured gazetteer if the database has none, then
+/// places what is not yet placed.
+pub fn init(core: &Core) -> Result<()> {
+  let cfg = &core.settings.gazetteer;
+  let conn = core.db.conn.get()?;
+  if !loaded(conn)? {
+    let dir = cfg.dir.clone().unwrap_or_else(|| core.data_dir.join("gazetteer"));
+    if !dir.is_dir() { return Ok(()); }
+    let r = load(conn, &dir)?;
+    info!("gazetteer: {} places, {} divisions, {} countries, {} outlines from {}", r.places, r.admins, r.countries, r.shapes, dir.display());
+    crate::quake::estimate_all(conn)?;
+  }
+  let n = annotate_all(conn, cfg)?;
+  if n > 0 { info!("gazetteer: placed {n} items"); }
+  Ok(())
+}
@@ -0,0 +1,124 @@
+//! Time travel over `event_version` and `alert_version`. `ipc::query`
+//! answers "as of T"; this module lists what changed between two moments,
+//! and plays those changes back paced for a map to animate.
+use anyhow::Result;
+use rusqlite::params;
+use serde::Serialize;
+use tokio::time::{sleep_until, Duration, Instant};
+use crate::db::Db;
+use crate::error::Error;
+use crate::ipc::{UiAlert, UiEvent};
+use crate::normalize::cap_severity;
+use crate::stream::StreamFilter;
+
+/// Most changes one call returns; narrow the range or filter for more.
+pub const MAX_CHANGES: usize = 20_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind { EventCreated, EventUpdated, AlertIssued, AlertUpdated, AlertExpired, AlertCancelled }
+
+/// One change at `at`. Event changes carry the event as it then read and
+/// alert changes the alert; an ended alert carries its last version.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct Change {
+  pub at: i64,
+  pub kind: ChangeKind,
+  pub id: String,
+  pub event: Option<UiEvent>,
+  pub alert: Option<UiAlert>,
+}
+
+impl Change {
+  fn accepted_by(&self, f: &StreamFilter) -> bool {
+    match (&self.event, &self.alert) {
+      (Some(e), _) => f.accepts(&e.class, e.severity as f64, (e.lon, e.lat, e.lon, e.lat)),
+      (_, Some(a)) => f.accepts("alert", cap_severity(&a.severity), a.bbox),
+      _ => true,
+    }
+  }
+}
+
+/// Changes in `(from, to]` that `filter` accepts, oldest first. Replaying
+/// them over `search_events` and `query_alerts` as of `from` gives the
+/// state as of `to`.
+pub fn changes(db: &Db, from: i64, to: i64, filter: &StreamFilter) -> Result<Vec<Change>> {
+  if to < from { return Err(Error::invalid("`to` is before `from`").into()); }
+  let mut out = Vec::new();
+  let mut stmt = db.conn.prepare(
+    "SELECT e.id, v.title, v.class, v.lat, v.lon, v.severity, e.first_seen, v.valid_from,
+       NOT EXISTS (SELECT 1 FROM event_version p WHERE p.event_id=v.event_id AND p.rowid < v.rowid)
+     FROM event_version v JOIN event e ON e.id = v.event_id
+     WHERE v.valid_from > ?1 AND v.valid_from <= ?2")?;
+  let rows = stmt.query_map(params![from, to], |r| {
+    let event = UiEvent::from_row(r)?;
+    let kind = if r.get(8)? { ChangeKind::EventCreated } else { ChangeKind::EventUpdated };
+    Ok(Change { at: r.get(7)?, kind, id: event.id.clone(), event: Some(event), alert: None })
+  })?;
+  for c in rows { out.push(c?); }
+
+  // issued and updated versions, then ends: the version in effect at its
+  // expiry, unless cancelled first, and cancellations
+  const COLUMNS: &str = "v.alert_id,v.headline,v.event,v.severity,v.urgency,v.certainty,v.onset,v.expires,v.polygon_geojson,v.bbox_minx,v.bbox_miny,v.bbox_maxx,v.bbox_maxy";
+  let alert_changes = |sql: &str, kind: fn(bool) -> ChangeKind| -> Result<Vec<Change>> {
+    let mut stmt = db.conn.prepare(sql)?;
+    let rows = stmt.query_map(params![from, to], |r| {
+      let alert = UiAlert::from_row(r)?;
+      Ok(Change { at: r.get(13)?, kind: kind(r.get(14)?), id: alert.id.clone(), event: None, alert: Some(alert) })
+    })?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+  };
+  let in_effect = |at: &str| format!("v.rowid = (SELECT rowid FROM alert_version WHERE alert_id=v.alert_id AND valid_from <= {at} ORDER BY valid_from DESC, rowid DESC LIMIT 1)");
+  out.extend(alert_changes(&format!(
+    "SELECT {COLUMNS}, v.valid_from, NOT EXISTS (SELECT 1 FROM alert_version p WHERE p.alert_id=v.alert_id AND p.rowid < v.rowid)
+     FROM alert_version v WHERE v.valid_from > ?1 AND v.valid_from <= ?2"),
+    |first| if first { ChangeKind::AlertIssued } else { ChangeKind::AlertUpdated })?);
+  out.extend(alert_changes(&format!(
+    "SELECT {COLUMNS}, v.expires, 0 FROM alert_version v JOIN alert a ON a.id = v.alert_id
+     WHERE v.expires > ?1 AND v.expires <= ?2 AND {} AND (a.cancelled_at IS NULL OR a.cancelled_at > v.expires)", in_effect("v.expires")),
+    |_| ChangeKind::AlertExpired)?);
+  out.extend(alert_changes(&format!(
+    "SELECT {COLUMNS}, a.cancelled_at, 0 FROM alert a JOIN alert_version v ON v.alert_id = a.id AND {}
+     WHERE a.cancelled_at > ?1 AND a.cancelled_at <= ?2 AND v.expires >= a.cancelled_at", in_effect("a.cancelled_at")),
+    |_| ChangeKind::AlertCancelled)?);
+
+  out.retain(|c| c.accepted_by(filter));
+  out.sort_by_key(|c| (c.at, c.kind as u8));
+  out.truncate(MAX_CHANGES);
+  Ok(out)
+}
+
+/// When a change `secs` history seconds after the first is due at
+/// `speed`, or `None` past what a timer can hold.
+fn due(start: Instant, secs: f64, speed: f64) -> Option<Instant> {
+  Duration::try_from_secs_f64(secs / speed).ok().and_then(|d| start.checked_add(d))
+}
+
+/// Rejects a `speed` that is negative, or so slow that the last of
+/// `changes` would be due past what a timer can hold.
+pub fn check_speed(changes: &[Change], speed: f64) -> Result<()> {
+  if speed.is_nan() || speed < 0.0 { return Err(Error::invalid("speed must be 0 or more").into()); }
+  let span = match (changes.first(), changes.last()) { (Some(a), Some(b)) => (b.at - a.at) as f64, _ => 0.0 };
+  if speed > 0.0 && due(Instant::now(), span, speed).is_none() {
+    return Err(Error::invalid(format!("speed {speed} is too slow to play these changes")).into());
+  }
+  Ok(())
+}
+
+/// Hands `changes` to `emit` paced `speed` history seconds per second of
+/// wall time (3600 plays an hour a second); 0 does not pause. Stops at the
+/// first error `emit` returns.
+pub async fn play<F: FnMut(&Change) -> Result<()>>(changes: &[Change], speed: f64, mut emit: F) -> Result<()> {
+  check_speed(changes, speed)?;
+  let (start, t0) = (Instant::now(), changes.first().map_or(0, |c| c.at));
+  for c in changes {
+    if speed > 0.0 {
+      let at = due(start, (c.at - t0) as f64, speed).ok_or_else(|| Error::invalid(format!("speed {speed} is too slow to play these changes")))?;
+      sleep_until(at).await;
+    }
+    emit(c)?;
+  }
+  Ok(())
+}
@@ -0,0 +1,176 @@
+use anyhow::Result;
+use quick_xml::events::Event as XEvent;
+use quick_xml::Reader;
+use crate::runtime::Core;
+use crate::ai::translate::same_language;
+use rusqlite::{params, OptionalExtension};
+
+const CAP_URLS: &[&str] = &[];
+
+pub async fn run(core: &Core) -> Result<usize> {
+  let mut n = 0;
+  for url in CAP_URLS {
+    n += ingest(core, &super::fetch(core, "cap", url).await?.error_for_status()?.body)?;
+  }
+  Ok(n)
+}
+
+/// Stores one CAP 1.2 `<alert>` document and returns the number of alerts
+/// it created.
+pub fn ingest(core: &Core, text: &str) -> Result<usize> {
+  let (created, cancelled) = match persist(core, "cap", None, text, chrono::Utc::now().timestamp())? {
+    Persisted::Created(id) => (vec![id], Vec::new()),
+    Persisted::Updated(_) | Persisted::Ignored => (Vec::new(), Vec::new()),
+    Persisted::Cancelled(ids) => (Vec::new(), ids),
+  };
+  super::publish_alerts(core, "cap", &created, &cancelled);
+  Ok(created.len())
+}
+
+pub(super) enum Persisted { Created(String), Updated(String), Cancelled(Vec<String>), Ignored }
+
+/// Stores the alert under `source` without publishing it. With `namespace`
+/// the alert, and the alerts a `Cancel` references, are keyed
+/// `<namespace>:<identifier>`, apart from any feed's alert of that id.
+pub(super) fn persist(core: &Core, source: &str, namespace: Option<&str>, text: &str, now: i64) -> Result<Persisted> {
+  let key = |identifier: String| match namespace { Some(ns) => format!("{ns}:{identifier}"), None => identifier };
+  let db = &core.db;
+  let mut reader = Reader::from_str(text);
+  reader.config_mut().trim_text(true);
+  let mut buf = Vec::new();
+
+  let mut identifier = String::new();
+  let mut sent = String::new();
+  let mut msg_type = String::new();
+  let mut references = String::new();
+  let mut infos: Vec<Info> = Vec::new();
+  let mut in_elem = String::new();
+
+  loop {
+    match reader.read_event_into(&mut buf) {
+      Ok(XEvent::Start(e)) => {
+        in_elem = String::from_utf8_lossy(e.name().as_ref()).to_string();
+        if in_elem == "info" { infos.push(Info::default()); }
+      }
+      Ok(XEvent::End(_)) => in_elem.clear(),
+      Ok(XEvent::Text(t)) => {
+        let v = t.unescape().unwrap_or_default().to_string();
+        match (in_elem.as_str(), infos.last_mut()) {
+          ("identifier", _) => identifier = v,
+          ("sent", _) => sent = v,
+          ("msgType", _) => msg_type = v,
+          ("references", _) => references = v,
+          ("language", Some(i)) => i.language = v,
+          ("headline", Some(i)) => i.headline = v,
+          ("event", Some(i)) => i.event = v,
+          ("severity", Some(i)) => i.severity = v,
+          ("urgency", Some(i)) => i.urgency = v,
+          ("certainty", Some(i)) => i.certainty = v,
+          ("description", Some(i)) => i.description = v,
+          ("instruction", Some(i)) => i.instruction = v,
+          ("areaDesc", Some(i)) => i.area_desc = v,
+          ("effective", Some(i)) => i.effective = v,
+          ("onset", Some(i)) => i.onset = v,
+          ("expires", Some(i)) => i.expires = v,
+          ("polygon", Some(i)) => i.polygons.push(v),
+          _ => {}
+        }
+      }
+      Ok(XEvent::Eof) => break,
+      _ => {}
+    }
+    buf.clear();
+  }
+
+  if msg_type == "Cancel" {
+    // each reference is "sender,identifier,sent"
+    let refs: Vec<String> = references.split_whitespace().filter_map(|r| r.split(',').nth(1)).map(|r| key(r.to_string())).collect();
+    return Ok(Persisted::Cancelled(super::cancel_alerts(&db.conn, &refs, now)?));
+  }
+  let Some(info) = pick_info(infos, &core.settings.translation.target_language) else { return Ok(Persisted::Ignored) };
+  let id = key(if identifier.is_empty() { uuid::Uuid::new_v4().to_string() } else { identifier });
+  let onset = parse_ts(&info.effective).or_else(|| parse_ts(&info.onset)).unwrap_or(now);
+  let sent_e = parse_ts(&sent).unwrap_or(now);
+  let exp = parse_ts(&info.expires).unwrap_or(now + 3600);
+  // CAP: a missing <language> means en-US
+  let language = if info.language.is_empty() { "en-US".to_string() } else { info.language };
+  let polygon_texts = info.polygons;
+
+  let (geojson_text, minx, miny, maxx, maxy) = cap_polygons_to_geojson_bbox(&polygon_texts);
+
+  let existed: Option<i64> = db.conn.get()?.query_row("SELECT 1 FROM alert WHERE id=?1", params![id], |r| r.get(0)).optional()?;
+  let tx = db.conn.unchecked_transaction()?;
+  tx.execute(
+    "INSERT OR REPLACE INTO alert(id, source, headline, event, severity, urgency, certainty, onset, sent, expires, area_desc, polygon_geojson, bbox_minx, bbox_miny, bbox_maxx, bbox_maxy, raw_json, last_seen, language, description, instruction)
+     VALUES (?1,?21,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15,?16,?17,?18,?19,?20)",
+    params![id, info.headline, info.event, info.severity, info.urgency, info.certainty, onset, sent_e, exp, info.area_desc, geojson_text, minx, miny, maxx, maxy, text, now,
+            language, info.description, info.instruction, source]
+  )?;
+  let rowid: i64 = tx.query_row("SELECT rowid FROM alert WHERE id=?1", params![id], |r| r.get(0))?;
+  tx.execute("INSERT OR REPLACE INTO alert_rtree(rowid,minx,maxx,miny,maxy) VALUES (?1,?2,?3,?4,?5)",
+    params![rowid, minx, maxx, miny, maxy])?;
+  tx.commit()?;
+  Ok(if existed.is_some() { Persisted::Updated(id) } else { Persisted::Created(id) })
+}
+
+/// One `<info>` block. Multilingual alerts repeat the block per language.
+#[derive(Default)]
+struct Info {
+  language: String,
+  headline: String,
+  event: String,
+  severity: String,
+  urgency: String,
+  certainty: String,
+  description: String,
+  instruction: String,
+  area_desc: String,
+  effective: String,
+  onset: String,
+  expires: String,
+  polygons: Vec<String>,
+}
+
+/// Prefers the block in `target`, then English, then the first one.
+fn pick_info(mut infos: Vec<Info>, target: &str) -> Option<Info> {
+  let lang = |i: &Info| if i.language.is_empty() { "en-US".to_string() } else { i.language.clone() };
+  let pos = infos.iter().position(|i| same_language(&lang(i), target))
+    .or_else(|| infos.iter().position(|i| same_language(&lang(i), "en")))
+    .unwrap_or(0);
+  if infos.is_empty() { None } else { Some(infos.swap_remove(pos)) }
+}
+
+fn parse_ts(s: &str) -> Option<i64> {
+  chrono::DateTime::parse_from_rfc3339(s).ok().map(|dt| dt.with_timezone(&chrono::Utc).timestamp())
+}
+
+fn cap_polygons_to_geojson_bbox(polys: &[String]) -> (String,f64,f64,f64,f64) {
+  use geojson::{Geometry, Value, Feature, FeatureCollection};
+  let mut features = Vec::new();
+  let mut minx=f64::INFINITY; let mut miny=f64::INFINITY; let mut maxx=f64::NEG_INFINITY; let mut maxy=f64::NEG_INFINITY;
+
+  for p in polys {
+    let ring: Vec<Vec<f64>> = p
+      .split_whitespace()
+      .filter_map(|pair| {
+        let mut it = pair.split(',');
+        let lat = it.next()?.parse::<f64>().ok()?;
+        let lon = it.next()?.parse::<f64>().ok()?;
+        Some(vec![lon, lat])
+      })
+      .collect();
+
+    if ring.len() >= 3 {
+      for pt in &ring { let (x,y)=(pt[0],pt[1]); minx=minx.min(x); miny=miny.min(y); maxx=maxx.max(x); maxy=maxy.max(y); }
+      let mut closed = ring.clone();
+      if closed.first() != closed.last() { closed.push(closed[0].clone()); }
+      let geom = Geometry::new(Value::Polygon(vec![closed]));
+      features.push(Feature{ geometry: Some(geom), properties: None, bbox: None, id: None, foreign_members: None });
+    }
+  }
+
+  let fc = FeatureCollection{ features, bbox: None, foreign_members: None };
+  let txt = serde_json::to_string(&geojson::GeoJson::from(fc)).unwrap_or("null".into());
+  if !minx.is_finite() { return ("null".into(), -180.0,-90.0,180.0,90.0); }
+  (txt, minx, miny, maxx, maxy)
+}
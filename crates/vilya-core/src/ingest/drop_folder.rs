@@ -0,0 +1,108 @@
+//! Feed files dropped into a folder, for air-gapped deployments. Each file
+//! is recognised by its content, stored through the parser of the feed it
+//! came from, and moved to the archive folder, or to the error folder
+//! beside a `<file>.error.txt` saying why.
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use anyhow::{bail, Context, Result};
+use quick_xml::events::Event as XEvent;
+use quick_xml::Reader;
+use serde_json::Value;
+use tracing::{info, warn};
+use crate::runtime::Core;
+use crate::settings::DropFolderSettings;
+
+/// Scans the configured folder once; returns the items stored.
+pub fn run(core: &Core) -> Result<usize> {
+  let Some(dir) = &core.settings.drop_folder.path else { bail!("no drop folder; set drop_folder.path in settings.json") };
+  scan(core, dir, &core.settings.drop_folder, SystemTime::now())
+}
+
+/// Ingests every settled file directly in `dir`. A file that fails is
+/// moved aside and does not fail the scan, nor does a file that cannot be
+/// moved.
+pub fn scan(core: &Core, dir: &Path, cfg: &DropFolderSettings, now: SystemTime) -> Result<usize> {
+  let archive = cfg.archive.clone().unwrap_or_else(|| dir.join("archive"));
+  let errors = cfg.errors.clone().unwrap_or_else(|| dir.join("error"));
+  let mut files: Vec<PathBuf> = std::fs::read_dir(dir).with_context(|| format!("read {}", dir.display()))?
+    .filter_map(|e| e.ok())
+    .filter(|e| e.file_type().is_ok_and(|t| t.is_file()))
+    // dotfiles are partial copies or editor and OS droppings
+    .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+    .filter(|e| e.metadata().and_then(|m| m.modified()).is_ok_and(|t| now.duration_since(t).unwrap_or_default() >= Duration::from_secs(cfg.settle_secs)))
+    .map(|e| e.path())
+    .collect();
+  files.sort();
+  let mut total = 0;
+  for file in files {
+    let res = std::fs::read_to_string(&file).context("not UTF-8 text")
+      .and_then(|body| {
+        let source = sniff(&body)?;
+        let n = super::ingest(core, source, &body).with_context(|| format!("as {source}"))?;
+        Ok((source, n))
+      });
+    let name = file.display().to_string();
+    match res {
+      Ok((source, n)) => {
+        info!("drop: {name}: {n} items as {source}");
+        total += n;
+        if let Err(e) = move_into(&file, &archive) { warn!("drop: {name}: {e:#}"); }
+      }
+      Err(e) => {
+        warn!("drop: {name}: {e:#}");
+        let moved = move_into(&file, &errors).and_then(|moved| {
+          let mut reason = moved.into_os_string();
+          reason.push(".error.txt");
+          Ok(std::fs::write(&reason, format!("{e:#}\n"))?)
+        });
+        if let Err(e) = moved { warn!("drop: {name}: {e:#}"); }
+      }
+    }
+  }
+  Ok(total)
+}
+
+/// The collector whose parser reads `body`: a CAP `<alert>`, an NWS ATOM
+/// `<feed>`, an EONET `events` list, or a FeatureCollection of USGS
+/// quakes (`mag`), EONET events (`categories` and `sources`) or NWS alerts
+/// (`messageType`).
+pub fn sniff(body: &str) -> Result<&'static str> {
+  let body = body.trim_start_matches('\u{feff}').trim_start();
+  if body.starts_with('<') {
+    let mut reader = Reader::from_str(body);
+    loop {
+      match reader.read_event().context("parse XML")? {
+        XEvent::Start(e) | XEvent::Empty(e) => return match e.local_name().as_ref() {
+          b"alert" => Ok("cap"),
+          b"feed" => Ok("nws"),
+          other => bail!("unrecognised XML root <{}>; expected a CAP <alert> or ATOM <feed>", String::from_utf8_lossy(other)),
+        },
+        XEvent::Eof => bail!("empty XML document"),
+        _ => {}
+      }
+    }
+  }
+  let v: Value = serde_json::from_str(body).context("neither XML nor JSON")?;
+  if v.get("events").is_some_and(Value::is_array) { return Ok("eonet"); }
+  let Some(features) = v.get("features").and_then(Value::as_array) else { bail!("JSON without `features` or `events`") };
+  let has = |k: &str| features.iter().any(|f| f.pointer(&format!("/properties/{k}")).is_some());
+  // EONET features carry `magnitudeValue`, not `mag`, but check them first
+  if has("categories") && has("sources") { return Ok("eonet"); }
+  if has("mag") { return Ok("usgs"); }
+  if has("messageType") || has("areaDesc") { return Ok("nws"); }
+  bail!("FeatureCollection is neither USGS quakes, EONET events nor NWS alerts")
+}
+
+/// Moves `file` into `dir`, prefixing the name with the time if one is
+/// already there. Falls back to copying across file systems.
+fn move_into(file: &Path, dir: &Path) -> Result<PathBuf> {
+  std::fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+  let name = file.file_name().context("no file name")?.to_string_lossy();
+  let mut to = dir.join(&*name);
+  if to.exists() { to = dir.join(format!("{}-{name}", chrono::Utc::now().format("%Y%m%dT%H%M%S%.3f"))); }
+  if std::fs::rename(file, &to).is_err() {
+    std::fs::copy(file, &to).with_context(|| format!("copy {} to {}", file.display(), to.display()))?;
+    std::fs::remove_file(file)?;
+  }
+  Ok(to)
+}
@@ -0,0 +1,65 @@
+use anyhow::Result;
+use futures::{SinkExt, StreamExt};
+use tokio_tungstenite::connect_async;
+use crate::quake::{self, Quake};
+use crate::runtime::Core;
+use super::{after_commit, upsert_event, NewEvent};
+
+/// Runs until the socket, or the replayed tape, closes; each message is
+/// reported as `source_status`.
+pub async fn run(core: &Core) -> Result<usize> {
+  let mut total = 0;
+  if let Some(mut frames) = core.tape.frames("emsc") {
+    while let Some(txt) = frames.recv().await { total += on_message(core, &txt); }
+    return Ok(total);
+  }
+  let (mut ws, _) = connect_async("wss://www.seismicportal.eu/standing_order/websocket").await?;
+  ws.send(tokio_tungstenite::tungstenite::Message::Text(r#"{"subscribe":"quakes"}"#.into())).await?;
+  while let Some(msg) = ws.next().await {
+    let txt = msg?.into_text()?;
+    core.tape.capture_frame("emsc", &txt);
+    total += on_message(core, &txt);
+  }
+  Ok(total)
+}
+
+/// Messages that fail to parse or store are reported, not dropped silently;
+/// the stream carries on with the next one.
+fn on_message(core: &Core, txt: &str) -> usize {
+  match ingest(core, txt) {
+    Ok(Some(n)) => { crate::stream::source_status(core, "emsc", &Ok(n)); n }
+    Ok(None) => 0,
+    Err(e) => { crate::stream::source_status(core, "emsc", &Err(e)); 0 }
+  }
+}
+
+/// Stores one websocket message. `None` if it carries no features, as
+/// subscription acknowledgements do not.
+pub fn ingest(core: &Core, text: &str) -> Result<Option<usize>> {
+  let db = &core.db;
+  let v: serde_json::Value = serde_json::from_str(text)?;
+  let Some(features) = v.get("features").and_then(|x| x.as_array()) else { return Ok(None) };
+  let tx = db.conn.get()?.unchecked_transaction()?;
+  let mut batch = Vec::new();
+  let now = chrono::Utc::now().timestamp();
+  for f in features {
+    let id = f.get("id").and_then(|x| x.as_str()).map(str::to_string).unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let empty = serde_json::json!({});
+    let props = f.get("properties").unwrap_or(&empty);
+    let quake = Quake::from_emsc(props);
+    let title = format!("EMSC M{:.1} {}", quake.mag, props.get("flynn_region").and_then(|x| x.as_str()).unwrap_or(""));
+    let coords = f.pointer("/geometry/coordinates").and_then(|x| x.as_array()).cloned().unwrap_or_default();
+    let lon = coords.first().and_then(|x| x.as_f64()).unwrap_or(0.0);
+    let lat = coords.get(1).and_then(|x| x.as_f64()).unwrap_or(0.0);
+    let estimate = quake::estimate(&tx, lat, lon, &quake)?;
+    let severity = estimate.severity;
+    let change = upsert_event(&tx, &NewEvent {
+      id: &id, title: &title, summary: &title, class: "eq", severity, confidence: 0.95, lat, lon,
+      geojson: Some(f.to_string()), source_rank: 4, seen: now,
+    })?;
+    quake::store(&tx, &id, lat, lon, &quake, &estimate, now)?;
+    batch.push((id, severity as f32, change));
+  }
+  tx.commit()?;
+  Ok(Some(after_commit(core, batch)))
+}
@@ -0,0 +1,85 @@
+use anyhow::Result;
+use serde_json::Value;
+use crate::runtime::Core;
+use super::{after_commit, upsert_event, NewEvent};
+
+pub async fn run(core: &Core) -> Result<usize> {
+  let url = "https://eonet.gsfc.nasa.gov/api/v3/events?status=open&limit=50";
+  ingest(core, &super::fetch(core, "eonet", url).await?.body)
+}
+
+/// One EONET event as either response form gives it.
+struct Item<'a> { id: String, title: &'a str, class: &'a str, lat: f64, lon: f64, payload: &'a Value }
+
+/// Stores an EONET v3 events response, or the GeoJSON form
+/// (`/api/v3/events/geojson`), which has a feature per event geometry.
+pub fn ingest(core: &Core, body: &str) -> Result<usize> {
+  let db = &core.db;
+  let v: Value = serde_json::from_str(body)?;
+  let items: Vec<Item> = if let Some(arr) = v.get("events").and_then(|x| x.as_array()) {
+    arr.iter().map(|e| {
+      let coords = e.pointer("/geometry/0/coordinates").and_then(|x| x.as_array()).cloned().unwrap_or_default();
+      item(e, e, &coords)
+    }).collect()
+  } else if let Some(arr) = v.get("features").and_then(|x| x.as_array()) {
+    // the latest geometry of each event
+    let mut latest: Vec<(&str, Item)> = Vec::new();
+    for f in arr {
+      let props = f.get("properties").unwrap_or(&Value::Null);
+      let date = props.get("date").and_then(|x| x.as_str()).unwrap_or("");
+      let it = item(props, f, &point_of(f.get("geometry")));
+      match latest.iter_mut().find(|(_, l)| l.id == it.id) {
+        Some(l) if date >= l.0 => *l = (date, it),
+        Some(_) => {}
+        None => latest.push((date, it)),
+      }
+    }
+    latest.into_iter().map(|(_, it)| it).collect()
+  } else {
+    return Ok(0);
+  };
+  let tx = db.conn.get()?.unchecked_transaction()?;
+  let mut batch = Vec::new();
+  let now = chrono::Utc::now().timestamp();
+  for it in items {
+    let change = upsert_event(&tx, &NewEvent {
+      id: &it.id, title: it.title, summary: it.title, class: it.class, severity: 0.6, confidence: 0.8, lat: it.lat, lon: it.lon,
+      geojson: Some(it.payload.to_string()), source_rank: 8, seen: now,
+    })?;
+    batch.push((it.id, 0.6, change));
+  }
+  tx.commit()?;
+  Ok(after_commit(core, batch))
+}
+
+/// `props` carries `id`, `title` and `categories`; `payload` is stored.
+fn item<'a>(props: &'a Value, payload: &'a Value, coords: &[Value]) -> Item<'a> {
+  Item {
+    id: props.get("id").and_then(|x| x.as_str()).map_or_else(|| uuid::Uuid::new_v4().to_string(), str::to_string),
+    title: props.get("title").and_then(|x| x.as_str()).unwrap_or("EONET event"),
+    class: props.get("categories").and_then(|c| c.get(0)).and_then(|c| c.get("id")).and_then(|x| x.as_str()).unwrap_or("natural"),
+    lon: coords.first().and_then(|x| x.as_f64()).unwrap_or(0.0),
+    lat: coords.get(1).and_then(|x| x.as_f64()).unwrap_or(0.0),
+    payload,
+  }
+}
+
+/// A point's coordinates, or the mean vertex of a polygon's outer ring.
+fn point_of(geometry: Option<&Value>) -> Vec<Value> {
+  let Some(g) = geometry else { return Vec::new() };
+  let coords = g.get("coordinates");
+  match g.get("type").and_then(|t| t.as_str()) {
+    Some("Point") => coords.and_then(|c| c.as_array()).cloned().unwrap_or_default(),
+    Some("Polygon") => {
+      let mut ring: Vec<(f64, f64)> = coords.and_then(|c| c.get(0)).and_then(|r| r.as_array()).into_iter().flatten()
+        .filter_map(|p| Some((p.get(0)?.as_f64()?, p.get(1)?.as_f64()?))).collect();
+      // a closed ring repeats its first vertex
+      if ring.len() > 1 && ring.first() == ring.last() { ring.pop(); }
+      if ring.is_empty() { return Vec::new(); }
+      let n = ring.len() as f64;
+      let (x, y) = ring.iter().fold((0.0, 0.0), |(x, y), (px, py)| (x + px / n, y + py / n));
+      vec![x.into(), y.into()]
+    }
+    _ => Vec::new(),
+  }
+}
@@ -0,0 +1,47 @@
+use anyhow::Result;
+use reqwest::StatusCode;
+use tracing::info;
+use crate::db::Db;
+use crate::runtime::Core;
+use super::{after_commit, TextChange};
+
+pub async fn run(core: &Core) -> Result<usize> {
+  let url = "https://www.gdacs.org/gdacsapi/api/Events/geteventlist/SEARCH?pageSize=100&pageNumber=1";
+  let resp = super::fetch(core, "gdacs", url).await?;
+  if resp.status==StatusCode::OK {
+    let n = ingest(core, &resp.body)?;
+    info!("gdacs: ok");
+    return Ok(n);
+  }
+  Ok(0)
+}
+
+/// Stores a GDACS event list response.
+pub fn ingest(core: &Core, body: &str) -> Result<usize> {
+  let v: serde_json::Value = serde_json::from_str(body)?;
+  let arr = v.get("features").or_else(|| v.get("events")).cloned().unwrap_or(serde_json::json!([]));
+  let inserted = persist_gdacs(&core.db, arr)?;
+  Ok(after_commit(core, inserted.into_iter().map(|id| (id, 0.5, TextChange::New)).collect()))
+}
+
+/// Inserts unseen GDACS events and returns their ids.
+fn persist_gdacs(db: &Db, items: serde_json::Value) -> Result<Vec<String>> {
+  let tx = db.conn.get()?.unchecked_transaction()?;
+  let mut inserted = Vec::new();
+  if let Some(arr)=items.as_array() {
+    for it in arr {
+      let title = it.pointer("/properties/eventname").or_else(|| it.get("title")).and_then(|x| x.as_str()).unwrap_or("GDACS event");
+      let lat = it.pointer("/geometry/coordinates/1").and_then(|x| x.as_f64()).unwrap_or(0.0);
+      let lon = it.pointer("/geometry/coordinates/0").and_then(|x| x.as_f64()).unwrap_or(0.0);
+      // ISO 3166-1 alpha-3; multi-country events list several, comma-separated
+      let country = it.pointer("/properties/iso3").and_then(|x| x.as_str()).filter(|s| !s.is_empty());
+      let id = it.pointer("/properties/eventid").and_then(|x| x.as_str()).unwrap_or_else(|| uuid::Uuid::new_v4().to_string().leak()).to_string();
+      tx.execute("INSERT OR IGNORE INTO event(id,first_seen,last_seen,title,summary,class,severity,confidence,lat,lon,geojson,source_rank,country)
+        VALUES (?1,strftime('%s','now'),strftime('%s','now'),?2,?3,?4,0.5,0.9,?5,?6,?7,10,?8)",
+        rusqlite::params![id, title, title, "alert", lat, lon, it.to_string(), country])?;
+      if tx.changes() > 0 { inserted.push(id); }
+    }
+  }
+  tx.commit()?;
+  Ok(inserted)
+}
@@ -0,0 +1,483 @@
+//! Files from field teams as a local source. GeoJSON features, KML
+//! placemarks and CSV rows become events, or alerts with `target: alert`;
+//! a CAP file becomes an alert through the CAP collector's parser. Every
+//! stored item is tagged `import:<name>` and also kept in `source_item`
+//! under its batch, so a whole file can be undone.
+use std::path::Path;
+use anyhow::{bail, Context, Result};
+use quick_xml::events::Event as XEvent;
+use quick_xml::Reader;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use crate::db::Db;
+use crate::export::geom;
+use crate::error::Error;
+use crate::normalize::{cap_severity, cap_word};
+use crate::runtime::Core;
+use super::cap_generic::{self, Persisted};
+use super::{after_commit, publish_alerts, upsert_event, NewEvent, TextChange};
+
+/// `source_rank` of imported events; see `rules::source_of_rank`.
+const RANK: i64 = 2;
+/// How long an imported alert without an expiry stays in force.
+const DEFAULT_ALERT_SECS: i64 = 24 * 3600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum ImportFormat { Geojson, Kml, Csv, Cap }
+
+impl ImportFormat {
+  /// From the extension: `.geojson` or `.json`, `.kml`, `.csv`, `.xml` or `.cap`.
+  pub fn from_path(path: &Path) -> Option<Self> {
+    match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+      "geojson" | "json" => Some(ImportFormat::Geojson),
+      "kml" => Some(ImportFormat::Kml),
+      "csv" => Some(ImportFormat::Csv),
+      "xml" | "cap" => Some(ImportFormat::Cap),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum ImportTarget { #[default] Event, Alert }
+
+/// The CSV column, or GeoJSON/KML property, that holds each field. Unset
+/// fields take the first of the usual names present (`title` or `name`,
+/// `lat` or `latitude`, ...), ignoring case.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(default)]
+pub struct ColumnMap {
+  /// Stable row id, so re-importing a corrected file updates its items.
+  /// Rows without one are keyed by their content.
+  pub id: Option<String>,
+  pub title: Option<String>,
+  pub summary: Option<String>,
+  pub class: Option<String>,
+  /// 0–1, or a CAP severity word.
+  pub severity: Option<String>,
+  pub lat: Option<String>,
+  pub lon: Option<String>,
+  /// When it happened: RFC 3339, `YYYY-MM-DD[ HH:MM[:SS]]`, or Unix
+  /// seconds or milliseconds.
+  pub time: Option<String>,
+  /// Alert expiry, in the same forms as `time`.
+  pub expires: Option<String>,
+}
+
+impl ColumnMap {
+  /// Maps `field` (`id`, `title`, `summary`, `class`, `severity`, `lat`,
+  /// `lon`, `time` or `expires`) to `column`.
+  pub fn set(&mut self, field: &str, column: String) -> Result<()> {
+    let slot = match field {
+      "id" => &mut self.id, "title" => &mut self.title, "summary" => &mut self.summary, "class" => &mut self.class,
+      "severity" => &mut self.severity, "lat" => &mut self.lat, "lon" => &mut self.lon, "time" => &mut self.time,
+      "expires" => &mut self.expires,
+      _ => return Err(Error::invalid(format!("unknown field `{field}`; expected id, title, summary, class, severity, lat, lon, time or expires")).into()),
+    };
+    *slot = Some(column);
+    Ok(())
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct ImportSpec {
+  /// Items are tagged `import:<name>`. Letters, digits, `-`, `_` and `.`.
+  pub name: String,
+  pub format: ImportFormat,
+  /// Ignored for CAP, which is always an alert.
+  #[serde(default)]
+  pub target: ImportTarget,
+  #[serde(default)]
+  pub columns: ColumnMap,
+}
+
+/// One imported file.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct ImportBatch {
+  pub id: String,
+  /// `import:<name>`.
+  pub source: String,
+  pub format: ImportFormat,
+  pub file: String,
+  pub created_at: i64,
+  pub events: usize,
+  pub alerts: usize,
+  /// Rows without a location or, for CAP, documents that carry no alert.
+  pub skipped: usize,
+  pub undone_at: Option<i64>,
+}
+
+const BATCH_COLUMNS: &str = "id,source,format,file,created_at,events,alerts,skipped,undone_at";
+
+fn batch_row(r: &rusqlite::Row) -> rusqlite::Result<ImportBatch> {
+  let format: String = r.get(2)?;
+  Ok(ImportBatch {
+    id: r.get(0)?, source: r.get(1)?,
+    format: serde_json::from_value(Value::String(format)).unwrap_or(ImportFormat::Geojson),
+    file: r.get(3)?, created_at: r.get(4)?, events: r.get(5)?, alerts: r.get(6)?, skipped: r.get(7)?, undone_at: r.get(8)?,
+  })
+}
+
+/// Reads and imports `path`; see `import`.
+pub fn import_file(core: &Core, spec: &ImportSpec, path: &Path) -> Result<ImportBatch> {
+  let body = std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+  let file = path.file_name().map_or_else(|| path.display().to_string(), |f| f.to_string_lossy().into_owned());
+  import(core, spec, &file, &body)
+}
+
+/// Stores the rows of `body` as one batch, then queues, evaluates rules
+/// for and streams the new items as a collector run would.
+pub fn import(core: &Core, spec: &ImportSpec, file: &str, body: &str) -> Result<ImportBatch> {
+  let name = spec.name.trim();
+  if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c)) {
+    return Err(Error::invalid(format!("import name `{name}` must be letters, digits, `-`, `_` or `.`")).into());
+  }
+  let now = chrono::Utc::now().timestamp();
+  let mut batch = ImportBatch {
+    id: uuid::Uuid::new_v4().to_string(), source: format!("import:{name}"), format: spec.format,
+    file: file.to_string(), created_at: now, events: 0, alerts: 0, skipped: 0, undone_at: None,
+  };
+  let (events, created, cancelled) = match spec.format {
+    ImportFormat::Cap => import_cap(core, &mut batch, body, now)?,
+    ImportFormat::Geojson => import_rows(core, spec, &mut batch, geojson_rows(body)?, now)?,
+    ImportFormat::Kml => import_rows(core, spec, &mut batch, kml_rows(body)?, now)?,
+    ImportFormat::Csv => import_rows(core, spec, &mut batch, csv_rows(body, &spec.columns)?, now)?,
+  };
+  after_commit(core, events);
+  publish_alerts(core, &batch.source, &created, &cancelled);
+  Ok(batch)
+}
+
+/// Events stored with their text change, alerts created and alerts cancelled.
+type Stored = (Vec<(String, f32, TextChange)>, Vec<String>, Vec<String>);
+
+/// Stores one CAP document under `import:<name>:<identifier>`, so it never
+/// replaces, and undo never deletes, a feed's alert of the same identifier.
+fn import_cap(core: &Core, batch: &mut ImportBatch, body: &str, now: i64) -> Result<Stored> {
+  // the CAP parser commits on its own; the batch records what it stored
+  let (mut created, mut cancelled) = (Vec::new(), Vec::new());
+  let stored = match cap_generic::persist(core, &batch.source, Some(&batch.source), body, now)? {
+    Persisted::Created(id) => { created.push(id.clone()); Some(id) }
+    Persisted::Updated(id) => Some(id),
+    Persisted::Cancelled(ids) => { cancelled = ids; None }
+    Persisted::Ignored => None,
+  };
+  let tx = core.db.conn.unchecked_transaction()?;
+  insert_batch(&tx, batch)?;
+  match stored {
+    Some(id) => {
+      let payload = Value::String(body.to_string());
+      let (title, lat, lon): (Option<String>, Option<f64>, Option<f64>) = tx.query_row(
+        "SELECT headline, (bbox_miny+bbox_maxy)/2, (bbox_minx+bbox_maxx)/2 FROM alert WHERE id=?1", [&id], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?;
+      insert_item(&tx, batch, &id, &payload, title.as_deref(), None, lat.zip(lon), None)?;
+      batch.alerts = 1;
+    }
+    None => batch.skipped = 1,
+  }
+  update_counts(&tx, batch)?;
+  tx.commit()?;
+  Ok((Vec::new(), created, cancelled))
+}
+
+fn import_rows(core: &Core, spec: &ImportSpec, batch: &mut ImportBatch, rows: Vec<Row>, now: i64) -> Result<Stored> {
+  let (mut events, mut created) = (Vec::new(), Vec::new());
+  let tx = core.db.conn.unchecked_transaction()?;
+  insert_batch(&tx, batch)?;
+  for row in rows {
+    let Some(item) = normalize(row, &spec.columns, now) else { batch.skipped += 1; continue };
+    let id = format!("{}:{}", batch.source, item.key);
+    let [minx, miny, maxx, maxy] = item.bbox;
+    let (lat, lon) = match &item.geometry { geojson::Value::Point(p) => (p[1], p[0]), _ => ((miny + maxy) / 2.0, (minx + maxx) / 2.0) };
+    insert_item(&tx, batch, &id, &item.payload, Some(&item.title), Some(&item.summary), Some((lat, lon)), Some(item.time))?;
+    match spec.target {
+      ImportTarget::Event => {
+        let change = upsert_event(&tx, &NewEvent {
+          id: &id, title: &item.title, summary: &item.summary, class: &item.class, severity: item.severity,
+          confidence: 0.5, lat, lon, geojson: None, source_rank: RANK, seen: item.time,
+        })?;
+        events.push((id, item.severity as f32, change));
+        batch.events += 1;
+      }
+      ImportTarget::Alert => {
+        let existed = tx.query_row("SELECT 1 FROM alert WHERE id=?1", [&id], |r| r.get::<_, i64>(0)).optional()?.is_some();
+        let area = serde_json::to_string(&geojson::Geometry::new(item.geometry.clone()))?;
+        let rowid: i64 = tx.query_row(
+          "INSERT INTO alert(id,source,headline,event,severity,urgency,certainty,onset,sent,expires,polygon_geojson,bbox_minx,bbox_miny,bbox_maxx,bbox_maxy,raw_json,last_seen,description)
+           VALUES (?1,?2,?3,?4,?5,'Unknown','Unknown',?6,?7,?8,?9,?10,?11,?12,?13,?14,?7,?15)
+           ON CONFLICT(id) DO UPDATE SET headline=excluded.headline,event=excluded.event,severity=excluded.severity,onset=excluded.onset,sent=excluded.sent,
+             expires=excluded.expires,polygon_geojson=excluded.polygon_geojson,bbox_minx=excluded.bbox_minx,bbox_miny=excluded.bbox_miny,
+             bbox_maxx=excluded.bbox_maxx,bbox_maxy=excluded.bbox_maxy,raw_json=excluded.raw_json,last_seen=excluded.last_seen,description=excluded.description
+           RETURNING rowid",
+          params![id, batch.source, item.title, item.class, cap_word(item.severity), item.time, now,
+            item.expires.unwrap_or(item.time + DEFAULT_ALERT_SECS), area, minx, miny, maxx, maxy, item.payload.to_string(), item.summary],
+          |r| r.get(0))?;
+        tx.execute("INSERT OR REPLACE INTO alert_rtree(rowid,minx,maxx,miny,maxy) VALUES (?1,?2,?3,?4,?5)", params![rowid, minx, maxx, miny, maxy])?;
+        if !existed { created.push(id); }
+        batch.alerts += 1;
+      }
+    }
+  }
+  update_counts(&tx, batch)?;
+  tx.commit()?;
+  Ok((events, created, Vec::new()))
+}
+
+/// Imported batches, newest first.
+pub fn batches(db: &Db) -> Result<Vec<ImportBatch>> {
+  let mut stmt = db.conn.prepare(&format!("SELECT {BATCH_COLUMNS} FROM import_batch ORDER BY created_at DESC, rowid DESC"))?;
+  let rows = stmt.query_map([], batch_row)?;
+  Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Deletes every event and alert that batch `id` stored, with their labels,
+/// rule matches and translations. Items a later import of the same rows
+/// took over belong to that batch and stay.
+pub fn undo(core: &Core, id: &str) -> Result<ImportBatch> {
+  let db = &core.db;
+  let mut batch = db.conn.query_row(&format!("SELECT {BATCH_COLUMNS} FROM import_batch WHERE id=?1"), [id], batch_row)
+    .optional()?.ok_or_else(|| Error::not_found(format!("import batch {id}")))?;
+  if batch.undone_at.is_some() { return Err(Error::invalid(format!("import batch {id} is already undone")).into()); }
+  let now = chrono::Utc::now().timestamp();
+  let tx = db.conn.unchecked_transaction()?;
+  let alerts: Vec<String> = {
+    let mut stmt = tx.prepare("SELECT a.id FROM alert a JOIN source_item s ON s.id = a.id WHERE s.batch_id=?1")?;
+    let rows = stmt.query_map([id], |r| r.get(0))?;
+    rows.collect::<rusqlite::Result<_>>()?
+  };
+  let items = "SELECT id FROM source_item WHERE batch_id=?1";
+  tx.execute(&format!("DELETE FROM alert_rtree WHERE rowid IN (SELECT rowid FROM alert WHERE id IN ({items}))"), [id])?;
+  tx.execute(&format!("DELETE FROM alert WHERE id IN ({items})"), [id])?;
+  tx.execute(&format!("DELETE FROM alert_version WHERE alert_id IN ({items})"), [id])?;
+  tx.execute(&format!("DELETE FROM event WHERE id IN ({items})"), [id])?;
+  tx.execute(&format!("DELETE FROM rule_match WHERE item_id IN ({items})"), [id])?;
+  tx.execute(&format!("DELETE FROM item_translation WHERE item_id IN ({items})"), [id])?;
+  tx.execute("DELETE FROM source_item WHERE batch_id=?1", [id])?;
+  tx.execute("UPDATE import_batch SET undone_at=?2 WHERE id=?1", params![id, now])?;
+  tx.commit()?;
+  // windows drop cancelled alerts from the map; removed events go on the next query
+  core.notify(crate::stream::Notice::AlertsCancelled { source: &batch.source, ids: &alerts });
+  batch.undone_at = Some(now);
+  Ok(batch)
+}
+
+fn insert_batch(tx: &rusqlite::Connection, b: &ImportBatch) -> rusqlite::Result<()> {
+  let format = serde_json::to_value(b.format).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+  tx.execute("INSERT INTO import_batch(id,source,format,file,created_at) VALUES (?1,?2,?3,?4,?5)",
+    params![b.id, b.source, format, b.file, b.created_at])?;
+  Ok(())
+}
+
+fn update_counts(tx: &rusqlite::Connection, b: &ImportBatch) -> rusqlite::Result<()> {
+  tx.execute("UPDATE import_batch SET events=?2, alerts=?3, skipped=?4 WHERE id=?1", params![b.id, b.events, b.alerts, b.skipped])?;
+  Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn insert_item(tx: &rusqlite::Connection, b: &ImportBatch, id: &str, payload: &Value, title: Option<&str>, body: Option<&str>,
+  at: Option<(f64, f64)>, occurred_at: Option<i64>) -> rusqlite::Result<()> {
+  let text = payload.to_string();
+  let hash = format!("{:x}", Sha256::digest(text.as_bytes()));
+  let (lat, lon) = at.unzip();
+  tx.execute("INSERT OR REPLACE INTO source_item(id,source,fetched_at,seen_at,payload_json,title,body,lat,lon,occurred_at,hash,batch_id)
+    VALUES (?1,?2,?3,?3,?4,?5,?6,?7,?8,?9,?10,?11)",
+    params![id, b.source, b.created_at, text, title, body, lat, lon, occurred_at, hash, b.id])?;
+  Ok(())
+}
+
+/// A feature, placemark or CSV row before mapping; `payload` is what
+/// `source_item` keeps.
+struct Row { props: Map<String, Value>, geometry: Option<geojson::Value>, payload: Value }
+
+/// A row mapped onto the fields events and alerts share.
+struct Item { key: String, title: String, summary: String, class: String, severity: f64, time: i64, expires: Option<i64>, geometry: geojson::Value, bbox: [f64; 4], payload: Value }
+
+/// `None` for rows without a geometry or coordinates, or with positions
+/// that are not a longitude and latitude.
+fn normalize(row: Row, map: &ColumnMap, now: i64) -> Option<Item> {
+  let get = |mapped: &Option<String>, defaults: &[&str]| field(&row.props, mapped.as_deref(), defaults);
+  let text = |v: Option<&Value>| v.map(|v| v.as_str().map_or_else(|| v.to_string(), str::to_string));
+  let number = |v: Option<&Value>| v.and_then(|v| v.as_f64().or_else(|| v.as_str()?.trim().parse().ok()));
+  let geometry = row.geometry.or_else(|| {
+    let lat = number(get(&map.lat, &["lat", "latitude", "y"]))?;
+    let lon = number(get(&map.lon, &["lon", "lng", "long", "longitude", "x"]))?;
+    Some(geojson::Value::Point(vec![lon, lat]))
+  })?;
+  let bbox = geom::lon_lat_envelope(&geometry)?;
+  let title = text(get(&map.title, &["title", "name", "headline"])).unwrap_or_else(|| "Imported report".into());
+  let key = text(get(&map.id, &["id", "identifier", "uid"]))
+    .unwrap_or_else(|| format!("{:x}", Sha256::digest(row.payload.to_string().as_bytes()))[..16].to_string());
+  Some(Item {
+    key,
+    summary: text(get(&map.summary, &["summary", "description", "details"])).unwrap_or_else(|| title.clone()),
+    title,
+    class: text(get(&map.class, &["class", "type", "category", "event"])).map_or_else(|| "other".into(), |c| c.to_lowercase()),
+    severity: get(&map.severity, &["severity"]).and_then(parse_severity).unwrap_or(0.5),
+    time: get(&map.time, &["time", "date", "datetime", "timestamp", "when", "begin", "onset"]).and_then(parse_time).unwrap_or(now),
+    expires: get(&map.expires, &["expires", "end"]).and_then(parse_time),
+    geometry,
+    bbox,
+    payload: row.payload,
+  })
+}
+
+/// The non-empty value of the mapped property, or of the first default
+/// present; names compare without case.
+fn field<'a>(props: &'a Map<String, Value>, mapped: Option<&str>, defaults: &[&str]) -> Option<&'a Value> {
+  let find = |k: &str| props.iter().find(|(p, _)| p.eq_ignore_ascii_case(k)).map(|(_, v)| v)
+    .filter(|v| !v.is_null() && v.as_str().is_none_or(|s| !s.trim().is_empty()));
+  match mapped { Some(k) => find(k), None => defaults.iter().find_map(|k| find(k)) }
+}
+
+/// A 0–1 number, or a CAP severity word in any case.
+fn parse_severity(v: &Value) -> Option<f64> {
+  if let Some(n) = v.as_f64().or_else(|| v.as_str()?.trim().parse().ok()) { return Some(n.clamp(0.0, 1.0)); }
+  let w = v.as_str()?.trim().to_lowercase();
+  let word: String = w.chars().take(1).flat_map(char::to_uppercase).chain(w.chars().skip(1)).collect();
+  Some(cap_severity(&word)).filter(|s| *s > 0.0)
+}
+
+fn parse_time(v: &Value) -> Option<i64> {
+  // seconds, or milliseconds as USGS and spreadsheets export them
+  let epoch = |n: f64| if n.abs() >= 1e11 { (n / 1000.0) as i64 } else { n as i64 };
+  if let Some(n) = v.as_f64() { return Some(epoch(n)); }
+  let s = v.as_str()?.trim();
+  if let Ok(n) = s.parse::<f64>() { return Some(epoch(n)); }
+  if let Ok(t) = chrono::DateTime::parse_from_rfc3339(s) { return Some(t.timestamp()); }
+  ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"].iter()
+    .find_map(|f| chrono::NaiveDateTime::parse_from_str(s, f).ok())
+    .or_else(|| chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0))
+    .map(|t| t.and_utc().timestamp())
+}
+
+fn geojson_rows(body: &str) -> Result<Vec<Row>> {
+  let v: Value = serde_json::from_str(body).context("parse GeoJSON")?;
+  let features = match v.get("type").and_then(Value::as_str) {
+    Some("FeatureCollection") => v.get("features").and_then(Value::as_array).cloned().unwrap_or_default(),
+    Some("Feature") => vec![v],
+    Some(_) => vec![json!({ "type": "Feature", "geometry": v, "properties": {} })],
+    None => bail!("not GeoJSON: no `type`"),
+  };
+  Ok(features.into_iter().map(|f| {
+    let mut props = f.get("properties").and_then(Value::as_object).cloned().unwrap_or_default();
+    if let Some(id) = f.get("id") { props.entry("id").or_insert_with(|| id.clone()); }
+    let geometry = f.get("geometry").filter(|g| !g.is_null())
+      .and_then(|g| geojson::Geometry::from_json_value(g.clone()).ok()).map(|g| g.value);
+    Row { props, geometry, payload: f }
+  }).collect())
+}
+
+/// A reader over CSV `text` as spreadsheets export it: a leading BOM
+/// dropped, cells trimmed and rows of any length.
+pub(crate) fn csv_reader(text: &str) -> csv::Reader<&[u8]> {
+  let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+  csv::ReaderBuilder::new().flexible(true).trim(csv::Trim::All).from_reader(text.as_bytes())
+}
+
+/// Index of the first header named like any of `names`, without case.
+pub(crate) fn column(headers: &csv::StringRecord, names: &[&str]) -> Option<usize> {
+  headers.iter().position(|h| names.iter().any(|n| h.eq_ignore_ascii_case(n)))
+}
+
+fn csv_rows(body: &str, map: &ColumnMap) -> Result<Vec<Row>> {
+  let mut reader = csv_reader(body);
+  let headers = reader.headers()?.clone();
+  let mapped = [&map.id, &map.title, &map.summary, &map.class, &map.severity, &map.lat, &map.lon, &map.time, &map.expires];
+  if let Some(col) = mapped.into_iter().flatten().find(|c| column(&headers, &[c.as_str()]).is_none()) {
+    return Err(Error::invalid(format!("column `{col}` is not in the file, which has {}", headers.iter().collect::<Vec<_>>().join(", "))).into());
+  }
+  reader.records().map(|rec| {
+    let props: Map<String, Value> = headers.iter().zip(rec?.iter()).map(|(h, v)| (h.to_string(), json!(v))).collect();
+    let payload = json!({ "type": "Feature", "geometry": null, "properties": props });
+    Ok(Row { props, geometry: None, payload })
+  }).collect()
+}
+
+/// Placemarks with their name, description, `ExtendedData`, time and
+/// geometry; several geometries become one multi-geometry.
+fn kml_rows(body: &str) -> Result<Vec<Row>> {
+  let mut reader = Reader::from_str(body);
+  reader.config_mut().trim_text(true);
+  let mut buf = Vec::new();
+  let mut path: Vec<String> = Vec::new();
+  let mut rows = Vec::new();
+  let mut placemark: Option<(Map<String, Value>, Vec<geojson::Value>)> = None;
+  let mut data_name = String::new();
+  loop {
+    let text = match reader.read_event_into(&mut buf).context("parse KML")? {
+      XEvent::Start(e) => {
+        let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+        if name == "Placemark" { placemark = Some(Default::default()); }
+        if name == "Data" || name == "SimpleData" {
+          data_name = e.try_get_attribute("name")?.map(|a| a.unescape_value().map(|v| v.into_owned())).transpose()?.unwrap_or_default();
+        }
+        path.push(name);
+        None
+      }
+      XEvent::End(_) => {
+        if path.pop().as_deref() == Some("Placemark") {
+          if let Some((props, parts)) = placemark.take() {
+            let geometry = combine(parts);
+            let payload = json!({ "type": "Feature", "properties": props, "geometry": geometry.clone().map(geojson::Geometry::new) });
+            rows.push(Row { props, geometry, payload });
+          }
+        }
+        None
+      }
+      XEvent::Text(t) => Some(t.unescape()?.into_owned()),
+      XEvent::CData(c) => Some(String::from_utf8_lossy(&c.into_inner()).into_owned()),
+      XEvent::Eof => break,
+      _ => None,
+    };
+    buf.clear();
+    let (Some(text), Some((props, parts))) = (text, placemark.as_mut()) else { continue };
+    let within = |e: &str| path.iter().any(|p| p == e);
+    let parent = path.len().checked_sub(2).map(|i| path[i].as_str());
+    match path.last().map(String::as_str) {
+      Some("coordinates") => {
+        let ps: Vec<Vec<f64>> = text.split_whitespace().filter_map(|t| {
+          let mut it = t.split(',').map(|n| n.parse::<f64>());
+          Some(vec![it.next()?.ok()?, it.next()?.ok()?])
+        }).collect();
+        if within("Point") {
+          parts.extend(ps.into_iter().next().map(geojson::Value::Point));
+        } else if within("innerBoundaryIs") {
+          if let Some(geojson::Value::Polygon(rings)) = parts.last_mut() { rings.push(ps); }
+        } else if within("LinearRing") {
+          parts.push(geojson::Value::Polygon(vec![ps]));
+        } else if within("LineString") {
+          parts.push(geojson::Value::LineString(ps));
+        }
+      }
+      Some(k @ ("name" | "description")) if parent == Some("Placemark") => { props.insert(k.into(), json!(text)); }
+      Some("value") if parent == Some("Data") => { props.insert(data_name.clone(), json!(text)); }
+      Some("SimpleData") => { props.insert(data_name.clone(), json!(text)); }
+      Some(k @ ("when" | "begin" | "end")) => { props.entry(k).or_insert(json!(text)); }
+      _ => {}
+    }
+  }
+  Ok(rows)
+}
+
+fn combine(mut parts: Vec<geojson::Value>) -> Option<geojson::Value> {
+  use geojson::Value as G;
+  if parts.len() <= 1 { return parts.pop(); }
+  let all = |f: fn(&G) -> bool| parts.iter().all(f);
+  Some(if all(|p| matches!(p, G::Point(_))) {
+    G::MultiPoint(parts.into_iter().filter_map(|p| if let G::Point(p) = p { Some(p) } else { None }).collect())
+  } else if all(|p| matches!(p, G::LineString(_))) {
+    G::MultiLineString(parts.into_iter().filter_map(|p| if let G::LineString(l) = p { Some(l) } else { None }).collect())
+  } else if all(|p| matches!(p, G::Polygon(_))) {
+    G::MultiPolygon(parts.into_iter().filter_map(|p| if let G::Polygon(r) = p { Some(r) } else { None }).collect())
+  } else {
+    G::GeometryCollection(parts.into_iter().map(geojson::Geometry::new).collect())
+  })
+}
@@ -0,0 +1,169 @@
+use rusqlite::{params, OptionalExtension};
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+use tracing::warn;
+use crate::runtime::Core;
+use crate::stream::{self, Notice};
+
+pub mod gdacs; pub mod usgs; pub mod eonet; pub mod emsc_ws;
+pub mod nws_alerts; pub mod cap_generic; pub mod import; pub mod drop_folder; pub mod tape;
+
+/// How an upsert changes an event's text, which decides whether it needs (re)labelling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextChange { New, Changed, Unchanged }
+
+pub fn text_change(conn: &rusqlite::Connection, id: &str, title: &str, summary: &str) -> rusqlite::Result<TextChange> {
+  let prev: Option<(Option<String>, Option<String>)> = conn.query_row(
+    "SELECT title, summary FROM event WHERE id=?1", [id], |r| Ok((r.get(0)?, r.get(1)?))).optional()?;
+  Ok(match prev {
+    None => TextChange::New,
+    Some((t, s)) if t.as_deref() == Some(title) && s.as_deref() == Some(summary) => TextChange::Unchanged,
+    Some(_) => TextChange::Changed,
+  })
+}
+
+/// An event as a collector or import has normalised it.
+pub struct NewEvent<'a> {
+  pub id: &'a str,
+  pub title: &'a str,
+  pub summary: &'a str,
+  pub class: &'a str,
+  pub severity: f64,
+  pub confidence: f64,
+  pub lat: f64,
+  pub lon: f64,
+  /// The feed item, if the event keeps it; imports keep theirs in `source_item`.
+  pub geojson: Option<String>,
+  pub source_rank: i64,
+  /// First seen when new, last seen either way.
+  pub seen: i64,
+}
+
+/// Inserts `e` or refreshes the stored event, keeping its first seen.
+pub fn upsert_event(conn: &rusqlite::Connection, e: &NewEvent) -> rusqlite::Result<TextChange> {
+  let change = text_change(conn, e.id, e.title, e.summary)?;
+  conn.execute("INSERT INTO event(id,first_seen,last_seen,title,summary,class,severity,confidence,lat,lon,geojson,source_rank)
+    VALUES (?1,?2,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11)
+    ON CONFLICT(id) DO UPDATE SET last_seen=excluded.last_seen,title=excluded.title,summary=excluded.summary,class=excluded.class,severity=excluded.severity,
+      confidence=excluded.confidence,lat=excluded.lat,lon=excluded.lon,geojson=excluded.geojson,source_rank=excluded.source_rank",
+    params![e.id, e.seen, e.title, e.summary, e.class, e.severity, e.confidence, e.lat, e.lon, e.geojson, e.source_rank])?;
+  Ok(change)
+}
+
+/// Runs after a collector commits a batch: queues (re)labelling, evaluates
+/// rules and pushes the changes to subscribed windows. Returns the batch size.
+pub fn after_commit(core: &Core, batch: Vec<(String, f32, TextChange)>) -> usize {
+  let n = batch.len();
+  let (mut created, mut updated) = (Vec::new(), Vec::new());
+  for (id, severity, change) in batch {
+    match change {
+      TextChange::New => created.push(id.clone()),
+      TextChange::Changed => updated.push(id.clone()),
+      TextChange::Unchanged => continue,
+    }
+    crate::ai::enqueue(core, &id, severity, change == TextChange::Changed);
+  }
+  publish_events(core, &created, &updated);
+  n
+}
+
+/// Places changed events and assesses their exposure, evaluates rules for
+/// them and streams them to the UI.
+pub fn publish_events(core: &Core, created: &[String], updated: &[String]) {
+  let ids: Vec<String> = created.iter().chain(updated).cloned().collect();
+  if let Err(e) = crate::geocode::annotate(&core.db.conn, "event", &ids, &core.settings.gazetteer) { warn!("gazetteer: {e}"); }
+  if let Err(e) = crate::exposure::assess(&core.db.conn, "event", &ids, &core.settings.exposure) { warn!("exposure: {e}"); }
+  let matches = crate::rules::evaluate(&core.db.conn, "event", &ids).unwrap_or_else(|e| { warn!("rules: {e}"); Vec::new() });
+  core.notify(Notice::Events { created: true, ids: created });
+  core.notify(Notice::Events { created: false, ids: updated });
+  core.notify(Notice::RulesMatched(&matches));
+}
+
+/// Alert counterpart of `publish_events`.
+pub fn publish_alerts(core: &Core, source: &str, created: &[String], cancelled: &[String]) {
+  if let Err(e) = crate::geocode::annotate(&core.db.conn, "alert", created, &core.settings.gazetteer) { warn!("gazetteer: {e}"); }
+  if let Err(e) = crate::exposure::assess(&core.db.conn, "alert", created, &core.settings.exposure) { warn!("exposure: {e}"); }
+  let matches = crate::rules::evaluate(&core.db.conn, "alert", created).unwrap_or_else(|e| { warn!("rules: {e}"); Vec::new() });
+  core.notify(Notice::AlertsCreated(created));
+  core.notify(Notice::AlertsCancelled { source, ids: cancelled });
+  core.notify(Notice::RulesMatched(&matches));
+}
+
+/// Marks the alerts a `Cancel` message references as cancelled and returns
+/// the ids that were still active.
+pub fn cancel_alerts(conn: &rusqlite::Connection, refs: &[String], now: i64) -> rusqlite::Result<Vec<String>> {
+  let mut ids = Vec::new();
+  for r in refs {
+    let id: Option<String> = conn.query_row(
+      "UPDATE alert SET cancelled_at=?2 WHERE id=?1 AND cancelled_at IS NULL RETURNING id", params![r, now], |row| row.get(0))
+      .optional()?;
+    ids.extend(id);
+  }
+  Ok(ids)
+}
+
+/// Collector names, as reported in `source_status`.
+pub const SOURCES: &[&str] = &["gdacs", "usgs", "eonet", "emsc", "nws", "cap", "drop"];
+
+/// Fetches `source` once, or for `drop` scans its folder once. EMSC is a
+/// stream and has no single fetch.
+pub async fn run_once(core: &Core, source: &str) -> anyhow::Result<usize> {
+  let res = match source {
+    "gdacs" => gdacs::run(core).await,
+    "usgs" => usgs::run(core).await,
+    "eonet" => eonet::run(core).await,
+    "nws" => nws_alerts::run(core).await,
+    "cap" => cap_generic::run(core).await,
+    "drop" => drop_folder::run(core),
+    "emsc" => anyhow::bail!("emsc is a websocket stream; import a saved message instead"),
+    _ => anyhow::bail!("unknown source `{source}`; expected one of {}", SOURCES.join(", ")),
+  };
+  core.tape.finish(source);
+  report(core, source, res)
+}
+
+/// GETs `url` for `source`'s run: recorded to the tape when recording,
+/// and read from it when replaying.
+pub async fn fetch(core: &Core, source: &str, url: &str) -> anyhow::Result<tape::Response> {
+  if core.tape.replaying() { return core.tape.take(source, url); }
+  let resp = reqwest::get(url).await?;
+  let resp = tape::Response { url: url.to_string(), status: resp.status().as_u16(), body: resp.text().await? };
+  core.tape.capture(source, &resp);
+  Ok(resp)
+}
+
+/// Stores a response body saved from `source` as if it had just been
+/// fetched.
+pub fn ingest(core: &Core, source: &str, body: &str) -> anyhow::Result<usize> {
+  match source {
+    "gdacs" => gdacs::ingest(core, body),
+    "usgs" => usgs::ingest(core, body),
+    "eonet" => eonet::ingest(core, body),
+    "emsc" => Ok(emsc_ws::ingest(core, body)?.unwrap_or(0)),
+    "nws" => nws_alerts::ingest(core, body),
+    "cap" => cap_generic::ingest(core, body),
+    "drop" => anyhow::bail!("drop is a folder; import each file with the source it came from"),
+    _ => anyhow::bail!("unknown source `{source}`; expected one of {}", SOURCES.join(", ")),
+  }
+}
+
+/// Logs a collector run and reports it as `source_status`.
+fn report(core: &Core, source: &str, res: anyhow::Result<usize>) -> anyhow::Result<usize> {
+  if let Err(e) = &res { warn!("{source}: {e}"); }
+  stream::source_status(core, source, &res);
+  res
+}
+
+pub fn spawn_collectors(core: Arc<Core>) {
+  let h = core.clone(); tokio::spawn(async move { loop { let _ = run_once(&h, "gdacs").await; sleep(Duration::from_secs(90)).await; }});
+  let h = core.clone(); tokio::spawn(async move { loop { let _ = run_once(&h, "usgs").await; sleep(Duration::from_secs(60)).await; }});
+  let h = core.clone(); tokio::spawn(async move { loop { let _ = run_once(&h, "eonet").await; sleep(Duration::from_secs(180)).await; }});
+  let h = core.clone(); tokio::spawn(async move { let _ = report(&h, "emsc", emsc_ws::run(&h).await); });
+  let h = core.clone(); tokio::spawn(async move { loop { let _ = run_once(&h, "nws").await; sleep(Duration::from_secs(75)).await; }});
+  if core.settings.drop_folder.path.is_some() {
+    let every = Duration::from_secs(core.settings.drop_folder.poll_secs.max(1));
+    let h = core.clone(); tokio::spawn(async move { loop { let _ = run_once(&h, "drop").await; sleep(every).await; }});
+  }
+  // optional CAP XML feeds after configuring URLs
+  // let h = core.clone(); tokio::spawn(async move { loop { let _ = run_once(&h, "cap").await; sleep(Duration::from_secs(180)).await; }});
+}
@@ -0,0 +1,177 @@
+use anyhow::{Result, Context};
+use chrono::{DateTime, Utc};
+use geojson::{feature::Id, Feature, FeatureCollection, GeoJson, Value};
+use quick_xml::events::Event as XEvent;
+use quick_xml::Reader;
+use rusqlite::{params, OptionalExtension};
+use crate::runtime::Core;
+
+fn to_epoch(ts: Option<&str>) -> Option<i64> {
+  ts.and_then(|s| DateTime::parse_from_rfc3339(s).ok()).map(|dt| dt.with_timezone(&Utc).timestamp())
+}
+
+fn bbox_of(value: &Value) -> Option<(f64,f64,f64,f64)> {
+  match value {
+    Value::Polygon(poly) => {
+      let coords = &poly[0];
+      let (mut minx, mut miny, mut maxx, mut maxy) = (f64::INFINITY,f64::INFINITY,f64::NEG_INFINITY,f64::NEG_INFINITY);
+      for c in coords {
+        if c.len() >= 2 {
+          let (x,y) = (c[0], c[1]);
+          minx=minx.min(x); miny=miny.min(y); maxx=maxx.max(x); maxy=maxy.max(y);
+        }
+      }
+      Some((minx,miny,maxx,maxy))
+    }
+    Value::MultiPolygon(mpoly) => {
+      let mut minx=f64::INFINITY; let mut miny=f64::INFINITY; let mut maxx=f64::NEG_INFINITY; let mut maxy=f64::NEG_INFINITY;
+      for poly in mpoly {
+        for c in &poly[0] {
+          if c.len()>=2 { let (x,y)=(c[0],c[1]); minx=minx.min(x); miny=miny.min(y); maxx=maxx.max(x); maxy=maxy.max(y); }
+        }
+      }
+      if minx.is_finite() { Some((minx,miny,maxx,maxy)) } else { None }
+    }
+    _ => None
+  }
+}
+
+pub async fn run(core: &Core) -> Result<usize> {
+  let url = "https://api.weather.gov/alerts/active?limit=200";
+  ingest(core, &super::fetch(core, "nws", url).await?.error_for_status()?.body)
+}
+
+/// Stores an NWS `alerts/active` response, as GeoJSON or as the ATOM feed
+/// whose entries carry the alert in `cap:` elements.
+pub fn ingest(core: &Core, body: &str) -> Result<usize> {
+  let features = if body.trim_start().starts_with('<') {
+    atom_features(body)?
+  } else {
+    match body.parse::<GeoJson>().context("parse nws geojson")? {
+      GeoJson::FeatureCollection(FeatureCollection { features, .. }) => features,
+      _ => return Ok(0),
+    }
+  };
+  let db = &core.db;
+  let now = chrono::Utc::now().timestamp();
+  let n = features.len();
+  let (mut created, mut cancelled) = (Vec::new(), Vec::new());
+  let tx = db.conn.get()?.unchecked_transaction()?;
+  for f in features {
+    match persist_feature(&tx, f, now)? {
+      Persisted::Created(id) => created.push(id),
+      Persisted::Updated => {}
+      Persisted::Cancelled(ids) => cancelled.extend(ids),
+    }
+  }
+  tx.commit()?;
+  super::publish_alerts(core, "nws", &created, &cancelled);
+  Ok(n)
+}
+
+/// ATOM entries as the features of the GeoJSON API. `cap:polygon` is
+/// "lat,lon" pairs; `cap:references` is "sender,identifier,sent" triples.
+fn atom_features(body: &str) -> Result<Vec<Feature>> {
+  let mut reader = Reader::from_str(body);
+  reader.config_mut().trim_text(true);
+  let mut buf = Vec::new();
+  let mut features = Vec::new();
+  let mut entry: Option<serde_json::Map<String, serde_json::Value>> = None;
+  let mut elem = String::new();
+  loop {
+    match reader.read_event_into(&mut buf).context("parse nws atom")? {
+      XEvent::Start(e) => {
+        elem = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+        if elem == "entry" { entry = Some(Default::default()); }
+      }
+      XEvent::End(e) => {
+        if e.local_name().as_ref() == b"entry" { features.extend(entry.take().map(atom_feature)); }
+        elem.clear();
+      }
+      XEvent::Text(t) => if let Some(props) = entry.as_mut() {
+        let v = t.unescape()?.into_owned();
+        let key = match elem.as_str() {
+          "title" => "headline", "summary" => "description", "msgType" => "messageType",
+          k @ ("id" | "event" | "sent" | "effective" | "onset" | "expires" | "severity" | "urgency" | "certainty"
+            | "areaDesc" | "instruction" | "polygon" | "references") => k,
+          _ => continue,
+        };
+        props.insert(key.into(), serde_json::Value::String(v));
+      },
+      XEvent::Eof => break,
+      _ => {}
+    }
+    buf.clear();
+  }
+  Ok(features)
+}
+
+fn atom_feature(mut props: serde_json::Map<String, serde_json::Value>) -> Feature {
+  let text = |v: Option<serde_json::Value>| v.and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+  let id = text(props.remove("id"));
+  let ring: Vec<Vec<f64>> = text(props.remove("polygon")).split_whitespace().filter_map(|p| {
+    let (lat, lon) = p.split_once(',')?;
+    Some(vec![lon.parse().ok()?, lat.parse().ok()?])
+  }).collect();
+  let refs: Vec<serde_json::Value> = text(props.remove("references")).split_whitespace()
+    .filter_map(|r| r.split(',').nth(1)).map(|id| serde_json::json!({ "@id": id })).collect();
+  if !refs.is_empty() { props.insert("references".into(), refs.into()); }
+  Feature {
+    id: (!id.is_empty()).then_some(Id::String(id)),
+    geometry: (ring.len() >= 4).then(|| geojson::Geometry::new(Value::Polygon(vec![ring]))),
+    properties: Some(props),
+    bbox: None,
+    foreign_members: None,
+  }
+}
+
+enum Persisted { Created(String), Updated, Cancelled(Vec<String>) }
+
+fn persist_feature(conn: &rusqlite::Connection, f: Feature, now: i64) -> Result<Persisted> {
+  let id = match &f.id {
+    Some(Id::String(s)) => s.clone(),
+    Some(Id::Number(n)) => n.to_string(),
+    None => uuid::Uuid::new_v4().to_string(),
+  };
+  let props = f.properties.clone().unwrap_or_default();
+
+  if props.get("messageType").and_then(|v| v.as_str()) == Some("Cancel") {
+    // references carry the cancelled alert's URL, which is also its feature id
+    let refs: Vec<String> = props.get("references").and_then(|v| v.as_array()).into_iter().flatten()
+      .filter_map(|r| r.get("@id").and_then(|v| v.as_str()).map(str::to_string))
+      .collect();
+    return Ok(Persisted::Cancelled(super::cancel_alerts(conn, &refs, now)?));
+  }
+
+  let headline = props.get("headline").and_then(|v| v.as_str()).unwrap_or("").to_string();
+  let event = props.get("event").and_then(|v| v.as_str()).unwrap_or("").to_string();
+  let severity = props.get("severity").and_then(|v| v.as_str()).unwrap_or("Unknown").to_string();
+  let urgency = props.get("urgency").and_then(|v| v.as_str()).unwrap_or("Unknown").to_string();
+  let certainty = props.get("certainty").and_then(|v| v.as_str()).unwrap_or("Unknown").to_string();
+  let description = props.get("description").and_then(|v| v.as_str()).map(str::to_string);
+  let instruction = props.get("instruction").and_then(|v| v.as_str()).map(str::to_string);
+  let area = props.get("areaDesc").and_then(|v| v.as_str()).unwrap_or("").to_string();
+  let onset = to_epoch(props.get("effective").and_then(|v| v.as_str())).or_else(|| to_epoch(props.get("onset").and_then(|v| v.as_str()))).unwrap_or(now);
+  let sent = to_epoch(props.get("sent").and_then(|v| v.as_str())).unwrap_or(now);
+  let expires = to_epoch(props.get("expires").and_then(|v| v.as_str())).unwrap_or(now + 3600);
+
+  let geojson_text = serde_json::to_string(&f.geometry).ok().unwrap_or("null".into());
+  let (minx, miny, maxx, maxy) = f.geometry.as_ref()
+    .and_then(|g| bbox_of(&g.value))
+    .unwrap_or((-180.0,-90.0,180.0,90.0));
+
+  let raw_json = serde_json::to_string(&f).unwrap_or_default();
+  let existed: Option<i64> = conn.query_row("SELECT 1 FROM alert WHERE id=?1", params![id], |r| r.get(0)).optional()?;
+
+  conn.execute(
+    "INSERT OR REPLACE INTO alert(id, source, headline, event, severity, urgency, certainty, onset, sent, expires, area_desc, polygon_geojson, bbox_minx, bbox_miny, bbox_maxx, bbox_maxy, raw_json, last_seen, language, description, instruction)
+     VALUES (?1,'nws',?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15,?16,?17,'en-US',?18,?19)",
+    params![id, headline, event, severity, urgency, certainty, onset, sent, expires, area, geojson_text, minx, miny, maxx, maxy, raw_json, now, description, instruction]
+  )?;
+
+  let rowid: i64 = conn.query_row("SELECT rowid FROM alert WHERE id=?1", params![id], |r| r.get(0)).unwrap();
+  conn.execute("INSERT OR REPLACE INTO alert_rtree(rowid,minx,maxx,miny,maxy) VALUES (?1,?2,?3,?4,?5)",
+    params![rowid, minx, maxx, miny, maxy])?;
+
+  Ok(if existed.is_some() { Persisted::Updated } else { Persisted::Created(id) })
+}
@@ -0,0 +1,190 @@
+//! Recording and replay of collector traffic. With `tape.record` set, every
+//! HTTP response and websocket frame the collectors receive is appended to
+//! `<tape dir>/<start time>.jsonl`, one line per collector run. `replay`
+//! feeds such a tape back through the collectors' own `run` code, as fast
+//! as recorded or faster, so a bug or an exercise can be rerun offline.
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::time::{sleep_until, Duration, Instant};
+use tracing::warn;
+use crate::runtime::Core;
+use crate::settings::TapeSettings;
+
+/// One HTTP response a collector received.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Response {
+  pub url: String,
+  pub status: u16,
+  pub body: String,
+}
+
+impl Response {
+  pub fn error_for_status(self) -> Result<Self> {
+    if !(200..300).contains(&self.status) { bail!("HTTP {} from {}", self.status, self.url); }
+    Ok(self)
+  }
+}
+
+/// One line of a tape: the responses of one collector run, or websocket
+/// frames, which are written as they arrive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Run {
+  /// Unix milliseconds.
+  pub at: i64,
+  pub source: String,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub responses: Vec<Response>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub frames: Vec<String>,
+}
+
+/// The collectors' recorder and, during `replay`, their source of responses.
+pub struct Tape {
+  /// Set when recording; the file is created on the first write.
+  path: Option<PathBuf>,
+  file: Mutex<Option<File>>,
+  /// Responses of each source's run in progress.
+  pending: Mutex<HashMap<String, Vec<Response>>>,
+  replay: Mutex<Option<Replay>>,
+}
+
+#[derive(Default)]
+struct Replay {
+  /// Responses for each source's next run.
+  responses: HashMap<String, VecDeque<Response>>,
+  /// Frames for streaming sources, taken by their `run`.
+  frames: HashMap<String, mpsc::UnboundedReceiver<String>>,
+}
+
+impl Tape {
+  pub fn new(settings: &TapeSettings, data_dir: &Path) -> Tape {
+    let path = settings.record.then(|| {
+      let dir = settings.dir.clone().unwrap_or_else(|| data_dir.join("tapes"));
+      dir.join(format!("{}.jsonl", chrono::Utc::now().format("%Y%m%dT%H%M%SZ")))
+    });
+    Tape { path, file: Mutex::new(None), pending: Mutex::new(HashMap::new()), replay: Mutex::new(None) }
+  }
+
+  /// The tape being recorded to, if any.
+  pub fn path(&self) -> Option<&Path> { self.path.as_deref() }
+
+  pub fn replaying(&self) -> bool { self.replay.lock().unwrap().is_some() }
+
+  /// Adds `resp` to the run `source` has in progress.
+  pub(crate) fn capture(&self, source: &str, resp: &Response) {
+    if self.path.is_none() || self.replaying() { return; }
+    self.pending.lock().unwrap().entry(source.to_string()).or_default().push(resp.clone());
+  }
+
+  /// Writes the responses `source` received in the run just ended.
+  pub(crate) fn finish(&self, source: &str) {
+    let Some(responses) = self.pending.lock().unwrap().remove(source) else { return };
+    self.write(&Run { at: chrono::Utc::now().timestamp_millis(), source: source.into(), responses, frames: Vec::new() });
+  }
+
+  pub(crate) fn capture_frame(&self, source: &str, text: &str) {
+    if self.path.is_none() || self.replaying() { return; }
+    self.write(&Run { at: chrono::Utc::now().timestamp_millis(), source: source.into(), responses: Vec::new(), frames: vec![text.into()] });
+  }
+
+  fn write(&self, run: &Run) {
+    let Some(path) = &self.path else { return };
+    let res = (|| -> Result<()> {
+      let mut file = self.file.lock().unwrap();
+      if file.is_none() {
+        if let Some(dir) = path.parent() { std::fs::create_dir_all(dir)?; }
+        *file = Some(File::options().create(true).append(true).open(path)?);
+      }
+      let mut line = serde_json::to_string(run)?;
+      line.push('\n');
+      file.as_mut().expect("opened above").write_all(line.as_bytes())?;
+      Ok(())
+    })();
+    if let Err(e) = res { warn!("tape {}: {e:#}", path.display()); }
+  }
+
+  /// The recorded response to `url` in the run being replayed.
+  pub(crate) fn take(&self, source: &str, url: &str) -> Result<Response> {
+    let mut replay = self.replay.lock().unwrap();
+    let queue = replay.as_mut().and_then(|r| r.responses.get_mut(source));
+    let Some(queue) = queue else { bail!("{source}: nothing recorded for this run") };
+    let Some(i) = queue.iter().position(|r| r.url == url) else { bail!("{source}: no recorded response for {url}") };
+    Ok(queue.remove(i).expect("index found above"))
+  }
+
+  /// Replayed frames for streaming `source`, once.
+  pub(crate) fn frames(&self, source: &str) -> Option<mpsc::UnboundedReceiver<String>> {
+    self.replay.lock().unwrap().as_mut()?.frames.remove(source)
+  }
+}
+
+/// The runs on the tape at `path`, oldest first.
+pub fn read(path: &Path) -> Result<Vec<Run>> {
+  let file = File::open(path).with_context(|| format!("open {}", path.display()))?;
+  let mut runs = std::io::BufReader::new(file).lines().enumerate()
+    .filter(|(_, l)| l.as_ref().map_or(true, |l| !l.trim().is_empty()))
+    .map(|(i, l)| serde_json::from_str::<Run>(&l?).with_context(|| format!("{}:{}", path.display(), i + 1)))
+    .collect::<Result<Vec<_>>>()?;
+  runs.sort_by_key(|r| r.at);
+  Ok(runs)
+}
+
+/// What a replay stored.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReplayReport {
+  pub runs: usize,
+  pub items: usize,
+  /// Runs whose collector returned an error, as it did when recorded or
+  /// because the tape lacks a response it asked for.
+  pub failed: usize,
+}
+
+/// Replays the tape at `path` through the collectors, `speed` times as
+/// fast as it was recorded; 0 replays without pauses. Each run is reported
+/// as `source_status` like a live one.
+pub async fn replay(core: &Core, path: &Path, speed: f64) -> Result<ReplayReport> {
+  if speed.is_nan() || speed < 0.0 { bail!("speed must be 0 or more"); }
+  let runs = read(path)?;
+  let t0 = runs.first().map_or(0, |r| r.at);
+  // when a run is due, or `None` past what a timer can hold
+  let due = move |start: Instant, at: i64| Duration::try_from_secs_f64((at - t0).max(0) as f64 / 1000.0 / speed).ok().and_then(|d| start.checked_add(d));
+  if speed > 0.0 && runs.iter().any(|r| due(Instant::now(), r.at).is_none()) { bail!("speed {speed} is too slow to replay this tape"); }
+  let streamed = runs.iter().any(|r| !r.frames.is_empty());
+  let (tx, rx) = mpsc::unbounded_channel();
+  *core.tape.replay.lock().unwrap() = Some(Replay { frames: HashMap::from([("emsc".to_string(), rx)]), ..Default::default() });
+
+  let drive = async move {
+    let mut report = ReplayReport::default();
+    let start = Instant::now();
+    for run in runs {
+      if let Some(at) = due(start, run.at).filter(|_| speed > 0.0) { sleep_until(at).await; }
+      report.runs += 1;
+      if !run.frames.is_empty() {
+        for f in run.frames { let _ = tx.send(f); }
+        continue;
+      }
+      if let Some(r) = core.tape.replay.lock().unwrap().as_mut() { r.responses.insert(run.source.clone(), run.responses.into()); }
+      match super::run_once(core, &run.source).await {
+        Ok(n) => report.items += n,
+        Err(_) => report.failed += 1,
+      }
+    }
+    // closing the channel ends the stream's `run`
+    drop(tx);
+    report
+  };
+  let stream = async {
+    if !streamed { return 0; }
+    super::report(core, "emsc", super::emsc_ws::run(core).await).unwrap_or(0)
+  };
+  let (mut report, streamed_items) = tokio::join!(drive, stream);
+  *core.tape.replay.lock().unwrap() = None;
+  report.items += streamed_items;
+  Ok(report)
+}
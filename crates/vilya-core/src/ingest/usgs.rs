@@ -0,0 +1,41 @@
+use anyhow::Result;
+use crate::quake::{self, Quake};
+use crate::runtime::Core;
+use super::{after_commit, upsert_event, NewEvent};
+
+pub async fn run(core: &Core) -> Result<usize> {
+  let url = "https://earthquake.usgs.gov/earthquakes/feed/v1.0/summary/all_hour.geojson";
+  ingest(core, &super::fetch(core, "usgs", url).await?.body)
+}
+
+/// Stores a USGS GeoJSON summary feed.
+pub fn ingest(core: &Core, body: &str) -> Result<usize> {
+  let db = &core.db;
+  let v: serde_json::Value = serde_json::from_str(body)?;
+  if let Some(arr)=v.get("features").and_then(|x| x.as_array()) {
+    let tx = db.conn.get()?.unchecked_transaction()?;
+    let mut batch = Vec::new();
+    let now = chrono::Utc::now().timestamp();
+    for f in arr {
+      let id = f.get("id").and_then(|x| x.as_str()).unwrap_or_else(|| uuid::Uuid::new_v4().to_string().leak()).to_string();
+      let empty = serde_json::json!({});
+      let props = f.get("properties").unwrap_or(&empty);
+      let title = props.get("title").and_then(|x| x.as_str()).unwrap_or("USGS event");
+      let coords = f.pointer("/geometry/coordinates").and_then(|x| x.as_array()).cloned().unwrap_or_default();
+      let lon = coords.first().and_then(|x| x.as_f64()).unwrap_or(0.0);
+      let lat = coords.get(1).and_then(|x| x.as_f64()).unwrap_or(0.0);
+      let quake = Quake::from_usgs(props, &coords);
+      let estimate = quake::estimate(&tx, lat, lon, &quake)?;
+      let severity = estimate.severity;
+      let change = upsert_event(&tx, &NewEvent {
+        id: &id, title, summary: title, class: "eq", severity, confidence: 0.95, lat, lon,
+        geojson: Some(f.to_string()), source_rank: 5, seen: now,
+      })?;
+      quake::store(&tx, &id, lat, lon, &quake, &estimate, now)?;
+      batch.push((id, severity as f32, change));
+    }
+    tx.commit()?;
+    return Ok(after_commit(core, batch));
+  }
+  Ok(0)
+}
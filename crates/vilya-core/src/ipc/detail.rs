@@ -0,0 +1,104 @@
+use anyhow::Result;
+use geo::{HaversineDistance, Intersects, Point};
+use rusqlite::{params, OptionalExtension};
+use crate::ai::{labels, review};
+use crate::db::Db;
+use crate::{exposure, geocode, quake};
+use crate::rules;
+use super::{dto::{parse_geojson, EventDetail, NearbyEvent, SourcePayload}, IpcError, UiAlert, UiEvent};
+
+pub const DEFAULT_RADIUS_KM: f64 = 100.0;
+pub const DEFAULT_WINDOW_SECS: i64 = 48 * 3600;
+
+/// Everything known about one event: base columns, raw source items, the
+/// current AI label and review, alerts in force at its location at `now`,
+/// events within `radius_km` and `window_secs` of it, and matched rules.
+pub fn event_detail(db: &Db, id: &str, radius_km: f64, window_secs: i64, now: i64) -> Result<EventDetail> {
+  let (mut d, rank): (EventDetail, i64) = db.conn.query_row(
+    "SELECT id,title,summary,class,severity,confidence,lat,lon,geojson,first_seen,last_seen,COALESCE(source_rank,0) FROM event WHERE id=?1", [id], |r| Ok((EventDetail {
+      id: r.get(0)?, title: r.get(1)?, summary: r.get(2)?, class: r.get(3)?, severity: r.get(4)?, confidence: r.get(5)?,
+      lat: r.get(6)?, lon: r.get(7)?, geojson: parse_geojson(r.get(8)?), first_seen: r.get(9)?, last_seen: r.get(10)?,
+      place: None, exposure: None, shaking: None, duplicate_of: None, sources: Vec::new(), label: None, review: None, alerts: Vec::new(), nearby: Vec::new(), rules: Vec::new(),
+    }, r.get(11)?))).optional()?.ok_or_else(|| IpcError::not_found(format!("event {id}")))?;
+
+  d.sources = sources(db, id, rank, d.geojson.as_ref(), d.last_seen)?;
+  d.label = labels::current(db, id)?;
+  d.review = review::get(db, id)?;
+  d.rules = rules::matches_for(&db.conn, "event", id)?;
+  d.place = geocode::place_of(&db.conn, "event", id)?;
+  d.exposure = exposure::exposure_of(&db.conn, "event", id)?;
+  d.shaking = quake::shaking_of(&db.conn, id)?;
+  d.duplicate_of = db.conn.query_row("SELECT duplicate_of FROM event_duplicate WHERE event_id=?1", [id], |r| r.get(0)).optional()?;
+  if let (Some(lat), Some(lon)) = (d.lat, d.lon) {
+    let p = Point::new(lon, lat);
+    d.alerts = covering_alerts(db, p, now, None)?;
+    d.nearby = nearby(db, id, p, d.first_seen, radius_km, window_secs)?;
+  }
+  Ok(d)
+}
+
+/// Collectors keep the feed item as the event's `geojson`; imported items
+/// also land in `source_item` under the event id.
+fn sources(db: &Db, id: &str, rank: i64, geojson: Option<&serde_json::Value>, last_seen: i64) -> Result<Vec<SourcePayload>> {
+  let mut out: Vec<SourcePayload> = geojson.map(|g| SourcePayload {
+    source: rules::source_of_rank(rank).into(), fetched_at: Some(last_seen), payload: g.clone(),
+  }).into_iter().collect();
+  let mut stmt = db.conn.prepare("SELECT source, fetched_at, payload_json FROM source_item WHERE id=?1 ORDER BY fetched_at DESC")?;
+  let rows = stmt.query_map([id], |r| Ok((r.get::<_,String>(0)?, r.get::<_,i64>(1)?, r.get::<_,String>(2)?)))?;
+  for row in rows {
+    let (source, fetched_at, payload) = row?;
+    let payload = serde_json::from_str(&payload).unwrap_or(serde_json::Value::String(payload));
+    out.push(SourcePayload { source, fetched_at: Some(fetched_at), payload });
+  }
+  Ok(out)
+}
+
+/// Whether an alert area contains `p`. Areas without a usable geometry
+/// count as their bbox, which the R-tree lookup already matched.
+fn covers(area: Option<&serde_json::Value>, p: Point) -> bool {
+  let Some(gj) = area.and_then(|v| geojson::GeoJson::from_json_value(v.clone()).ok()) else { return true };
+  match geo::GeometryCollection::<f64>::try_from(&gj) {
+    Ok(c) if !c.0.is_empty() => c.0.iter().any(|g| g.intersects(&p)),
+    _ => true,
+  }
+}
+
+/// Alerts in force at `now` whose area contains `p`; with `as_of`, those
+/// in force then, as `query::query_alerts` finds them.
+pub fn covering_alerts(db: &Db, p: Point, now: i64, as_of: Option<i64>) -> Result<Vec<UiAlert>> {
+  if as_of.is_some() {
+    let alerts = super::query::query_alerts(db, [p.x(), p.y(), p.x(), p.y()], now, as_of)?;
+    return Ok(alerts.into_iter().filter(|a| covers(a.geojson.as_ref(), p)).collect());
+  }
+  let mut stmt = db.conn.prepare(
+    "SELECT a.id,a.headline,a.event,a.severity,a.urgency,a.certainty,a.onset,a.expires,a.polygon_geojson,a.bbox_minx,a.bbox_miny,a.bbox_maxx,a.bbox_maxy
+     FROM alert_rtree r JOIN alert a ON a.rowid = r.rowid
+     WHERE r.minx <= ?1 AND r.maxx >= ?1 AND r.miny <= ?2 AND r.maxy >= ?2
+       AND a.expires >= ?3 AND a.cancelled_at IS NULL
+     ORDER BY a.onset DESC")?;
+  let rows = stmt.query_map(params![p.x(), p.y(), now], UiAlert::from_row)?;
+  let alerts = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+  Ok(alerts.into_iter().filter(|a| covers(a.geojson.as_ref(), p)).collect())
+}
+
+fn nearby(db: &Db, id: &str, p: Point, at: i64, radius_km: f64, window_secs: i64) -> Result<Vec<NearbyEvent>> {
+  // a degree box around the radius keeps the haversine pass small; past
+  // ±180 its longitudes continue a turn either way
+  let dlat = radius_km / 111.0;
+  let dlon = (radius_km / (111.0 * p.y().to_radians().cos().max(0.01))).min(180.0);
+  let mut stmt = db.conn.prepare(
+    "SELECT id,title,class,lat,lon,severity,first_seen FROM event
+     WHERE id != ?1 AND lat BETWEEN ?2 AND ?3
+       AND (lon BETWEEN ?4 AND ?5 OR lon BETWEEN ?4 - 360 AND ?5 - 360 OR lon BETWEEN ?4 + 360 AND ?5 + 360)
+       AND first_seen BETWEEN ?6 AND ?7")?;
+  let rows = stmt.query_map(
+    params![id, p.y() - dlat, p.y() + dlat, p.x() - dlon, p.x() + dlon, at - window_secs, at + window_secs], UiEvent::from_row)?;
+  let mut out = Vec::new();
+  for row in rows {
+    let e = row?;
+    let km = p.haversine_distance(&Point::new(e.lon, e.lat)) / 1000.0;
+    if km <= radius_km { out.push(NearbyEvent { secs: e.ts - at, km, event: e }); }
+  }
+  out.sort_by(|a, b| a.km.total_cmp(&b.km));
+  Ok(out)
+}
@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use crate::ai::{labels::{LabelComparison, LabelRecord}, review::Review};
+use crate::exposure::Exposure;
+use crate::geocode::Place;
+use crate::quake::Shaking;
+use crate::rules::MatchedRule;
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct UiEvent { pub id: String, pub title: String, pub class: String, pub lat: f64, pub lon: f64, pub severity: f32, pub ts: i64 }
+
+impl UiEvent {
+  /// Reads `id,title,class,lat,lon,severity,first_seen` columns.
+  pub fn from_row(r: &rusqlite::Row) -> rusqlite::Result<Self> {
+    Ok(UiEvent {
+      id: r.get(0)?, title: r.get::<_, Option<String>>(1)?.unwrap_or_default(), class: r.get::<_, Option<String>>(2)?.unwrap_or_default(),
+      lat: r.get::<_, Option<f64>>(3)?.unwrap_or(0.0), lon: r.get::<_, Option<f64>>(4)?.unwrap_or(0.0),
+      severity: r.get::<_, Option<f64>>(5)?.unwrap_or(0.0) as f32, ts: r.get(6)?,
+    })
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct UiAlert {
+  pub id: String, pub headline: String, pub event: String,
+  pub severity: String, pub urgency: String, pub certainty: String,
+  pub onset: i64, pub expires: i64,
+  pub bbox: (f64,f64,f64,f64),
+  /// GeoJSON geometry or FeatureCollection of the alert area.
+  pub geojson: Option<serde_json::Value>
+}
+
+impl UiAlert {
+  /// Reads `id,headline,event,severity,urgency,certainty,onset,expires,
+  /// polygon_geojson,bbox_minx,bbox_miny,bbox_maxx,bbox_maxy` columns.
+  pub fn from_row(r: &rusqlite::Row) -> rusqlite::Result<Self> {
+    let text = |i: usize| r.get::<_, Option<String>>(i).map(Option::unwrap_or_default);
+    Ok(UiAlert {
+      id: r.get(0)?, headline: text(1)?, event: text(2)?,
+      severity: text(3)?, urgency: text(4)?, certainty: text(5)?,
+      onset: r.get::<_, Option<i64>>(6)?.unwrap_or(0), expires: r.get::<_, Option<i64>>(7)?.unwrap_or(0),
+      geojson: parse_geojson(r.get(8)?),
+      bbox: (r.get(9)?, r.get(10)?, r.get(11)?, r.get(12)?),
+    })
+  }
+}
+
+/// Stored GeoJSON text as a value; `"null"` and unparsable text become `None`.
+pub fn parse_geojson(text: Option<String>) -> Option<serde_json::Value> {
+  text.and_then(|t| serde_json::from_str(&t).ok()).filter(|v: &serde_json::Value| !v.is_null())
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct EventDetail {
+  pub id: String,
+  pub title: Option<String>,
+  pub summary: Option<String>,
+  pub class: Option<String>,
+  pub severity: Option<f64>,
+  pub confidence: Option<f64>,
+  pub lat: Option<f64>,
+  pub lon: Option<f64>,
+  pub geojson: Option<serde_json::Value>,
+  pub first_seen: i64,
+  pub last_seen: i64,
+  /// Country, divisions and nearest place, once the gazetteer has placed it.
+  pub place: Option<Place>,
+  /// People and facilities within its severity radius, once assessed.
+  pub exposure: Option<Exposure>,
+  /// Magnitude, depth and estimated intensities of an earthquake.
+  pub shaking: Option<Shaking>,
+  /// The first report of the same incident, when this one was merged into it.
+  pub duplicate_of: Option<String>,
+  /// Raw feed items behind the event.
+  pub sources: Vec<SourcePayload>,
+  /// The AI label currently in effect, with its run provenance.
+  pub label: Option<LabelRecord>,
+  pub review: Option<Review>,
+  /// Active alerts whose area covers the event location.
+  pub alerts: Vec<UiAlert>,
+  /// Other events within the requested radius and time window, nearest first.
+  pub nearby: Vec<NearbyEvent>,
+  pub rules: Vec<MatchedRule>,
+}
+
+/// One intensity disc of an earthquake, for the map.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct ShakingContour { pub event_id: String, pub mmi: u8, pub radius_km: f64, pub geojson: serde_json::Value }
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct SourcePayload { pub source: String, pub fetched_at: Option<i64>, pub payload: serde_json::Value }
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct NearbyEvent { pub event: UiEvent, pub km: f64, pub secs: i64 }
+
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct Aoi { pub id: String, pub name: String, pub bbox: (f64,f64,f64,f64) }
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct RelabelReport { pub batch_id: String, pub results: Vec<LabelComparison> }
@@ -0,0 +1,69 @@
+use serde::Serialize;
+
+/// Error returned by every IPC command. Serialises as
+/// `{ "code": "not_found", "message": "..." }` so the UI can branch on
+/// `code` instead of parsing text.
+#[derive(Debug, Clone, thiserror::Error, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(tag = "code", content = "message", rename_all = "snake_case")]
+pub enum IpcError {
+  #[error("not found: {0}")]
+  NotFound(String),
+  #[error("invalid argument: {0}")]
+  InvalidArgument(String),
+  /// The HTTP API was called without its token, or with a wrong one.
+  #[error("unauthorized: {0}")]
+  Unauthorized(String),
+  /// The database is locked by a collector write; retrying usually works.
+  #[error("database busy: {0}")]
+  DbBusy(String),
+  #[error("{0}")]
+  Internal(String),
+}
+
+pub type IpcResult<T> = Result<T, IpcError>;
+
+impl IpcError {
+  pub fn not_found(what: impl std::fmt::Display) -> Self { IpcError::NotFound(what.to_string()) }
+  pub fn invalid(why: impl std::fmt::Display) -> Self { IpcError::InvalidArgument(why.to_string()) }
+
+  fn from_sqlite(e: &rusqlite::Error, message: String) -> Self {
+    use rusqlite::ErrorCode::{DatabaseBusy, DatabaseLocked};
+    match e {
+      rusqlite::Error::QueryReturnedNoRows => IpcError::NotFound(message),
+      rusqlite::Error::SqliteFailure(f, _) if matches!(f.code, DatabaseBusy | DatabaseLocked) => IpcError::DbBusy(message),
+      _ => IpcError::Internal(message),
+    }
+  }
+}
+
+impl From<rusqlite::Error> for IpcError {
+  fn from(e: rusqlite::Error) -> Self { let m = e.to_string(); IpcError::from_sqlite(&e, m) }
+}
+
+impl From<serde_json::Error> for IpcError {
+  fn from(e: serde_json::Error) -> Self { IpcError::Internal(e.to_string()) }
+}
+
+impl From<crate::error::Error> for IpcError {
+  fn from(e: crate::error::Error) -> Self {
+    match e {
+      crate::error::Error::NotFound(m) => IpcError::NotFound(m),
+      crate::error::Error::InvalidArgument(m) => IpcError::InvalidArgument(m),
+    }
+  }
+}
+
+/// Domain code returns `anyhow`; a domain `Error`, `IpcError` or SQLite
+/// error anywhere in the chain decides the code, everything else is
+/// `internal`.
+impl From<anyhow::Error> for IpcError {
+  fn from(e: anyhow::Error) -> Self {
+    for cause in e.chain() {
+      if let Some(domain) = cause.downcast_ref::<crate::error::Error>() { return domain.clone().into(); }
+      if let Some(ipc) = cause.downcast_ref::<IpcError>() { return ipc.clone(); }
+      if let Some(sql) = cause.downcast_ref::<rusqlite::Error>() { return IpcError::from_sqlite(sql, format!("{e:#}")); }
+    }
+    IpcError::Internal(format!("{e:#}"))
+  }
+}
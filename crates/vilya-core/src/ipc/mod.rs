@@ -0,0 +1,5 @@
+//! Types that cross the desktop IPC boundary, shared with other front ends.
+pub mod detail; pub mod dto; pub mod error; pub mod query;
+
+pub use dto::{Aoi, EventDetail, NearbyEvent, RelabelReport, ShakingContour, SourcePayload, UiAlert, UiEvent};
+pub use error::{IpcError, IpcResult};
@@ -0,0 +1,82 @@
+use anyhow::Result;
+use rusqlite::params;
+use crate::db::Db;
+use crate::quake;
+use super::{dto::parse_geojson, ShakingContour, UiAlert, UiEvent};
+
+/// Events whose title or place (nearest place, division or country name,
+/// or ISO 3166-1 alpha-3 code) contains `q`, first seen within
+/// `[since, until]`, newest first. Reports merged into an earlier report of
+/// the same incident are left out. With `as_of`, only the events known
+/// then, as they read then.
+pub fn search_events(db: &Db, q: Option<&str>, since: Option<i64>, until: Option<i64>, as_of: Option<i64>, limit: usize) -> Result<Vec<UiEvent>> {
+  let sql = if as_of.is_some() {
+    format!("SELECT e.id,v.title,v.class,v.lat,v.lon,v.severity,e.first_seen FROM event e
+     JOIN event_version v ON v.rowid = (SELECT rowid FROM event_version WHERE event_id=e.id AND valid_from <= ?4 ORDER BY valid_from DESC, rowid DESC LIMIT 1)
+     WHERE (?1 IS NULL OR v.title LIKE '%'||?1||'%' OR {PLACE_MATCHES}) AND (?2 IS NULL OR e.first_seen >= ?2) AND (?3 IS NULL OR e.first_seen <= ?3)
+       AND NOT EXISTS (SELECT 1 FROM event_duplicate d WHERE d.event_id=e.id AND d.merged_at <= ?4)
+     ORDER BY e.first_seen DESC LIMIT ?5")
+  } else {
+    format!("SELECT e.id,e.title,e.class,e.lat,e.lon,e.severity,e.first_seen FROM event e
+     WHERE (?1 IS NULL OR e.title LIKE '%'||?1||'%' OR {PLACE_MATCHES}) AND (?2 IS NULL OR e.first_seen >= ?2) AND (?3 IS NULL OR e.first_seen <= ?3)
+       AND NOT EXISTS (SELECT 1 FROM event_duplicate d WHERE d.event_id=e.id)
+     ORDER BY e.first_seen DESC LIMIT ?5")
+  };
+  let mut stmt = db.conn.prepare(&sql)?;
+  let rows = stmt.query_map(params![q, since, until, as_of, limit as i64], UiEvent::from_row)?;
+  Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+const PLACE_MATCHES: &str = "EXISTS (SELECT 1 FROM item_place p WHERE p.item_kind='event' AND p.item_id=e.id
+  AND (p.place LIKE '%'||?1||'%' OR p.admin1 LIKE '%'||?1||'%' OR p.admin2 LIKE '%'||?1||'%' OR p.country_name LIKE '%'||?1||'%' OR p.country = UPPER(?1)))";
+
+/// Alerts in force at `now` whose bbox meets `[minx, miny, maxx, maxy]`,
+/// most severe first. With `as_of`, the alerts in force at that moment as
+/// they read then, instead: issued by then, not yet expired or cancelled.
+pub fn query_alerts(db: &Db, [minx, miny, maxx, maxy]: [f64; 4], now: i64, as_of: Option<i64>) -> Result<Vec<UiAlert>> {
+  let Some(t) = as_of else {
+    let mut stmt = db.conn.prepare(
+      "SELECT a.id,a.headline,a.event,a.severity,a.urgency,a.certainty,a.onset,a.expires,a.polygon_geojson,a.bbox_minx,a.bbox_miny,a.bbox_maxx,a.bbox_maxy
+       FROM alert_rtree r JOIN alert a ON a.rowid = r.rowid
+       WHERE r.minx <= ?3 AND r.maxx >= ?1 AND r.miny <= ?4 AND r.maxy >= ?2
+         AND a.expires >= ?5 AND a.cancelled_at IS NULL
+       ORDER BY a.severity DESC, a.onset DESC LIMIT 500"
+    )?;
+    let rows = stmt.query_map(params![minx, miny, maxx, maxy, now], UiAlert::from_row)?;
+    return Ok(rows.collect::<rusqlite::Result<_>>()?);
+  };
+  let mut stmt = db.conn.prepare(
+    "SELECT a.id,v.headline,v.event,v.severity,v.urgency,v.certainty,v.onset,v.expires,v.polygon_geojson,v.bbox_minx,v.bbox_miny,v.bbox_maxx,v.bbox_maxy
+     FROM alert a
+     JOIN alert_version v ON v.rowid = (SELECT rowid FROM alert_version WHERE alert_id=a.id AND valid_from <= ?5 ORDER BY valid_from DESC, rowid DESC LIMIT 1)
+     WHERE v.bbox_minx <= ?3 AND v.bbox_maxx >= ?1 AND v.bbox_miny <= ?4 AND v.bbox_maxy >= ?2
+       AND v.expires >= ?5 AND (a.cancelled_at IS NULL OR a.cancelled_at > ?5)
+     ORDER BY v.severity DESC, v.onset DESC LIMIT 500"
+  )?;
+  let rows = stmt.query_map(params![minx, miny, maxx, maxy, t], UiAlert::from_row)?;
+  Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// How far back the map looks for earthquake footprints by default.
+pub const SHAKING_WINDOW_SECS: i64 = 7 * 86400;
+
+/// Intensity discs meeting `[minx, miny, maxx, maxy]` of earthquakes first
+/// seen since `since`, newest quake first and its weakest disc first, so
+/// stronger shaking draws on top. Discs over the antimeridian keep bboxes
+/// past ±180, so the box is also tried a turn either way.
+pub fn query_shaking(db: &Db, bbox: [f64; 4], since: i64) -> Result<Vec<ShakingContour>> {
+  let mut stmt = db.conn.prepare(
+    "SELECT c.event_id, c.mmi, c.radius_km, c.polygon_geojson
+     FROM quake_contour c JOIN event e ON e.id = c.event_id
+     WHERE c.id IN (SELECT id FROM quake_contour_rtree WHERE minx <= ?3 AND maxx >= ?1 AND miny <= ?4 AND maxy >= ?2
+       UNION SELECT id FROM quake_contour_rtree WHERE minx <= ?7 AND maxx >= ?6 AND miny <= ?4 AND maxy >= ?2
+       UNION SELECT id FROM quake_contour_rtree WHERE minx <= ?9 AND maxx >= ?8 AND miny <= ?4 AND maxy >= ?2)
+       AND e.first_seen >= ?5
+     ORDER BY e.first_seen DESC, c.event_id, c.mmi LIMIT 2000"
+  )?;
+  let [[minx, miny, maxx, maxy], [wminx, _, wmaxx, _], [eminx, _, emaxx, _]] = quake::wrapped(bbox);
+  let rows = stmt.query_map(params![minx, miny, maxx, maxy, since, wminx, wmaxx, eminx, emaxx], |r| Ok(ShakingContour {
+    event_id: r.get(0)?, mmi: r.get(1)?, radius_km: r.get(2)?, geojson: parse_geojson(r.get(3)?).unwrap_or_default(),
+  }))?;
+  Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
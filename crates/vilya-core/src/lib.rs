@@ -0,0 +1,7 @@
+//! The Vilya pipeline: collectors, normalisation, merging, AI labelling and
+//! rules over one SQLite database, independent of any UI.
+pub mod ai; pub mod analytics; pub mod api; pub mod db; pub mod entities; pub mod error; pub mod export; pub mod exposure; pub mod geocode; pub mod history; pub mod ingest; pub mod ipc;
+pub mod merge; pub mod normalize; pub mod quake; pub mod rules; pub mod runtime; pub mod settings; pub mod stream; pub mod telemetry;
+#[cfg(test)] mod tests;
+
+pub use runtime::Core;
@@ -0,0 +1,93 @@
+use rusqlite::{params, OptionalExtension};
+use crate::db::Db;
+
+pub fn dedup_alert_into_events(db: &Db) -> anyhow::Result<usize> {
+  let mut created = 0usize;
+  let mut stmt = db.conn.prepare("SELECT id,event,severity,(bbox_minx+bbox_maxx)/2.0 AS cx,(bbox_miny+bbox_maxy)/2.0 AS cy,onset
+                                  FROM alert WHERE severity IN ('Severe','Extreme')")?;
+  let mut rows = stmt.query([])?;
+  while let Some(r) = rows.next()? {
+    let _id: String = r.get(0)?; let name: String = r.get(1)?;
+    let _sev: String = r.get(2)?; let cx: f64 = r.get(3)?; let cy: f64 = r.get(4)?; let onset: i64 = r.get(5)?;
+    let key = format!("cap:{}:{:.2}:{:.2}", name, cx, cy);
+    db.conn.execute(
+      "INSERT OR IGNORE INTO event(id,first_seen,last_seen,title,summary,class,severity,confidence,lat,lon,geojson,source_rank)
+       VALUES (?1,?2,?2,?3,?3,'alert',?4,0.9,?5,?6,NULL,1)",
+      params![key, onset, name, 0.9f64, cy, cx]
+    )?;
+    if db.conn.changes() > 0 { created += 1; }
+  }
+  Ok(created)
+}
+
+#[derive(Debug, serde::Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct DuplicateCandidate { pub a: String, pub b: String, pub score: f32, pub km: f64, pub secs: i64 }
+
+/// Pairs of events whose text embeddings (from `model`) are at least
+/// `min_score` similar and which lie within `max_km` and `max_secs` of each
+/// other: likely the same incident reported by different sources.
+pub fn semantic_duplicates(db: &Db, model: &str, min_score: f32, max_km: f64, max_secs: i64, since: i64) -> anyhow::Result<Vec<DuplicateCandidate>> {
+  pairs(db, model, min_score, max_km, max_secs, (since, i64::MAX))
+}
+
+/// `a` is the earlier of each pair.
+fn pairs(db: &Db, model: &str, min_score: f32, max_km: f64, max_secs: i64, (since, until): (i64, i64)) -> anyhow::Result<Vec<DuplicateCandidate>> {
+  use geo::{HaversineDistance, Point};
+  let mut stmt = db.conn.prepare(
+    "SELECT e.id, COALESCE(e.lat,0), COALESCE(e.lon,0), e.first_seen, m.vector
+     FROM event_embedding m JOIN event e ON e.id=m.event_id
+     WHERE m.model=?1 AND e.first_seen >= ?2 AND e.first_seen <= ?3 ORDER BY e.first_seen")?;
+  let rows = stmt.query_map(params![model, since, until], |r| {
+    let v: Vec<u8> = r.get(4)?;
+    Ok((r.get::<_,String>(0)?, Point::new(r.get::<_,f64>(2)?, r.get::<_,f64>(1)?), r.get::<_,i64>(3)?, crate::ai::embed::from_blob(&v)))
+  })?;
+  let items = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+
+  let mut out = Vec::new();
+  for (i, (id_a, pa, ta, va)) in items.iter().enumerate() {
+    // sorted by time, so stop once the window is exceeded
+    for (id_b, pb, tb, vb) in items[i+1..].iter().take_while(|b| b.2 - ta <= max_secs) {
+      if va.len() != vb.len() { continue; }
+      let km = pa.haversine_distance(pb) / 1000.0;
+      if km > max_km { continue; }
+      let score = crate::ai::embed::dot(va, vb);
+      if score >= min_score {
+        out.push(DuplicateCandidate { a: id_a.clone(), b: id_b.clone(), score, km, secs: tb - ta });
+      }
+    }
+  }
+  out.sort_by(|x, y| y.score.total_cmp(&x.score));
+  Ok(out)
+}
+
+/// Similarity at which reports are merged without review; stricter than the
+/// candidates listed for analysts.
+pub const MERGE_MIN_SCORE: f32 = 0.92;
+pub const MERGE_MAX_KM: f64 = 100.0;
+pub const MERGE_MAX_SECS: i64 = 6 * 3600;
+
+/// Merges `event_id` with the semantic duplicates around it that another
+/// source reported: the later report of each pair is linked to the first
+/// report of the incident. Reports from one source are never merged, as a
+/// feed lists aftershocks and repeat fires with near-identical wording.
+/// Returns how many events were linked.
+pub fn merge_semantic_duplicates(db: &Db, model: &str, event_id: &str) -> anyhow::Result<usize> {
+  let t: i64 = db.conn.query_row("SELECT first_seen FROM event WHERE id=?1", [event_id], |r| r.get(0))?;
+  let window = (t - MERGE_MAX_SECS, t + MERGE_MAX_SECS);
+  let rank = |id: &str| db.conn.query_row("SELECT COALESCE(source_rank,0) FROM event WHERE id=?1", [id], |r| r.get::<_, i64>(0));
+  let first_report = |id: &str| db.conn.query_row("SELECT duplicate_of FROM event_duplicate WHERE event_id=?1", [id], |r| r.get::<_, String>(0)).optional();
+  let mut merged = 0;
+  for c in pairs(db, model, MERGE_MIN_SCORE, MERGE_MAX_KM, MERGE_MAX_SECS, window)? {
+    if (c.a != event_id && c.b != event_id) || rank(&c.a)? == rank(&c.b)? { continue; }
+    let into = first_report(&c.a)?.unwrap_or(c.a);
+    if into == c.b || rank(&into)? == rank(&c.b)? || first_report(&c.b)?.is_some() { continue; }
+    db.conn.execute(
+      "INSERT INTO event_duplicate(event_id,duplicate_of,model,score,km,merged_at) VALUES (?1,?2,?3,?4,?5,strftime('%s','now'))",
+      params![c.b, into, model, c.score, c.km])?;
+    // reports already merged into `b` follow it
+    db.conn.execute("UPDATE event_duplicate SET duplicate_of=?2 WHERE duplicate_of=?1", params![c.b, into])?;
+    merged += 1;
+  }
+  Ok(merged)
+}
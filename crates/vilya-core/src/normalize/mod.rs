@@ -0,0 +1,23 @@
+// normalization helpers
+
+/// Maps a CAP `<severity>` onto the 0..1 scale used for event severity.
+pub fn cap_severity(s: &str) -> f64 {
+  match s {
+    "Extreme" => 1.0,
+    "Severe" => 0.75,
+    "Moderate" => 0.5,
+    "Minor" => 0.25,
+    _ => 0.0,
+  }
+}
+
+/// The CAP `<severity>` word whose `cap_severity` is nearest to `s`.
+pub fn cap_word(s: f64) -> &'static str {
+  match s {
+    s if s >= 0.875 => "Extreme",
+    s if s >= 0.625 => "Severe",
+    s if s >= 0.375 => "Moderate",
+    s if s > 0.0 => "Minor",
+    _ => "Unknown",
+  }
+}
@@ -0,0 +1,271 @@
+//! Earthquake source parameters and the shaking expected from them.
+//! Intensity follows the hypocentral intensity prediction equation of
+//! Allen, Wald & Worden (2012, "Intensity attenuation for active crustal
+//! regions", J. Seismol. 16:409–433), which takes moment magnitude; other
+//! magnitude types are used as if they were Mw. The footprint is stored as
+//! one disc per whole MMI, and an event's severity is the intensity at the
+//! most shaken gazetteer place rather than its magnitude.
+use anyhow::Result;
+use geo::{HaversineDistance, Point};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::Value;
+use tracing::info;
+use crate::geocode;
+
+/// Depth assumed when a feed gives none, where USGS fixes poorly
+/// constrained ones.
+pub const DEFAULT_DEPTH_KM: f64 = 10.0;
+/// The lowest contour: felt by a few people at rest.
+pub const FELT_MMI: u8 = 2;
+/// Contours stop here, well past the distances the equation was fitted to.
+pub const MAX_RADIUS_KM: f64 = 1500.0;
+const RING_VERTICES: usize = 72;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct Quake {
+  pub mag: f64,
+  /// As the feed spells it: `mw`, `ml`, `mb`, ...
+  pub mag_type: Option<String>,
+  pub depth_km: Option<f64>,
+  /// Uncertainties, when the feed reports them.
+  pub mag_error: Option<f64>,
+  pub depth_error_km: Option<f64>,
+  pub horizontal_error_km: Option<f64>,
+}
+
+impl Quake {
+  /// From a USGS feature: `mag`, `magType`, depth as the third coordinate,
+  /// and `magError`, `depthError` and `horizontalError` as USGS CSV and
+  /// detail feeds carry them.
+  pub fn from_usgs(props: &Value, coords: &[Value]) -> Quake {
+    let num = |k: &str| props.get(k).and_then(Value::as_f64);
+    Quake {
+      mag: num("mag").unwrap_or(0.0),
+      mag_type: props.get("magType").and_then(Value::as_str).map(str::to_string),
+      depth_km: coords.get(2).and_then(Value::as_f64),
+      mag_error: num("magError"),
+      depth_error_km: num("depthError"),
+      horizontal_error_km: num("horizontalError"),
+    }
+  }
+
+  /// From an EMSC feature's `mag`, `magtype` and `depth` properties.
+  pub fn from_emsc(props: &Value) -> Quake {
+    Quake {
+      mag: props.get("mag").and_then(Value::as_f64).unwrap_or(0.0),
+      mag_type: props.get("magtype").and_then(Value::as_str).map(str::to_string),
+      depth_km: props.get("depth").and_then(Value::as_f64),
+      mag_error: None, depth_error_km: None, horizontal_error_km: None,
+    }
+  }
+
+  fn depth(&self) -> f64 { self.depth_km.filter(|d| d.is_finite()).unwrap_or(DEFAULT_DEPTH_KM).max(0.0) }
+}
+
+/// Estimated MMI `km` from the epicentre of a magnitude `mag` quake
+/// `depth_km` deep.
+pub fn mmi(mag: f64, depth_km: f64, km: f64) -> f64 {
+  // Table 3, hypocentral distance; R_M = m1 + m2·e^(M−5) saturates near the source
+  let (c0, c1, c2, c4, m1, m2) = (2.085, 1.428, -1.402, 0.078, -0.209, 2.042);
+  let r_hyp = (km * km + depth_km * depth_km).sqrt();
+  let r_m = m1 + m2 * (mag - 5.0).exp();
+  let mut i = c0 + c1 * mag + c2 * (r_hyp * r_hyp + r_m * r_m).sqrt().ln();
+  if r_hyp > 50.0 { i += c4 * (r_hyp / 50.0).ln(); }
+  i.clamp(1.0, 12.0)
+}
+
+/// The 0..1 severity of shaking at `mmi`: I is none, X and above is 1.
+pub fn mmi_severity(mmi: f64) -> f64 {
+  ((mmi - 1.0) / 9.0).clamp(0.0, 1.0)
+}
+
+/// How far from the epicentre shaking stays at or above `level`; `None`
+/// where even the epicentre is below it.
+fn radius_km(q: &Quake, level: f64) -> Option<f64> {
+  let at = |km| mmi(q.mag, q.depth(), km);
+  if at(0.0) < level { return None; }
+  if at(MAX_RADIUS_KM) >= level { return Some(MAX_RADIUS_KM); }
+  let (mut lo, mut hi) = (0.0, MAX_RADIUS_KM);
+  while hi - lo > 0.05 {
+    let mid = (lo + hi) / 2.0;
+    if at(mid) >= level { lo = mid } else { hi = mid }
+  }
+  Some((lo * 10.0).round() / 10.0)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct Contour { pub mmi: u8, pub radius_km: f64 }
+
+/// Discs of shaking at or above each whole MMI from `FELT_MMI` up, largest
+/// first.
+pub fn contours(q: &Quake) -> Vec<Contour> {
+  (FELT_MMI..=12).map_while(|level| Some(Contour { mmi: level, radius_km: radius_km(q, level as f64)? })).collect()
+}
+
+/// A disc of `km` around `p` as GeoJSON, with its bbox. Longitudes run on
+/// from the centre's, so a disc over the antimeridian has a bbox past ±180
+/// and is split there into a MultiPolygon, as RFC 7946 asks.
+fn disc(p: Point, km: f64) -> (Value, [f64; 4]) {
+  let (lat, lon) = (p.y().to_radians(), ((p.x() + 180.0).rem_euclid(360.0) - 180.0).to_radians());
+  let d = km / 6371.0088;
+  let mut ring = Vec::with_capacity(RING_VERTICES);
+  let mut bbox = [f64::MAX, f64::MAX, f64::MIN, f64::MIN];
+  for i in 0..RING_VERTICES {
+    let bearing = i as f64 / RING_VERTICES as f64 * std::f64::consts::TAU;
+    let lat2 = (lat.sin() * d.cos() + lat.cos() * d.sin() * bearing.cos()).asin();
+    let lon2 = lon + (bearing.sin() * d.sin() * lat.cos()).atan2(d.cos() - lat.sin() * lat2.sin());
+    let (x, y) = (lon2.to_degrees(), lat2.to_degrees());
+    bbox = [bbox[0].min(x), bbox[1].min(y), bbox[2].max(x), bbox[3].max(y)];
+    ring.push((x, y));
+  }
+  let round = |v: f64| (v * 1e5).round() / 1e5;
+  bbox = bbox.map(round);
+  let coords = |ring: &[(f64, f64)], shift: f64| {
+    let mut c: Vec<Value> = ring.iter().map(|&(x, y)| serde_json::json!([round(x + shift), round(y)])).collect();
+    c.push(c[0].clone());
+    serde_json::json!([c])
+  };
+  let geometry = if bbox[2] > 180.0 {
+    serde_json::json!({ "type": "MultiPolygon", "coordinates": [coords(&clip(&ring, 180.0, true), 0.0), coords(&clip(&ring, 180.0, false), -360.0)] })
+  } else if bbox[0] < -180.0 {
+    serde_json::json!({ "type": "MultiPolygon", "coordinates": [coords(&clip(&ring, -180.0, false), 0.0), coords(&clip(&ring, -180.0, true), 360.0)] })
+  } else {
+    serde_json::json!({ "type": "Polygon", "coordinates": coords(&ring, 0.0) })
+  };
+  (geometry, bbox)
+}
+
+/// The part of the open, convex `ring` west (or east) of longitude `at`.
+fn clip(ring: &[(f64, f64)], at: f64, west: bool) -> Vec<(f64, f64)> {
+  let inside = |x: f64| if west { x <= at } else { x >= at };
+  let mut out = Vec::with_capacity(ring.len());
+  for (i, &(x, y)) in ring.iter().enumerate() {
+    let (nx, ny) = ring[(i + 1) % ring.len()];
+    if inside(x) { out.push((x, y)); }
+    if inside(x) != inside(nx) { out.push((at, y + (ny - y) * (at - x) / (nx - x))); }
+  }
+  out
+}
+
+/// `[minx, miny, maxx, maxy]` and its copies a turn either way, which
+/// between them meet every box with longitudes within a turn of ±180.
+pub(crate) fn wrapped([minx, miny, maxx, maxy]: [f64; 4]) -> [[f64; 4]; 3] {
+  [[minx, miny, maxx, maxy], [minx - 360.0, miny, maxx - 360.0, maxy], [minx + 360.0, miny, maxx + 360.0, maxy]]
+}
+
+/// What `estimate` expects of a quake at a location.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Estimate {
+  /// From shaking at gazetteer places; from magnitude without a gazetteer.
+  pub severity: f64,
+  pub max_mmi: Option<f64>,
+  pub centre: Option<String>,
+  pub centre_km: Option<f64>,
+  pub contours: Vec<Contour>,
+}
+
+/// Contours of `q` at `lat`,`lon`, and the strongest shaking at a populated
+/// gazetteer place inside the felt area; severity 0 when no place feels it.
+pub fn estimate(conn: &Connection, lat: f64, lon: f64, q: &Quake) -> Result<Estimate> {
+  let contours = contours(q);
+  let mut e = Estimate { severity: (q.mag / 10.0).clamp(0.0, 1.0), max_mmi: None, centre: None, centre_km: None, contours };
+  if !geocode::loaded(conn)? { return Ok(e); }
+  e.severity = 0.0;
+  let Some(felt) = e.contours.first().map(|c| c.radius_km) else { return Ok(e) };
+  let p = Point::new(lon, lat);
+  let (_, bbox) = disc(p, felt);
+  let mut stmt = conn.prepare_cached(
+    "SELECT g.name, g.lat, g.lon FROM gazetteer_place_rtree r JOIN gazetteer_place g ON g.id = r.rowid
+     WHERE r.minx <= ?3 AND r.maxx >= ?1 AND r.miny <= ?4 AND r.maxy >= ?2 AND g.population > 0")?;
+  let mut places = Vec::new();
+  for [minx, miny, maxx, maxy] in wrapped(bbox) {
+    let rows = stmt.query_map(params![minx, miny, maxx, maxy], |r| Ok((r.get::<_, String>(0)?, r.get::<_, f64>(1)?, r.get::<_, f64>(2)?)))?;
+    places.extend(rows.collect::<rusqlite::Result<Vec<_>>>()?);
+  }
+  for (name, plat, plon) in places {
+    let km = p.haversine_distance(&Point::new(plon, plat)) / 1000.0;
+    if km > felt { continue; }
+    let i = mmi(q.mag, q.depth(), km);
+    if e.max_mmi.is_none_or(|m| i > m) {
+      e.max_mmi = Some((i * 10.0).round() / 10.0);
+      e.centre = Some(name);
+      e.centre_km = Some((km * 10.0).round() / 10.0);
+    }
+  }
+  e.severity = e.max_mmi.map_or(0.0, mmi_severity);
+  Ok(e)
+}
+
+/// Keeps `q` and its estimate for event `id`, replacing its contours.
+pub fn store(conn: &Connection, id: &str, lat: f64, lon: f64, q: &Quake, e: &Estimate, now: i64) -> Result<()> {
+  conn.execute("INSERT OR REPLACE INTO quake(event_id,mag,mag_type,depth_km,mag_error,depth_error_km,horizontal_error_km,max_mmi,centre,centre_km,computed_at)
+    VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11)",
+    params![id, q.mag, q.mag_type, q.depth_km, q.mag_error, q.depth_error_km, q.horizontal_error_km, e.max_mmi, e.centre, e.centre_km, now])?;
+  conn.execute("DELETE FROM quake_contour WHERE event_id=?1", [id])?;
+  for c in &e.contours {
+    let (polygon, [minx, miny, maxx, maxy]) = disc(Point::new(lon, lat), c.radius_km);
+    let rowid: i64 = conn.prepare_cached("INSERT INTO quake_contour(event_id,mmi,radius_km,polygon_geojson) VALUES (?1,?2,?3,?4) RETURNING id")?
+      .query_row(params![id, c.mmi, c.radius_km, polygon.to_string()], |r| r.get(0))?;
+    conn.prepare_cached("INSERT INTO quake_contour_rtree(id,minx,maxx,miny,maxy) VALUES (?1,?2,?3,?4,?5)")?
+      .execute(params![rowid, minx, maxx, miny, maxy])?;
+  }
+  Ok(())
+}
+
+/// Re-estimates every stored quake and its event's severity, after the
+/// gazetteer changes. Returns the number whose severity changed.
+pub fn estimate_all(conn: &Connection) -> Result<usize> {
+  let quakes: Vec<(String, f64, f64, Quake, Option<f64>)> = {
+    let mut stmt = conn.prepare(
+      "SELECT q.event_id, e.lat, e.lon, q.mag, q.mag_type, q.depth_km, q.mag_error, q.depth_error_km, q.horizontal_error_km, e.severity
+       FROM quake q JOIN event e ON e.id = q.event_id WHERE e.lat IS NOT NULL AND e.lon IS NOT NULL")?;
+    let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, Quake {
+      mag: r.get(3)?, mag_type: r.get(4)?, depth_km: r.get(5)?, mag_error: r.get(6)?, depth_error_km: r.get(7)?, horizontal_error_km: r.get(8)?,
+    }, r.get(9)?)))?;
+    rows.collect::<rusqlite::Result<_>>()?
+  };
+  let now = chrono::Utc::now().timestamp();
+  let tx = conn.unchecked_transaction()?;
+  let mut n = 0;
+  for (id, lat, lon, q, severity) in quakes {
+    let e = estimate(&tx, lat, lon, &q)?;
+    store(&tx, &id, lat, lon, &q, &e, now)?;
+    if severity != Some(e.severity) {
+      tx.execute("UPDATE event SET severity=?2 WHERE id=?1", params![id, e.severity])?;
+      n += 1;
+    }
+  }
+  tx.commit()?;
+  if n > 0 { info!("quake: re-estimated severity of {n} events"); }
+  Ok(n)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct Shaking {
+  pub quake: Quake,
+  /// Estimated MMI at the most shaken gazetteer place, which it names.
+  pub max_mmi: Option<f64>,
+  pub centre: Option<String>,
+  pub centre_km: Option<f64>,
+  /// Largest disc first.
+  pub contours: Vec<Contour>,
+  pub computed_at: i64,
+}
+
+/// The stored parameters and estimate of event `id`.
+pub fn shaking_of(conn: &Connection, id: &str) -> Result<Option<Shaking>> {
+  let row = conn.query_row(
+    "SELECT mag, mag_type, depth_km, mag_error, depth_error_km, horizontal_error_km, max_mmi, centre, centre_km, computed_at FROM quake WHERE event_id=?1", [id],
+    |r| Ok(Shaking {
+      quake: Quake { mag: r.get(0)?, mag_type: r.get(1)?, depth_km: r.get(2)?, mag_error: r.get(3)?, depth_error_km: r.get(4)?, horizontal_error_km: r.get(5)? },
+      max_mmi: r.get(6)?, centre: r.get(7)?, centre_km: r.get(8)?, contours: Vec::new(), computed_at: r.get(9)?,
+    })).optional()?;
+  let Some(mut s) = row else { return Ok(None) };
+  let mut stmt = conn.prepare_cached("SELECT mmi, radius_km FROM quake_contour WHERE event_id=?1 ORDER BY mmi")?;
+  s.contours = stmt.query_map([id], |r| Ok(Contour { mmi: r.get(0)?, radius_km: r.get(1)? }))?.collect::<rusqlite::Result<_>>()?;
+  Ok(Some(s))
+}
@@ -0,0 +1,196 @@
+use anyhow::{Result, Context, bail};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::db::Db;
+use crate::normalize::cap_severity;
+use rusqlite::{params, Connection, OptionalExtension};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all="snake_case")]
+pub struct RuleYaml {
+  pub id: String,
+  pub name: String,
+  pub enabled: Option<bool>,
+  pub target: String,
+  #[serde(rename="where")]
+  pub where_: serde_json::Value
+}
+
+/// The `where` block of a rule. Every field that is set must hold.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Condition {
+  /// Event class, or the CAP `<event>` name for alerts; case-insensitive.
+  pub class: Option<Vec<String>>,
+  /// 0..1; alert severities are mapped with `cap_severity`.
+  pub min_severity: Option<f64>,
+  /// [minx, miny, maxx, maxy]; alerts match when their bbox intersects it.
+  pub bbox: Option<[f64; 4]>,
+  /// Case-insensitive substring of the title or headline.
+  pub text: Option<String>,
+  pub source: Option<Vec<String>>,
+  /// ISO 3166-1 alpha-3 code or name of the country, from the gazetteer or
+  /// the feed; case-insensitive.
+  pub country: Option<Vec<String>>,
+  /// Name of the first- or second-level division, e.g. `Texas` or `Harris
+  /// County`; case-insensitive.
+  pub region: Option<Vec<String>>,
+  /// The nearest populated place is at most this far, in km.
+  pub max_place_km: Option<f64>,
+  /// At least this many people within an event's radius or an alert's area.
+  pub min_population: Option<f64>,
+  /// A facility of one of these kinds (e.g. `hospital`) within it;
+  /// case-insensitive.
+  pub facility: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct RuleMatch { pub rule_id: String, pub rule_name: String, pub target: String, pub item_id: String }
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct MatchedRule { pub rule_id: String, pub rule_name: String, pub matched_at: i64 }
+
+/// What a condition is tested against, for either target.
+struct Subject {
+  class: String, severity: f64, bbox: (f64, f64, f64, f64), text: String, source: String,
+  /// Country codes and names.
+  countries: Vec<String>,
+  regions: Vec<String>,
+  place_km: Option<f64>,
+  population: Option<f64>,
+  facility_kinds: Vec<String>,
+}
+
+impl Condition {
+  fn matches(&self, s: &Subject) -> bool {
+    let any_eq = |list: &Option<Vec<String>>, v: &str| list.as_ref().is_none_or(|l| l.iter().any(|x| x.eq_ignore_ascii_case(v)));
+    let any_of = |list: &Option<Vec<String>>, vs: &[String]| list.as_ref().is_none_or(|l| l.iter().any(|x| vs.iter().any(|v| x.eq_ignore_ascii_case(v))));
+    any_eq(&self.class, &s.class)
+      && any_eq(&self.source, &s.source)
+      && any_of(&self.country, &s.countries)
+      && any_of(&self.region, &s.regions)
+      && self.max_place_km.is_none_or(|m| s.place_km.is_some_and(|km| km <= m))
+      && self.min_population.is_none_or(|m| s.population.is_some_and(|p| p >= m))
+      && any_of(&self.facility, &s.facility_kinds)
+      && self.min_severity.is_none_or(|m| s.severity >= m)
+      && self.bbox.is_none_or(|[minx, miny, maxx, maxy]| s.bbox.0 <= maxx && s.bbox.2 >= minx && s.bbox.1 <= maxy && s.bbox.3 >= miny)
+      && self.text.as_ref().is_none_or(|t| s.text.to_lowercase().contains(&t.to_lowercase()))
+  }
+}
+
+/// Parses and checks rules without storing them.
+pub fn parse(txt: &str) -> Result<Vec<(RuleYaml, Condition)>> {
+  let rules: Vec<RuleYaml> = serde_yaml::from_str(txt).context("parse rules.yaml")?;
+  rules.into_iter().map(|r| {
+    if r.target != "event" && r.target != "alert" { bail!("rule {}: target must be `event` or `alert`", r.id); }
+    let cond: Condition = serde_json::from_value(r.where_.clone()).with_context(|| format!("rule {}", r.id))?;
+    Ok((r, cond))
+  }).collect()
+}
+
+/// Stores the rules in `rules.yaml` under `data_dir`, if there is one.
+pub fn load_and_compile(db: &Db, data_dir: &Path) -> Result<usize> {
+  let path = data_dir.join("rules.yaml");
+  if !path.exists() { return Ok(0); }
+  let txt = std::fs::read_to_string(path)?;
+  let rules = parse(&txt)?;
+  let tx = db.conn.unchecked_transaction()?;
+  for (r, cond) in &rules {
+    let en = r.enabled.unwrap_or(true);
+    let spec = serde_json::to_string(cond)?;
+    // upsert rather than replace: replacing would cascade-delete the rule's matches
+    tx.execute("INSERT INTO rule(id,name,enabled,target,spec_json,updated_at) VALUES (?1,?2,?3,?4,?5,strftime('%s','now'))
+      ON CONFLICT(id) DO UPDATE SET name=excluded.name,enabled=excluded.enabled,target=excluded.target,spec_json=excluded.spec_json,updated_at=excluded.updated_at",
+      params![r.id, r.name, if en {1} else {0}, r.target, spec])?;
+  }
+  tx.commit()?;
+  Ok(rules.len())
+}
+
+fn subject(conn: &Connection, target: &str, id: &str) -> Result<Option<Subject>> {
+  let mut s = if target == "alert" {
+    conn.query_row(
+      "SELECT COALESCE(event,''), COALESCE(severity,''), bbox_minx, bbox_miny, bbox_maxx, bbox_maxy, COALESCE(headline,''), source FROM alert WHERE id=?1",
+      [id], |r| Ok(Subject {
+        class: r.get(0)?, severity: cap_severity(&r.get::<_,String>(1)?),
+        bbox: (r.get(2)?, r.get(3)?, r.get(4)?, r.get(5)?), text: r.get(6)?, source: r.get(7)?,
+        countries: Vec::new(), regions: Vec::new(), place_km: None, population: None, facility_kinds: Vec::new(),
+      })).optional()?
+  } else {
+    conn.query_row(
+      "SELECT COALESCE(class,''), COALESCE(severity,0), COALESCE(lat,0), COALESCE(lon,0), COALESCE(title,''), COALESCE(source_rank,0),
+         (SELECT source FROM source_item WHERE source_item.id=event.id), country FROM event WHERE id=?1",
+      [id], |r| {
+        let (lat, lon): (f64, f64) = (r.get(2)?, r.get(3)?);
+        // GDACS lists every country a multi-country event touches
+        let countries = r.get::<_, Option<String>>(7)?.map(|c| c.split(',').map(|c| c.trim().to_string()).collect()).unwrap_or_default();
+        Ok(Subject {
+          class: r.get(0)?, severity: r.get(1)?, bbox: (lon, lat, lon, lat), text: r.get(4)?, source: source_of_event(r.get(5)?, r.get(6)?),
+          countries, regions: Vec::new(), place_km: None, population: None, facility_kinds: Vec::new(),
+        })
+      }).optional()?
+  };
+  if let (Some(s), Some(p)) = (s.as_mut(), crate::geocode::place_of(conn, target, id)?) {
+    s.countries.extend(p.country.into_iter().chain(p.country_name));
+    s.regions.extend(p.admin1.into_iter().chain(p.admin2));
+    s.place_km = p.place_km;
+  }
+  if let (Some(s), Some(e)) = (s.as_mut(), crate::exposure::exposure_of(conn, target, id)?) {
+    s.population = Some(e.population);
+    s.facility_kinds = e.facility_counts.into_keys().collect();
+  }
+  Ok(s)
+}
+
+/// Imported events are tagged `import:<name>` by their `source_item`.
+fn source_of_event(rank: i64, item_source: Option<String>) -> String {
+  match item_source { Some(s) if rank == 2 => s, _ => source_of_rank(rank).into() }
+}
+
+/// Events keep only a `source_rank`; each collector writes its own.
+pub fn source_of_rank(rank: i64) -> &'static str {
+  match rank { 1 => "cap", 2 => "import", 4 => "emsc", 5 => "usgs", 8 => "eonet", 10 => "gdacs", _ => "unknown" }
+}
+
+/// Tests the enabled rules for `target` ("event" or "alert") against `ids`
+/// and records matches. Returns only matches that are new.
+pub fn evaluate(conn: &Connection, target: &str, ids: &[String]) -> Result<Vec<RuleMatch>> {
+  let rules: Vec<(String, String, Condition)> = {
+    let mut stmt = conn.prepare("SELECT id, name, spec_json FROM rule WHERE enabled=1 AND target=?1")?;
+    let rows = stmt.query_map([target], |r| Ok((r.get(0)?, r.get(1)?, r.get::<_,String>(2)?)))?;
+    let mut out = Vec::new();
+    for row in rows {
+      let (id, name, spec) = row?;
+      match serde_json::from_str(&spec) {
+        Ok(cond) => out.push((id, name, cond)),
+        Err(e) => tracing::warn!("rule {id}: {e}"),
+      }
+    }
+    out
+  };
+  let mut matches = Vec::new();
+  if rules.is_empty() { return Ok(matches); }
+  for id in ids {
+    let Some(s) = subject(conn, target, id)? else { continue };
+    for (rule_id, rule_name, cond) in &rules {
+      if !cond.matches(&s) { continue; }
+      conn.execute("INSERT OR IGNORE INTO rule_match(rule_id,target,item_id,matched_at) VALUES (?1,?2,?3,strftime('%s','now'))",
+        params![rule_id, target, id])?;
+      if conn.changes() > 0 {
+        matches.push(RuleMatch { rule_id: rule_id.clone(), rule_name: rule_name.clone(), target: target.into(), item_id: id.clone() });
+      }
+    }
+  }
+  Ok(matches)
+}
+
+/// Rules an item has matched, most recent first.
+pub fn matches_for(conn: &Connection, target: &str, id: &str) -> Result<Vec<MatchedRule>> {
+  let mut stmt = conn.prepare(
+    "SELECT m.rule_id, r.name, m.matched_at FROM rule_match m JOIN rule r ON r.id=m.rule_id
+     WHERE m.target=?1 AND m.item_id=?2 ORDER BY m.matched_at DESC")?;
+  let rows = stmt.query_map(params![target, id], |r| Ok(MatchedRule { rule_id: r.get(0)?, rule_name: r.get(1)?, matched_at: r.get(2)? }))?;
+  Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
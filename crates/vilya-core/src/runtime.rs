@@ -0,0 +1,84 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use crate::ai::queue::AiQueue;
+use crate::db::Db;
+use crate::ingest::tape::Tape;
+use crate::settings::{self, Settings};
+use crate::stream::{Notice, Notifier, StreamItem};
+use tokio::sync::broadcast;
+
+pub const DB_FILE: &str = "vilya.sqlite";
+
+/// The desktop app's bundle identifier, which names its data directory.
+pub const APP_ID: &str = "com.vilya.app";
+
+/// `VILYA_DATA_DIR`, else the desktop app's data directory.
+pub fn default_data_dir() -> Result<PathBuf> {
+  if let Some(d) = std::env::var_os("VILYA_DATA_DIR") { return Ok(d.into()); }
+  Ok(dirs::data_dir().context("no data directory for this user; pass --data-dir")?.join(APP_ID))
+}
+
+/// Shared state of the ingest, AI and rules pipeline. The desktop app and
+/// `vilya-daemon` each open one over the same data directory layout:
+/// `vilya.sqlite`, `settings.json` and `rules.yaml`.
+pub struct Core {
+  pub db: Db,
+  pub settings: Settings,
+  pub data_dir: PathBuf,
+  pub queue: AiQueue,
+  /// Records collector traffic, or serves it back during a replay.
+  pub tape: Tape,
+  /// Every notice as a `StreamItem`, for the HTTP API's event stream. Only
+  /// filled while something subscribes.
+  pub live: broadcast::Sender<StreamItem>,
+  notifier: Box<dyn Notifier>,
+}
+
+impl Core {
+  /// Opens the database and settings in `data_dir`.
+  pub fn open(data_dir: PathBuf, notifier: Box<dyn Notifier>) -> Result<Arc<Core>> {
+    Core::open_db(data_dir.join(DB_FILE), data_dir, notifier)
+  }
+
+  /// Like `open`, with the database at `db_path` instead of in `data_dir`.
+  pub fn open_db(db_path: PathBuf, data_dir: PathBuf, notifier: Box<dyn Notifier>) -> Result<Arc<Core>> {
+    std::fs::create_dir_all(&data_dir)?;
+    let db = Db::open(db_path)?;
+    let settings = settings::load(&data_dir).unwrap_or_else(|e| {
+      tracing::warn!("settings: {e}; using defaults");
+      Settings::default()
+    });
+    let (live, _) = broadcast::channel(1024);
+    let tape = Tape::new(&settings.tape, &data_dir);
+    Ok(Arc::new(Core { db, settings, data_dir, queue: AiQueue::default(), tape, live, notifier }))
+  }
+
+  /// Loads `rules.yaml`, the gazetteer and exposure layers and starts the
+  /// AI worker, every collector and, if enabled, the HTTP API. Only one process per database should do this.
+  pub fn start(self: &Arc<Self>) -> Result<()> {
+    if let Err(e) = crate::rules::load_and_compile(&self.db, &self.data_dir) { tracing::warn!("rules: {e}"); }
+    // full GeoNames and population loads take a while; collectors place
+    // and assess what they store meanwhile
+    let h = self.clone();
+    std::thread::spawn(move || {
+      if let Err(e) = crate::geocode::init(&h) { tracing::warn!("gazetteer: {e:#}"); }
+      if let Err(e) = crate::exposure::init(&h) { tracing::warn!("exposure: {e:#}"); }
+    });
+    crate::ai::spawn(self.clone())?;
+    crate::ingest::spawn_collectors(self.clone());
+    if let Err(e) = crate::api::spawn(self.clone()) { tracing::warn!("api: {e}"); }
+    Ok(())
+  }
+
+  pub fn notify(&self, notice: Notice) {
+    if self.live.receiver_count() > 0 {
+      match StreamItem::from_notice(&self.db, notice) {
+        // no receivers left is not an error
+        Ok(items) => for item in items { let _ = self.live.send(item); },
+        Err(e) => tracing::warn!("live: {e}"),
+      }
+    }
+    if let Err(e) = self.notifier.notify(self, notice) { tracing::warn!("notify: {e}"); }
+  }
+}
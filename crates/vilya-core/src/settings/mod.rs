@@ -0,0 +1,175 @@
+use anyhow::{Result, Context};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+  pub ai: AiSettings,
+  pub translation: TranslationSettings,
+  pub desktop: DesktopSettings,
+  pub api: ApiSettings,
+  pub drop_folder: DropFolderSettings,
+  pub tape: TapeSettings,
+  pub gazetteer: GazetteerSettings,
+  pub exposure: ExposureSettings,
+}
+
+/// The local HTTP API. Off unless `enabled`, and then only with a `token`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiSettings {
+  pub enabled: bool,
+  /// Port on 127.0.0.1.
+  pub port: u16,
+  /// Clients send it as `Authorization: Bearer <token>` or `?token=`.
+  pub token: Option<String>,
+}
+
+impl Default for ApiSettings {
+  fn default() -> Self { Self { enabled: false, port: 8787, token: None } }
+}
+
+/// A directory the `drop` collector ingests files from, for deployments
+/// where feeds arrive by USB or file share. Off unless `path` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DropFolderSettings {
+  pub path: Option<PathBuf>,
+  /// Where ingested files are moved; `<path>/archive` if unset.
+  pub archive: Option<PathBuf>,
+  /// Where files that fail are moved, each with a `.error.txt` giving the
+  /// reason; `<path>/error` if unset.
+  pub errors: Option<PathBuf>,
+  pub poll_secs: u64,
+  /// Files modified more recently are assumed to be still copying.
+  pub settle_secs: u64,
+}
+
+impl Default for DropFolderSettings {
+  fn default() -> Self { Self { path: None, archive: None, errors: None, poll_secs: 10, settle_secs: 5 } }
+}
+
+/// Recording of what the collectors receive, for `vilya replay`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TapeSettings {
+  pub record: bool,
+  /// Where tapes are written; `<data dir>/tapes` if unset.
+  pub dir: Option<PathBuf>,
+}
+
+/// The offline gazetteer events and alerts are placed with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GazetteerSettings {
+  /// GeoNames files loaded on start when the database has no gazetteer;
+  /// `<data dir>/gazetteer` if unset.
+  pub dir: Option<PathBuf>,
+  /// Farther from every populated place, an item gets no nearest place.
+  pub max_place_km: f64,
+}
+
+impl Default for GazetteerSettings {
+  fn default() -> Self { Self { dir: None, max_place_km: 300.0 } }
+}
+
+/// Population and facility layers events and alerts are assessed against.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExposureSettings {
+  /// Loaded on start when the database has no layers: `population/` holds
+  /// ASCII grids and point CSVs, `facilities/` GeoJSON and CSV files.
+  /// `<data dir>/exposure` if unset.
+  pub dir: Option<PathBuf>,
+  /// Radius around an event by severity: the first entry whose
+  /// `min_severity` the event reaches.
+  pub radii: Vec<ExposureRadius>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExposureRadius { pub min_severity: f64, pub km: f64 }
+
+impl Default for ExposureSettings {
+  fn default() -> Self {
+    let radii = [(0.75, 100.0), (0.5, 50.0), (0.25, 25.0), (0.0, 10.0)].map(|(min_severity, km)| ExposureRadius { min_severity, km });
+    Self { dir: None, radii: radii.into() }
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all="snake_case")]
+pub enum DesktopMode {
+  /// The app runs the collectors, AI worker and rules itself.
+  #[default]
+  Embedded,
+  /// The app only reads a database that `vilya-daemon` writes.
+  Attach,
+}
+
+/// Read by the desktop app only; the daemon ignores it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DesktopSettings {
+  pub mode: DesktopMode,
+  /// The daemon's `vilya.sqlite`, for attach mode.
+  pub database: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TranslationSettings {
+  pub enabled: bool,
+  /// BCP 47 language tag translations are written in, e.g. `en` or `fr`.
+  pub target_language: String,
+}
+
+impl Default for TranslationSettings {
+  fn default() -> Self { Self { enabled: false, target_language: "en".into() } }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all="snake_case")]
+pub enum AiProviderKind { Ollama, Openai, Mock }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AiSettings {
+  pub provider: AiProviderKind,
+  /// Base URL of the provider, e.g. `http://127.0.0.1:11434` for Ollama or
+  /// `http://127.0.0.1:8080/v1` for a llama.cpp / vLLM / LM Studio server.
+  pub endpoint: String,
+  pub model: String,
+  pub api_key: Option<String>,
+  pub temperature: f32,
+  pub connect_timeout_secs: u64,
+  pub request_timeout_secs: u64,
+  /// Attempts per event before a "label failed" record is written.
+  pub max_attempts: u32,
+  /// Model used for text embeddings; `None` disables them.
+  pub embedding_model: Option<String>,
+}
+
+impl Default for AiSettings {
+  fn default() -> Self {
+    Self {
+      provider: AiProviderKind::Ollama,
+      endpoint: "http://127.0.0.1:11434".into(),
+      model: "llama3.1:8b".into(),
+      api_key: None,
+      temperature: 0.1,
+      connect_timeout_secs: 5,
+      request_timeout_secs: 120,
+      max_attempts: 3,
+      embedding_model: Some("nomic-embed-text".into()),
+    }
+  }
+}
+
+/// Reads `settings.json` from the data dir; a missing file yields defaults.
+pub fn load(data_dir: &Path) -> Result<Settings> {
+  let path = data_dir.join("settings.json");
+  if !path.exists() { return Ok(Settings::default()); }
+  let txt = std::fs::read_to_string(&path)?;
+  serde_json::from_str(&txt).with_context(|| format!("parse {}", path.display()))
+}
@@ -0,0 +1,206 @@
+use std::sync::Arc;
+use anyhow::Result;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tokio::time::{sleep, Duration};
+use crate::ai::output::AiOutput;
+use crate::db::Db;
+use crate::ipc::{UiAlert, UiEvent};
+use crate::normalize::cap_severity;
+use crate::rules::RuleMatch;
+use crate::runtime::Core;
+
+/// What a window wants pushed. Unset fields do not filter. Events and
+/// alerts only reach windows that subscribed; rule matches and source
+/// status go to every window.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(default)]
+pub struct StreamFilter {
+  /// [minx, miny, maxx, maxy]
+  pub bbox: Option<[f64; 4]>,
+  /// Event classes; alerts count as class `alert`.
+  pub classes: Option<Vec<String>>,
+  pub min_severity: Option<f64>,
+}
+
+impl StreamFilter {
+  pub fn accepts(&self, class: &str, severity: f64, (minx, miny, maxx, maxy): (f64, f64, f64, f64)) -> bool {
+    self.classes.as_ref().is_none_or(|c| c.iter().any(|x| x == class))
+      && self.min_severity.is_none_or(|m| severity >= m)
+      && self.bbox.is_none_or(|[x0, y0, x1, y1]| minx <= x1 && maxx >= x0 && miny <= y1 && maxy >= y0)
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct AlertCancelled { pub id: String, pub source: String }
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct SourceStatus { pub source: String, pub ok: bool, pub items: usize, pub error: Option<String>, pub at: i64 }
+
+/// A change the pipeline reports to whoever hosts it.
+#[derive(Debug, Clone, Copy)]
+pub enum Notice<'a> {
+  Events { created: bool, ids: &'a [String] },
+  AlertsCreated(&'a [String]),
+  AlertsCancelled { source: &'a str, ids: &'a [String] },
+  RulesMatched(&'a [RuleMatch]),
+  SourceStatus(&'a SourceStatus),
+  AiLabel { id: &'a str, labels: &'a AiOutput, model: &'a str },
+  AiLabelFailed { id: &'a str, error: &'a str },
+}
+
+/// Receives pipeline notices. The desktop app pushes them to its windows;
+/// the daemon logs them.
+pub trait Notifier: Send + Sync {
+  fn notify(&self, core: &Core, notice: Notice) -> Result<()>;
+}
+
+/// Logs rule matches and collector runs; drops everything else.
+pub struct LogNotifier;
+
+impl Notifier for LogNotifier {
+  fn notify(&self, _core: &Core, notice: Notice) -> Result<()> {
+    match notice {
+      Notice::RulesMatched(ms) => for m in ms { tracing::info!("rule {} matched {} {}", m.rule_id, m.target, m.item_id); },
+      Notice::SourceStatus(s) if s.ok => tracing::info!("{}: {} items", s.source, s.items),
+      _ => {}
+    }
+    Ok(())
+  }
+}
+
+/// A notice with its rows read, for subscribers outside the process (the
+/// HTTP API's event stream). `type` matches the desktop window event names.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum StreamItem {
+  EventCreated(UiEvent),
+  EventUpdated(UiEvent),
+  AlertCreated(UiAlert),
+  AlertCancelled(AlertCancelled),
+  RuleMatched(RuleMatch),
+  SourceStatus(SourceStatus),
+  AiLabel { id: String, labels: AiOutput, model: String },
+  AiLabelFailed { id: String, error: String },
+}
+
+impl StreamItem {
+  pub fn name(&self) -> &'static str {
+    match self {
+      StreamItem::EventCreated(_) => "event_created",
+      StreamItem::EventUpdated(_) => "event_updated",
+      StreamItem::AlertCreated(_) => "alert_created",
+      StreamItem::AlertCancelled(_) => "alert_cancelled",
+      StreamItem::RuleMatched(_) => "rule_matched",
+      StreamItem::SourceStatus(_) => "source_status",
+      StreamItem::AiLabel { .. } => "ai_label",
+      StreamItem::AiLabelFailed { .. } => "ai_label_failed",
+    }
+  }
+
+  /// Only events and alerts are filtered, as for windows.
+  pub fn accepted_by(&self, f: &StreamFilter) -> bool {
+    match self {
+      StreamItem::EventCreated(e) | StreamItem::EventUpdated(e) => f.accepts(&e.class, e.severity as f64, (e.lon, e.lat, e.lon, e.lat)),
+      StreamItem::AlertCreated(a) => f.accepts("alert", cap_severity(&a.severity), a.bbox),
+      _ => true,
+    }
+  }
+
+  pub fn from_notice(db: &Db, notice: Notice) -> Result<Vec<StreamItem>> {
+    Ok(match notice {
+      Notice::Events { created, ids } => {
+        let mut out = Vec::new();
+        for id in ids {
+          let Some(e) = ui_event(db, id)? else { continue };
+          out.push(if created { StreamItem::EventCreated(e) } else { StreamItem::EventUpdated(e) });
+        }
+        out
+      }
+      Notice::AlertsCreated(ids) => {
+        let mut out = Vec::new();
+        for id in ids { out.extend(ui_alert(db, id)?.map(StreamItem::AlertCreated)); }
+        out
+      }
+      Notice::AlertsCancelled { source, ids } =>
+        ids.iter().map(|id| StreamItem::AlertCancelled(AlertCancelled { id: id.clone(), source: source.into() })).collect(),
+      Notice::RulesMatched(ms) => ms.iter().cloned().map(StreamItem::RuleMatched).collect(),
+      Notice::SourceStatus(s) => vec![StreamItem::SourceStatus(s.clone())],
+      Notice::AiLabel { id, labels, model } => vec![StreamItem::AiLabel { id: id.into(), labels: labels.clone(), model: model.into() }],
+      Notice::AiLabelFailed { id, error } => vec![StreamItem::AiLabelFailed { id: id.into(), error: error.into() }],
+    })
+  }
+}
+
+pub fn ui_event(db: &Db, id: &str) -> Result<Option<UiEvent>> {
+  Ok(db.conn.query_row("SELECT id,title,class,lat,lon,severity,first_seen FROM event WHERE id=?1", [id], UiEvent::from_row).optional()?)
+}
+
+pub fn ui_alert(db: &Db, id: &str) -> Result<Option<UiAlert>> {
+  Ok(db.conn.query_row(
+    "SELECT id,headline,event,severity,urgency,certainty,onset,expires,polygon_geojson,bbox_minx,bbox_miny,bbox_maxx,bbox_maxy FROM alert WHERE id=?1",
+    [id], UiAlert::from_row).optional()?)
+}
+
+pub fn source_status(core: &Core, source: &str, res: &Result<usize>) {
+  let status = SourceStatus {
+    source: source.into(),
+    ok: res.is_ok(),
+    items: *res.as_ref().unwrap_or(&0),
+    error: res.as_ref().err().map(|e| e.to_string()),
+    at: chrono::Utc::now().timestamp(),
+  };
+  core.notify(Notice::SourceStatus(&status));
+}
+
+/// Changes written by another process since `cursor`, as notices.
+pub(crate) fn changes_since(core: &Core, cursor: i64) -> Result<()> {
+  let conn = core.db.conn.get()?;
+  let ids = |sql: &str| -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map([cursor], |r| r.get(0))?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+  };
+  let created = ids("SELECT id FROM event WHERE first_seen > ?1")?;
+  let updated = ids("SELECT id FROM event WHERE last_seen > ?1 AND first_seen <= ?1")?;
+  // first received since: collectors rewrite every active alert on each
+  // poll, and an alert's first version dates from its first receipt
+  let alerts = ids("SELECT DISTINCT v.alert_id FROM alert_version v JOIN alert a ON a.id = v.alert_id
+    WHERE v.valid_from > ?1 AND a.cancelled_at IS NULL
+      AND NOT EXISTS (SELECT 1 FROM alert_version o WHERE o.alert_id = v.alert_id AND o.valid_from <= ?1)")?;
+  let mut stmt = conn.prepare("SELECT id, source FROM alert WHERE cancelled_at > ?1")?;
+  let cancelled = stmt.query_map([cursor], |r| Ok((r.get::<_,String>(0)?, r.get::<_,String>(1)?)))?
+    .collect::<rusqlite::Result<Vec<_>>>()?;
+  let mut stmt = conn.prepare(
+    "SELECT m.rule_id, r.name, m.target, m.item_id FROM rule_match m JOIN rule r ON r.id=m.rule_id WHERE m.matched_at > ?1")?;
+  let matches = stmt.query_map(params![cursor], |r| Ok(RuleMatch { rule_id: r.get(0)?, rule_name: r.get(1)?, target: r.get(2)?, item_id: r.get(3)? }))?
+    .collect::<rusqlite::Result<Vec<_>>>()?;
+
+  if !created.is_empty() { core.notify(Notice::Events { created: true, ids: &created }); }
+  if !updated.is_empty() { core.notify(Notice::Events { created: false, ids: &updated }); }
+  if !alerts.is_empty() { core.notify(Notice::AlertsCreated(&alerts)); }
+  for (id, source) in cancelled { core.notify(Notice::AlertsCancelled { source: &source, ids: &[id] }); }
+  if !matches.is_empty() { core.notify(Notice::RulesMatched(&matches)); }
+  Ok(())
+}
+
+/// For a process that does not run the pipeline itself (the desktop app
+/// attached to a daemon's database): polls for rows the other process
+/// wrote and reports them as notices. Timestamps are whole seconds, so the
+/// cursor trails by one second and a change may be reported twice.
+pub fn follow(core: Arc<Core>, every: Duration) {
+  tokio::spawn(async move {
+    let mut cursor = chrono::Utc::now().timestamp();
+    loop {
+      sleep(every).await;
+      let now = chrono::Utc::now().timestamp() - 1;
+      match changes_since(&core, cursor) {
+        Ok(()) => cursor = now.max(cursor),
+        Err(e) => tracing::warn!("follow: {e}"),
+      }
+    }
+  });
+}
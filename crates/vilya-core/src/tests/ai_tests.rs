@@ -0,0 +1,131 @@
+use crate::ai::{labels, mock::MockProvider, output, provider::AiProvider, queue::AiQueue, review::{self, Verdict}, translate};
+use crate::db::Db;
+
+#[tokio::test]
+async fn mock_provider_is_deterministic(){
+  let p = MockProvider::default();
+  let a = p.complete("Text:\nM5.2 earthquake near Izmir", None).await.unwrap();
+  let b = p.complete("Text:\nM5.2 earthquake near Izmir", None).await.unwrap();
+  assert_eq!(a, b);
+  let v: serde_json::Value = serde_json::from_str(&a).unwrap();
+  assert_eq!(v["class"], "eq");
+}
+
+#[tokio::test]
+async fn mock_provider_canned_response(){
+  let p = MockProvider::with_response(r#"{"class":"flood"}"#);
+  assert_eq!(p.complete("anything", None).await.unwrap(), r#"{"class":"flood"}"#);
+}
+
+#[test]
+fn queue_serves_most_severe_first_and_dedups(){
+  let q = AiQueue::default();
+  q.push("low", 0.1, false);
+  q.push("high", 0.9, false);
+  q.push("mid", 0.5, false);
+  q.push("low", 0.7, false);
+  assert_eq!(q.stats().depth, 3);
+  let order: Vec<String> = std::iter::from_fn(|| q.try_pop()).map(|j| j.event_id).collect();
+  assert_eq!(order, ["high", "low", "mid"]);
+  assert_eq!(q.stats().enqueued, 3);
+}
+
+#[test]
+fn output_parse_tolerates_chatty_responses(){
+  let raw = "Sure! Here is the {classification}:\n```json\n{\"class\":\"Earthquake\",\"confidence\":0.9,\"severity\":0.4,\"entities\":[{\"name\":\"Izmir\",\"kind\":\"location\"},{\"name\":\" izmir \",\"kind\":\"location\"}]}\n```";
+  let out = output::parse(raw).unwrap();
+  assert_eq!(out.class, "eq");
+  assert_eq!(out.entities.len(), 1);
+  assert_eq!(out.entities[0].name, "Izmir");
+}
+
+#[test]
+fn output_parse_rejects_invalid_values(){
+  assert!(output::parse(r#"{"class":"meteor","confidence":0.9,"severity":0.4,"entities":[]}"#).is_err());
+  assert!(output::parse(r#"{"class":"flood","confidence":1.5,"severity":0.4,"entities":[]}"#).is_err());
+  assert!(output::parse("no json here").is_err());
+}
+
+#[tokio::test]
+async fn relabel_batch_keeps_history_and_compares(){
+  let db = Db::open(":memory:".into()).unwrap();
+  db.conn.execute("INSERT INTO event(id,first_seen,last_seen,title,summary,class,severity) VALUES ('a',1,1,'M5 earthquake','','eq',0.5)", []).unwrap();
+  let p = MockProvider::default();
+  let run = labels::classify(&p, "M5 earthquake", "", 3).await;
+  // the first reply validated
+  assert_eq!(run.attempts, 1);
+  labels::store(&db, "a", &run, None, true).unwrap();
+
+  let stale = labels::RelabelFilter { stale_only: true, ..Default::default() };
+  let batch = labels::relabel(&db, &p, &stale, 3, false).await.unwrap();
+  assert!(labels::compare(&db, &batch).unwrap().is_empty());
+
+  let batch = labels::relabel(&db, &p, &labels::RelabelFilter::default(), 3, false).await.unwrap();
+  let cmp = labels::compare(&db, &batch).unwrap();
+  assert_eq!(cmp.len(), 1);
+  assert!(cmp[0].before.is_some());
+  assert_eq!(labels::history(&db, "a").unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn human_correction_overrides_collector_updates(){
+  let db = Db::open(":memory:".into()).unwrap();
+  db.conn.execute("INSERT INTO event(id,first_seen,last_seen,title,summary,class,severity) VALUES ('a',1,1,'M5 earthquake','','alert',0.2)", []).unwrap();
+  let run = labels::classify(&MockProvider::default(), "M5 earthquake", "", 1).await;
+  labels::store(&db, "a", &run, None, true).unwrap();
+  assert!(review::review(&db, "a", Verdict::Corrected, Some("meteor".into()), None, None).is_err());
+  review::review(&db, "a", Verdict::Corrected, Some("flood".into()), None, None).unwrap();
+  db.conn.execute("UPDATE event SET class='eq' WHERE id='a'", []).unwrap();
+  let class: String = db.conn.query_row("SELECT class FROM event WHERE id='a'", [], |r| r.get(0)).unwrap();
+  assert_eq!(class, "flood");
+}
+
+#[tokio::test]
+async fn translations_are_cached_and_skip_target_language(){
+  let db = Db::open(":memory:".into()).unwrap();
+  db.conn.execute("INSERT INTO alert(id,source,headline,description,language,raw_json,last_seen) VALUES ('de1','cap','Sturmwarnung','','de-DE','{}',1)", []).unwrap();
+  db.conn.execute("INSERT INTO alert(id,source,headline,description,language,raw_json,last_seen) VALUES ('de2','cap','Sturmwarnung','','de-DE','{}',2)", []).unwrap();
+  db.conn.execute("INSERT INTO alert(id,source,headline,language,raw_json,last_seen) VALUES ('en1','cap','Storm warning','en-US','{}',3)", []).unwrap();
+  let p = MockProvider::with_response(r#"{"source_language":"de","translation":"Storm warning"}"#);
+  assert_eq!(translate::translate_pending(&db, &p, "en", 10).await.unwrap(), 3);
+  let cached: i64 = db.conn.query_row("SELECT COUNT(*) FROM translation_cache", [], |r| r.get(0)).unwrap();
+  assert_eq!(cached, 1);
+  let t = translate::for_item(&db, "alert", "de2", "en").unwrap();
+  assert_eq!((t[0].original.as_str(), t[0].translated.as_str()), ("Sturmwarnung", "Storm warning"));
+  assert_eq!(translate::translate_pending(&db, &p, "en", 10).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn failed_translations_back_off_and_undeclared_target_text_is_kept(){
+  let db = Db::open(":memory:".into()).unwrap();
+  db.conn.execute("INSERT INTO alert(id,source,headline,language,raw_json,last_seen) VALUES ('de1','cap','Sturmwarnung','de','{}',1)", []).unwrap();
+  db.conn.execute("INSERT INTO event(id,first_seen,last_seen,title,summary,class,severity)
+    VALUES ('e1',1,1,'Flooding along the river','Water levels are rising near the town and the bridge is closed','flood',0.3)", []).unwrap();
+  assert_eq!(translate::guess_language("Water levels are rising near the town and the bridge is closed"), Some("en"));
+  assert_eq!(translate::guess_language("Sturmwarnung für die Küste und das Hinterland"), Some("de"));
+  assert_eq!(translate::guess_language("M 5.1 - 10 km SW"), None);
+
+  // the reply never parses: only the undeclared English event gets through
+  let p = MockProvider::with_response("no json here");
+  assert_eq!(translate::translate_pending(&db, &p, "en", 10).await.unwrap(), 1);
+  let t = translate::for_item(&db, "event", "e1", "en").unwrap();
+  assert!(t.iter().all(|t| t.original == t.translated && t.source_lang.as_deref() == Some("en")), "{t:?}");
+  let attempts = || db.conn.query_row("SELECT attempts FROM translation_failure WHERE item_id='de1'", [], |r| r.get::<_, i64>(0)).unwrap();
+  assert_eq!(attempts(), 1);
+
+  // backing off: not retried until due, then with a longer delay
+  assert_eq!(translate::translate_pending(&db, &p, "en", 10).await.unwrap(), 0);
+  assert_eq!(attempts(), 1);
+  db.conn.execute("UPDATE translation_failure SET retry_at=0", []).unwrap();
+  translate::translate_pending(&db, &p, "en", 10).await.unwrap();
+  assert_eq!(attempts(), 2);
+  let delay: i64 = db.conn.query_row("SELECT retry_at - strftime('%s','now') FROM translation_failure", [], |r| r.get(0)).unwrap();
+  assert!((110..=120).contains(&delay), "{delay}");
+
+  // new text is tried at once, and success clears the failure
+  db.conn.execute("UPDATE alert SET headline='Unwetterwarnung' WHERE id='de1'", []).unwrap();
+  let ok = MockProvider::with_response(r#"{"source_language":"de","translation":"Severe weather warning"}"#);
+  assert_eq!(translate::translate_pending(&db, &ok, "en", 10).await.unwrap(), 1);
+  let left: i64 = db.conn.query_row("SELECT COUNT(*) FROM translation_failure", [], |r| r.get(0)).unwrap();
+  assert_eq!(left, 0);
+}
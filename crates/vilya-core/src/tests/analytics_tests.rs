@@ -0,0 +1,62 @@
+use crate::analytics::{self, AnalyticsQuery, Bucket, GroupBy};
+use crate::db::Db;
+
+const DAY: i64 = 86400;
+// 2024-01-01, a Monday
+const T0: i64 = 1_704_067_200;
+
+fn ev(db: &Db, id: &str, ts: i64, class: &str, lat: f64, lon: f64) {
+  db.conn.execute("INSERT INTO event(id,first_seen,last_seen,title,class,severity,lat,lon,source_rank) VALUES (?1,?2,?2,?1,?3,0.6,?4,?5,8)",
+    rusqlite::params![id, ts, class, lat, lon]).unwrap();
+}
+
+#[test]
+fn buckets_align_to_utc_boundaries(){
+  assert_eq!(Bucket::Day.start(T0 + 5000), T0);
+  assert_eq!(Bucket::Hour.start(T0 + 5000), T0 + 3600);
+  assert_eq!(Bucket::Week.start(T0 + 6 * DAY), T0);
+  assert_eq!(Bucket::Week.start(T0 - 1), T0 - 7 * DAY);
+}
+
+#[test]
+fn series_flags_spikes_over_trailing_baseline(){
+  let db = Db::open(":memory:".into()).unwrap();
+  // one wildfire a day for a week, then five on one day; floods stay flat
+  for d in 0..7 { ev(&db, &format!("wf{d}"), T0 + d * DAY + 600, "wildfire", 38.0, 23.7); ev(&db, &format!("fl{d}"), T0 + d * DAY, "flood", 39.5, -0.4); }
+  for i in 0..5 { ev(&db, &format!("spike{i}"), T0 + 7 * DAY + i * 60, "wildfire", 38.1, 23.8); }
+  ev(&db, "fl7", T0 + 7 * DAY, "flood", 39.5, -0.4);
+
+  let q = AnalyticsQuery { since: Some(T0 + 7 * DAY), until: Some(T0 + 8 * DAY), group_by: Some(GroupBy::Class), ..Default::default() };
+  let s = analytics::series(&db, &q, 0).unwrap();
+  assert_eq!(s.points.len(), 2);
+  let wf = s.points.iter().find(|p| p.group == "wildfire").unwrap();
+  assert_eq!((wf.count, wf.baseline, wf.anomaly), (5, 1.0, true));
+  assert!(!s.points.iter().find(|p| p.group == "flood").unwrap().anomaly);
+
+  let q = AnalyticsQuery { since: Some(T0), until: Some(T0 + 8 * DAY), group_by: Some(GroupBy::Source), bucket: Bucket::Week, ..Default::default() };
+  let s = analytics::series(&db, &q, 0).unwrap();
+  assert_eq!(s.points.iter().map(|p| (p.group.as_str(), p.count)).collect::<Vec<_>>(), [("eonet", 14), ("eonet", 6)]);
+
+  db.conn.execute("INSERT INTO aoi VALUES ('attica','Attica',23,37,25,39,0)", []).unwrap();
+  let q = AnalyticsQuery { since: Some(T0 + 7 * DAY), until: Some(T0 + 8 * DAY), group_by: Some(GroupBy::Aoi), ..Default::default() };
+  let s = analytics::series(&db, &q, 0).unwrap();
+  assert_eq!(s.points.iter().map(|p| (p.group.as_str(), p.count)).collect::<Vec<_>>(), [("attica", 5)]);
+
+  let q = AnalyticsQuery { since: Some(T0), until: Some(T0 + 3650 * DAY), bucket: Bucket::Hour, ..Default::default() };
+  assert!(analytics::series(&db, &q, 0).is_err());
+}
+
+#[test]
+fn heatmap_counts_geohash_cells(){
+  let db = Db::open(":memory:".into()).unwrap();
+  for i in 0..4 { ev(&db, &format!("a{i}"), T0 + i, "wildfire", 38.0, 23.7); }
+  ev(&db, "b", T0, "flood", 39.5, -0.4);
+  ev(&db, "old", T0 - 2 * DAY, "wildfire", 38.0, 23.7);
+  let q = AnalyticsQuery { since: Some(T0), until: Some(T0 + DAY), ..Default::default() };
+  let cells = analytics::heatmap(&db, &q, 3, 0).unwrap();
+  assert_eq!(cells.len(), 2);
+  assert_eq!((cells[0].count, cells[0].anomaly), (4, true));
+  assert!((cells[0].baseline - 1.0 / 7.0).abs() < 1e-9);
+  assert!((cells[0].lat - 38.0).abs() < 1.0 && (cells[0].lon - 23.7).abs() < 1.0);
+  assert_eq!(cells[1].count, 1);
+}
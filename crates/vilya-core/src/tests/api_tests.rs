@@ -0,0 +1,59 @@
+use crate::api;
+use crate::ingest;
+use crate::runtime::Core;
+use crate::stream::LogNotifier;
+
+#[tokio::test]
+async fn api_requires_token_and_streams_new_events(){
+  let dir = std::env::temp_dir().join(format!("vilya-test-{}", uuid::Uuid::new_v4()));
+  let core = Core::open_db(":memory:".into(), dir.clone(), Box::new(LogNotifier)).unwrap();
+  let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+  let base = format!("http://{}", listener.local_addr().unwrap());
+  let app = api::router(core.clone(), "s3cret".into());
+  tokio::spawn(async move { axum::serve(listener, app).await });
+  let http = reqwest::Client::new();
+
+  assert_eq!(http.get(format!("{base}/api/events")).send().await.unwrap().status(), 401);
+  let denied = http.get(format!("{base}/api/events?token=wrong")).send().await.unwrap();
+  assert_eq!(denied.status(), 401);
+  assert_eq!(denied.json::<serde_json::Value>().await.unwrap()["code"], "unauthorized");
+  let missing = http.get(format!("{base}/api/events/nope")).bearer_auth("s3cret").send().await.unwrap();
+  assert_eq!(missing.status(), 404);
+  assert_eq!(missing.json::<serde_json::Value>().await.unwrap()["code"], "not_found");
+
+  // only classes the stream asked for come through
+  let mut sse = http.get(format!("{base}/api/stream?token=s3cret&classes=eq")).send().await.unwrap();
+  assert_eq!(sse.status(), 200);
+  let usgs = r#"{"features":[{"id":"q1","properties":{"mag":5.0,"title":"M 5.0"},"geometry":{"coordinates":[10,20]}}]}"#;
+  ingest::ingest(&core, "usgs", usgs).unwrap();
+  let mut got = String::new();
+  while !got.contains("\n\n") {
+    let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), sse.chunk()).await.unwrap().unwrap().unwrap();
+    got.push_str(&String::from_utf8_lossy(&chunk));
+  }
+  assert!(got.contains("event: event_created"), "{got}");
+  assert!(got.contains(r#""id":"q1""#), "{got}");
+
+  let found: Vec<serde_json::Value> = http.get(format!("{base}/api/events?q=M%205")).bearer_auth("s3cret").send().await.unwrap().json().await.unwrap();
+  assert_eq!(found.len(), 1);
+  let d: serde_json::Value = http.get(format!("{base}/api/events/q1")).bearer_auth("s3cret").send().await.unwrap().json().await.unwrap();
+  assert_eq!(d["title"], "M 5.0");
+  let _ = std::fs::remove_dir_all(dir);
+}
+
+#[tokio::test]
+async fn query_tokens_are_url_decoded(){
+  let dir = std::env::temp_dir().join(format!("vilya-test-{}", uuid::Uuid::new_v4()));
+  let core = Core::open_db(":memory:".into(), dir.clone(), Box::new(LogNotifier)).unwrap();
+  let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+  let base = format!("http://{}", listener.local_addr().unwrap());
+  let app = api::router(core.clone(), "a+b/c=d é".into());
+  tokio::spawn(async move { axum::serve(listener, app).await });
+  let http = reqwest::Client::new();
+
+  // an unencoded `+` is a space
+  for (query, status) in [("token=a%2Bb%2Fc%3Dd+%C3%A9", 200), ("limit=1&token=a%2Bb%2Fc%3Dd%20%C3%A9", 200), ("token=a+b%2Fc%3Dd+%C3%A9", 401)] {
+    assert_eq!(http.get(format!("{base}/api/events?{query}")).send().await.unwrap().status(), status, "{query}");
+  }
+  let _ = std::fs::remove_dir_all(dir);
+}
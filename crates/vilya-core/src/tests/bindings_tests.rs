@@ -0,0 +1,41 @@
+use ts_rs::TS;
+use crate::ai::{embed::SimilarEvent, labels::{LabelComparison, LabelRecord, RelabelFilter}, output::{AiOutput, Entity, EntityKind}, queue::QueueStats, review::{Review, Verdict}, summary::{Summary, SummaryScope}, translate::FieldTranslation};
+use crate::analytics::{AnalyticsQuery, Baseline, Bucket, GroupBy, HeatCell, SeriesPoint, TimeSeries};
+use crate::export::{ExportFormat, ExportQuery};
+use crate::exposure::{ExposedFacility, Exposure, LayerReport};
+use crate::geocode::Place;
+use crate::history::{Change, ChangeKind};
+use crate::ingest::import::{ColumnMap, ImportBatch, ImportFormat, ImportSpec, ImportTarget};
+use crate::entities::{EntityGraph, EntityRow};
+use crate::ipc::{Aoi, EventDetail, IpcError, NearbyEvent, RelabelReport, ShakingContour, SourcePayload, UiAlert, UiEvent};
+use crate::merge::DuplicateCandidate;
+use crate::quake::{Contour, Quake, Shaking};
+use crate::rules::{MatchedRule, RuleMatch};
+use crate::stream::{AlertCancelled, SourceStatus, StreamFilter};
+
+const HEADER: &str = "// Generated by `cargo test export_ts_bindings` from the Rust IPC types. Do not edit.\n";
+
+/// Rewrites `apps/desktop/ui/src/lib/bindings.ts`. Run `cargo test` after changing any
+/// type that crosses IPC and commit the regenerated file with the change.
+#[test]
+fn export_ts_bindings(){
+  let decls = [
+    serde_json::Value::decl(), IpcError::decl(),
+    UiEvent::decl(), UiAlert::decl(), EventDetail::decl(), SourcePayload::decl(), NearbyEvent::decl(), Aoi::decl(),
+    StreamFilter::decl(), AlertCancelled::decl(), SourceStatus::decl(), RuleMatch::decl(), MatchedRule::decl(),
+    QueueStats::decl(), AiOutput::decl(), Entity::decl(), EntityKind::decl(),
+    LabelRecord::decl(), LabelComparison::decl(), RelabelFilter::decl(), RelabelReport::decl(),
+    SummaryScope::decl(), Summary::decl(), SimilarEvent::decl(), DuplicateCandidate::decl(),
+    EntityRow::decl(), EntityGraph::decl(), FieldTranslation::decl(), Review::decl(), Verdict::decl(),
+    AnalyticsQuery::decl(), Baseline::decl(), Bucket::decl(), GroupBy::decl(), SeriesPoint::decl(), TimeSeries::decl(), HeatCell::decl(),
+    ExportQuery::decl(), ExportFormat::decl(),
+    ImportSpec::decl(), ImportFormat::decl(), ImportTarget::decl(), ColumnMap::decl(), ImportBatch::decl(),
+    Change::decl(), ChangeKind::decl(), Place::decl(), Exposure::decl(), ExposedFacility::decl(), LayerReport::decl(),
+    Quake::decl(), Shaking::decl(), Contour::decl(), ShakingContour::decl(),
+  ];
+  let mut out = String::from(HEADER);
+  // ts-rs types i64/u64 as bigint, but IPC payloads are plain JSON numbers
+  for d in decls { out.push_str("\nexport "); out.push_str(&d.replace("bigint", "number")); out.push('\n'); }
+  let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../apps/desktop/ui/src/lib/bindings.ts");
+  std::fs::write(path, out).unwrap();
+}
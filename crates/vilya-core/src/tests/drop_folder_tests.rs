@@ -0,0 +1,98 @@
+use std::time::{Duration, SystemTime};
+use crate::ingest::drop_folder;
+use crate::runtime::Core;
+use crate::settings::DropFolderSettings;
+use crate::stream::LogNotifier;
+
+const ATOM: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom" xmlns:cap="urn:oasis:names:tc:emergency:cap:1.2">
+  <id>https://api.weather.gov/alerts/active</id><title>Current watches, warnings, and advisories</title>
+  <entry>
+    <id>urn:oid:2.49.0.1.840.0.a1</id><title>Flood Warning issued for Harris County</title>
+    <summary>Heavy rain has caused flooding.</summary>
+    <cap:event>Flood Warning</cap:event><cap:msgType>Alert</cap:msgType>
+    <cap:expires>2099-01-01T00:00:00-05:00</cap:expires><cap:severity>Severe</cap:severity>
+    <cap:areaDesc>Harris, TX</cap:areaDesc>
+    <cap:polygon>29.5,-95.5 29.5,-95.0 30.0,-95.0 30.0,-95.5 29.5,-95.5</cap:polygon>
+  </entry>
+</feed>"#;
+
+/// `/api/v3/events/geojson`: one feature per geometry of an event.
+const EONET_GEOJSON: &str = r#"{"type":"FeatureCollection","features":[
+  {"type":"Feature","properties":{"id":"EONET_1","title":"Wildfire A","date":"2024-06-01T00:00:00Z","magnitudeValue":900,
+    "categories":[{"id":"wildfires","title":"Wildfires"}],"sources":[{"id":"InciWeb"}]},"geometry":{"type":"Point","coordinates":[-120,38]}},
+  {"type":"Feature","properties":{"id":"EONET_1","title":"Wildfire A","date":"2024-06-02T00:00:00Z","magnitudeValue":1200,
+    "categories":[{"id":"wildfires","title":"Wildfires"}],"sources":[{"id":"InciWeb"}]},
+    "geometry":{"type":"Polygon","coordinates":[[[-121,39],[-119,39],[-119,41],[-121,41],[-121,39]]]}}]}"#;
+
+#[test]
+fn sniffs_feed_files_by_content(){
+  assert_eq!(drop_folder::sniff("<alert xmlns=\"urn:oasis:names:tc:emergency:cap:1.2\"/>").unwrap(), "cap");
+  assert_eq!(drop_folder::sniff(ATOM).unwrap(), "nws");
+  assert_eq!(drop_folder::sniff(r#"{"events":[]}"#).unwrap(), "eonet");
+  assert_eq!(drop_folder::sniff(r#"{"features":[{"properties":{"mag":4.2}}]}"#).unwrap(), "usgs");
+  assert_eq!(drop_folder::sniff(EONET_GEOJSON).unwrap(), "eonet");
+  assert_eq!(drop_folder::sniff(r#"{"features":[{"properties":{"messageType":"Alert"}}]}"#).unwrap(), "nws");
+  assert!(drop_folder::sniff("<rss/>").is_err());
+  assert!(drop_folder::sniff("name,lat\n").is_err());
+}
+
+#[test]
+fn scan_ingests_settled_files_and_moves_them_out(){
+  let dir = std::env::temp_dir().join(format!("vilya-test-{}", uuid::Uuid::new_v4()));
+  let inbox = dir.join("inbox");
+  std::fs::create_dir_all(&inbox).unwrap();
+  let core = Core::open_db(":memory:".into(), dir.clone(), Box::new(LogNotifier)).unwrap();
+  std::fs::write(inbox.join("quakes.json"), r#"{"type":"FeatureCollection","features":[
+    {"type":"Feature","id":"q1","properties":{"mag":5.0,"title":"M 5.0 - Alpha"},"geometry":{"type":"Point","coordinates":[10,20,5]}}]}"#).unwrap();
+  std::fs::write(inbox.join("nws.atom"), ATOM).unwrap();
+  std::fs::write(inbox.join("notes.txt"), "not a feed").unwrap();
+  std::fs::write(inbox.join(".copying.json"), "{").unwrap();
+
+  let cfg = DropFolderSettings::default();
+  // nothing has settled yet
+  assert_eq!(drop_folder::scan(&core, &inbox, &cfg, SystemTime::now()).unwrap(), 0);
+  assert!(inbox.join("quakes.json").exists());
+
+  let later = SystemTime::now() + Duration::from_secs(60);
+  assert_eq!(drop_folder::scan(&core, &inbox, &cfg, later).unwrap(), 2);
+  assert!(inbox.join("archive/quakes.json").exists() && inbox.join("archive/nws.atom").exists());
+  let reason = std::fs::read_to_string(inbox.join("error/notes.txt.error.txt")).unwrap();
+  assert!(reason.contains("neither XML nor JSON"), "{reason}");
+  assert!(inbox.join(".copying.json").exists());
+
+  let (source, severity, maxy): (String, String, f64) = core.db.conn.query_row(
+    "SELECT source, severity, bbox_maxy FROM alert WHERE id='urn:oid:2.49.0.1.840.0.a1'", [], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?))).unwrap();
+  assert_eq!((source.as_str(), severity.as_str(), maxy), ("nws", "Severe", 30.0));
+  let events: i64 = core.db.conn.query_row("SELECT COUNT(*) FROM event", [], |r| r.get(0)).unwrap();
+  assert_eq!(events, 1);
+
+  // a second drop of the same name does not overwrite the archived one
+  std::fs::write(inbox.join("nws.atom"), ATOM).unwrap();
+  drop_folder::scan(&core, &inbox, &cfg, later).unwrap();
+  assert_eq!(std::fs::read_dir(inbox.join("archive")).unwrap().count(), 3);
+  let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn eonet_geojson_is_stored_and_unmovable_files_do_not_stop_the_scan(){
+  let dir = std::env::temp_dir().join(format!("vilya-test-{}", uuid::Uuid::new_v4()));
+  let inbox = dir.join("inbox");
+  std::fs::create_dir_all(&inbox).unwrap();
+  let core = Core::open_db(":memory:".into(), dir.clone(), Box::new(LogNotifier)).unwrap();
+  std::fs::write(inbox.join("a-fires.geojson"), EONET_GEOJSON).unwrap();
+  std::fs::write(inbox.join("b-nws.atom"), ATOM).unwrap();
+  // the archive cannot be created: files stay, but every one is stored
+  std::fs::write(dir.join("archive"), "").unwrap();
+  let cfg = DropFolderSettings { archive: Some(dir.join("archive")), ..Default::default() };
+  let later = SystemTime::now() + Duration::from_secs(60);
+  assert_eq!(drop_folder::scan(&core, &inbox, &cfg, later).unwrap(), 2);
+  assert!(inbox.join("a-fires.geojson").exists());
+
+  // the latest geometry, a polygon, placed at its mean vertex
+  let (class, lat, lon): (String, f64, f64) = core.db.conn.query_row(
+    "SELECT class, lat, lon FROM event WHERE id='EONET_1'", [], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?))).unwrap();
+  assert_eq!(class, "wildfires");
+  assert!((lat - 40.0).abs() < 1e-9 && (lon + 120.0).abs() < 1e-9, "{lat},{lon}");
+  let _ = std::fs::remove_dir_all(dir);
+}
@@ -0,0 +1,96 @@
+use anyhow::Result;
+use futures::future::BoxFuture;
+use crate::ai::{embed, mock::MockProvider, provider::AiProvider};
+use crate::db::Db;
+use crate::ipc::{detail, query};
+use crate::merge;
+
+/// The mock's vectors, halved, under another model id.
+struct OtherModel(MockProvider);
+
+impl AiProvider for OtherModel {
+  fn name(&self) -> &'static str { "other" }
+  fn model(&self) -> &str { "other" }
+  fn complete<'a>(&'a self, prompt: &'a str, schema: Option<&'a serde_json::Value>) -> BoxFuture<'a, Result<String>> { self.0.complete(prompt, schema) }
+  fn embedding_model(&self) -> Option<&str> { Some("other") }
+  fn embed<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f32>>> {
+    Box::pin(async move { Ok(self.0.embed(text).await?.into_iter().take(32).collect()) })
+  }
+}
+
+fn event(db: &Db, id: &str, rank: i64, t: i64, lat: f64, title: &str) {
+  db.conn.execute("INSERT INTO event(id,first_seen,last_seen,title,summary,class,severity,lat,lon,source_rank) VALUES (?1,?2,?2,?3,'','other',0.3,?4,10.0,?5)",
+    rusqlite::params![id, t, title, lat, rank]).unwrap();
+}
+
+#[tokio::test]
+async fn embeddings_are_kept_per_model_and_text(){
+  let db = Db::open(":memory:".into()).unwrap();
+  event(&db, "a", 5, 100, 50.0, "River flood in Dresden");
+  let (mock, other) = (MockProvider::default(), OtherModel(MockProvider::default()));
+  assert!(embed::embed_event(&db, &mock, "a").await.unwrap());
+  assert!(!embed::embed_event(&db, &mock, "a").await.unwrap());
+  assert!(embed::embed_event(&db, &other, "a").await.unwrap());
+  let dims: Vec<(String, i64)> = db.conn.prepare("SELECT model, dim FROM event_embedding ORDER BY model").unwrap()
+    .query_map([], |r| Ok((r.get(0)?, r.get(1)?))).unwrap().collect::<rusqlite::Result<_>>().unwrap();
+  assert_eq!(dims, [("mock".to_string(), 64), ("other".to_string(), 32)]);
+
+  // new text replaces only that model's vector
+  db.conn.execute("UPDATE event SET title='River flood in Dresden and Meissen' WHERE id='a'", []).unwrap();
+  assert!(embed::embed_event(&db, &mock, "a").await.unwrap());
+  let n: i64 = db.conn.query_row("SELECT COUNT(*) FROM event_embedding WHERE event_id='a'", [], |r| r.get(0)).unwrap();
+  assert_eq!(n, 2);
+
+  // vectors are unit length, so a vector scores 1 against itself
+  let v = embed::from_blob(&embed::to_blob(&[3.0, 4.0]));
+  assert_eq!(v, [0.6, 0.8]);
+  assert!((embed::dot(&v, &v) - 1.0).abs() < 1e-6);
+}
+
+#[tokio::test]
+async fn similar_events_and_semantic_search_rank_by_shared_words(){
+  let db = Db::open(":memory:".into()).unwrap();
+  event(&db, "flood1", 5, 100, 50.0, "River flood in Dresden");
+  event(&db, "flood2", 8, 200, 50.1, "Dresden river flood warning");
+  event(&db, "fire", 8, 300, 40.0, "Wildfire near Athens");
+  let (mock, other) = (MockProvider::default(), OtherModel(MockProvider::default()));
+  for id in ["flood1", "flood2", "fire"] { embed::embed_event(&db, &mock, id).await.unwrap(); }
+  embed::embed_event(&db, &other, "fire").await.unwrap();
+
+  let s = embed::similar_events(&db, "mock", "flood1", 10).unwrap();
+  assert_eq!(s.iter().map(|s| s.id.as_str()).collect::<Vec<_>>(), ["flood2", "fire"]);
+  assert!(s[0].score > 0.5 && s[1].score < 0.1, "{s:?}");
+  // only vectors of the queried model are compared
+  assert!(embed::similar_events(&db, "other", "flood1", 10).is_err());
+  assert!(embed::similar_events(&db, "other", "fire", 10).unwrap().is_empty());
+
+  let s = embed::semantic_search(&db, &mock, "wildfire Athens", 2).await.unwrap();
+  assert_eq!(s[0].id, "fire");
+  assert_eq!(s.len(), 2);
+}
+
+#[tokio::test]
+async fn duplicates_from_other_sources_are_merged_into_the_first_report(){
+  let db = Db::open(":memory:".into()).unwrap();
+  let mock = MockProvider::default();
+  event(&db, "usgs1", 5, 1000, 50.0, "M 5.1 earthquake near Dresden");
+  event(&db, "emsc1", 4, 1600, 50.2, "M 5.1 earthquake near Dresden");
+  // the same feed's aftershock and a far-away report stay separate
+  event(&db, "usgs2", 5, 1900, 50.1, "M 5.1 earthquake near Dresden");
+  event(&db, "gdacs1", 10, 1700, 60.0, "M 5.1 earthquake near Dresden");
+  for id in ["usgs1", "emsc1", "usgs2", "gdacs1"] { embed::embed_event(&db, &mock, id).await.unwrap(); }
+
+  assert_eq!(merge::merge_semantic_duplicates(&db, "mock", "emsc1").unwrap(), 1);
+  assert_eq!(merge::merge_semantic_duplicates(&db, "mock", "usgs2").unwrap(), 0);
+  assert_eq!(merge::merge_semantic_duplicates(&db, "mock", "gdacs1").unwrap(), 0);
+  assert_eq!(merge::merge_semantic_duplicates(&db, "mock", "emsc1").unwrap(), 0);
+  let links: Vec<(String, String)> = db.conn.prepare("SELECT event_id, duplicate_of FROM event_duplicate ORDER BY event_id").unwrap()
+    .query_map([], |r| Ok((r.get(0)?, r.get(1)?))).unwrap().collect::<rusqlite::Result<_>>().unwrap();
+  // usgs2 also pairs with emsc1, but emsc1's first report is from its own feed
+  assert_eq!(links, [("emsc1".to_string(), "usgs1".to_string())]);
+
+  let found = query::search_events(&db, Some("Dresden"), None, None, None, 10).unwrap();
+  assert_eq!(found.iter().map(|e| e.id.as_str()).collect::<Vec<_>>(), ["usgs2", "gdacs1", "usgs1"]);
+  let d = detail::event_detail(&db, "emsc1", 100.0, 3600, 0).unwrap();
+  assert_eq!(d.duplicate_of.as_deref(), Some("usgs1"));
+}
@@ -0,0 +1,86 @@
+use crate::ai::{labels, mock::MockProvider};
+use crate::db::Db;
+use crate::entities;
+
+async fn label(db: &Db, id: &str, last_seen: i64, entities: &str) {
+  db.conn.execute("INSERT INTO event(id,first_seen,last_seen,title,summary,class,severity) VALUES (?1,?2,?2,'Flood','','flood',0.5)",
+    rusqlite::params![id, last_seen]).unwrap();
+  let p = MockProvider::with_response(format!(r#"{{"class":"flood","confidence":0.8,"severity":0.5,"entities":{entities}}}"#));
+  let run = labels::classify(&p, "Flood", "", 1).await;
+  labels::store(db, id, &run, None, true).unwrap();
+}
+
+fn names(rows: &[entities::EntityRow]) -> Vec<(&str, &str, i64)> {
+  rows.iter().map(|r| (r.kind.as_str(), r.name.as_str(), r.events)).collect()
+}
+
+#[tokio::test]
+async fn labels_link_typed_entities_by_normalised_name(){
+  let db = Db::open(":memory:".into()).unwrap();
+  assert_eq!(entities::normalize("The Port of  Rotterdam."), "port of rotterdam");
+  assert_eq!(entities::normalize("The"), "the");
+  label(&db, "a", 100, r#"[{"name":"Port of Rotterdam","kind":"facility"},{"name":"Rotterdam","kind":"gpe"},"Red Cross"]"#).await;
+  label(&db, "b", 200, r#"[{"name":"the port of rotterdam","kind":"facility"},{"name":"Rotterdam","kind":"person"},{"name":"...","kind":"location"}]"#).await;
+  assert_eq!(names(&entities::list(&db, None, None, 10).unwrap()),
+    [("facility", "Port of Rotterdam", 2), ("other", "Red Cross", 1), ("location", "Rotterdam", 1), ("person", "Rotterdam", 1)]);
+  assert_eq!(names(&entities::list(&db, Some("ROTTERDAM"), Some("location"), 10).unwrap()), [("location", "Rotterdam", 1)]);
+
+  // relabelling replaces the event's links
+  label(&db, "c", 300, "[]").await;
+  let run = labels::classify(&MockProvider::with_response(r#"{"class":"flood","confidence":0.8,"severity":0.5,"entities":["Red Cross"]}"#), "Flood", "", 1).await;
+  labels::store(&db, "a", &run, None, true).unwrap();
+  let linked: Vec<String> = db.conn.prepare("SELECT n.name FROM event_entity x JOIN entity n ON n.id=x.entity_id WHERE x.event_id='a'").unwrap()
+    .query_map([], |r| r.get(0)).unwrap().collect::<rusqlite::Result<_>>().unwrap();
+  assert_eq!(linked, ["Red Cross"]);
+
+  // labels stored before linking are backfilled once
+  db.conn.execute("DELETE FROM event_entity", []).unwrap();
+  assert_eq!(entities::backfill(&db).unwrap(), 2);
+  assert_eq!(entities::backfill(&db).unwrap(), 0);
+}
+
+#[tokio::test]
+async fn merged_entities_keep_links_and_resolve_by_alias(){
+  let db = Db::open(":memory:".into()).unwrap();
+  label(&db, "a", 100, r#"[{"name":"IFRC","kind":"organisation"}]"#).await;
+  label(&db, "b", 200, r#"[{"name":"Red Cross","kind":"other"},{"name":"IFRC","kind":"organisation"}]"#).await;
+  let id = |kind: &str, name: &str| entities::resolve(&db.conn, kind, name).unwrap().unwrap();
+  let (keep, gone) = (id("organisation", "IFRC"), id("other", "Red Cross"));
+
+  assert!(entities::merge(&db, keep, keep).is_err());
+  assert!(entities::merge(&db, keep, 999).is_err());
+  entities::merge(&db, keep, gone).unwrap();
+  let rows = entities::list(&db, None, None, 10).unwrap();
+  assert_eq!(names(&rows), [("organisation", "IFRC", 2)]);
+  assert_eq!(rows[0].aliases, ["red cross"]);
+  // the merged name resolves under its old kind and the kept one
+  assert_eq!((id("other", "red cross"), id("organisation", "Red Cross")), (keep, keep));
+  label(&db, "c", 300, r#"[{"name":"Red Cross","kind":"organisation"}]"#).await;
+  assert_eq!(names(&entities::list(&db, None, None, 10).unwrap()), [("organisation", "IFRC", 3)]);
+}
+
+#[tokio::test]
+async fn graph_counts_co_mentions_among_recent_events(){
+  let db = Db::open(":memory:".into()).unwrap();
+  label(&db, "old", 10, r#"[{"name":"Dresden","kind":"location"},{"name":"Elbe","kind":"location"}]"#).await;
+  label(&db, "a", 100, r#"[{"name":"Dresden","kind":"location"},{"name":"Elbe","kind":"location"},{"name":"THW","kind":"organisation"}]"#).await;
+  label(&db, "b", 200, r#"[{"name":"Dresden","kind":"location"},{"name":"Elbe","kind":"location"}]"#).await;
+  label(&db, "c", 300, r#"[{"name":"Dresden","kind":"location"},{"name":"Leipzig","kind":"location"}]"#).await;
+  let id = |name: &str| entities::resolve(&db.conn, "location", name).unwrap()
+    .or_else(|| entities::resolve(&db.conn, "organisation", name).unwrap()).unwrap();
+
+  let g = entities::graph(&db, 50, 1, 10).unwrap();
+  assert_eq!(g.nodes.iter().map(|n| (n.name.as_str(), n.events)).collect::<Vec<_>>()[..2], [("Dresden", 3), ("Elbe", 2)]);
+  assert_eq!(g.nodes.len(), 4);
+  let (dresden, elbe) = (id("Dresden"), id("Elbe"));
+  assert_eq!(g.edges[0], (dresden.min(elbe), dresden.max(elbe), 2));
+  assert_eq!(g.edges.len(), 4);
+
+  // a heavier threshold and fewer nodes prune the edges with them
+  let g = entities::graph(&db, 50, 2, 10).unwrap();
+  assert_eq!(g.edges, [(dresden.min(elbe), dresden.max(elbe), 2)]);
+  let g = entities::graph(&db, 50, 1, 1).unwrap();
+  assert_eq!((g.nodes.len(), g.edges.len()), (1, 0));
+  assert!(entities::graph(&db, 1000, 1, 10).unwrap().nodes.is_empty());
+  let _ = id("THW");
+}
@@ -0,0 +1,81 @@
+use crate::db::Db;
+use crate::export::{self, geom, ExportFormat, ExportQuery};
+
+fn seeded() -> Db {
+  let db = Db::open(":memory:".into()).unwrap();
+  db.conn.execute("INSERT INTO event(id,first_seen,last_seen,title,class,severity,lat,lon,source_rank) VALUES ('e1',1000,2000,'Quake & aftershock','eq',0.8,20,10,5)", []).unwrap();
+  db.conn.execute("INSERT INTO event(id,first_seen,last_seen,title,class,severity,lat,lon,source_rank) VALUES ('e2',1000,2000,'Far away','flood',0.1,-40,100,8)", []).unwrap();
+  let area = r#"{"type":"FeatureCollection","features":[
+    {"type":"Feature","properties":null,"geometry":{"type":"Polygon","coordinates":[[[9,19],[12,19],[12,21],[9,19]]]}},
+    {"type":"Feature","properties":null,"geometry":{"type":"Polygon","coordinates":[[[0,0],[1,0],[1,1],[0,0]]]}}]}"#;
+  db.conn.execute("INSERT INTO alert(id,source,headline,event,severity,onset,expires,polygon_geojson,bbox_minx,bbox_miny,bbox_maxx,bbox_maxy,raw_json,last_seen)
+    VALUES ('a1','cap','Flood <warning>','Flood','Severe',500,5000,?1,0,0,12,21,'{}',1)", [area]).unwrap();
+  db.conn.execute("INSERT INTO alert_rtree(rowid,minx,maxx,miny,maxy) VALUES ((SELECT rowid FROM alert WHERE id='a1'),0,12,0,21)", []).unwrap();
+  db
+}
+
+fn text(db: &Db, q: &ExportQuery, f: ExportFormat) -> String {
+  let mut out = Vec::new();
+  export::write(&export::records(db, q, 1500).unwrap(), f, &mut out).unwrap();
+  String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn exports_events_and_alerts_in_each_format(){
+  let db = seeded();
+  let events = ExportQuery::Events { q: None, since: None, until: None, bbox: Some([0.0, 0.0, 50.0, 50.0]) };
+  let alerts = ExportQuery::Alerts { bbox: None, at: None };
+
+  let gj: serde_json::Value = serde_json::from_str(&text(&db, &events, ExportFormat::Geojson)).unwrap();
+  assert_eq!(gj["features"].as_array().unwrap().len(), 1);
+  let props = &gj["features"][0]["properties"];
+  assert_eq!((props["class"].as_str(), props["source"].as_str(), props["severity_label"].as_str()), (Some("eq"), Some("usgs"), Some("extreme")));
+  assert_eq!(props["start"], "1970-01-01T00:16:40Z");
+
+  let csv = text(&db, &alerts, ExportFormat::Csv);
+  let row = csv.lines().nth(1).unwrap();
+  assert!(row.starts_with("a1,alert,Flood <warning>,Flood,0.750,Severe,cap,"), "{row}");
+  assert!(row.ends_with(r#""GEOMETRYCOLLECTION (POLYGON ((9 19, 12 19, 12 21, 9 19)), POLYGON ((0 0, 1 0, 1 1, 0 0)))""#), "{row}");
+
+  let kml = text(&db, &alerts, ExportFormat::Kml);
+  assert!(kml.contains("<name>Flood &lt;warning&gt;</name><styleUrl>#severe</styleUrl>"), "{kml}");
+  assert_eq!(kml.matches("<Polygon>").count(), 2);
+  // expired at `at`
+  assert!(export::records(&db, &ExportQuery::Alerts { bbox: None, at: Some(6000) }, 0).unwrap().is_empty());
+  // a later cancellation still leaves the alert in force at an earlier `at`
+  db.conn.execute("UPDATE alert SET cancelled_at=3000 WHERE id='a1'", []).unwrap();
+  let at = |t| export::records(&db, &ExportQuery::Alerts { bbox: None, at: Some(t) }, 0).unwrap().len();
+  assert_eq!((at(2000), at(3000)), (1, 0));
+
+  // events are what search_events finds, so merged duplicates stay out
+  db.conn.execute("INSERT INTO event(id,first_seen,last_seen,title,class,severity,lat,lon,source_rank) VALUES ('e3',1100,2000,'Quake report','eq',0.8,20,10,4)", []).unwrap();
+  let quakes = ExportQuery::Events { q: Some("Quake".into()), since: None, until: None, bbox: None };
+  let ids = |q| export::records(&db, q, 0).unwrap().into_iter().map(|r| r.id).collect::<Vec<_>>();
+  assert_eq!(ids(&quakes), ["e3", "e1"]);
+  db.conn.execute("INSERT INTO event_duplicate(event_id,duplicate_of,model,score,km,merged_at) VALUES ('e3','e1','mock',0.99,0,0)", []).unwrap();
+  assert_eq!(ids(&quakes), ["e1"]);
+  assert_eq!((quakes.file_name(ExportFormat::Gpkg), alerts.file_name(ExportFormat::Kml)), ("events.gpkg".to_string(), "alerts.kml".to_string()));
+}
+
+#[test]
+fn geopackage_has_metadata_and_wkb_geometry(){
+  let db = seeded();
+  let path = std::env::temp_dir().join(format!("vilya-{}.gpkg", uuid::Uuid::new_v4()));
+  let q = ExportQuery::Events { q: Some("Quake".into()), since: None, until: None, bbox: None };
+  assert_eq!(export::to_file(&db, &q, ExportFormat::Gpkg, &path, 0).unwrap(), 1);
+  let g = rusqlite::Connection::open(&path).unwrap();
+  let app_id: i64 = g.query_row("PRAGMA application_id", [], |r| r.get(0)).unwrap();
+  assert_eq!(app_id, 0x4750_4B47);
+  let (kind, srs): (String, i64) = g.query_row("SELECT data_type, srs_id FROM gpkg_contents WHERE table_name='events'", [], |r| Ok((r.get(0)?, r.get(1)?))).unwrap();
+  assert_eq!((kind.as_str(), srs), ("features", 4326));
+  let blob: Vec<u8> = g.query_row("SELECT geom FROM events", [], |r| r.get(0)).unwrap();
+  assert_eq!(&blob[..2], b"GP");
+  // header (8) + envelope (32), then little-endian WKB point (1 + 4 + 16)
+  let wkb = &blob[40..];
+  assert_eq!((wkb[0], u32::from_le_bytes(wkb[1..5].try_into().unwrap())), (1, 1));
+  assert_eq!(f64::from_le_bytes(wkb[5..13].try_into().unwrap()), 10.0);
+  assert_eq!(f64::from_le_bytes(wkb[13..21].try_into().unwrap()), 20.0);
+  drop(g);
+  let _ = std::fs::remove_file(path);
+  assert_eq!(geom::envelope(&geojson::Value::LineString(vec![vec![1.0, 5.0], vec![-2.0, 3.0]])), Some([-2.0, 3.0, 1.0, 5.0]));
+}
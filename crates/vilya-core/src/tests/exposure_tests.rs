@@ -0,0 +1,109 @@
+use crate::exposure;
+use crate::ingest;
+use crate::ipc::detail;
+use crate::rules;
+use crate::runtime::Core;
+use crate::settings::ExposureSettings;
+use crate::stream::LogNotifier;
+
+#[test]
+fn layers_load_from_grids_points_and_geojson(){
+  let dir = std::env::temp_dir().join(format!("vilya-test-{}", uuid::Uuid::new_v4()));
+  std::fs::create_dir_all(&dir).unwrap();
+  let core = Core::open_db(":memory:".into(), dir.clone(), Box::new(LogNotifier)).unwrap();
+  let conn = &core.db.conn;
+  // 3 x 2 cells of 0.1 degrees; the top row is the northern one
+  std::fs::write(dir.join("tile.asc"), "ncols 3\nnrows 2\nxllcorner 10.0\nyllcorner 20.0\ncellsize 0.1\nNODATA_value -9999\n1000 -9999 0\n200 300 400\n").unwrap();
+  let r = exposure::load_population(conn, &dir.join("tile.asc")).unwrap();
+  assert_eq!(r.rows, 4);
+  let (lat, lon): (f64, f64) = conn.query_row("SELECT lat, lon FROM population_point WHERE population=1000", [], |r| Ok((r.get(0)?, r.get(1)?))).unwrap();
+  assert!((lat - 20.15).abs() < 1e-9 && (lon - 10.05).abs() < 1e-9, "{lat},{lon}");
+  // loading the same file again replaces its layer
+  exposure::load_population(conn, &dir.join("tile.asc")).unwrap();
+  let n: i64 = conn.query_row("SELECT COUNT(*) FROM population_rtree", [], |r| r.get(0)).unwrap();
+  assert_eq!(n, 4);
+
+  std::fs::write(dir.join("points.csv"), "X,Y,Z\n10.5,20.5,50\n10.6,20.6,0\n").unwrap();
+  assert_eq!(exposure::load_population(conn, &dir.join("points.csv")).unwrap().rows, 1);
+  std::fs::write(dir.join("bad.csv"), "a,b\n1,2\n").unwrap();
+  assert!(exposure::load_population(conn, &dir.join("bad.csv")).is_err());
+
+  std::fs::write(dir.join("health.geojson"), r#"{"type":"FeatureCollection","features":[
+    {"type":"Feature","properties":{"amenity":"hospital","name":"General"},"geometry":{"type":"Point","coordinates":[10.1,20.1]}},
+    {"type":"Feature","properties":{"name":"Clinic"},"geometry":{"type":"Polygon","coordinates":[[[10,20],[10.2,20],[10.2,20.2],[10,20.2],[10,20]]]}}]}"#).unwrap();
+  let r = exposure::load_facilities(conn, &dir.join("health.geojson"), None).unwrap();
+  assert_eq!(r.rows, 2);
+  let kinds: Vec<(String, f64)> = conn.prepare("SELECT kind, lat FROM facility ORDER BY name").unwrap()
+    .query_map([], |r| Ok((r.get(0)?, r.get(1)?))).unwrap().collect::<rusqlite::Result<_>>().unwrap();
+  assert_eq!(kinds, [("health".to_string(), 20.1), ("hospital".to_string(), 20.1)]);
+  std::fs::write(dir.join("airports.csv"), "name,latitude,longitude\nIntl,20.3,10.3\n").unwrap();
+  exposure::load_facilities(conn, &dir.join("airports.csv"), Some("airport")).unwrap();
+  let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn events_and_alerts_are_assessed_and_matched_by_rules(){
+  let dir = std::env::temp_dir().join(format!("vilya-test-{}", uuid::Uuid::new_v4()));
+  std::fs::create_dir_all(&dir).unwrap();
+  let core = Core::open_db(":memory:".into(), dir.clone(), Box::new(LogNotifier)).unwrap();
+  let conn = &core.db.conn;
+  std::fs::write(dir.join("people.csv"), "lat,lon,population\n20.0,10.0,5000\n20.3,10.0,2000\n21.0,10.0,90000\n").unwrap();
+  std::fs::write(dir.join("sites.csv"), "lat,lon,name,kind\n20.05,10.0,General,hospital\n20.0,10.4,Intl,airport\n").unwrap();
+  exposure::load_population(conn, &dir.join("people.csv")).unwrap();
+  exposure::load_facilities(conn, &dir.join("sites.csv"), None).unwrap();
+  conn.execute("INSERT INTO rule(id,name,enabled,target,spec_json,updated_at) VALUES
+    ('crowd','Many people',1,'event','{\"min_population\":6000}',0),
+    ('hosp','Hospital nearby',1,'event','{\"facility\":[\"Hospital\"]}',0),
+    ('area','Alert over people',1,'alert','{\"min_population\":1000,\"facility\":[\"airport\"]}',0)", []).unwrap();
+
+  // M5.0 -> severity 0.5 -> 50 km: the two southern points and both sites
+  let usgs = r#"{"type":"FeatureCollection","features":[
+    {"type":"Feature","id":"q1","properties":{"mag":5.0,"title":"M 5.0"},"geometry":{"type":"Point","coordinates":[10.0,20.0,5]}}]}"#;
+  ingest::ingest(&core, "usgs", usgs).unwrap();
+  let e = detail::event_detail(&core.db, "q1", 100.0, 3600, 0).unwrap().exposure.unwrap();
+  let severity: f64 = conn.query_row("SELECT severity FROM event WHERE id='q1'", [], |r| r.get(0)).unwrap();
+  assert_eq!(e.radius_km, exposure::radius_km(&ExposureSettings::default(), severity));
+  assert_eq!(e.population, 7000.0);
+  assert_eq!(e.facility_counts.into_iter().collect::<Vec<_>>(), [("airport".to_string(), 1), ("hospital".to_string(), 1)]);
+  assert_eq!(e.facilities.iter().map(|f| f.name.as_deref().unwrap()).collect::<Vec<_>>(), ["General", "Intl"]);
+  let matched = |kind: &str, id: &str| rules::matches_for(conn, kind, id).unwrap().into_iter().map(|m| m.rule_id).collect::<Vec<_>>();
+  let mut m = matched("event", "q1");
+  m.sort();
+  assert_eq!(m, ["crowd", "hosp"]);
+
+  let cap = r#"<alert><identifier>c1</identifier><msgType>Alert</msgType><info><event>Flood</event><headline>Flood</headline>
+    <expires>2099-01-01T00:00:00Z</expires><area><polygon>19.9,9.9 19.9,10.5 20.1,10.5 20.1,9.9 19.9,9.9</polygon></area></info></alert>"#;
+  ingest::ingest(&core, "cap", cap).unwrap();
+  let a = exposure::exposure_of(conn, "alert", "c1").unwrap().unwrap();
+  assert_eq!((a.radius_km, a.population, a.facilities.len()), (None, 5000.0, 2));
+  assert_eq!(matched("alert", "c1"), ["area"]);
+
+  // unchanged items are not reassessed; reloading layers reassesses all
+  assert_eq!(exposure::assess(conn, "event", &["q1".into()], &core.settings.exposure).unwrap(), 0);
+  assert_eq!(exposure::assess_all(conn, &core.settings.exposure).unwrap(), 2);
+  // an area redrawn around the same centre is reassessed
+  conn.execute("UPDATE alert SET polygon_geojson='{\"type\":\"Polygon\",\"coordinates\":[[[9.9,19.9],[10.5,19.9],[10.5,20.1],[9.9,19.9]]]}' WHERE id='c1'", []).unwrap();
+  assert_eq!(exposure::assess(conn, "alert", &["c1".into()], &core.settings.exposure).unwrap(), 1);
+  assert_eq!(exposure::assess(conn, "alert", &["c1".into()], &core.settings.exposure).unwrap(), 0);
+  // starting with layers loaded assesses what was stored meanwhile
+  conn.execute("DELETE FROM item_exposure WHERE item_id='q1'", []).unwrap();
+  exposure::init(&core).unwrap();
+  assert_eq!(exposure::exposure_of(conn, "event", "q1").unwrap().map(|e| e.population), Some(7000.0));
+  let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn exposure_counts_people_across_the_antimeridian(){
+  let dir = std::env::temp_dir().join(format!("vilya-test-{}", uuid::Uuid::new_v4()));
+  std::fs::create_dir_all(&dir).unwrap();
+  let core = Core::open_db(":memory:".into(), dir.clone(), Box::new(LogNotifier)).unwrap();
+  let conn = &core.db.conn;
+  std::fs::write(dir.join("fiji.csv"), "lat,lon,population\n-16.9,179.95,100\n-16.9,-179.95,200\n").unwrap();
+  exposure::load_population(conn, &dir.join("fiji.csv")).unwrap();
+  let usgs = r#"{"type":"FeatureCollection","features":[
+    {"type":"Feature","id":"q1","properties":{"mag":5.0,"title":"M 5.0"},"geometry":{"type":"Point","coordinates":[179.99,-16.9,5]}}]}"#;
+  ingest::ingest(&core, "usgs", usgs).unwrap();
+  let e = exposure::exposure_of(conn, "event", "q1").unwrap().unwrap();
+  assert_eq!(e.population, 300.0);
+  let _ = std::fs::remove_dir_all(dir);
+}
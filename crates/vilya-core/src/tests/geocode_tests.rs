@@ -0,0 +1,138 @@
+use std::path::{Path, PathBuf};
+use crate::analytics::{self, AnalyticsQuery, GroupBy};
+use crate::geocode::{self, Place};
+use crate::ingest;
+use crate::ipc::{detail, query};
+use crate::rules;
+use crate::runtime::Core;
+use crate::settings::GazetteerSettings;
+use crate::stream::LogNotifier;
+
+/// A two-country GeoNames extract around the Rio Grande mouth.
+fn gazetteer(dir: &Path) -> PathBuf {
+  let g = dir.join("gazetteer");
+  std::fs::create_dir_all(&g).unwrap();
+  let city = |id: u32, name: &str, lat: f64, lon: f64, cc: &str, a1: &str, a2: &str, pop: u32|
+    format!("{id}\t{name}\t{name}\t\t{lat}\t{lon}\tP\tPPL\t{cc}\t\t{a1}\t{a2}\t\t\t{pop}\t\t10\tAmerica/Chicago\t2024-01-01\n");
+  std::fs::write(g.join("cities15000.txt"), [
+    city(4699066, "Houston", 29.76, -95.36, "US", "TX", "201", 2_300_000),
+    city(4692883, "Galveston", 29.30, -94.79, "US", "TX", "167", 53_000),
+    city(3523183, "Matamoros", 25.87, -97.50, "MX", "28", "022", 520_000),
+  ].concat()).unwrap();
+  std::fs::write(g.join("countryInfo.txt"),
+    "#ISO\tISO3\tISO-Numeric\tfips\tCountry\n\
+     US\tUSA\t840\tUS\tUnited States\tWashington\t9629091\t327167434\tNA\t.us\tUSD\tDollar\t1\t\t\ten-US\t6252001\tCA,MX\t\n\
+     MX\tMEX\t484\tMX\tMexico\tMexico City\t1972550\t126190788\tNA\t.mx\tMXN\tPeso\t52\t\t\tes-MX\t3996063\tGT,US,BZ\t\n").unwrap();
+  std::fs::write(g.join("admin1CodesASCII.txt"), "US.TX\tTexas\tTexas\t4736286\nMX.28\tTamaulipas\tTamaulipas\t3516391\n").unwrap();
+  std::fs::write(g.join("admin2Codes.txt"), "US.TX.201\tHarris County\tHarris County\t4696376\n").unwrap();
+  // GeoNames' own outline format, and a Natural Earth style FeatureCollection
+  std::fs::write(g.join("shapes_simplified_low.json"),
+    "geoNameId\tgeoJSON\n6252001\t{\"type\":\"Polygon\",\"coordinates\":[[[-100,26],[-93,26],[-93,32],[-100,32],[-100,26]]]}\n").unwrap();
+  std::fs::write(g.join("mexico.geojson"), r#"{"type":"FeatureCollection","features":[{"type":"Feature","properties":{"ISO_A2":"-99","ISO_A3":"MEX"},
+    "geometry":{"type":"Polygon","coordinates":[[[-100,20],[-97,20],[-97,26],[-100,26],[-100,20]]]}}]}"#).unwrap();
+  g
+}
+
+#[test]
+fn lookup_places_points_by_outline_and_nearest_place(){
+  let dir = std::env::temp_dir().join(format!("vilya-test-{}", uuid::Uuid::new_v4()));
+  let core = Core::open_db(":memory:".into(), dir.clone(), Box::new(LogNotifier)).unwrap();
+  let conn = &core.db.conn;
+  assert_eq!(geocode::lookup(conn, 29.7, -95.4, 300.0).unwrap(), None);
+  let r = geocode::load(conn, &gazetteer(&dir)).unwrap();
+  assert_eq!((r.places, r.admins, r.countries, r.shapes), (3, 3, 2, 2));
+
+  let p = geocode::lookup(conn, 29.70, -95.40, 300.0).unwrap().unwrap();
+  assert_eq!(p.country.as_deref(), Some("USA"));
+  assert_eq!((p.admin1.as_deref(), p.admin2.as_deref(), p.place.as_deref()), (Some("Texas"), Some("Harris County"), Some("Houston")));
+  assert!(p.place_km.unwrap() < 10.0, "{p:?}");
+  // offshore: no country or divisions, but still near Galveston
+  let p = geocode::lookup(conn, 28.9, -92.5, 300.0).unwrap().unwrap();
+  assert_eq!((p.country, p.admin1, p.place.as_deref()), (None, None, Some("Galveston")));
+  // across the border the nearest place's divisions are not borrowed
+  let p = geocode::lookup(conn, 26.1, -97.3, 300.0).unwrap().unwrap();
+  assert_eq!((p.country.as_deref(), p.admin1.as_deref(), p.place.as_deref()), (Some("USA"), None, Some("Matamoros")));
+  assert_eq!(geocode::lookup(conn, 0.0, -140.0, 300.0).unwrap(), Some(Place::default()));
+
+  // a load that fails keeps the gazetteer it would have replaced
+  let g = gazetteer(&dir);
+  std::fs::write(g.join("cities500.txt"), "1	Broken
+").unwrap();
+  assert!(geocode::load(conn, &g).is_err());
+  let country = |lat, lon| geocode::lookup(conn, lat, lon, 300.0).unwrap().unwrap().country;
+  assert_eq!(country(29.70, -95.40).as_deref(), Some("USA"));
+  // a reload replaces the outlines cached from the previous one
+  std::fs::remove_file(g.join("cities500.txt")).unwrap();
+  std::fs::write(g.join("mexico.geojson"), r#"{"type":"FeatureCollection","features":[{"type":"Feature","properties":{"ISO_A3":"MEX"},
+    "geometry":{"type":"Polygon","coordinates":[[[-100,26],[-93,26],[-93,32],[-100,32],[-100,26]]]}}]}"#).unwrap();
+  std::fs::remove_file(g.join("shapes_simplified_low.json")).unwrap();
+  assert_eq!(geocode::load(conn, &g).unwrap().shapes, 1);
+  assert_eq!(country(29.70, -95.40).as_deref(), Some("MEX"));
+  let staged: i64 = conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE name LIKE 'load_%'", [], |r| r.get(0)).unwrap();
+  assert_eq!(staged, 0);
+  let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn nearest_place_is_found_across_the_antimeridian(){
+  let dir = std::env::temp_dir().join(format!("vilya-test-{}", uuid::Uuid::new_v4()));
+  let g = dir.join("gazetteer");
+  std::fs::create_dir_all(&g).unwrap();
+  let core = Core::open_db(":memory:".into(), dir.clone(), Box::new(LogNotifier)).unwrap();
+  let conn = &core.db.conn;
+  std::fs::write(g.join("countryInfo.txt"), "FJ\tFJI\t242\tFJ\tFiji\n").unwrap();
+  std::fs::write(g.join("cities15000.txt"), [
+    "1\tTaveuni\tTaveuni\t\t-16.9\t-179.9\tP\tPPL\tFJ\t\t03\t\t\t\t15000\t\t10\tPacific/Fiji\t2024-01-01\n",
+    "2\tLabasa\tLabasa\t\t-16.4\t179.4\tP\tPPL\tFJ\t\t03\t\t\t\t28000\t\t10\tPacific/Fiji\t2024-01-01\n",
+  ].concat()).unwrap();
+  geocode::load(conn, &g).unwrap();
+  // Taveuni is 21 km away over the line, Labasa 75 km away on this side
+  for (lat, lon) in [(-16.9, 179.9), (-16.9, -179.7)] {
+    let p = geocode::lookup(conn, lat, lon, 300.0).unwrap().unwrap();
+    assert_eq!(p.place.as_deref(), Some("Taveuni"), "{lat},{lon}");
+    assert!(p.place_km.unwrap() < 25.0, "{p:?}");
+  }
+  let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn stored_events_are_placed_searchable_and_grouped(){
+  let dir = std::env::temp_dir().join(format!("vilya-test-{}", uuid::Uuid::new_v4()));
+  let core = Core::open_db(":memory:".into(), dir.clone(), Box::new(LogNotifier)).unwrap();
+  let usgs = r#"{"type":"FeatureCollection","features":[
+    {"type":"Feature","id":"q1","properties":{"mag":4.2,"title":"M 4.2 - "},"geometry":{"type":"Point","coordinates":[-95.30,29.80,5]}},
+    {"type":"Feature","id":"q2","properties":{"mag":3.0,"title":"M 3.0 - "},"geometry":{"type":"Point","coordinates":[-98.0,24.0,5]}}]}"#;
+  // stored before the gazetteer: placed by `annotate_all`
+  assert_eq!(ingest::ingest(&core, "usgs", usgs).unwrap(), 2);
+  assert!(geocode::place_of(&core.db.conn, "event", "q1").unwrap().is_none());
+  geocode::load(&core.db.conn, &gazetteer(&dir)).unwrap();
+  assert_eq!(geocode::annotate_all(&core.db.conn, &GazetteerSettings::default()).unwrap(), 2);
+  assert_eq!(geocode::annotate_all(&core.db.conn, &GazetteerSettings::default()).unwrap(), 0);
+
+  let ids = |q: &str| query::search_events(&core.db, Some(q), None, None, None, 10).unwrap().into_iter().map(|e| e.id).collect::<Vec<_>>();
+  assert_eq!(ids("harris"), ["q1"]);
+  assert_eq!(ids("Mexico"), ["q2"]);
+  assert_eq!(ids("mex"), ["q2"]);
+  let d = detail::event_detail(&core.db, "q1", 100.0, 3600, 0).unwrap();
+  assert_eq!(d.place.and_then(|p| p.place).as_deref(), Some("Houston"));
+  let country: String = core.db.conn.query_row("SELECT country FROM event WHERE id='q2'", [], |r| r.get(0)).unwrap();
+  assert_eq!(country, "MEX");
+
+  // stored after: placed before rules run
+  core.db.conn.execute("INSERT INTO rule(id,name,enabled,target,spec_json,updated_at) VALUES
+    ('tx','Texas quakes',1,'event','{\"region\":[\"texas\"],\"max_place_km\":50}',0),
+    ('mx','Mexico',1,'event','{\"country\":[\"MEX\"]}',0)", []).unwrap();
+  let more = r#"{"type":"FeatureCollection","features":[
+    {"type":"Feature","id":"q3","properties":{"mag":3.5,"title":"M 3.5 - "},"geometry":{"type":"Point","coordinates":[-94.9,29.4,5]}}]}"#;
+  ingest::ingest(&core, "usgs", more).unwrap();
+  let matched = |id: &str| rules::matches_for(&core.db.conn, "event", id).unwrap().into_iter().map(|m| m.rule_id).collect::<Vec<_>>();
+  assert_eq!(matched("q3"), ["tx"]);
+  assert!(rules::parse("- {id: a, name: a, target: event, where: {region: [Texas], country: [USA]}}").is_ok());
+
+  let now = chrono::Utc::now().timestamp();
+  let q = AnalyticsQuery { since: Some(now - 86400), until: Some(now + 86400), group_by: Some(GroupBy::Region), ..Default::default() };
+  let mut groups: Vec<_> = analytics::series(&core.db, &q, now).unwrap().points.into_iter().map(|p| (p.group, p.count)).collect();
+  groups.sort();
+  assert_eq!(groups, [("Tamaulipas, MEX".to_string(), 1), ("Texas, USA".to_string(), 2)]);
+  let _ = std::fs::remove_dir_all(dir);
+}
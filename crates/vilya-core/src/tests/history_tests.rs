@@ -0,0 +1,81 @@
+use crate::db::Db;
+use crate::history::{self, ChangeKind};
+use crate::ipc::{detail, query};
+use crate::stream::StreamFilter;
+
+fn alert(db: &Db, id: &str, headline: &str, expires: i64, last_seen: i64) {
+  db.conn.execute("INSERT OR REPLACE INTO alert(id,source,headline,severity,expires,polygon_geojson,bbox_minx,bbox_miny,bbox_maxx,bbox_maxy,raw_json,last_seen)
+    VALUES (?1,'cap',?2,'Severe',?3,'{\"type\":\"Polygon\",\"coordinates\":[[[138,34],[140,34],[140,36],[138,36],[138,34]]]}',138,34,140,36,'{}',?4)",
+    rusqlite::params![id, headline, expires, last_seen]).unwrap();
+}
+
+#[test]
+fn queries_answer_as_of_a_past_time(){
+  let db = Db::open(":memory:".into()).unwrap();
+  db.conn.execute("INSERT INTO event(id,first_seen,last_seen,title,class,severity,lat,lon,geojson,source_rank)
+    VALUES ('q1',1000,1000,'M 4.0 - Alpha','eq',0.4,35,139,'{}',5)", []).unwrap();
+  // a revision is dated by the report that brought it
+  db.conn.execute("UPDATE event SET title='M 5.1 - Alpha', severity=0.6, last_seen=1100 WHERE id='q1'", []).unwrap();
+  db.conn.execute("UPDATE event SET last_seen=1200 WHERE id='q1'", []).unwrap();
+  // rewrites that are not reports, such as re-rating, keep no version
+  db.conn.execute("UPDATE event SET severity=0.9 WHERE id='q1'", []).unwrap();
+  // a report older than the last version is dated with it, and wins
+  db.conn.execute("UPDATE event SET title='M 5.0 - Alpha', last_seen=1050 WHERE id='q1'", []).unwrap();
+  let now = chrono::Utc::now().timestamp();
+  let title = |as_of| query::search_events(&db, None, None, None, as_of, 10).unwrap().into_iter().map(|e| e.title).collect::<Vec<_>>();
+  assert_eq!(title(Some(999)), Vec::<String>::new());
+  assert_eq!(title(Some(1099)), ["M 4.0 - Alpha"]);
+  assert_eq!(title(Some(1100)), ["M 5.0 - Alpha"]);
+  assert_eq!(title(None), ["M 5.0 - Alpha"]);
+  let versions: Vec<(i64, f64)> = db.conn.prepare("SELECT valid_from, severity FROM event_version ORDER BY rowid").unwrap()
+    .query_map([], |r| Ok((r.get(0)?, r.get(1)?))).unwrap().collect::<rusqlite::Result<_>>().unwrap();
+  assert_eq!(versions, [(1000, 0.4), (1100, 0.6), (1100, 0.9)]);
+
+  alert(&db, "a1", "Flood Watch", 5000, 1000);
+  // an unchanged re-poll keeps no version
+  alert(&db, "a1", "Flood Watch", 5000, 1500);
+  alert(&db, "a1", "Flood Warning", 6000, 2000);
+  db.conn.execute("UPDATE alert SET cancelled_at=3000 WHERE id='a1'", []).unwrap();
+  let bbox = [138.0, 34.0, 140.0, 36.0];
+  let headline = |t| query::query_alerts(&db, bbox, now, Some(t)).unwrap().into_iter().map(|a| a.headline).collect::<Vec<_>>();
+  assert_eq!(headline(1200), ["Flood Watch"]);
+  assert_eq!(headline(2500), ["Flood Warning"]);
+  assert!(headline(3000).is_empty());
+  assert!(query::query_alerts(&db, bbox, 0, None).unwrap().is_empty());
+  let at = |t| detail::covering_alerts(&db, geo::Point::new(139.0, 35.0), now, Some(t)).unwrap().len();
+  assert_eq!((at(1200), at(900)), (1, 0));
+}
+
+#[tokio::test]
+async fn changes_between_two_times_in_order(){
+  let db = Db::open(":memory:".into()).unwrap();
+  db.conn.execute("INSERT INTO event(id,first_seen,last_seen,title,class,severity,lat,lon,geojson,source_rank)
+    VALUES ('q1',1500,1500,'M 4.0 - Alpha','eq',0.4,35,139,'{}',5)", []).unwrap();
+  alert(&db, "a1", "Flood Watch", 5000, 1000);
+  alert(&db, "a1", "Flood Warning", 6000, 2000);
+  db.conn.execute("UPDATE alert SET cancelled_at=3000 WHERE id='a1'", []).unwrap();
+  alert(&db, "a2", "Wind Advisory", 4000, 1000);
+
+  let all = history::changes(&db, 0, 10_000, &StreamFilter::default()).unwrap();
+  let kinds: Vec<_> = all.iter().map(|c| (c.at, c.kind, c.id.as_str())).collect();
+  assert_eq!(kinds, [
+    (1000, ChangeKind::AlertIssued, "a1"), (1000, ChangeKind::AlertIssued, "a2"),
+    (1500, ChangeKind::EventCreated, "q1"), (2000, ChangeKind::AlertUpdated, "a1"),
+    (3000, ChangeKind::AlertCancelled, "a1"), (4000, ChangeKind::AlertExpired, "a2"),
+  ]);
+  assert_eq!(all[4].alert.as_ref().unwrap().headline, "Flood Warning");
+  // the range is half-open and the filter applies
+  assert_eq!(history::changes(&db, 1000, 2000, &StreamFilter::default()).unwrap().len(), 2);
+  let quakes = StreamFilter { classes: Some(vec!["eq".into()]), ..Default::default() };
+  assert_eq!(history::changes(&db, 0, 10_000, &quakes).unwrap().len(), 1);
+  assert!(history::changes(&db, 10, 0, &StreamFilter::default()).is_err());
+
+  let mut seen = Vec::new();
+  history::play(&all, 0.0, |c| { seen.push(c.at); Ok(()) }).await.unwrap();
+  assert_eq!(seen.len(), 6);
+  assert!(history::play(&all, -1.0, |_| Ok(())).await.is_err());
+  // too slow for a timer: an error, not a panic, and nothing is played
+  let mut seen = 0;
+  assert!(history::play(&all, 1e-300, |_| { seen += 1; Ok(()) }).await.is_err());
+  assert_eq!(seen, 0);
+}
@@ -0,0 +1,108 @@
+use crate::ingest::import::{self, ColumnMap, ImportFormat, ImportSpec, ImportTarget};
+use crate::ipc::{query, IpcError};
+use crate::runtime::Core;
+use crate::stream::LogNotifier;
+
+fn core() -> (std::sync::Arc<Core>, std::path::PathBuf) {
+  let dir = std::env::temp_dir().join(format!("vilya-test-{}", uuid::Uuid::new_v4()));
+  (Core::open_db(":memory:".into(), dir.clone(), Box::new(LogNotifier)).unwrap(), dir)
+}
+
+fn spec(format: ImportFormat, target: ImportTarget, columns: ColumnMap) -> ImportSpec {
+  ImportSpec { name: "field".into(), format, target, columns }
+}
+
+#[test]
+fn csv_rows_map_columns_and_undo_as_a_batch(){
+  let (core, dir) = core();
+  let csv = "\u{feff}Ref,Incident,Y,X,Sev,Reported\n\
+    r1,Bridge washed out,29.7,-95.3,severe,2024-05-01 10:00\n\
+    r2,Road closed,29.8,-95.4,0.2,1714557600000\n\
+    r3,No location,,,,\n";
+  let mut map = ColumnMap::default();
+  for (f, c) in [("id", "Ref"), ("title", "Incident"), ("lat", "Y"), ("lon", "X"), ("severity", "Sev"), ("time", "Reported")] { map.set(f, c.into()).unwrap(); }
+  assert!(map.set("colour", "C".into()).is_err());
+
+  let b = import::import(&core, &spec(ImportFormat::Csv, ImportTarget::Event, map.clone()), "reports.csv", csv).unwrap();
+  assert_eq!((b.source.as_str(), b.events, b.alerts, b.skipped), ("import:field", 2, 0, 1));
+  let events = query::search_events(&core.db, Some("Bridge"), None, None, None, 10).unwrap();
+  assert_eq!(events.len(), 1);
+  assert_eq!(events[0].id, "import:field:r1");
+  assert_eq!(events[0].severity, 0.75);
+  let (first_seen, source): (i64, String) = core.db.conn.query_row(
+    "SELECT e.first_seen, s.source FROM event e JOIN source_item s ON s.id=e.id WHERE e.id='import:field:r2'", [], |r| Ok((r.get(0)?, r.get(1)?))).unwrap();
+  assert_eq!((first_seen, source.as_str()), (1_714_557_600, "import:field"));
+
+  // a mapped column the file lacks is rejected before anything is stored
+  map.set("summary", "Notes".into()).unwrap();
+  let err = import::import(&core, &spec(ImportFormat::Csv, ImportTarget::Event, map), "reports.csv", csv).unwrap_err();
+  assert!(matches!(IpcError::from(err), IpcError::InvalidArgument(_)));
+
+  let undone = import::undo(&core, &b.id).unwrap();
+  assert!(undone.undone_at.is_some());
+  assert!(query::search_events(&core.db, None, None, None, None, 10).unwrap().is_empty());
+  let items: i64 = core.db.conn.query_row("SELECT COUNT(*) FROM source_item", [], |r| r.get(0)).unwrap();
+  assert_eq!(items, 0);
+  assert!(import::undo(&core, &b.id).is_err());
+  assert_eq!(import::batches(&core.db).unwrap().len(), 1);
+  let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn kml_and_geojson_become_alerts_or_events(){
+  let (core, dir) = core();
+  let kml = r#"<?xml version="1.0"?><kml xmlns="http://www.opengis.net/kml/2.2"><Document>
+    <Placemark><name>Evacuation zone</name><description><![CDATA[Leave <b>now</b>]]></description>
+      <ExtendedData><Data name="severity"><value>Extreme</value></Data></ExtendedData>
+      <Polygon><outerBoundaryIs><LinearRing><coordinates>10,20 12,20 12,22 10,20</coordinates></LinearRing></outerBoundaryIs></Polygon>
+    </Placemark>
+    <Placemark><name>Folder note</name></Placemark>
+  </Document></kml>"#;
+  let b = import::import(&core, &spec(ImportFormat::Kml, ImportTarget::Alert, ColumnMap::default()), "zones.kml", kml).unwrap();
+  assert_eq!((b.alerts, b.skipped), (1, 1));
+  let now = chrono::Utc::now().timestamp();
+  let alerts = query::query_alerts(&core.db, [11.0, 20.5, 11.5, 21.0], now, None).unwrap();
+  assert_eq!(alerts.len(), 1);
+  assert_eq!((alerts[0].headline.as_str(), alerts[0].severity.as_str()), ("Evacuation zone", "Extreme"));
+
+  let gj = r#"{"type":"FeatureCollection","features":[
+    {"type":"Feature","id":7,"properties":{"Name":"Shelter full","Type":"Shelter"},"geometry":{"type":"Point","coordinates":[1,2]}}]}"#;
+  let e = import::import(&core, &spec(ImportFormat::Geojson, ImportTarget::Event, ColumnMap::default()), "shelters.geojson", gj).unwrap();
+  assert_eq!(e.events, 1);
+  let (class, lat, lon): (String, f64, f64) = core.db.conn.query_row(
+    "SELECT class, lat, lon FROM event WHERE id='import:field:7'", [], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?))).unwrap();
+  assert_eq!((class.as_str(), lat, lon), ("shelter", 2.0, 1.0));
+  // positions off the globe or missing are skipped, not placed at 0,0
+  let bad = r#"{"type":"FeatureCollection","features":[
+    {"type":"Feature","id":8,"properties":{},"geometry":{"type":"Point","coordinates":[200,2]}},
+    {"type":"Feature","id":9,"properties":{},"geometry":{"type":"MultiPolygon","coordinates":[]}}]}"#;
+  let e = import::import(&core, &spec(ImportFormat::Geojson, ImportTarget::Event, ColumnMap::default()), "bad.geojson", bad).unwrap();
+  assert_eq!((e.events, e.skipped), (0, 2));
+  let far = kml.replace("10,20 12,20", "10,95 12,20");
+  assert_eq!(import::import(&core, &spec(ImportFormat::Kml, ImportTarget::Alert, ColumnMap::default()), "far.kml", &far).unwrap().skipped, 2);
+
+  // undoing the KML batch leaves the GeoJSON one
+  import::undo(&core, &b.id).unwrap();
+  assert!(query::query_alerts(&core.db, [11.0, 20.5, 11.5, 21.0], now, None).unwrap().is_empty());
+  assert_eq!(query::search_events(&core.db, None, None, None, None, 10).unwrap().len(), 1);
+  assert!(import::import(&core, &ImportSpec { name: "bad name".into(), ..spec(ImportFormat::Csv, ImportTarget::Event, ColumnMap::default()) }, "x.csv", "a\n").is_err());
+  let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn cap_imports_never_touch_a_feeds_alert_of_the_same_id(){
+  let (core, dir) = core();
+  let cap = r#"<alert><identifier>x1</identifier><msgType>Alert</msgType><info><event>Flood</event><headline>HEADLINE</headline>
+    <expires>2099-01-01T00:00:00Z</expires><area><polygon>19.9,9.9 19.9,10.5 20.1,10.5 20.1,9.9 19.9,9.9</polygon></area></info></alert>"#;
+  crate::ingest::ingest(&core, "cap", &cap.replace("HEADLINE", "Live")).unwrap();
+  let b = import::import(&core, &spec(ImportFormat::Cap, ImportTarget::Alert, ColumnMap::default()), "old.xml", &cap.replace("HEADLINE", "Archived")).unwrap();
+  assert_eq!(b.alerts, 1);
+  let headline = |id: &str| core.db.conn.query_row("SELECT headline FROM alert WHERE id=?1", [id], |r| r.get::<_, String>(0)).ok();
+  assert_eq!((headline("x1").as_deref(), headline("import:field:x1").as_deref()), (Some("Live"), Some("Archived")));
+
+  import::undo(&core, &b.id).unwrap();
+  assert_eq!((headline("x1").as_deref(), headline("import:field:x1")), (Some("Live"), None));
+  let versions: i64 = core.db.conn.query_row("SELECT COUNT(*) FROM alert_version WHERE alert_id='x1'", [], |r| r.get(0)).unwrap();
+  assert!(versions > 0);
+  let _ = std::fs::remove_dir_all(dir);
+}
@@ -0,0 +1,93 @@
+use crate::db::Db;
+use crate::ingest;
+use crate::ipc::{detail, query, IpcError};
+use crate::runtime::Core;
+use crate::stream::LogNotifier;
+
+#[test]
+fn event_detail_gathers_context(){
+  let db = Db::open(":memory:".into()).unwrap();
+  let ev = |id: &str, ts: i64, lat: f64, lon: f64| db.conn.execute(
+    "INSERT INTO event(id,first_seen,last_seen,title,class,severity,lat,lon,geojson,source_rank) VALUES (?1,?2,?2,?1,'eq',0.5,?3,?4,'{\"id\":\"x\"}',5)",
+    rusqlite::params![id, ts, lat, lon]).unwrap();
+  ev("a", 1000, 35.0, 139.0);
+  ev("near", 2000, 35.3, 139.2);
+  ev("far", 2000, 40.0, 139.0);
+  ev("late", 1000 + 5 * 86400, 35.0, 139.0);
+  // square alert over the event, and one whose polygon misses it though its bbox does not
+  let square = r#"{"type":"Polygon","coordinates":[[[138,34],[140,34],[140,36],[138,36],[138,34]]]}"#;
+  let corner = r#"{"type":"Polygon","coordinates":[[[138,34],[138.5,34],[138,34.5],[138,34]]]}"#;
+  for (id, geo) in [("in", square), ("out", corner)] {
+    db.conn.execute("INSERT INTO alert(id,source,headline,expires,polygon_geojson,bbox_minx,bbox_miny,bbox_maxx,bbox_maxy,raw_json,last_seen)
+      VALUES (?1,'cap',?1,5000,?2,138,34,140,36,'{}',1)", rusqlite::params![id, geo]).unwrap();
+    db.conn.execute("INSERT INTO alert_rtree(rowid,minx,maxx,miny,maxy) VALUES ((SELECT rowid FROM alert WHERE id=?1),138,140,34,36)", [id]).unwrap();
+  }
+
+  let d = detail::event_detail(&db, "a", 100.0, 48 * 3600, 3000).unwrap();
+  assert_eq!(d.sources.len(), 1);
+  assert_eq!(d.sources[0].source, "usgs");
+  assert_eq!(d.alerts.iter().map(|a| a.id.as_str()).collect::<Vec<_>>(), ["in"]);
+  assert_eq!(d.nearby.iter().map(|n| n.event.id.as_str()).collect::<Vec<_>>(), ["near"]);
+  assert!(d.label.is_none() && d.rules.is_empty());
+  // expired alerts drop out
+  assert!(detail::event_detail(&db, "a", 100.0, 48 * 3600, 6000).unwrap().alerts.is_empty());
+  assert!(matches!(detail::event_detail(&db, "zz", 100.0, 0, 0).map_err(IpcError::from), Err(IpcError::NotFound(_))));
+  // neighbours over the antimeridian count
+  ev("west", 1000, -16.9, 179.9);
+  ev("east", 1500, -16.9, -179.9);
+  for (id, other) in [("west", "east"), ("east", "west")] {
+    let d = detail::event_detail(&db, id, 100.0, 48 * 3600, 3000).unwrap();
+    assert_eq!(d.nearby.iter().map(|n| n.event.id.as_str()).collect::<Vec<_>>(), [other]);
+  }
+}
+
+#[test]
+fn saved_feeds_ingest_and_query(){
+  let dir = std::env::temp_dir().join(format!("vilya-test-{}", uuid::Uuid::new_v4()));
+  let core = Core::open_db(":memory:".into(), dir.clone(), Box::new(LogNotifier)).unwrap();
+  let usgs = r#"{"type":"FeatureCollection","features":[
+    {"type":"Feature","id":"q1","properties":{"mag":5.0,"title":"M 5.0 - Alpha"},"geometry":{"type":"Point","coordinates":[10,20,5]}},
+    {"type":"Feature","id":"q2","properties":{"mag":3.0,"title":"M 3.0 - Beta"},"geometry":{"type":"Point","coordinates":[11,21,5]}}]}"#;
+  assert_eq!(ingest::ingest(&core, "usgs", usgs).unwrap(), 2);
+  core.db.conn.execute("UPDATE event SET first_seen=100 WHERE id='q2'", []).unwrap();
+  let cap = r#"<alert><identifier>c1</identifier><msgType>Alert</msgType><info><event>Flood</event><headline>Flood</headline>
+    <expires>2099-01-01T00:00:00Z</expires><area><polygon>19,9 19,12 22,12 22,9 19,9</polygon></area></info></alert>"#;
+  assert_eq!(ingest::ingest(&core, "cap", cap).unwrap(), 1);
+  assert!(ingest::ingest(&core, "nope", "{}").is_err());
+
+  let ids = |v: Vec<crate::ipc::UiEvent>| v.into_iter().map(|e| e.id).collect::<Vec<_>>();
+  assert_eq!(ids(query::search_events(&core.db, Some("Beta"), None, None, None, 10).unwrap()), ["q2"]);
+  assert_eq!(ids(query::search_events(&core.db, None, Some(1000), None, None, 10).unwrap()), ["q1"]);
+  assert_eq!(ids(query::search_events(&core.db, None, None, Some(1000), None, 10).unwrap()), ["q2"]);
+  let now = chrono::Utc::now().timestamp();
+  assert_eq!(query::query_alerts(&core.db, [0.0, 0.0, 10.0, 20.0], now, None).unwrap().len(), 1);
+  assert!(query::query_alerts(&core.db, [50.0, 50.0, 60.0, 60.0], now, None).unwrap().is_empty());
+  let _ = std::fs::remove_dir_all(dir);
+}
+
+/// Records the alerts each `AlertsCreated` notice carries.
+struct Recorder(std::sync::Mutex<Vec<Vec<String>>>);
+
+impl crate::stream::Notifier for std::sync::Arc<Recorder> {
+  fn notify(&self, _core: &Core, notice: crate::stream::Notice) -> anyhow::Result<()> {
+    if let crate::stream::Notice::AlertsCreated(ids) = notice { self.0.lock().unwrap().push(ids.to_vec()); }
+    Ok(())
+  }
+}
+
+#[test]
+fn following_reports_new_alerts_not_refreshed_ones(){
+  let dir = std::env::temp_dir().join(format!("vilya-test-{}", uuid::Uuid::new_v4()));
+  let rec = std::sync::Arc::new(Recorder(Default::default()));
+  let core = Core::open_db(":memory:".into(), dir.clone(), Box::new(rec.clone())).unwrap();
+  let alert = |id: &str, headline: &str, last_seen: i64| core.db.conn.execute(
+    "INSERT OR REPLACE INTO alert(id,source,headline,severity,expires,bbox_minx,bbox_miny,bbox_maxx,bbox_maxy,raw_json,last_seen)
+     VALUES (?1,'nws',?2,'Severe',9999999999,0,0,1,1,'{}',?3)", rusqlite::params![id, headline, last_seen]).unwrap();
+  alert("a1", "Flood Watch", 1000);
+  alert("a1", "Flood Watch", 2000);
+  alert("a2", "Wind Advisory", 2000);
+  rec.0.lock().unwrap().clear();
+  crate::stream::changes_since(&core, 1500).unwrap();
+  assert_eq!(*rec.0.lock().unwrap(), [vec!["a2".to_string()]]);
+  let _ = std::fs::remove_dir_all(dir);
+}
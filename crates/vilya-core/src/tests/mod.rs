@@ -0,0 +1,19 @@
+mod basic_tests;
+mod ai_tests;
+mod rules_tests;
+mod bindings_tests;
+mod ipc_tests;
+mod analytics_tests;
+mod api_tests;
+mod ogc_tests;
+mod export_tests;
+mod import_tests;
+mod drop_folder_tests;
+mod tape_tests;
+mod history_tests;
+mod geocode_tests;
+mod exposure_tests;
+mod quake_tests;
+mod embed_tests;
+mod summary_tests;
+mod entities_tests;
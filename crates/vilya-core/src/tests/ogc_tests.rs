@@ -0,0 +1,70 @@
+use crate::api;
+use crate::runtime::Core;
+use crate::stream::LogNotifier;
+
+#[tokio::test]
+async fn ogc_items_filter_by_bbox_datetime_and_page(){
+  let dir = std::env::temp_dir().join(format!("vilya-test-{}", uuid::Uuid::new_v4()));
+  let core = Core::open_db(":memory:".into(), dir.clone(), Box::new(LogNotifier)).unwrap();
+  for (id, ts, lon) in [("a", 1_700_000_000, 10.0), ("b", 1_700_003_600, 11.0), ("c", 1_700_007_200, 170.0)] {
+    core.db.conn.execute("INSERT INTO event(id,first_seen,last_seen,title,class,severity,lat,lon,source_rank) VALUES (?1,?2,?2,?1,'eq',0.5,20,?3,5)",
+      rusqlite::params![id, ts, lon]).unwrap();
+  }
+  // a later report merged into b is not listed
+  core.db.conn.execute("INSERT INTO event(id,first_seen,last_seen,title,class,severity,lat,lon,source_rank) VALUES ('d',1700003700,1700003700,'d','eq',0.5,20,11,3)", []).unwrap();
+  core.db.conn.execute("INSERT INTO event_duplicate(event_id,duplicate_of,model,score,km,merged_at) VALUES ('d','b','m',0.95,1,1)", []).unwrap();
+  // a CAP alert keeps its polygons as a FeatureCollection
+  let area = r#"{"type":"FeatureCollection","features":[{"type":"Feature","properties":null,"geometry":{"type":"Polygon","coordinates":[[[9,19],[12,19],[12,21],[9,21],[9,19]]]}}]}"#;
+  core.db.conn.execute("INSERT INTO alert(id,source,headline,onset,expires,polygon_geojson,bbox_minx,bbox_miny,bbox_maxx,bbox_maxy,raw_json,last_seen)
+    VALUES ('al','cap','Flood',1000,4102444800,?1,9,19,12,21,'{}',1)", [area]).unwrap();
+  core.db.conn.execute("INSERT INTO alert_rtree(rowid,minx,maxx,miny,maxy) VALUES ((SELECT rowid FROM alert WHERE id='al'),9,12,19,21)", []).unwrap();
+
+  let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+  let base = format!("http://{}", listener.local_addr().unwrap());
+  let app = api::router(core.clone(), "t".into());
+  tokio::spawn(async move { axum::serve(listener, app).await });
+  let http = reqwest::Client::new();
+  let get = |path: String| { let r = http.get(format!("{base}{path}")).bearer_auth("t"); async move { r.send().await.unwrap() } };
+  let ids = |v: &serde_json::Value| v["features"].as_array().unwrap().iter().map(|f| f["id"].as_str().unwrap().to_string()).collect::<Vec<_>>();
+
+  let cols: serde_json::Value = get("/ogc/collections".into()).await.json().await.unwrap();
+  assert_eq!(cols["collections"].as_array().unwrap().len(), 2);
+
+  let page = get("/ogc/collections/events/items?limit=2".into()).await;
+  assert_eq!(page.headers()["content-type"], "application/geo+json");
+  let page: serde_json::Value = page.json().await.unwrap();
+  assert_eq!(ids(&page), ["c", "b"]);
+  assert_eq!(page["numberMatched"], 3);
+  let next = page["links"].as_array().unwrap().iter().find(|l| l["rel"] == "next").unwrap()["href"].as_str().unwrap().to_string();
+  let rest: serde_json::Value = http.get(next).bearer_auth("t").send().await.unwrap().json().await.unwrap();
+  assert_eq!(ids(&rest), ["a"]);
+
+  let v: serde_json::Value = get("/ogc/collections/events/items?bbox=9,19,10.5,21".into()).await.json().await.unwrap();
+  assert_eq!(ids(&v), ["a"]);
+  // across the antimeridian
+  let v: serde_json::Value = get("/ogc/collections/events/items?bbox=160,19,-170,21".into()).await.json().await.unwrap();
+  assert_eq!(ids(&v), ["c"]);
+  let v: serde_json::Value = get("/ogc/collections/events/items?datetime=2023-11-14T22:30:00Z/..".into()).await.json().await.unwrap();
+  assert_eq!(ids(&v), ["c", "b"]);
+  assert_eq!(get("/ogc/collections/events/items?datetime=yesterday".into()).await.status(), 400);
+
+  let v: serde_json::Value = get("/ogc/collections/alerts/items?bbox=10,20,10.5,20.5".into()).await.json().await.unwrap();
+  assert_eq!(ids(&v), ["al"]);
+  assert_eq!(v["features"][0]["geometry"]["type"], "Polygon");
+  let v: serde_json::Value = get("/ogc/collections/alerts/items?bbox=50,50,60,60".into()).await.json().await.unwrap();
+  assert!(ids(&v).is_empty());
+  let one: serde_json::Value = get("/ogc/collections/events/items/b".into()).await.json().await.unwrap();
+  assert_eq!(one["geometry"]["coordinates"][0], 11.0);
+  assert_eq!(get("/ogc/collections/nope/items".into()).await.status(), 404);
+
+  // links carry a query token, so a client that used one can follow them
+  let page: serde_json::Value = http.get(format!("{base}/ogc/collections/events/items?limit=2&token=t")).send().await.unwrap().json().await.unwrap();
+  let next = page["links"].as_array().unwrap().iter().find(|l| l["rel"] == "next").unwrap()["href"].as_str().unwrap().to_string();
+  assert!(next.ends_with("&token=t"), "{next}");
+  let rest: serde_json::Value = http.get(next).send().await.unwrap().json().await.unwrap();
+  assert_eq!(ids(&rest), ["a"]);
+  let landing: serde_json::Value = http.get(format!("{base}/ogc?token=t")).send().await.unwrap().json().await.unwrap();
+  let data = landing["links"][2]["href"].as_str().unwrap();
+  assert_eq!(http.get(data).send().await.unwrap().status(), 200);
+  let _ = std::fs::remove_dir_all(dir);
+}
@@ -0,0 +1,123 @@
+use crate::geocode;
+use crate::ingest;
+use crate::ipc::{detail, query};
+use crate::quake::{self, Quake};
+use crate::runtime::Core;
+use crate::stream::LogNotifier;
+
+fn quake(mag: f64, depth_km: f64) -> Quake {
+  Quake { mag, mag_type: Some("mw".into()), depth_km: Some(depth_km), mag_error: None, depth_error_km: None, horizontal_error_km: None }
+}
+
+#[test]
+fn intensity_falls_off_and_contours_nest(){
+  // Allen, Wald & Worden (2012): M6 at 10 km hypocentral distance ~ VII
+  assert!((quake::mmi(6.0, 10.0, 0.0) - 7.25).abs() < 0.01, "{}", quake::mmi(6.0, 10.0, 0.0));
+  // near-source saturation: M8 at 10 km stays near VIII
+  assert!((quake::mmi(8.0, 10.0, 0.0) - 8.27).abs() < 0.01, "{}", quake::mmi(8.0, 10.0, 0.0));
+  // far field beyond 50 km: M6 at 500 km ~ II
+  assert!((quake::mmi(6.0, 10.0, 500.0) - 2.12).abs() < 0.01, "{}", quake::mmi(6.0, 10.0, 500.0));
+  assert!(quake::mmi(6.0, 10.0, 100.0) < quake::mmi(6.0, 10.0, 40.0));
+  assert!(quake::mmi(6.0, 100.0, 0.0) < quake::mmi(6.0, 10.0, 0.0));
+
+  let c = quake::contours(&quake(6.0, 10.0));
+  assert_eq!(c.iter().map(|c| c.mmi).collect::<Vec<_>>(), [2, 3, 4, 5, 6, 7]);
+  assert!(c.windows(2).all(|w| w[0].radius_km > w[1].radius_km), "{c:?}");
+  for c in &c {
+    let at = quake::mmi(6.0, 10.0, c.radius_km);
+    assert!((at - c.mmi as f64).abs() < 0.01 || c.radius_km == quake::MAX_RADIUS_KM, "{c:?} {at}");
+  }
+  assert!(quake::contours(&quake(1.0, 600.0)).is_empty());
+  assert_eq!((quake::mmi_severity(1.0), quake::mmi_severity(10.0), quake::mmi_severity(12.0)), (0.0, 1.0, 1.0));
+}
+
+#[test]
+fn feeds_keep_parameters_and_shaking_rates_severity(){
+  let dir = std::env::temp_dir().join(format!("vilya-test-{}", uuid::Uuid::new_v4()));
+  std::fs::create_dir_all(&dir).unwrap();
+  let core = Core::open_db(":memory:".into(), dir.clone(), Box::new(LogNotifier)).unwrap();
+  let conn = &core.db.conn;
+  let usgs = r#"{"type":"FeatureCollection","features":[
+    {"type":"Feature","id":"q1","properties":{"mag":6.0,"magType":"mww","magError":0.05,"title":"M 6.0"},"geometry":{"type":"Point","coordinates":[139.0,35.0,10]}}]}"#;
+  ingest::ingest(&core, "usgs", usgs).unwrap();
+  let emsc = r#"{"action":"create","data":{},"features":[
+    {"type":"Feature","id":"e1","properties":{"mag":4.1,"magtype":"ml","depth":33.0,"flynn_region":"SOUTHERN ITALY"},"geometry":{"type":"Point","coordinates":[15.0,38.0,-33.0]}}]}"#;
+  ingest::ingest(&core, "emsc", emsc).unwrap();
+
+  // without a gazetteer severity stays magnitude-based
+  let severity = |id: &str| conn.query_row("SELECT severity FROM event WHERE id=?1", [id], |r| r.get::<_, f64>(0)).unwrap();
+  assert_eq!(severity("q1"), 0.6);
+  let s = detail::event_detail(&core.db, "q1", 100.0, 3600, 0).unwrap().shaking.unwrap();
+  assert_eq!((s.quake.mag_type.as_deref(), s.quake.depth_km, s.quake.mag_error), (Some("mww"), Some(10.0), Some(0.05)));
+  assert_eq!((s.max_mmi, s.contours.first().map(|c| c.mmi)), (None, Some(2)));
+  let e = quake::shaking_of(conn, "e1").unwrap().unwrap();
+  assert_eq!((e.quake.mag_type.as_deref(), e.quake.depth_km), (Some("ml"), Some(33.0)));
+
+  let now = chrono::Utc::now().timestamp();
+  let discs = query::query_shaking(&core.db, [138.5, 34.5, 139.5, 35.5], now - 3600).unwrap();
+  assert_eq!(discs.iter().map(|c| c.mmi).collect::<Vec<_>>(), [2, 3, 4, 5, 6, 7]);
+  assert!(discs.iter().all(|c| c.event_id == "q1" && c.geojson["type"] == "Polygon"));
+  assert!(query::query_shaking(&core.db, [138.5, 34.5, 139.5, 35.5], now + 3600).unwrap().is_empty());
+
+  // a place 60 km away rates q1 by its shaking; none feels e1
+  let g = dir.join("gazetteer");
+  std::fs::create_dir_all(&g).unwrap();
+  std::fs::write(g.join("countryInfo.txt"), "JP\tJPN\t392\tJA\tJapan\n").unwrap();
+  std::fs::write(g.join("cities15000.txt"),
+    "1\tOdawara\tOdawara\t\t35.54\t139.0\tP\tPPL\tJP\t\t19\t\t\t\t190000\t\t10\tAsia/Tokyo\t2024-01-01\n").unwrap();
+  geocode::load(conn, &g).unwrap();
+  assert_eq!(quake::estimate_all(conn).unwrap(), 2);
+  let s = quake::shaking_of(conn, "q1").unwrap().unwrap();
+  assert_eq!(s.centre.as_deref(), Some("Odawara"));
+  assert!((59.0..61.0).contains(&s.centre_km.unwrap()), "{s:?}");
+  assert_eq!(severity("q1"), quake::mmi_severity(s.max_mmi.unwrap()));
+  assert!(severity("q1") > 0.3 && severity("q1") < 0.6, "{}", severity("q1"));
+  assert_eq!(severity("e1"), 0.0);
+  assert_eq!(quake::estimate_all(conn).unwrap(), 0);
+
+  conn.execute("DELETE FROM event WHERE id='q1'", []).unwrap();
+  let left: i64 = conn.query_row("SELECT (SELECT COUNT(*) FROM quake_contour WHERE event_id='q1') + (SELECT COUNT(*) FROM quake_contour_rtree)
+    - (SELECT COUNT(*) FROM quake_contour)", [], |r| r.get(0)).unwrap();
+  assert_eq!(left, 0);
+  let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn shaking_crosses_the_antimeridian(){
+  let dir = std::env::temp_dir().join(format!("vilya-test-{}", uuid::Uuid::new_v4()));
+  let g = dir.join("gazetteer");
+  std::fs::create_dir_all(&g).unwrap();
+  let core = Core::open_db(":memory:".into(), dir.clone(), Box::new(LogNotifier)).unwrap();
+  let conn = &core.db.conn;
+  std::fs::write(g.join("countryInfo.txt"), "FJ\tFJI\t242\tFJ\tFiji\n").unwrap();
+  std::fs::write(g.join("cities15000.txt"),
+    "1\tTaveuni\tTaveuni\t\t-16.9\t-179.9\tP\tPPL\tFJ\t\t03\t\t\t\t15000\t\t10\tPacific/Fiji\t2024-01-01\n").unwrap();
+  geocode::load(conn, &g).unwrap();
+  let usgs = r#"{"type":"FeatureCollection","features":[
+    {"type":"Feature","id":"q1","properties":{"mag":6.0,"magType":"mww","title":"M 6.0"},"geometry":{"type":"Point","coordinates":[179.9,-16.9,10]}}]}"#;
+  ingest::ingest(&core, "usgs", usgs).unwrap();
+
+  // the place 21 km away over the line rates the quake
+  let s = quake::shaking_of(conn, "q1").unwrap().unwrap();
+  assert_eq!(s.centre.as_deref(), Some("Taveuni"));
+  assert!((20.0..23.0).contains(&s.centre_km.unwrap()), "{s:?}");
+
+  // discs over ±180 are split there and found from either side
+  let now = chrono::Utc::now().timestamp();
+  for bbox in [[179.0, -17.5, 180.0, -16.5], [-180.0, -17.5, -179.0, -16.5]] {
+    let discs = query::query_shaking(&core.db, bbox, now - 3600).unwrap();
+    let reach = if bbox[0] < 0.0 { 10.7 } else { 0.0 };
+    assert_eq!(discs.iter().map(|c| c.mmi).collect::<Vec<_>>(),
+      s.contours.iter().filter(|c| c.radius_km > reach).map(|c| c.mmi).collect::<Vec<_>>(), "{bbox:?}");
+    assert!(discs.len() >= 4, "{discs:?}");
+    for c in &discs {
+      // the line is 10.6 km east of the epicentre
+      assert_eq!(c.geojson["type"], if c.radius_km > 10.7 { "MultiPolygon" } else { "Polygon" }, "{c:?}");
+      let polygons = match c.geojson["type"].as_str() { Some("Polygon") => vec![c.geojson["coordinates"].clone()], _ => c.geojson["coordinates"].as_array().unwrap().clone() };
+      let xs: Vec<f64> = polygons.iter().flat_map(|p| p[0].as_array().unwrap().iter().map(|v| v[0].as_f64().unwrap())).collect();
+      assert!(xs.iter().all(|x| (-180.0..=180.0).contains(x)), "{xs:?}");
+    }
+  }
+  assert!(query::query_shaking(&core.db, [0.0, -17.5, 10.0, -16.5], now - 3600).unwrap().is_empty());
+  let _ = std::fs::remove_dir_all(dir);
+}
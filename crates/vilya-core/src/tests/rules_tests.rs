@@ -0,0 +1,43 @@
+use crate::db::Db;
+use crate::rules;
+
+const RULES: &str = r#"
+- id: big-quakes
+  name: Big quakes in Japan
+  target: event
+  where: { class: [eq], min_severity: 0.6, bbox: [128, 30, 146, 46] }
+- id: tornado
+  name: Tornado warnings
+  target: alert
+  where: { class: [Tornado Warning] }
+"#;
+
+fn load(db: &Db) {
+  for (r, cond) in rules::parse(RULES).unwrap() {
+    db.conn.execute("INSERT INTO rule(id,name,enabled,target,spec_json,updated_at) VALUES (?1,?2,1,?3,?4,0)",
+      rusqlite::params![r.id, r.name, r.target, serde_json::to_string(&cond).unwrap()]).unwrap();
+  }
+}
+
+#[test]
+fn rules_match_once_per_item(){
+  let db = Db::open(":memory:".into()).unwrap();
+  load(&db);
+  db.conn.execute("INSERT INTO event(id,first_seen,last_seen,title,class,severity,lat,lon) VALUES ('jp',1,1,'M7 Honshu','eq',0.7,38.0,142.0)", []).unwrap();
+  db.conn.execute("INSERT INTO event(id,first_seen,last_seen,title,class,severity,lat,lon) VALUES ('cl',1,1,'M7 Chile','eq',0.7,-33.0,-71.0)", []).unwrap();
+  db.conn.execute("INSERT INTO alert(id,source,headline,event,severity,bbox_minx,bbox_miny,bbox_maxx,bbox_maxy,raw_json,last_seen)
+    VALUES ('t1','nws','Tornado','tornado warning','Extreme',-98,35,-97,36,'{}',1)", []).unwrap();
+
+  let ids = ["jp".to_string(), "cl".to_string()];
+  let m = rules::evaluate(&db.conn, "event", &ids).unwrap();
+  assert_eq!(m.len(), 1);
+  assert_eq!((m[0].rule_id.as_str(), m[0].item_id.as_str()), ("big-quakes", "jp"));
+  assert!(rules::evaluate(&db.conn, "event", &ids).unwrap().is_empty());
+  assert_eq!(rules::evaluate(&db.conn, "alert", &["t1".to_string()]).unwrap().len(), 1);
+}
+
+#[test]
+fn rules_reject_unknown_conditions(){
+  assert!(rules::parse("- {id: x, name: x, target: event, where: {severity_min: 0.5}}").is_err());
+  assert!(rules::parse("- {id: x, name: x, target: place, where: {}}").is_err());
+}
@@ -0,0 +1,58 @@
+use crate::ai::{mock::MockProvider, summary::{self, SummaryScope}};
+use crate::db::Db;
+
+fn event(db: &Db, id: &str, seen: i64, lon: f64, severity: f64, title: &str) {
+  db.conn.execute("INSERT INTO event(id,first_seen,last_seen,title,class,severity,lat,lon) VALUES (?1,?2,?2,?3,'flood',?4,50.0,?5)",
+    rusqlite::params![id, seen, title, severity, lon]).unwrap();
+}
+
+#[tokio::test]
+async fn briefings_keep_only_citations_of_their_inputs(){
+  let db = Db::open(":memory:".into()).unwrap();
+  let now = chrono::Utc::now().timestamp();
+  event(&db, "f1", now - 600, 13.7, 0.8, "River flood in Dresden");
+  event(&db, "f2", now - 300, 12.4, 0.4, "Flooding in Leipzig");
+  event(&db, "far", now - 300, 100.0, 0.9, "Flood in Thailand");
+  let p = MockProvider::with_response("The Elbe floods Dresden [f1]. Rain spreads [zz]. Leipzig follows [f2, zz] [f1].");
+  let scope = SummaryScope { bbox: Some([10.0, 45.0, 20.0, 55.0]), hours: Some(2), ..Default::default() };
+
+  let s = summary::summarize(&db, &p, &scope, false).await.unwrap();
+  assert_eq!(s.events, ["f1", "f2"]);
+  assert_eq!(s.text, "The Elbe floods Dresden [f1]. Rain spreads. Leipzig follows [f2] [f1].");
+  assert_eq!(s.citations, ["f1", "f2"]);
+  assert!(!s.cached);
+
+  let empty = SummaryScope { bbox: Some([-10.0, -10.0, 0.0, 0.0]), ..Default::default() };
+  let s = summary::summarize(&db, &p, &empty, false).await.unwrap();
+  assert!(s.events.is_empty() && s.citations.is_empty(), "{s:?}");
+  assert!(summary::summarize(&db, &p, &SummaryScope { aoi: Some("nope".into()), ..Default::default() }, false).await.is_err());
+}
+
+#[tokio::test]
+async fn briefings_are_cached_until_their_items_change(){
+  let db = Db::open(":memory:".into()).unwrap();
+  let now = chrono::Utc::now().timestamp();
+  event(&db, "f1", now - 600, 13.7, 0.8, "River flood in Dresden");
+  let p = MockProvider::with_response("The Elbe floods Dresden [f1].");
+  let scope = SummaryScope { bbox: Some([10.0, 45.0, 20.0, 55.0]), hours: Some(2), ..Default::default() };
+
+  let first = summary::summarize(&db, &p, &scope, false).await.unwrap();
+  let again = summary::summarize(&db, &p, &scope, false).await.unwrap();
+  assert!(again.cached);
+  assert_eq!((again.id, again.text.as_str()), (first.id, first.text.as_str()));
+
+  // an item seen again, or a new one, invalidates the briefing
+  db.conn.execute("UPDATE event SET last_seen=last_seen+60 WHERE id='f1'", []).unwrap();
+  let updated = summary::summarize(&db, &p, &scope, false).await.unwrap();
+  assert!(!updated.cached && updated.id != first.id);
+  event(&db, "f2", now - 300, 12.4, 0.4, "Flooding in Leipzig");
+  let grown = summary::summarize(&db, &p, &scope, false).await.unwrap();
+  assert!(!grown.cached && grown.id != updated.id);
+  assert!(summary::summarize(&db, &p, &scope, false).await.unwrap().cached);
+  // another scope, or force, writes a new briefing
+  let wider = SummaryScope { bbox: Some([0.0, 40.0, 20.0, 60.0]), ..scope.clone() };
+  assert!(!summary::summarize(&db, &p, &wider, false).await.unwrap().cached);
+  assert!(!summary::summarize(&db, &p, &scope, true).await.unwrap().cached);
+  let (stored, hashes): (i64, i64) = db.conn.query_row("SELECT COUNT(*), COUNT(DISTINCT prompt_hash) FROM ai_summary", [], |r| Ok((r.get(0)?, r.get(1)?))).unwrap();
+  assert_eq!((stored, hashes), (5, 1));
+}